@@ -0,0 +1,72 @@
+//! Serves the inventory and miniSEED records read from files by means of an in-process server and
+//! fetches the packets buffered with the crate's own client.
+//!
+//! Usage: `cargo run --example end_to_end -- <inventory.json> <records.mseed>`
+
+use std::env;
+use std::process;
+
+use futures::StreamExt;
+
+use slink::{Connection, DataTransferMode, SeedLinkConnectionInfo, SeedLinkPacket, StreamItem};
+use slink_server::file::{publish_file, FileBackend};
+use slink_server::{PacketBuffer, RetentionPolicy};
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <inventory.json> <records.mseed>", args[0]);
+        process::exit(1);
+    }
+
+    let buffer = PacketBuffer::new(RetentionPolicy::default());
+    let backend = FileBackend::open(&args[1], buffer)
+        .unwrap()
+        .with_data_center_description("File DC");
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+    let published = publish_file(&server_handle, &args[2]).await.unwrap();
+    println!("published {} records", published);
+
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let mut con = Connection::from_duplex(stream, &SeedLinkConnectionInfo::default())
+        .await
+        .unwrap();
+
+    let stations = con.request_stations_info().await.unwrap();
+    for station in stations.station.iter() {
+        let id = station.id();
+        con.add_stream(
+            id.net_code(),
+            id.sta_code(),
+            &None,
+            &Some("0".to_string()),
+            &None,
+        )
+        .unwrap();
+    }
+    con.configure(DataTransferMode::DialUp, None, false)
+        .await
+        .unwrap();
+
+    let mut packets = con.packets(None);
+    while let Some(item) = packets.next().await {
+        match item {
+            StreamItem::Packet(SeedLinkPacket::V4(packet)) => println!(
+                "{}: packet {} ({} bytes)",
+                packet.sta_id().as_deref().unwrap_or_default(),
+                packet.sequence_number(),
+                packet.len_payload()
+            ),
+            StreamItem::Packet(_) => {}
+            StreamItem::End(end) => {
+                println!("end of stream: {:?}", end);
+                break;
+            }
+        }
+    }
+
+    server_handle.shutdown().await;
+}
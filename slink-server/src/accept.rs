@@ -1,20 +1,102 @@
 use std::io;
-use std::net::SocketAddr;
+use std::net::{Ipv4Addr, SocketAddr};
 
-use crate::client::{self, ClientInfo};
+use crate::client::{self, ClientInfo, ClientStream};
 use crate::server::{ServerHandle, ToServer};
+use crate::task::Subsystem;
+use crate::HIGHEST_SUPPORTED_PROTO_VERSION;
 
+use tokio::io::DuplexStream;
 use tokio::net::TcpListener;
 
+/// Buffer size of in-memory client connections.
+const MEM_BUF_SIZE: usize = 64 * 1024;
+
+/// Listener specific configuration.
+///
+/// TODO(damb): serve SeedLink `v3` commands (apart from unknown commands), i.e. listeners
+/// advertising `3.x` versions (including gzip compressed `INFO` responses, see
+/// `slink::compress_info_payload_v3`)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ListenerConfig {
+    protocol_versions: Vec<(u8, u8)>,
+    data_center_description: Option<String>,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            protocol_versions: vec![HIGHEST_SUPPORTED_PROTO_VERSION],
+            data_center_description: None,
+        }
+    }
+}
+
+impl ListenerConfig {
+    /// Configures the protocol versions advertised in response to `HELLO` (e.g. a legacy port
+    /// announcing `3.x` versions, only). Clients may only switch to advertised protocol versions.
+    ///
+    /// Panics if `protocol_versions` is empty.
+    pub fn with_protocol_versions(mut self, mut protocol_versions: Vec<(u8, u8)>) -> Self {
+        assert!(!protocol_versions.is_empty());
+
+        protocol_versions.sort_unstable_by(|a, b| b.cmp(a));
+        protocol_versions.dedup();
+        self.protocol_versions = protocol_versions;
+        self
+    }
+
+    /// Returns the protocol versions advertised (sorted in descending order).
+    pub fn protocol_versions(&self) -> &[(u8, u8)] {
+        &self.protocol_versions
+    }
+
+    /// Configures the data center description announced to the clients of the listener (e.g.
+    /// per tenant), overriding [`SeedLinkServer::data_center_description`].
+    ///
+    /// [`SeedLinkServer::data_center_description`]: crate::SeedLinkServer::data_center_description
+    pub fn with_data_center_description(mut self, description: impl Into<String>) -> Self {
+        self.data_center_description = Some(description.into());
+        self
+    }
+
+    /// Returns the data center description configured, if any.
+    pub fn data_center_description(&self) -> Option<&str> {
+        self.data_center_description.as_deref()
+    }
+}
+
+/// Spawns a task accepting client connections.
+///
+/// The task is shut down together with the server (see [`ServerHandle::shutdown`]) or once the
+/// server is drained (see [`ServerHandle::drain`]).
+pub fn spawn_accept(bind: SocketAddr, server_handle: ServerHandle, config: ListenerConfig) {
+    let tasks = server_handle.tasks().clone();
+    tasks.spawn(Subsystem::Accept, start_accept(bind, server_handle, config));
+}
+
 /// Starts accepting client connections.
-pub async fn start_accept(bind: SocketAddr, mut server_handle: ServerHandle) {
-    if let Some(err) = accept_loop(bind, server_handle.clone()).await.err() {
+pub async fn start_accept(
+    bind: SocketAddr,
+    mut server_handle: ServerHandle,
+    config: ListenerConfig,
+) {
+    if let Some(err) = accept_loop(bind, server_handle.clone(), config).await.err() {
         server_handle.send(ToServer::FatalError(err)).await;
     }
 }
 
-async fn accept_loop(bind: SocketAddr, server_handle: ServerHandle) -> Result<(), io::Error> {
+async fn accept_loop(
+    bind: SocketAddr,
+    server_handle: ServerHandle,
+    config: ListenerConfig,
+) -> Result<(), io::Error> {
     let listen = TcpListener::bind(bind).await?;
+    // XXX(damb): encode once, i.e. not per connection
+    let data_center_description = config
+        .data_center_description
+        .as_deref()
+        .map(|description| server_handle.description_encoding().encode(description));
 
     loop {
         let (tcp, ip) = listen.accept().await?;
@@ -24,11 +106,45 @@ async fn accept_loop(bind: SocketAddr, server_handle: ServerHandle) -> Result<()
         let data = ClientInfo {
             ip,
             id,
-            tcp,
+            stream: ClientStream::Tcp(tcp),
             handle: server_handle.clone(),
+            protocol_versions: config.protocol_versions.clone(),
+            data_center_description: data_center_description.clone(),
         };
 
         client::spawn_client(data);
     }
 }
 
+/// Accepts an in-memory client connection and returns the client side of the connection.
+///
+/// This allows clients (e.g. `slink::Connection::from_duplex`) to be wired directly to the server
+/// in-process, i.e. without sockets. The default [`ListenerConfig`] applies.
+pub fn accept_mem(server_handle: ServerHandle) -> DuplexStream {
+    accept_mem_with_config(server_handle, ListenerConfig::default())
+}
+
+/// Accepts an in-memory client connection the listener specific configuration `config` applies
+/// to (see [`accept_mem`]).
+pub fn accept_mem_with_config(server_handle: ServerHandle, config: ListenerConfig) -> DuplexStream {
+    let (client_stream, server_stream) = tokio::io::duplex(MEM_BUF_SIZE);
+
+    let id = server_handle.next_id();
+    let data_center_description = config
+        .data_center_description
+        .as_deref()
+        .map(|description| server_handle.description_encoding().encode(description));
+
+    let data = ClientInfo {
+        ip: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        id,
+        stream: ClientStream::Mem(server_stream),
+        handle: server_handle,
+        protocol_versions: config.protocol_versions,
+        data_center_description,
+    };
+
+    client::spawn_client(data);
+
+    client_stream
+}
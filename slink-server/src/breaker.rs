@@ -0,0 +1,145 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::{self, Instant};
+use tracing::warn;
+
+use slink::{ErrorCodeV4, ProtocolErrorV4};
+
+/// Default maximum duration of a single backend call.
+const DEFAULT_BACKEND_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default number of consecutive backend failures opening the circuit breaker.
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+/// Default duration the circuit breaker stays open.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Limits protecting the server against slow or misbehaving backends.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct BackendLimits {
+    /// Maximum duration of a single backend call. Backend calls exceeding the timeout result in
+    /// an `INTERNAL` error. If `None`, backend calls never time out.
+    pub timeout: Option<Duration>,
+    /// Number of consecutive backend failures (i.e. timeouts and `INTERNAL` errors) opening the
+    /// circuit breaker.
+    pub failure_threshold: u32,
+    /// Duration the circuit breaker stays open.
+    ///
+    /// While open, `INFO STATIONS` and `INFO STREAMS` responses are served from the `INFO`
+    /// response cache regardless of their age (see [`SeedLinkServer::info_cache_ttl`]) without
+    /// calling the backend.
+    ///
+    /// [`SeedLinkServer::info_cache_ttl`]: crate::SeedLinkServer::info_cache_ttl
+    pub cooldown: Duration,
+}
+
+impl Default for BackendLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Some(DEFAULT_BACKEND_TIMEOUT),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+}
+
+/// Circuit breaker keeping track of consecutive backend failures.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CircuitBreaker {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Returns whether the circuit breaker is open.
+    pub fn is_open(&mut self) -> bool {
+        match self.open_until {
+            Some(open_until) if Instant::now() < open_until => true,
+            Some(_) => {
+                self.open_until = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Calls the backend by means of `fut` applying the timeout of `limits`. The result is
+    /// recorded.
+    pub async fn call<T, F>(&mut self, limits: &BackendLimits, fut: F) -> Result<T, ProtocolErrorV4>
+    where
+        F: Future<Output = Result<T, ProtocolErrorV4>>,
+    {
+        let res = match limits.timeout {
+            Some(timeout) => time::timeout(timeout, fut).await.unwrap_or_else(|_| {
+                let mut err = ProtocolErrorV4::internal();
+                err.message = Some("backend call timed out".into());
+                Err(err)
+            }),
+            None => fut.await,
+        };
+
+        match res {
+            Err(ref err) if err.code == ErrorCodeV4::Internal => self.record_failure(limits),
+            _ => self.failures = 0,
+        }
+
+        res
+    }
+
+    fn record_failure(&mut self, limits: &BackendLimits) {
+        self.failures += 1;
+        if self.failures >= limits.failure_threshold && self.open_until.is_none() {
+            warn!(
+                "backend failed {} times in a row, degrading INFO for {:?}",
+                self.failures, limits.cooldown
+            );
+            self.failures = 0;
+            self.open_until = Some(Instant::now() + limits.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::future;
+
+    const LIMITS: BackendLimits = BackendLimits {
+        timeout: Some(Duration::from_secs(1)),
+        failure_threshold: 2,
+        cooldown: Duration::from_secs(60),
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn open_on_timeouts() {
+        let mut breaker = CircuitBreaker::default();
+
+        let res = breaker
+            .call(&LIMITS, future::pending::<Result<(), _>>())
+            .await;
+        assert_eq!(res.unwrap_err().code, ErrorCodeV4::Internal);
+        assert!(!breaker.is_open());
+
+        // other errors do not count as failures
+        let res: Result<(), _> = breaker
+            .call(&LIMITS, async {
+                Err(ProtocolErrorV4::incorrect_arguments())
+            })
+            .await;
+        assert!(res.is_err());
+        assert!(!breaker.is_open());
+
+        for _ in 0..2 {
+            let res = breaker
+                .call(&LIMITS, future::pending::<Result<(), _>>())
+                .await;
+            assert!(res.is_err());
+        }
+        assert!(breaker.is_open());
+
+        time::advance(LIMITS.cooldown).await;
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.call(&LIMITS, async { Ok(42) }).await.unwrap(), 42);
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use time::OffsetDateTime;
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+use tracing::debug;
+
+use slink::StationsInfoV4;
+
+use crate::task::Subsystem;
+use crate::ServerHandle;
+
+/// Retention policy of the packets buffered per station.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RetentionPolicy {
+    max_packets: Option<usize>,
+    max_bytes: Option<usize>,
+    max_age: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    /// Limits the number of packets buffered. Panics if `max_packets` is zero.
+    pub fn with_max_packets(mut self, max_packets: usize) -> Self {
+        assert_ne!(max_packets, 0, "max_packets must be greater than zero");
+        self.max_packets = Some(max_packets);
+        self
+    }
+
+    /// Limits the total payload size of the packets buffered. Panics if `max_bytes` is zero.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        assert_ne!(max_bytes, 0, "max_bytes must be greater than zero");
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Retains packets ending within the retention window `max_age` (e.g. 24 hours).
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Returns the maximum number of packets buffered, if any.
+    pub fn max_packets(&self) -> Option<usize> {
+        self.max_packets
+    }
+
+    /// Returns the maximum total payload size of the packets buffered, if any.
+    pub fn max_bytes(&self) -> Option<usize> {
+        self.max_bytes
+    }
+
+    /// Returns the retention window, if any.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+}
+
+/// A packet buffered.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BufferedPacket {
+    /// The packet sequence number.
+    pub seq_num: u64,
+    /// The stream identifier (i.e. `LOC_B_S_SS`).
+    pub stream_id: String,
+    /// Start time of the first sample.
+    pub start_time: OffsetDateTime,
+    /// End time of the last sample.
+    pub end_time: OffsetDateTime,
+    /// The packet payload (e.g. a miniSEED record).
+    pub data: Bytes,
+}
+
+/// Extent of the packets of a station buffered.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StationExtent {
+    /// First packet sequence number available.
+    pub start_seq: u64,
+    /// Next sequence number available (i.e. last sequence number available + 1).
+    pub end_seq: u64,
+    /// Start time of the first packet available per stream.
+    pub start_times: HashMap<String, OffsetDateTime>,
+}
+
+#[derive(Debug, Default)]
+struct StationBuffer {
+    packets: VecDeque<BufferedPacket>,
+    next_seq: u64,
+    /// Total payload size of the packets buffered.
+    bytes: usize,
+}
+
+impl StationBuffer {
+    /// Evicts packets according to `policy`. Returns the number of packets evicted.
+    fn evict(&mut self, policy: &RetentionPolicy, now: OffsetDateTime) -> usize {
+        let retain_from = policy.max_age.map(|max_age| now - max_age);
+
+        let mut evicted = 0;
+        while let Some(packet) = self.packets.front() {
+            let exceeded = policy
+                .max_packets
+                .map_or(false, |max| self.packets.len() > max)
+                || policy.max_bytes.map_or(false, |max| self.bytes > max)
+                || retain_from.map_or(false, |t| packet.end_time < t);
+            if !exceeded {
+                break;
+            }
+
+            self.bytes -= packet.data.len();
+            self.packets.pop_front();
+            evicted += 1;
+        }
+
+        evicted
+    }
+
+    fn extent(&self) -> StationExtent {
+        let mut start_times = HashMap::new();
+        for packet in self.packets.iter() {
+            start_times
+                .entry(packet.stream_id.clone())
+                .and_modify(|t: &mut OffsetDateTime| *t = (*t).min(packet.start_time))
+                .or_insert(packet.start_time);
+        }
+
+        StationExtent {
+            start_seq: self
+                .packets
+                .front()
+                .map_or(self.next_seq, |packet| packet.seq_num),
+            end_seq: self.next_seq,
+            start_times,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Policies {
+    default: RetentionPolicy,
+    /// Retention policies per priority class.
+    classes: HashMap<String, RetentionPolicy>,
+    /// Priority classes per station.
+    station_classes: HashMap<String, String>,
+    /// Retention policies per station.
+    stations: HashMap<String, RetentionPolicy>,
+}
+
+impl Policies {
+    /// Resolves the retention policy of the station identified by `station_id`, i.e. the station
+    /// specific policy, the policy of its priority class or the default policy.
+    fn resolve(&self, station_id: &str) -> RetentionPolicy {
+        if let Some(policy) = self.stations.get(station_id) {
+            return *policy;
+        }
+
+        self.station_classes
+            .get(station_id)
+            .and_then(|class| self.classes.get(class))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    policies: Policies,
+    stations: HashMap<String, StationBuffer>,
+}
+
+/// Packet buffer keeping track of the packets per station (identified by `NET_STA`) subject to
+/// retention policies.
+///
+/// Retention policies are configured per station or per priority class (e.g. strong-motion
+/// stations keeping a longer history). Size based limits are enforced when pushing packets, retention windows by means of
+/// [`PacketBuffer::evict`] (see also [`spawn_eviction`]).
+#[derive(Clone, Debug, Default)]
+pub struct PacketBuffer {
+    inner: Arc<Mutex<Inner>>,
+    // notifies the streaming sessions about packets pushed
+    notify: Arc<Notify>,
+}
+
+impl PacketBuffer {
+    /// Creates a new buffer applying `policy` to all stations.
+    pub fn new(policy: RetentionPolicy) -> Self {
+        let buffer = Self::default();
+        buffer.inner.lock().unwrap().policies.default = policy;
+        buffer
+    }
+
+    /// Overrides the retention policy of the station identified by `station_id`. Takes precedence
+    /// over the policy of the station's priority class.
+    pub fn set_retention(&self, station_id: &str, policy: RetentionPolicy) {
+        self.inner
+            .lock()
+            .unwrap()
+            .policies
+            .stations
+            .insert(station_id.to_string(), policy);
+    }
+
+    /// Defines (or redefines) the priority class `class` with the retention policy `policy`.
+    pub fn define_class(&self, class: &str, policy: RetentionPolicy) {
+        self.inner
+            .lock()
+            .unwrap()
+            .policies
+            .classes
+            .insert(class.to_string(), policy);
+    }
+
+    /// Assigns the station identified by `station_id` to the priority class `class`. Stations of
+    /// undefined classes are subject to the default policy.
+    pub fn assign_class(&self, station_id: &str, class: &str) {
+        self.inner
+            .lock()
+            .unwrap()
+            .policies
+            .station_classes
+            .insert(station_id.to_string(), class.to_string());
+    }
+
+    /// Returns the retention policy effective for the station identified by `station_id`.
+    pub fn retention(&self, station_id: &str) -> RetentionPolicy {
+        self.inner.lock().unwrap().policies.resolve(station_id)
+    }
+
+    /// Buffers a packet of the station identified by `station_id` and returns its sequence number.
+    pub fn push(
+        &self,
+        station_id: &str,
+        stream_id: &str,
+        start_time: OffsetDateTime,
+        end_time: OffsetDateTime,
+        data: Bytes,
+    ) -> u64 {
+        let seq_num = {
+            let mut inner = self.inner.lock().unwrap();
+            let policy = inner.policies.resolve(station_id);
+            let station = inner.stations.entry(station_id.to_string()).or_default();
+
+            let seq_num = station.next_seq;
+            station.next_seq += 1;
+            station.bytes += data.len();
+            station.packets.push_back(BufferedPacket {
+                seq_num,
+                stream_id: stream_id.to_string(),
+                start_time,
+                end_time,
+                data,
+            });
+            station.evict(
+                &RetentionPolicy {
+                    max_age: None,
+                    ..policy
+                },
+                end_time,
+            );
+
+            seq_num
+        };
+        self.notify.notify_waiters();
+
+        seq_num
+    }
+
+    /// Returns a future completing once the next packet is pushed.
+    ///
+    /// Note that the future is notified about packets pushed after its creation, even if it was
+    /// not polled, yet.
+    pub(crate) fn notified(&self) -> Notified<'_> {
+        self.notify.notified()
+    }
+
+    /// Returns the packets of the station identified by `station_id` starting at `seq_num`.
+    pub fn packets_from(&self, station_id: &str, seq_num: u64) -> Vec<BufferedPacket> {
+        let inner = self.inner.lock().unwrap();
+        match inner.stations.get(station_id) {
+            Some(station) => station
+                .packets
+                .iter()
+                .filter(|packet| packet.seq_num >= seq_num)
+                .cloned()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Evicts the packets outside the retention windows at `now`. Returns the number of packets
+    /// evicted.
+    pub fn evict(&self, now: OffsetDateTime) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let Inner { policies, stations } = &mut *inner;
+
+        stations
+            .iter_mut()
+            .map(|(station_id, station)| station.evict(&policies.resolve(station_id), now))
+            .sum()
+    }
+
+    /// Returns the extent of the packets of the station identified by `station_id` buffered.
+    pub fn extent(&self, station_id: &str) -> Option<StationExtent> {
+        let inner = self.inner.lock().unwrap();
+        inner.stations.get(station_id).map(StationBuffer::extent)
+    }
+}
+
+/// Adjusts the station start sequence numbers and stream start times of an `INFO STATIONS` or
+/// `INFO STREAMS` response according to the packets buffered by `buffer`.
+pub fn apply_retention(info: &mut StationsInfoV4, buffer: &PacketBuffer) {
+    for station in info.station.iter_mut() {
+        let extent = match buffer.extent(&station.id().to_string()) {
+            Some(extent) => extent,
+            None => continue,
+        };
+
+        station.set_start_seq(extent.start_seq.max(station.start_seq()));
+        if let Some(streams) = station.streams_mut() {
+            for stream in streams.iter_mut() {
+                if let Some(start_time) = extent.start_times.get(&stream.id().to_string()) {
+                    let start_time = (*start_time).max(*stream.start_time());
+                    stream.set_start_time(start_time);
+                }
+            }
+        }
+    }
+}
+
+/// Spawns a task evicting the packets of `buffer` outside the retention windows every `interval`.
+///
+/// Cached `INFO` responses are invalidated whenever packets were evicted. The task is shut down
+/// together with the server (see [`ServerHandle::shutdown`]).
+pub fn spawn_eviction(buffer: PacketBuffer, interval: Duration, server_handle: ServerHandle) {
+    let tasks = server_handle.tasks().clone();
+    tasks.spawn(
+        Subsystem::Buffer,
+        eviction_loop(buffer, interval, server_handle),
+    );
+}
+
+async fn eviction_loop(buffer: PacketBuffer, interval: Duration, mut server_handle: ServerHandle) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+
+        let evicted = buffer.evict(OffsetDateTime::now_utc());
+        if evicted > 0 {
+            debug!("evicted {} packets", evicted);
+            server_handle.invalidate_info_cache().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use time::macros::datetime;
+
+    #[test]
+    fn priority_classes() {
+        let buffer = PacketBuffer::new(RetentionPolicy::default().with_max_packets(1));
+        buffer.define_class(
+            "strong-motion",
+            RetentionPolicy::default().with_max_bytes(8),
+        );
+        buffer.assign_class("GE_WLF", "strong-motion");
+        buffer.assign_class("GE_APE", "undefined");
+        buffer.set_retention("GE_STU", RetentionPolicy::default());
+        buffer.assign_class("GE_STU", "strong-motion");
+
+        assert_eq!(
+            buffer.retention("GE_APE"),
+            RetentionPolicy::default().with_max_packets(1)
+        );
+        assert_eq!(buffer.retention("GE_STU"), RetentionPolicy::default());
+
+        let t = datetime!(2023-01-01 12:00 UTC);
+        for _ in 0..3 {
+            for station_id in ["GE_WLF", "GE_APE", "GE_STU"] {
+                buffer.push(station_id, "_B_H_Z", t, t, Bytes::from_static(b"1234"));
+            }
+        }
+
+        assert_eq!(buffer.extent("GE_WLF").unwrap().start_seq, 1);
+        assert_eq!(buffer.extent("GE_APE").unwrap().start_seq, 2);
+        assert_eq!(buffer.extent("GE_STU").unwrap().start_seq, 0);
+    }
+
+    #[test]
+    fn evict_retention_window() {
+        let buffer =
+            PacketBuffer::new(RetentionPolicy::default().with_max_age(Duration::from_secs(3600)));
+        buffer.set_retention("GE_APE", RetentionPolicy::default().with_max_packets(1));
+
+        for (i, start_time) in [
+            datetime!(2023-01-01 09:00 UTC),
+            datetime!(2023-01-01 10:00 UTC),
+            datetime!(2023-01-01 11:00 UTC),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let end_time = start_time + Duration::from_secs(60);
+            let seq_num = buffer.push("GE_WLF", "_B_H_Z", start_time, end_time, Bytes::new());
+            assert_eq!(seq_num, i as u64);
+            buffer.push("GE_APE", "_B_H_Z", start_time, end_time, Bytes::new());
+        }
+
+        // size based limit
+        let extent = buffer.extent("GE_APE").unwrap();
+        assert_eq!((extent.start_seq, extent.end_seq), (2, 3));
+
+        assert_eq!(buffer.evict(datetime!(2023-01-01 11:30 UTC)), 2);
+        let extent = buffer.extent("GE_WLF").unwrap();
+        assert_eq!((extent.start_seq, extent.end_seq), (2, 3));
+        assert_eq!(
+            extent.start_times.get("_B_H_Z"),
+            Some(&datetime!(2023-01-01 11:00 UTC))
+        );
+        assert_eq!(buffer.packets_from("GE_WLF", 0).len(), 1);
+
+        assert_eq!(buffer.evict(datetime!(2023-01-01 13:00 UTC)), 1);
+        let extent = buffer.extent("GE_WLF").unwrap();
+        assert_eq!((extent.start_seq, extent.end_seq), (3, 3));
+        assert!(extent.start_times.is_empty());
+        assert!(buffer.extent("GE_FOO").is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use slink::InfoCmdV4;
+
+// TODO(damb): invalidate cached responses on publish events
+
+/// Cache key of a serialized `INFO` response.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+struct CacheKey {
+    cmd: InfoCmdV4,
+    /// The data center description announced by the response, i.e. the description of the listener
+    /// the client connected to.
+    data_center_description: String,
+    /// Whether holdback windows were applied to the response.
+    holdback: bool,
+    /// The protocol versions advertised by the response.
+    protocol_versions: Vec<(u8, u8)>,
+}
+
+#[derive(Clone, Debug)]
+struct CacheEntry {
+    packet: Vec<u8>,
+    created: Instant,
+}
+
+/// `INFO` response cache statistics.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct InfoCacheStats {
+    /// Number of responses served from the cache.
+    pub hits: u64,
+    /// Number of responses generated.
+    pub misses: u64,
+    /// Number of cached responses.
+    pub entries: usize,
+}
+
+impl InfoCacheStats {
+    /// Returns the cache hit rate, if any response was requested at all.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return None;
+        }
+
+        Some(self.hits as f64 / total as f64)
+    }
+}
+
+/// Cache of serialized `INFO` responses keyed by the `INFO` item and patterns.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct InfoCache {
+    entries: HashMap<CacheKey, CacheEntry>,
+
+    hits: u64,
+    misses: u64,
+}
+
+impl InfoCache {
+    /// Returns the cached response packet, if any. Responses older than `ttl` are evicted.
+    pub fn get(
+        &mut self,
+        cmd: &InfoCmdV4,
+        data_center_description: &str,
+        holdback: bool,
+        protocol_versions: &[(u8, u8)],
+        ttl: Duration,
+    ) -> Option<Vec<u8>> {
+        let key = CacheKey {
+            cmd: cmd.clone(),
+            data_center_description: data_center_description.to_string(),
+            holdback,
+            protocol_versions: protocol_versions.to_vec(),
+        };
+
+        match self.entries.get(&key) {
+            Some(entry) if entry.created.elapsed() < ttl => {
+                self.hits += 1;
+                Some(entry.packet.clone())
+            }
+            Some(_) => {
+                self.entries.remove(&key);
+                self.misses += 1;
+                None
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches the response packet `packet`.
+    pub fn insert(
+        &mut self,
+        cmd: InfoCmdV4,
+        data_center_description: &str,
+        holdback: bool,
+        protocol_versions: &[(u8, u8)],
+        packet: Vec<u8>,
+    ) {
+        self.entries.insert(
+            CacheKey {
+                cmd,
+                data_center_description: data_center_description.to_string(),
+                holdback,
+                protocol_versions: protocol_versions.to_vec(),
+            },
+            CacheEntry {
+                packet,
+                created: Instant::now(),
+            },
+        );
+    }
+
+    /// Invalidates all cached responses.
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the cache statistics.
+    pub fn stats(&self) -> InfoCacheStats {
+        InfoCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use slink::InfoCmdItemV4;
+
+    const TTL: Duration = Duration::from_secs(60);
+    const VERSIONS: &[(u8, u8)] = &[(4, 0)];
+    const DESCRIPTION: &str = "GEOFON";
+
+    #[tokio::test(start_paused = true)]
+    async fn get_and_expire() {
+        let mut cache = InfoCache::default();
+        let cmd = InfoCmdV4::new(InfoCmdItemV4::Streams);
+
+        assert_eq!(cache.get(&cmd, DESCRIPTION, true, VERSIONS, TTL), None);
+        cache.insert(cmd.clone(), DESCRIPTION, true, VERSIONS, b"foo".to_vec());
+        assert_eq!(
+            cache.get(&cmd, DESCRIPTION, true, VERSIONS, TTL),
+            Some(b"foo".to_vec())
+        );
+        assert_eq!(cache.get(&cmd, DESCRIPTION, false, VERSIONS, TTL), None);
+
+        tokio::time::advance(TTL).await;
+        assert_eq!(cache.get(&cmd, DESCRIPTION, true, VERSIONS, TTL), None);
+
+        let stats = cache.stats();
+        assert_eq!(
+            stats,
+            InfoCacheStats {
+                hits: 1,
+                misses: 3,
+                entries: 0
+            }
+        );
+        assert_eq!(stats.hit_rate(), Some(0.25));
+    }
+
+    #[tokio::test]
+    async fn invalidate() {
+        let mut cache = InfoCache::default();
+        let cmd = InfoCmdV4::new(InfoCmdItemV4::Stations);
+
+        cache.insert(cmd.clone(), DESCRIPTION, false, VERSIONS, b"foo".to_vec());
+        cache.invalidate();
+        assert_eq!(cache.get(&cmd, DESCRIPTION, false, VERSIONS, TTL), None);
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[tokio::test]
+    async fn keyed_by_data_center_description() {
+        let mut cache = InfoCache::default();
+        let cmd = InfoCmdV4::new(InfoCmdItemV4::Stations);
+
+        cache.insert(cmd.clone(), "GEOFON", false, VERSIONS, b"foo".to_vec());
+        assert_eq!(cache.get(&cmd, "ORFEUS", false, VERSIONS, TTL), None);
+        assert_eq!(
+            cache.get(&cmd, "GEOFON", false, VERSIONS, TTL),
+            Some(b"foo".to_vec())
+        );
+    }
+}
@@ -1,34 +1,42 @@
 use std::io;
 use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
 use std::time::Duration;
 
+use bytes::BytesMut;
+use futures::future::FutureExt;
 use futures::stream::StreamExt;
 use serde::Serialize;
 use socket2::{SockRef, TcpKeepalive};
-use tokio::io::AsyncWriteExt;
-use tokio::net::{
-    tcp::{ReadHalf, WriteHalf},
-    TcpStream,
-};
+use time::OffsetDateTime;
+use tokio::io::{self as tokio_io, AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio::net::TcpStream;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot;
-use tokio::task::JoinHandle;
+use tokio::task::AbortHandle;
 use tokio::{select, try_join};
-use tokio_util::codec::FramedRead;
-use tracing::{error, trace};
+use tokio_util::codec::{Encoder, FramedRead};
+use tracing::{error, info_span, trace, Instrument};
 
+use slink::wire::{self, conformance};
 use slink::{
-    pack_info_err_v4, pack_info_ok_v4, to_first_hello_resp_line_v4, CommandV4, InfoV4,
-    ProtocolErrorV4,
+    pack_info_err_v4, pack_info_ok_v4, sign_packet_v4, CommandV3, CommandV4, InfoV4,
+    ProtocolErrorV3, ProtocolErrorV4,
 };
 
 use crate::negotiate::StationNegotiator;
+use crate::replay::ReplayRequest;
 use crate::response::Hello;
-use crate::seedlink::{ParseError, ProtocolVersion, SeedLinkCodec};
+use crate::seedlink::{Command, ParseError, ProtocolVersion, SeedLinkCodec};
 use crate::server::{ServerHandle, ToServer};
+use crate::streaming::StreamingHandle;
+use crate::task::{panic_message, Subsystem};
+use crate::trace;
+use crate::traffic::{TrafficRecorder, TrafficStats};
+use crate::ClientId;
 use crate::Select;
-use crate::{ClientId, HIGHEST_SUPPORTED_PROTO_VERSION};
 
 /// Messages received from the main server loop.
 pub enum FromServer {
@@ -36,6 +44,13 @@ pub enum FromServer {
     Info(InfoV4),
     Ok,
     Error(String),
+    Raw(Vec<u8>),
+    /// SeedLink `v4` data packet (already encoded, see [`Quarantine::encode_v4`]).
+    ///
+    /// [`Quarantine::encode_v4`]: crate::Quarantine::encode_v4
+    Packet(Vec<u8>),
+    /// Terminates the connection with `END`, e.g. when draining the server.
+    End,
 }
 
 /// A handle to the client actor, used by the server.
@@ -43,15 +58,23 @@ pub enum FromServer {
 pub struct ClientHandle {
     pub id: ClientId,
     chan: Sender<FromServer>,
-    kill: JoinHandle<()>,
+    kill: AbortHandle,
 
     ip: SocketAddr,
+    protocol_versions: Vec<(u8, u8)>,
+    data_center_description: Option<String>,
 
     pub useragent_info: Vec<(String, String)>,
     authenticated: bool,
+    auth_expires: Option<OffsetDateTime>,
 
     pub selects: Vec<Select>,
     pub negotiator: Option<StationNegotiator>,
+    /// The playback requested, if any (see [`CAPABILITY_REPLAY`](crate::CAPABILITY_REPLAY)).
+    pub replay: Option<ReplayRequest>,
+    streaming: Option<StreamingHandle>,
+
+    traffic: Arc<TrafficRecorder>,
 }
 
 impl ClientHandle {
@@ -60,16 +83,86 @@ impl ClientHandle {
         &self.ip
     }
 
+    /// Returns the protocol versions advertised to the client (sorted in descending order).
+    pub fn protocol_versions(&self) -> &Vec<(u8, u8)> {
+        &self.protocol_versions
+    }
+
+    /// Returns the data center description of the listener the client connected to, if
+    /// configured (see [`ListenerConfig::with_data_center_description`]).
+    ///
+    /// [`ListenerConfig::with_data_center_description`]: crate::ListenerConfig::with_data_center_description
+    pub fn data_center_description(&self) -> Option<&str> {
+        self.data_center_description.as_deref()
+    }
+
     /// Returns whether the client is authenticated.
+    ///
+    /// Note that clients with expired credentials are not authenticated.
     pub fn authenticated(&self) -> bool {
+        if let Some(auth_expires) = self.auth_expires {
+            return self.authenticated && auth_expires > OffsetDateTime::now_utc();
+        }
+
         self.authenticated
     }
 
+    /// Marks the client as authenticated until `expires` (if any).
+    pub fn set_authenticated(&mut self, expires: Option<OffsetDateTime>) {
+        self.authenticated = true;
+        self.auth_expires = expires;
+    }
+
+    /// Revokes the client's authentication.
+    pub fn revoke_authentication(&mut self) {
+        self.authenticated = false;
+        self.auth_expires = None;
+    }
+
+    /// Returns whether the client is exempt from station holdback windows.
+    pub fn holdback_exempt(&self) -> bool {
+        self.authenticated()
+    }
+
+    /// Returns the byte accounting of the client connection.
+    pub fn traffic(&self) -> TrafficStats {
+        self.traffic.snapshot()
+    }
+
     /// Returns whether the client is currently negotiating.
     pub fn is_negotiating(&self) -> bool {
         self.negotiator.is_some()
     }
 
+    /// Returns whether the client is currently streaming (i.e. after `END` or `ENDFETCH`).
+    pub fn is_streaming(&self) -> bool {
+        self.streaming.is_some()
+    }
+
+    /// Attaches the streaming session `streaming` to the client. The session is aborted when the
+    /// client disconnects.
+    pub(crate) fn set_streaming(&mut self, streaming: StreamingHandle) {
+        self.streaming = Some(streaming);
+    }
+
+    /// Terminates the connection with `END` after the data queued. Streaming clients receive the
+    /// packets available, first (see [`StreamingHandle::drain`]).
+    pub(crate) fn drain(&mut self) -> Result<(), io::Error> {
+        match self.streaming {
+            Some(ref mut streaming) => {
+                streaming.drain();
+                Ok(())
+            }
+            None => self.send(FromServer::End),
+        }
+    }
+
+    /// Returns a sender to this client actor, e.g. used by streaming sessions. Contrary to
+    /// [`ClientHandle::send`], sending by means of the sender awaits capacity.
+    pub(crate) fn sender(&self) -> Sender<FromServer> {
+        self.chan.clone()
+    }
+
     /// Sends a message to this client actor.
     ///
     /// Will emit an error if sending does not succeed immediately, as this means that forwarding
@@ -93,12 +186,24 @@ impl Drop for ClientHandle {
     }
 }
 
+/// Enumeration of streams a client actor talks to.
+pub enum ClientStream {
+    Tcp(TcpStream),
+    /// In-memory stream, e.g. used for testing.
+    Mem(DuplexStream),
+}
+
 /// Struct constructed by the accept loop and used as the argument to `spawn_client`.
 pub struct ClientInfo {
     pub ip: SocketAddr,
     pub id: ClientId,
     pub handle: ServerHandle,
-    pub tcp: TcpStream,
+    pub stream: ClientStream,
+    /// The protocol versions advertised to the client (sorted in descending order).
+    pub protocol_versions: Vec<(u8, u8)>,
+    /// The data center description announced to the client (already encoded), overriding the
+    /// server's description.
+    pub data_center_description: Option<String>,
 }
 
 /// Struct storing the information used internally by the client actor.
@@ -106,36 +211,58 @@ struct ClientData {
     id: ClientId,
     handle: ServerHandle,
     recv: Receiver<FromServer>,
-    tcp: TcpStream,
+    stream: ClientStream,
+    protocol_versions: Vec<(u8, u8)>,
+    traffic: Arc<TrafficRecorder>,
 }
 
 /// Spawns a new client actor.
 pub fn spawn_client(info: ClientInfo) {
     let (send, recv) = channel(64);
+    let traffic = Arc::new(TrafficRecorder::default());
 
     let data = ClientData {
         id: info.id,
         handle: info.handle.clone(),
-        tcp: info.tcp,
+        stream: info.stream,
         recv,
+        protocol_versions: info.protocol_versions.clone(),
+        traffic: traffic.clone(),
     };
 
-    // XXX(damb): spawn client actor task
+    // XXX(damb): spawn client actor task; the connection is dropped if the server is shutting down
     let (my_send, my_recv) = oneshot::channel();
-    let client_join_handle = tokio::spawn(start_client(my_recv, data));
+    let client_abort_handle = match info.handle.tasks().spawn(
+        Subsystem::Client,
+        start_client(my_recv, data).instrument(info_span!(
+            target: trace::CLIENT,
+            "client",
+            client_id = ?info.id,
+            peer = %info.ip
+        )),
+    ) {
+        Some(abort_handle) => abort_handle,
+        None => return,
+    };
 
     // Then we create a ClientHandle to this new task, and use the oneshot
     // channel to send it to the task.
     let client_handle = ClientHandle {
         id: info.id,
         chan: send,
-        kill: client_join_handle,
+        kill: client_abort_handle,
 
         ip: info.ip,
+        protocol_versions: info.protocol_versions,
+        data_center_description: info.data_center_description,
         useragent_info: Vec::default(),
         authenticated: false,
+        auth_expires: None,
         selects: vec![],
         negotiator: None,
+        replay: None,
+        streaming: None,
+        traffic,
     };
 
     // Ignore sending errors here. Should only happen if the server is shutting
@@ -146,7 +273,7 @@ pub fn spawn_client(info: ClientInfo) {
 async fn start_client(my_handle: oneshot::Receiver<ClientHandle>, mut data: ClientData) {
     // Wait for `client_handle` to send us the `ClientHandle` so we can forward
     // it to the main loop. We need the oneshot channel because we cannot
-    // otherwise get the `JoinHandle` returned by `tokio::spawn`. We forward it
+    // otherwise get the `AbortHandle` returned when spawning. We forward it
     // from here instead of in `spawn_client` because we want the server to see
     // the NewClient message before this actor starts sending other messages.
     let client_handle = match my_handle.await {
@@ -160,79 +287,129 @@ async fn start_client(my_handle: oneshot::Receiver<ClientHandle>, mut data: Clie
 
     // We sent the client handle to the main server loop. Start talking to the tcp
     // connection.
-    let res = client_loop(data).await;
-    match res {
-        Ok(()) => {}
-        Err(err) => {
-            error!("Error while shutting down client loop: {}.", err);
+    //
+    // XXX(damb): isolate panics, i.e. a panicking client actor must not affect other clients
+    let res = AssertUnwindSafe(client_loop(data)).catch_unwind().await;
+    let msg = match res {
+        Ok(Ok(())) => ToServer::DisconnectClient(client_id),
+        Ok(Err(err)) => {
+            error!(
+                "{:?}: Error while shutting down client loop: {}.",
+                client_id, err
+            );
+            ToServer::DisconnectClient(client_id)
+        }
+        Err(payload) => {
+            server_handle.record_client_panic();
+            ToServer::ClientPanicked(client_id, panic_message(payload.as_ref()))
         }
     };
 
     // Inform server about the client loop termination.
-    server_handle
-        .send(ToServer::DisconnectClient(client_id))
-        .await;
-    println!("shutdown");
+    server_handle.send(msg).await;
+    trace!("{:?}: client actor terminated", client_id);
 }
 
 /// This method performs the actual job of running the client actor.
-async fn client_loop(mut client_data: ClientData) -> Result<(), io::Error> {
-    let sock_ref = SockRef::from(&client_data.tcp);
-
-    let tcp_keepalive = TcpKeepalive::new()
-        .with_time(Duration::from_secs(60))
-        .with_interval(Duration::from_secs(20));
-
-    sock_ref.set_tcp_keepalive(&tcp_keepalive)?;
+async fn client_loop(client_data: ClientData) -> Result<(), io::Error> {
+    match client_data.stream {
+        ClientStream::Tcp(tcp) => {
+            let sock_ref = SockRef::from(&tcp);
+
+            let tcp_keepalive = TcpKeepalive::new()
+                .with_time(Duration::from_secs(60))
+                .with_interval(Duration::from_secs(20));
+
+            sock_ref.set_tcp_keepalive(&tcp_keepalive)?;
+
+            stream_loop(
+                client_data.id,
+                tcp,
+                client_data.handle,
+                client_data.recv,
+                client_data.protocol_versions,
+                client_data.traffic,
+            )
+            .await
+        }
+        ClientStream::Mem(mem) => {
+            stream_loop(
+                client_data.id,
+                mem,
+                client_data.handle,
+                client_data.recv,
+                client_data.protocol_versions,
+                client_data.traffic,
+            )
+            .await
+        }
+    }
+}
 
-    let (read, write) = client_data.tcp.split();
+/// Talks to the stream `stream` until either side terminates.
+async fn stream_loop<S: AsyncRead + AsyncWrite + Send + Unpin>(
+    client_id: ClientId,
+    mut stream: S,
+    server_handle: ServerHandle,
+    recv: Receiver<FromServer>,
+    protocol_versions: Vec<(u8, u8)>,
+    traffic: Arc<TrafficRecorder>,
+) -> Result<(), io::Error> {
+    let (read, write) = tokio_io::split(&mut stream);
 
     // direct communication between tcp_read and tcp_write
-    let (send, recv) = unbounded_channel();
+    let (send, from_tcp_read) = unbounded_channel();
 
+    let signing_key = server_handle.packet_signing_key().cloned();
     let ((), ()) = try_join! {
-        tcp_read(client_data.id, read, client_data.handle, send),
-        tcp_write(client_data.id, write, client_data.recv, recv),
+        tcp_read(client_id, read, server_handle, send, &protocol_versions, &traffic),
+        tcp_write(client_id, write, recv, from_tcp_read, signing_key.as_deref(), &traffic),
     }?;
 
-    let _ = client_data.tcp.shutdown().await;
+    let _ = stream.shutdown().await;
 
     Ok(())
 }
 
 #[derive(Debug)]
 enum InternalMessage {
+    Ok,
     ProtocolError(ProtocolErrorV4),
+    ProtocolErrorV3(ProtocolErrorV3),
 }
 
-async fn tcp_read(
+async fn tcp_read<R: AsyncRead + Unpin>(
     client_id: ClientId,
-    read: ReadHalf<'_>,
+    read: R,
     mut server_handle: ServerHandle,
     to_tcp_write: UnboundedSender<InternalMessage>,
+    protocol_versions: &[(u8, u8)],
+    traffic: &TrafficRecorder,
 ) -> Result<(), io::Error> {
-    let mut framed_read = FramedRead::new(read, SeedLinkCodec::new(client_id));
+    let codec = SeedLinkCodec::new(client_id)
+        .with_limits(server_handle.command_line_limits())
+        .with_protocol_versions(protocol_versions);
+    let mut framed_read = FramedRead::new(read, codec);
     let mut next_cmd = framed_read.next().await;
     while let Some(ref res) = next_cmd {
         trace!("{:?}: <- {:?} ", client_id, res);
+        traffic.set_bytes_received(framed_read.decoder().bytes_decoded());
         match res {
-            Ok(cmd_v4) => {
+            Ok(Command::V4(cmd_v4)) => {
                 // handle protocol version request
                 if let CommandV4::SlProto(slproto) = cmd_v4 {
                     let res = framed_read
                         .decoder_mut()
                         .try_set_protocol_version((slproto.major, slproto.minor).into());
-                    match res {
-                        Ok(_) => {}
-                        Err(err) => {
-                            to_tcp_write
-                                .send(InternalMessage::ProtocolError(err))
-                                .map_err(|e| {
-                                    io::Error::new(io::ErrorKind::BrokenPipe, e.to_string())
-                                })?;
-                        }
+                    let msg = match res {
+                        Ok(_) => InternalMessage::Ok,
+                        Err(err) => InternalMessage::ProtocolError(err),
                     };
+                    to_tcp_write
+                        .send(msg)
+                        .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
 
+                    next_cmd = framed_read.next().await;
                     continue;
                 } else {
                     match cmd_v4 {
@@ -245,25 +422,18 @@ async fn tcp_read(
                     }
                 }
 
-                if let CommandV4::Unknown(cmd) = cmd_v4 {
-                    let mut unsupported_err = ProtocolErrorV4::unsupported_command();
-                    unsupported_err.message = Some(
-                        format!(
-                            "{}: '{}'",
-                            unsupported_err.code.description(),
-                            cmd.command_name
-                        )
-                        .into(),
-                    );
-
-                    to_tcp_write
-                        .send(InternalMessage::ProtocolError(unsupported_err))
-                        .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
-                } else {
-                    server_handle
-                        .send(ToServer::Command(client_id, cmd_v4.clone()))
-                        .await;
+                server_handle
+                    .send(ToServer::Command(client_id, cmd_v4.clone()))
+                    .await;
+            }
+            Ok(Command::V3(cmd_v3)) => {
+                if !matches!(cmd_v3, CommandV3::Hello(_)) {
+                    framed_read.decoder_mut().lock_protocol_version();
                 }
+
+                server_handle
+                    .send(ToServer::CommandV3(client_id, cmd_v3.clone()))
+                    .await;
             }
             Err(err) => {
                 // XXX(damb): usually errors inform `FramedRead` that the stream is corrupted and
@@ -278,6 +448,16 @@ async fn tcp_read(
                         // XXX(damb): do not recover from this error
                         break;
                     }
+                    ParseError::ProtocolErrorV3(err) => {
+                        to_tcp_write
+                            .send(InternalMessage::ProtocolErrorV3(err.clone()))
+                            .map_err(|e| {
+                                io::Error::new(io::ErrorKind::BrokenPipe, e.to_string())
+                            })?;
+
+                        // XXX(damb): resume the stream and don't disconnect the client
+                        let _ = framed_read.next().await;
+                    }
                     ParseError::ProtocolError(err) => {
                         if err.info {
                             // XXX(damb): `INFO` command errors require special treatment and are
@@ -306,21 +486,28 @@ async fn tcp_read(
     Ok(())
 }
 
-// TODO(damb): implement encoder which allows versionized response encoding
-async fn tcp_write(
+// TODO(damb): encode responses other than `HELLO` by means of the codec
+async fn tcp_write<W: AsyncWrite + Unpin>(
     client_id: ClientId,
-    mut write: WriteHalf<'_>,
+    mut write: W,
     mut recv: Receiver<FromServer>,
     mut from_tcp_read: UnboundedReceiver<InternalMessage>,
+    signing_key: Option<&[u8]>,
+    traffic: &TrafficRecorder,
 ) -> Result<(), io::Error> {
+    let mut codec = SeedLinkCodec::new(client_id);
+    let mut buf = BytesMut::new();
     loop {
         select! {
             msg = recv.recv() => match msg {
                 Some(FromServer::Hello(msg)) => {
                     trace!("{:?}: -> {:?}", client_id, msg);
-            let msg = format!("{first_resp_line}\r\n{dc_desc}\r\n", first_resp_line = to_first_hello_resp_line_v4(&msg.implementation, &msg.implementation_version, &vec![(HIGHEST_SUPPORTED_PROTO_VERSION.0, HIGHEST_SUPPORTED_PROTO_VERSION.1)], &None), dc_desc = msg.data_center_description);
+                    buf.clear();
+                    codec.encode(FromServer::Hello(msg), &mut buf)?;
 
-                    write.write_all(msg.as_bytes()).await?;
+                    debug_assert_eq!(conformance::check_lines(&buf), Ok(()));
+                    write.write_all(&buf).await?;
+                    traffic.add_bytes_sent(buf.len());
                 },
                 Some(FromServer::Info(info_v4)) => {
                     trace!("{:?}: -> {:?}", client_id, info_v4);
@@ -341,27 +528,82 @@ async fn tcp_write(
                         pack_info_ok_v4(&serialized).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?,
                     };
 
+                    debug_assert_eq!(conformance::check_packet_v4(&packet), Ok(()));
+                    let packet = match signing_key {
+                        Some(key) => sign_packet_v4(&packet, key).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?,
+                        None => packet,
+                    };
+
                     write.write_all(&packet).await?;
+                    traffic.add_packet_sent(packet.len() - serialized.len(), serialized.len());
                 },
                 Some(FromServer::Ok) => {
                     trace!("{:?}: -> OK", client_id);
-                    write.write_all("OK\r\n".as_bytes()).await?
+                    write.write_all("OK\r\n".as_bytes()).await?;
+                    traffic.add_bytes_sent(4);
 
                 }
                 Some(FromServer::Error(msg)) => {
                     trace!("{:?}: -> {:?}", client_id, msg);
+                    debug_assert_eq!(conformance::check_line(msg.as_bytes()), Ok(()));
                     write.write_all(msg.as_bytes()).await?;
-                    write.write_all(&[b'\r', b'\n']).await?
+                    write.write_all(&[b'\r', b'\n']).await?;
+                    traffic.add_bytes_sent(msg.len() + 2);
+                }
+                Some(FromServer::Raw(buf)) => {
+                    trace!("{:?}: -> {} bytes", client_id, buf.len());
+                    // XXX(damb): raw responses are opaque (e.g. extension command responses) and
+                    // thus, not checked for conformance
+                    write.write_all(&buf).await?;
+                    traffic.add_bytes_sent(buf.len());
+                }
+                Some(FromServer::Packet(packet)) => {
+                    trace!("{:?}: -> {} bytes (packet)", client_id, packet.len());
+                    debug_assert_eq!(conformance::check_packet_v4(&packet), Ok(()));
+                    let len_header = wire::v4::parse_header(&packet)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?
+                        .len_header();
+                    let len_payload = packet.len() - len_header;
+                    let packet = match signing_key {
+                        Some(key) => sign_packet_v4(&packet, key).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?,
+                        None => packet,
+                    };
+
+                    write.write_all(&packet).await?;
+                    traffic.add_packet_sent(packet.len() - len_payload, len_payload);
+                }
+                Some(FromServer::End) => {
+                    trace!("{:?}: -> END", client_id);
+                    write.write_all("END\r\n".as_bytes()).await?;
+                    traffic.add_bytes_sent(5);
+                    // XXX(damb): nothing follows, i.e. signal the client to disconnect
+                    write.shutdown().await?;
+                    break;
                 }
                 None => {
                     break;
                 },
             },
             msg = from_tcp_read.recv() => match msg {
+                Some(InternalMessage::Ok) => {
+                    trace!("{:?}: -> OK", client_id);
+                    write.write_all("OK\r\n".as_bytes()).await?;
+                    traffic.add_bytes_sent(4);
+                },
                 Some(InternalMessage::ProtocolError(err)) => {
                     trace!("{:?}: -> {:?}", client_id, err);
-                    write.write_all(err.to_string().as_bytes()).await?;
-                    write.write_all(&[b'\r', b'\n']).await?
+                    debug_assert_eq!(conformance::check_line(err.to_string().as_bytes()), Ok(()));
+                    let msg = err.to_string();
+                    write.write_all(msg.as_bytes()).await?;
+                    write.write_all(&[b'\r', b'\n']).await?;
+                    traffic.add_bytes_sent(msg.len() + 2);
+                },
+                Some(InternalMessage::ProtocolErrorV3(err)) => {
+                    trace!("{:?}: -> {:?}", client_id, err);
+                    let msg = err.to_string();
+                    write.write_all(msg.as_bytes()).await?;
+                    write.write_all(&[b'\r', b'\n']).await?;
+                    traffic.add_bytes_sent(msg.len() + 2);
                 },
                 None => {
                     break;
@@ -378,6 +620,7 @@ fn send_generic_error(
     to_tcp_write: &UnboundedSender<InternalMessage>,
 ) {
     let msg = match protocol_version.major {
+        0..=3 => InternalMessage::ProtocolErrorV3(ProtocolErrorV3),
         4 => InternalMessage::ProtocolError(ProtocolErrorV4::generic()),
         _ => {
             todo!();
@@ -1,24 +1,50 @@
 use std::io;
+use std::time::Duration;
 
-use slink::{CommandV4, InfoCmdItemV4, InfoV4, ProtocolErrorV4};
+use time::OffsetDateTime;
+use tracing::{debug, warn};
 
+use slink::wire::conformance;
+use slink::{
+    pack_info_ok_v4, AuthV4, CommandV3, CommandV4, ErrorInfoV4, InfoBuilderV4, InfoCmdItemV4,
+    InfoCmdV4, InfoV4, ProtocolErrorV3, ProtocolErrorV4, StationV4, UnknownCmdV4,
+    PACKET_SIGNATURE_CAPABILITY_V4,
+};
+
+use crate::breaker::CircuitBreaker;
+use crate::buffer::apply_retention;
+use crate::cache::InfoCache;
 use crate::client::{ClientHandle, FromServer};
+use crate::holdback::apply_holdback;
 use crate::negotiate::StationNegotiator;
-use crate::response::Hello;
-use crate::select::Select;
+use crate::replay::{ReplayRequest, REPLAY_COMMAND};
+use crate::response::{Hello, HelloV4};
+use crate::select::{validate_pattern, Select};
+use crate::streaming::{Session, TransferMode};
+use crate::task::TaskRegistry;
 use crate::util::to_id_info_v4;
-use crate::{SeedLinkServer, HIGHEST_SUPPORTED_PROTO_VERSION};
+use crate::{
+    ExtensionResponse, Quarantine, RequestContext, SeedLinkServer, UnknownCommandPolicy,
+    CAPABILITY_AUTH_REFRESH, CAPABILITY_REPLAY,
+};
 
 #[derive(Clone, Debug, Default)]
 pub struct Dispatcher<T> {
     server: T,
+
+    info_cache: InfoCache,
+    breaker: CircuitBreaker,
+
+    // data center description encoded
+    data_center_description: String,
+    // whether SeedLink v4 packets are signed
+    sign_packets: bool,
+
+    tasks: TaskRegistry,
+    quarantine: Quarantine,
 }
 
 impl<T> Dispatcher<T> {
-    pub fn new(mut service: T) -> Self {
-        Self { server: service }
-    }
-
     pub fn server(&self) -> &T {
         &self.server
     }
@@ -26,23 +52,409 @@ impl<T> Dispatcher<T> {
     pub fn server_mut(&mut self) -> &mut T {
         &mut self.server
     }
+
+    pub fn info_cache(&self) -> &InfoCache {
+        &self.info_cache
+    }
+
+    pub fn info_cache_mut(&mut self) -> &mut InfoCache {
+        &mut self.info_cache
+    }
 }
 
 impl<T: SeedLinkServer> Dispatcher<T> {
+    pub fn new(mut service: T, tasks: TaskRegistry, quarantine: Quarantine) -> Self {
+        let data_center_description = service
+            .description_encoding()
+            .encode(service.data_center_description());
+        let sign_packets = service.packet_signing_key().is_some();
+        Self {
+            server: service,
+            info_cache: InfoCache::default(),
+            breaker: CircuitBreaker::default(),
+            data_center_description,
+            sign_packets,
+            tasks,
+            quarantine,
+        }
+    }
+
+    /// Returns the data center description announced to the client `client_handle`, i.e. the
+    /// description of the listener the client connected to or the server's description.
+    pub fn data_center_description<'a>(&'a self, client_handle: &'a ClientHandle) -> &'a str {
+        client_handle
+            .data_center_description()
+            .unwrap_or(&self.data_center_description)
+    }
+
+    /// Returns the capabilities advertised, i.e. the server's capabilities including the packet
+    /// signature capability if packets are signed (see [`SeedLinkServer::packet_signing_key`]).
+    pub fn capabilities(&self) -> Option<Vec<String>> {
+        let mut capabilities = self.server().capabilities();
+        if self.sign_packets {
+            capabilities
+                .get_or_insert_with(Vec::new)
+                .push(PACKET_SIGNATURE_CAPABILITY_V4.to_string());
+        }
+        capabilities
+    }
+
+    /// Returns whether the server advertises the capability `capability`.
+    fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities()
+            .map_or(false, |caps| caps.iter().any(|c| c == capability))
+    }
+
     pub async fn dispatch(
         &mut self,
         cmd: &CommandV4,
         client_handle: &mut ClientHandle,
+        ctx: &RequestContext,
     ) -> Result<(), io::Error> {
-        self.dispatch_v4(cmd, client_handle).await
+        self.dispatch_v4(cmd, client_handle, ctx).await
+    }
+
+    /// Responds to `INFO STATIONS` and `INFO STREAMS` requests. Serialized responses are cached if
+    /// configured (see [`SeedLinkServer::info_cache_ttl`]).
+    ///
+    /// While the circuit breaker is open (see [`SeedLinkServer::backend_limits`]), responses are
+    /// served from the cache regardless of their age. If no response is cached, an `INTERNAL`
+    /// error is returned without calling the backend.
+    async fn dispatch_info_inventory(
+        &mut self,
+        info_cmd: &InfoCmdV4,
+        client_handle: &mut ClientHandle,
+        ctx: &RequestContext,
+    ) -> Result<(), io::Error> {
+        let patterns = [
+            &info_cmd.station_pattern,
+            &info_cmd.stream_pattern,
+            &info_cmd.format_subformat_pattern,
+        ];
+        if let Err(err) = patterns
+            .into_iter()
+            .flatten()
+            .try_for_each(|p| validate_pattern(p))
+        {
+            let error_info = ErrorInfoV4 {
+                id: to_id_info_v4(
+                    self.server(),
+                    self.data_center_description(client_handle),
+                    client_handle.protocol_versions(),
+                    &self.capabilities(),
+                ),
+                error: err,
+                request_id: Some(ctx.request_id.to_string()),
+            };
+            return client_handle.send(FromServer::Info(InfoV4::Error(error_info)));
+        }
+
+        let holdback = !client_handle.holdback_exempt();
+        let ttl = self.server().info_cache_ttl();
+        let degraded = self.breaker.is_open();
+        let max_age = if degraded { Some(Duration::MAX) } else { ttl };
+        if let Some(max_age) = max_age {
+            if let Some(packet) = self.info_cache.get(
+                info_cmd,
+                client_handle
+                    .data_center_description()
+                    .unwrap_or(&self.data_center_description),
+                holdback,
+                client_handle.protocol_versions(),
+                max_age,
+            ) {
+                debug!(
+                    "{:?}: info cache hit (hit_rate={:?})",
+                    client_handle.id,
+                    self.info_cache.stats().hit_rate()
+                );
+                return client_handle.send(FromServer::Raw(packet));
+            }
+        }
+
+        let with_streams = info_cmd.item == InfoCmdItemV4::Streams;
+        let station_pattern = info_cmd.station_pattern.as_deref().unwrap_or("*");
+        let limits = self.server().backend_limits();
+        let stations = if degraded {
+            let mut err = ProtocolErrorV4::internal();
+            err.message = Some("backend temporarily unavailable".into());
+            Err(err)
+        } else if with_streams {
+            self.breaker
+                .call(
+                    &limits,
+                    self.server.inventory_streams(
+                        ctx,
+                        station_pattern,
+                        info_cmd.stream_pattern.clone(),
+                        info_cmd.format_subformat_pattern.clone(),
+                    ),
+                )
+                .await
+        } else {
+            self.breaker
+                .call(
+                    &limits,
+                    self.server.inventory_stations(
+                        ctx,
+                        station_pattern,
+                        info_cmd.stream_pattern.clone(),
+                        info_cmd.format_subformat_pattern.clone(),
+                    ),
+                )
+                .await
+        };
+
+        let id = to_id_info_v4(
+            self.server(),
+            self.data_center_description(client_handle),
+            client_handle.protocol_versions(),
+            &self.capabilities(),
+        );
+
+        let stations = match stations {
+            Ok(stations) => stations,
+            Err(err) => {
+                let error_info = ErrorInfoV4 {
+                    id,
+                    error: err,
+                    request_id: Some(ctx.request_id.to_string()),
+                };
+                return client_handle.send(FromServer::Info(InfoV4::Error(error_info)));
+            }
+        };
+
+        let builder = if with_streams {
+            InfoBuilderV4::streams(id)
+        } else {
+            InfoBuilderV4::stations(id)
+        };
+        let mut info = builder
+            .extend_stations(stations.iter().map(StationV4::from))
+            .build();
+        if let Some(buffer) = self.server().packet_buffer() {
+            apply_retention(&mut info, buffer);
+        }
+        if holdback {
+            apply_holdback(&mut info, self.server(), OffsetDateTime::now_utc());
+        }
+
+        if ttl.is_none() {
+            let info = if with_streams {
+                InfoV4::Streams(info)
+            } else {
+                InfoV4::Stations(info)
+            };
+            return client_handle.send(FromServer::Info(info));
+        }
+
+        let packet = serde_json::to_string(&info)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .and_then(|json| {
+                pack_info_ok_v4(&json)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+            })?;
+        debug_assert_eq!(conformance::check_packet_v4(&packet), Ok(()));
+        self.info_cache.insert(
+            info_cmd.clone(),
+            client_handle
+                .data_center_description()
+                .unwrap_or(&self.data_center_description),
+            holdback,
+            client_handle.protocol_versions(),
+            packet.clone(),
+        );
+
+        client_handle.send(FromServer::Raw(packet))
+    }
+
+    /// Passes the unknown command `unknown_cmd` to the server (see
+    /// [`SeedLinkServer::handle_unknown_command`]). Returns `None` if the command is not handled.
+    async fn handle_unknown_command(
+        &mut self,
+        unknown_cmd: &UnknownCmdV4,
+        ctx: &RequestContext,
+    ) -> Option<Result<ExtensionResponse, ProtocolErrorV4>> {
+        let limits = self.server().backend_limits();
+        let server = &self.server;
+        self.breaker
+            .call(&limits, async {
+                Ok(server.handle_unknown_command(ctx, unknown_cmd).await)
+            })
+            .await
+            .unwrap_or_else(|err| Some(Err(err)))
+    }
+
+    /// Dispatches SeedLink `v3` commands. Apart from unknown commands (see
+    /// [`SeedLinkServer::unknown_command_policy`]), commands are answered with `ERROR`.
+    ///
+    /// TODO(damb): serve SeedLink `v3` commands (see [`ListenerConfig`](crate::ListenerConfig))
+    pub async fn dispatch_v3(
+        &mut self,
+        cmd: &CommandV3,
+        client_handle: &mut ClientHandle,
+        ctx: &RequestContext,
+    ) -> Result<(), io::Error> {
+        match cmd {
+            CommandV3::Unknown(unknown_cmd) => {
+                // XXX(damb): extensions are implemented for both protocol versions at once
+                let unknown_cmd_v4 = UnknownCmdV4 {
+                    command_name: unknown_cmd.command_name.clone(),
+                    args: unknown_cmd.args.clone(),
+                };
+                match self.handle_unknown_command(&unknown_cmd_v4, ctx).await {
+                    Some(Ok(ExtensionResponse::Ok)) => client_handle.send(FromServer::Ok),
+                    Some(Ok(ExtensionResponse::Raw(buf))) => {
+                        client_handle.send(FromServer::Raw(buf))
+                    }
+                    Some(Err(_)) => {
+                        client_handle.send(FromServer::Error(ProtocolErrorV3.to_string()))
+                    }
+                    None => match self.server().unknown_command_policy() {
+                        UnknownCommandPolicy::Error => {
+                            warn!("{:?}: unknown command: '{}'", client_handle.id, unknown_cmd);
+                            client_handle.send(FromServer::Error(ProtocolErrorV3.to_string()))
+                        }
+                        UnknownCommandPolicy::Ignore => {
+                            debug!(
+                                "{:?}: ignoring unknown command: '{}'",
+                                client_handle.id, unknown_cmd
+                            );
+                            Ok(())
+                        }
+                    },
+                }
+            }
+            _ => client_handle.send(FromServer::Error(ProtocolErrorV3.to_string())),
+        }
+    }
+
+    /// Responds to `REPLAY` requests (see [`CAPABILITY_REPLAY`]). Playbacks may be requested once
+    /// stations were selected, only.
+    fn dispatch_replay(
+        &self,
+        replay_cmd: &UnknownCmdV4,
+        client_handle: &mut ClientHandle,
+    ) -> Result<(), io::Error> {
+        if client_handle.is_negotiating() || client_handle.selects.is_empty() {
+            return client_handle.send(FromServer::Error(
+                ProtocolErrorV4::unexpected_command().to_string(),
+            ));
+        }
+        if self.server().packet_buffer().is_none() {
+            return client_handle.send(FromServer::Error(
+                ProtocolErrorV4::unsupported_command().to_string(),
+            ));
+        }
+
+        match replay_cmd
+            .args
+            .as_deref()
+            .unwrap_or_default()
+            .parse::<ReplayRequest>()
+        {
+            Ok(request) => {
+                debug!("{:?}: playback requested: {:?}", client_handle.id, request);
+                client_handle.replay = Some(request);
+                client_handle.send(FromServer::Ok)
+            }
+            Err(err) => client_handle.send(FromServer::Error(err.to_string())),
+        }
+    }
+
+    /// Responds to `END` and `ENDFETCH` requests, i.e. starts streaming the packets of the
+    /// stations selected. Nothing is responded on success, i.e. the packets follow.
+    fn start_streaming(
+        &self,
+        client_handle: &mut ClientHandle,
+        mode: TransferMode,
+    ) -> Result<(), io::Error> {
+        if client_handle.is_negotiating()
+            || client_handle.selects.is_empty()
+            || client_handle.is_streaming()
+        {
+            return client_handle.send(FromServer::Error(
+                ProtocolErrorV4::unexpected_command().to_string(),
+            ));
+        }
+        let Some(buffer) = self.server().packet_buffer() else {
+            return client_handle.send(FromServer::Error(
+                ProtocolErrorV4::unsupported_command().to_string(),
+            ));
+        };
+
+        // XXX(damb): playbacks requested take precedence over the data transfer mode
+        let mode = match client_handle.replay.clone() {
+            Some(request) => TransferMode::Replay(request),
+            None => mode,
+        };
+        let holdback_exempt = client_handle.holdback_exempt();
+        let session = Session::new(
+            client_handle.id,
+            client_handle.sender(),
+            buffer.clone(),
+            self.quarantine.clone(),
+            &client_handle.selects,
+            |station_id| {
+                if holdback_exempt {
+                    return None;
+                }
+                self.server().holdback(station_id)
+            },
+            mode.clone(),
+        );
+        match session.spawn(&self.tasks) {
+            Some(streaming) => {
+                debug!("{:?}: streaming ({:?})", client_handle.id, mode);
+                client_handle.set_streaming(streaming);
+                Ok(())
+            }
+            // XXX(damb): the server is shutting down
+            None => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "server shutting down",
+            )),
+        }
     }
 
     async fn dispatch_v4(
         &mut self,
         cmd: &CommandV4,
         client_handle: &mut ClientHandle,
+        ctx: &RequestContext,
     ) -> Result<(), io::Error> {
         match cmd {
+            CommandV4::Auth(auth_cmd) => {
+                if client_handle.authenticated() && !self.has_capability(CAPABILITY_AUTH_REFRESH) {
+                    client_handle.send(FromServer::Error(
+                        ProtocolErrorV4::unexpected_command().to_string(),
+                    ))?;
+                    return Ok(());
+                }
+
+                let auth = AuthV4::from(auth_cmd.method());
+                let limits = self.server().backend_limits();
+                match self
+                    .breaker
+                    .call(&limits, self.server.authenticate(ctx, &auth))
+                    .await
+                {
+                    Ok(expires) => {
+                        debug!(
+                            "{:?}: authenticated (expires={:?})",
+                            client_handle.id, expires
+                        );
+                        client_handle.set_authenticated(expires);
+                        client_handle.send(FromServer::Ok)
+                    }
+                    Err(err) => {
+                        // XXX(damb): a failed re-authentication revokes previously granted
+                        // credentials
+                        client_handle.revoke_authentication();
+                        client_handle.send(FromServer::Error(err.to_string()))
+                    }
+                }
+            }
             CommandV4::Station(station_cmd) => {
                 if client_handle.negotiator.is_some() {
                     client_handle.send(FromServer::Error(
@@ -50,19 +462,36 @@ impl<T: SeedLinkServer> Dispatcher<T> {
                     ))?;
                     return Ok(());
                 }
+                if let Err(err) = validate_pattern(&station_cmd.station_pattern) {
+                    client_handle.send(FromServer::Error(err.to_string()))?;
+                    return Ok(());
+                }
 
+                let limits = self.server().backend_limits();
                 let stations = self
-                    .server()
-                    .inventory_streams(&station_cmd.station_pattern, None, None)
+                    .breaker
+                    .call(
+                        &limits,
+                        self.server.inventory_streams(
+                            ctx,
+                            &station_cmd.station_pattern,
+                            None,
+                            None,
+                        ),
+                    )
                     .await;
 
-                if let Err(err) = stations {
-                    client_handle.send(FromServer::Error(err.to_string()))?;
-                    return Ok(());
-                }
+                let stations = match stations {
+                    Ok(stations) => stations,
+                    Err(err) => {
+                        client_handle.send(FromServer::Error(err.to_string()))?;
+                        return Ok(());
+                    }
+                };
 
-                let select = Select::new(stations.unwrap().clone());
-                client_handle.negotiator = Some(StationNegotiator::new(select));
+                let select = Select::new(stations.clone());
+                client_handle.negotiator =
+                    Some(StationNegotiator::new(&station_cmd.station_pattern, select));
 
                 client_handle.send(FromServer::Ok)
             }
@@ -87,28 +516,30 @@ impl<T: SeedLinkServer> Dispatcher<T> {
 
                 match res {
                     Ok(_) => {
-                        client_handle
-                            .selects
-                            .push(client_handle.negotiator.take().unwrap().select);
+                        let negotiator = client_handle.negotiator.take().unwrap();
+                        self.server.stations_requested(
+                            ctx,
+                            &negotiator.station_pattern,
+                            &negotiator.select_patterns,
+                        );
+                        client_handle.selects.push(negotiator.select);
                         client_handle.send(FromServer::Ok)
                     }
                     Err(err) => client_handle.send(FromServer::Error(err.to_string())),
                 }
             }
-            CommandV4::End(end_cmd) => {
-                // XXX(damb): go into streaming mode
-                todo!()
-            }
-            CommandV4::EndFetch(endfetch_cmd) => {
-                // XXX(damb): go into streaming mode
-                todo!()
-            }
+            CommandV4::End(_) => self.start_streaming(client_handle, TransferMode::RealTime),
+            CommandV4::EndFetch(_) => self.start_streaming(client_handle, TransferMode::DialUp),
             CommandV4::Hello(_) => {
-                let hello = Hello {
+                let hello = Hello::V4(HelloV4 {
                     implementation: self.server.implementation().to_string(),
                     implementation_version: self.server.implementation_version().to_string(),
-                    data_center_description: self.server.data_center_description().to_string(),
-                };
+                    protocol_versions: client_handle.protocol_versions().clone(),
+                    data_center_description: self
+                        .data_center_description(client_handle)
+                        .to_string(),
+                    capabilities: self.capabilities(),
+                });
 
                 client_handle.send(FromServer::Hello(hello))
             }
@@ -116,19 +547,59 @@ impl<T: SeedLinkServer> Dispatcher<T> {
                 InfoCmdItemV4::Id => {
                     let id_info = to_id_info_v4(
                         self.server(),
-                        &vec![(
-                            HIGHEST_SUPPORTED_PROTO_VERSION.0,
-                            HIGHEST_SUPPORTED_PROTO_VERSION.1,
-                        )],
-                        &None,
+                        self.data_center_description(client_handle),
+                        client_handle.protocol_versions(),
+                        &self.capabilities(),
                     );
 
                     client_handle.send(FromServer::Info(InfoV4::Id(id_info)))
                 }
+                InfoCmdItemV4::Stations | InfoCmdItemV4::Streams => {
+                    self.dispatch_info_inventory(info_cmd, client_handle, ctx)
+                        .await
+                }
                 _ => {
                     todo!();
                 }
             },
+            CommandV4::Unknown(replay_cmd)
+                if replay_cmd.command_name.eq_ignore_ascii_case(REPLAY_COMMAND)
+                    && self.has_capability(CAPABILITY_REPLAY) =>
+            {
+                self.dispatch_replay(replay_cmd, client_handle)
+            }
+            CommandV4::Unknown(unknown_cmd) => {
+                match self.handle_unknown_command(unknown_cmd, ctx).await {
+                    Some(Ok(ExtensionResponse::Ok)) => client_handle.send(FromServer::Ok),
+                    Some(Ok(ExtensionResponse::Raw(buf))) => {
+                        client_handle.send(FromServer::Raw(buf))
+                    }
+                    Some(Err(err)) => client_handle.send(FromServer::Error(err.to_string())),
+                    None => match self.server().unknown_command_policy() {
+                        UnknownCommandPolicy::Error => {
+                            warn!("{:?}: unknown command: '{}'", client_handle.id, unknown_cmd);
+
+                            let mut unsupported_err = ProtocolErrorV4::unsupported_command();
+                            unsupported_err.message = Some(
+                                format!(
+                                    "{}: '{}'",
+                                    unsupported_err.code.description(),
+                                    unknown_cmd.command_name
+                                )
+                                .into(),
+                            );
+                            client_handle.send(FromServer::Error(unsupported_err.to_string()))
+                        }
+                        UnknownCommandPolicy::Ignore => {
+                            debug!(
+                                "{:?}: ignoring unknown command: '{}'",
+                                client_handle.id, unknown_cmd
+                            );
+                            Ok(())
+                        }
+                    },
+                }
+            }
             _ => {
                 // TODO
                 Ok(())
@@ -0,0 +1,137 @@
+//! File-backed server backend, i.e. a server serving an inventory and miniSEED records read from
+//! files.
+//!
+//! The inventory is read from a JSON file listing the stations including their streams (i.e. the
+//! `station` list of a SeedLink `v4` `INFO STREAMS` response). Records are published to the
+//! packet buffer of the backend by means of [`publish_file`], e.g. for testing clients against
+//! recorded data.
+//!
+//! Example usage::
+//!
+//! ```rust,no_run
+//! use slink_server::file::{publish_file, FileBackend};
+//! use slink_server::{ListenerConfig, PacketBuffer, RetentionPolicy};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let buffer = PacketBuffer::new(RetentionPolicy::default());
+//! let backend = FileBackend::open("inventory.json", buffer).unwrap();
+//!
+//! let (server_handle, join_handle) = slink_server::spawn_main_loop(backend);
+//! publish_file(&server_handle, "records.mseed").await.unwrap();
+//! slink_server::spawn_accept(
+//!     ([0, 0, 0, 0], 18000).into(),
+//!     server_handle,
+//!     ListenerConfig::default(),
+//! );
+//! join_handle.await.unwrap();
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use slink::{ProtocolErrorV4, Station, StationV4};
+
+use crate::mseed::record_len;
+use crate::{PacketBuffer, RequestContext, SeedLinkServer, ServerHandle};
+
+/// Server backend serving the inventory read from a file and the packets published to its packet
+/// buffer.
+#[derive(Debug)]
+pub struct FileBackend {
+    data_center_description: String,
+    stations: Vec<Station>,
+    packet_buffer: PacketBuffer,
+}
+
+impl FileBackend {
+    /// Reads the inventory from the JSON file `path`. Packets are buffered by `packet_buffer`.
+    pub fn open(path: impl AsRef<Path>, packet_buffer: PacketBuffer) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let stations: Vec<StationV4> = serde_json::from_reader(reader)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(Self {
+            data_center_description: String::new(),
+            stations: stations.into_iter().map(Station::from).collect(),
+            packet_buffer,
+        })
+    }
+
+    /// Sets the data center description.
+    pub fn with_data_center_description(mut self, data_center_description: &str) -> Self {
+        self.data_center_description = data_center_description.to_string();
+        self
+    }
+}
+
+#[crate::async_trait]
+impl SeedLinkServer for FileBackend {
+    fn implementation(&self) -> &str {
+        "slink-file"
+    }
+
+    fn implementation_version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn data_center_description(&self) -> &str {
+        &self.data_center_description
+    }
+
+    fn packet_buffer(&self) -> Option<&PacketBuffer> {
+        Some(&self.packet_buffer)
+    }
+
+    async fn inventory_stations(
+        &self,
+        _ctx: &RequestContext,
+        _station_pattern: &str,
+        _stream_pattern: Option<String>,
+        _format_subformat_pattern: Option<String>,
+    ) -> Result<&Vec<Station>, ProtocolErrorV4> {
+        Ok(&self.stations)
+    }
+
+    async fn inventory_streams(
+        &self,
+        _ctx: &RequestContext,
+        _station_pattern: &str,
+        _stream_pattern: Option<String>,
+        _format_subformat_pattern: Option<String>,
+    ) -> Result<&Vec<Station>, ProtocolErrorV4> {
+        Ok(&self.stations)
+    }
+}
+
+/// Publishes the miniSEED (version 2 or 3) records of the file `path` by means of
+/// `server_handle` (see [`ServerHandle::publish_raw`]). Returns the number of records published.
+///
+/// Records are split by means of their headers, i.e. miniSEED 2 records must include blockette
+/// 1000. Fails at the first record which cannot be published.
+pub async fn publish_file(
+    server_handle: &ServerHandle,
+    path: impl AsRef<Path>,
+) -> io::Result<usize> {
+    let buf = tokio::fs::read(path).await?;
+
+    let mut published = 0;
+    let mut offset = 0;
+    while offset < buf.len() {
+        let len = record_len(&buf[offset..])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let record = buf
+            .get(offset..offset + len)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated record"))?;
+        server_handle
+            .publish_raw(record)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        published += 1;
+        offset += len;
+    }
+
+    Ok(published)
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+
+use slink::{StationId, StationsInfoV4};
+
+use crate::SeedLinkServer;
+
+/// Returns the time until which data subject to the holdback window `holdback` is released at
+/// `now`.
+pub fn release_time(holdback: Duration, now: OffsetDateTime) -> OffsetDateTime {
+    now - holdback
+}
+
+/// Returns whether data ending at `end_time` is withheld at `now` with regard to the holdback
+/// window `holdback`.
+pub fn is_withheld(
+    end_time: &OffsetDateTime,
+    holdback: Option<Duration>,
+    now: OffsetDateTime,
+) -> bool {
+    match holdback {
+        Some(holdback) => *end_time > release_time(holdback, now),
+        None => false,
+    }
+}
+
+/// Returns the end time advertised for data ending at `end_time` with regard to the holdback
+/// window `holdback`.
+pub fn advertised_end_time(
+    end_time: OffsetDateTime,
+    holdback: Option<Duration>,
+    now: OffsetDateTime,
+) -> OffsetDateTime {
+    match holdback {
+        Some(holdback) => end_time.min(release_time(holdback, now)),
+        None => end_time,
+    }
+}
+
+/// Adjusts the stream end times of an `INFO STATIONS` or `INFO STREAMS` response according to the
+/// holdback windows configured by `server`.
+pub fn apply_holdback(
+    info: &mut StationsInfoV4,
+    server: &impl SeedLinkServer,
+    now: OffsetDateTime,
+) {
+    for station in info.station.iter_mut() {
+        let holdback = server.holdback(&StationId::from(station.id().clone()));
+        if holdback.is_none() {
+            continue;
+        }
+
+        if let Some(streams) = station.streams_mut() {
+            for stream in streams.iter_mut() {
+                let end_time = advertised_end_time(*stream.end_time(), holdback, now);
+                stream.set_end_time(end_time);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use time::macros::datetime;
+
+    #[test]
+    fn withheld() {
+        let now = datetime!(2023-01-01 12:00 UTC);
+        let holdback = Some(Duration::from_secs(30 * 60));
+
+        assert!(is_withheld(&datetime!(2023-01-01 11:45 UTC), holdback, now));
+        assert!(!is_withheld(
+            &datetime!(2023-01-01 11:15 UTC),
+            holdback,
+            now
+        ));
+        assert!(!is_withheld(&datetime!(2023-01-01 11:45 UTC), None, now));
+    }
+
+    #[test]
+    fn advertised() {
+        let now = datetime!(2023-01-01 12:00 UTC);
+        let holdback = Some(Duration::from_secs(30 * 60));
+
+        assert_eq!(
+            advertised_end_time(datetime!(2023-01-01 11:45 UTC), holdback, now),
+            datetime!(2023-01-01 11:30 UTC)
+        );
+        assert_eq!(
+            advertised_end_time(datetime!(2023-01-01 11:15 UTC), holdback, now),
+            datetime!(2023-01-01 11:15 UTC)
+        );
+        assert_eq!(
+            advertised_end_time(datetime!(2023-01-01 11:45 UTC), None, now),
+            datetime!(2023-01-01 11:45 UTC)
+        );
+    }
+}
@@ -1,18 +1,47 @@
 mod accept;
+mod breaker;
+mod buffer;
+mod cache;
 mod client;
 mod dispatch;
+pub mod file;
+pub mod holdback;
+mod mseed;
 mod negotiate;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+mod quarantine;
+pub mod replay;
 mod response;
 mod seedlink;
 mod select;
 mod server;
+mod streaming;
+mod task;
+pub mod trace;
+mod traffic;
 mod util;
 
-pub use accept::start_accept;
-pub use server::{spawn_main_loop, ServerHandle};
+pub use accept::{accept_mem, accept_mem_with_config, spawn_accept, start_accept, ListenerConfig};
+pub use breaker::BackendLimits;
+pub use buffer::{
+    apply_retention, spawn_eviction, BufferedPacket, PacketBuffer, RetentionPolicy, StationExtent,
+};
+pub use cache::InfoCacheStats;
+pub use quarantine::Quarantine;
+pub use response::DescriptionEncoding;
+pub use server::{spawn_main_loop, PublishError, ServerHandle};
+pub use seedlink::CommandLineLimits;
 pub use select::Select;
+pub use task::{Subsystem, TaskPanic};
+pub use traffic::TrafficStats;
 
-use slink::{AuthV4, Station, ProtocolErrorV4};
+use std::fmt;
+use std::time::Duration;
+
+use time::OffsetDateTime;
+
+use slink::{AuthV4, ProtocolErrorV4, SelectCmdPatternV4, Station, StationId, UnknownCmdV4};
 
 /// A re-export of [`async-trait`](https://docs.rs/async-trait) for convenience.
 pub use async_trait::async_trait;
@@ -22,14 +51,67 @@ pub const DEFAULT_PROTO_VERSION: (u8, u8) = (4, 0);
 /// Server-side highest supported protocol version.
 pub const HIGHEST_SUPPORTED_PROTO_VERSION: (u8, u8) = (4, 0);
 
+/// Capability indicating that authenticated clients may re-authenticate (e.g. with a refreshed
+/// JSON Web Token) before their credentials expire.
+///
+/// Note that this is a non-standard extension.
+pub const CAPABILITY_AUTH_REFRESH: &str = slink::CAPABILITY_AUTH_REFRESH_V4;
+
+/// Capability indicating that clients may request the time-shifted playback of buffered packets
+/// (see [`replay`]).
+///
+/// Note that this is a non-standard extension.
+pub const CAPABILITY_REPLAY: &str = "REPLAY";
+
+/// Enumeration of server responses to unknown commands.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnknownCommandPolicy {
+    /// Respond with an error.
+    #[default]
+    Error,
+    /// Ignore the command, i.e. do not respond at all.
+    Ignore,
+}
+
+/// Response of a server extension to an otherwise unknown command.
+#[derive(Clone, Debug)]
+pub enum ExtensionResponse {
+    /// Respond with `OK`.
+    Ok,
+    /// Respond with the raw, already encoded bytes (e.g. a vendor specific `INFO` packet).
+    Raw(Vec<u8>),
+}
+
 /// Client identifier.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct ClientId(usize);
 
+/// Request identifier, i.e. a per-command correlation identifier.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct RequestId(u64);
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Context of a client request passed to backend calls.
+#[derive(Clone, Debug)]
+pub struct RequestContext {
+    /// The client issuing the request.
+    pub client_id: ClientId,
+    /// The correlation identifier of the request.
+    pub request_id: RequestId,
+}
+
 /// Trait implemented by SeedLink server implementations.
 ///
 /// This interface allows servers adhering to the SeedLink protocol to be implemented in a safe way
 /// without exposing the low-level implementation details.
+///
+/// Backend calls triggered by client commands receive a [`RequestContext`] which allows to
+/// correlate e.g. log messages with the request.
 #[async_trait]
 pub trait SeedLinkServer: Send + Sync + 'static {
     /// Returns the software implementation.
@@ -38,19 +120,112 @@ pub trait SeedLinkServer: Send + Sync + 'static {
     /// Returns the software implementation version.
     fn implementation_version(&self) -> &str;
 
-    /// Returns the data center description.
+    /// Returns the data center description. Listeners may override the description (see
+    /// [`ListenerConfig::with_data_center_description`]).
     fn data_center_description(&self) -> &str;
 
+    /// Returns the encoding applied to data center descriptions (see
+    /// [`SeedLinkServer::data_center_description`]).
+    fn description_encoding(&self) -> DescriptionEncoding {
+        DescriptionEncoding::default()
+    }
+
+    /// Returns the capabilities advertised in response to the `HELLO` command.
+    fn capabilities(&self) -> Option<Vec<String>> {
+        None
+    }
+
     /// Authenticates a client.
     ///
+    /// On success, returns the time the credentials expire, if any. Once expired, the client is
+    /// treated as unauthenticated. Clients may re-authenticate before expiry only if the server
+    /// advertises the [`CAPABILITY_AUTH_REFRESH`] capability.
+    ///
     /// TODO(damb): support multiple protocol versions
-    async fn authenticate(&self, auth: &AuthV4) -> Result<(), ProtocolErrorV4> {
+    async fn authenticate(
+        &self,
+        ctx: &RequestContext,
+        auth: &AuthV4,
+    ) -> Result<Option<OffsetDateTime>, ProtocolErrorV4> {
         Err(ProtocolErrorV4::unsupported_command())
     }
 
+    /// Returns the holdback window (data embargo) of the station identified by `station_id`, if
+    /// any.
+    ///
+    /// Data younger than the holdback window is withheld from unauthenticated clients and not
+    /// advertised (see [`holdback::apply_holdback`]).
+    fn holdback(&self, station_id: &StationId) -> Option<Duration> {
+        None
+    }
+
+    /// Returns the packet buffer of the server, if any.
+    ///
+    /// The station start sequence numbers and stream start times advertised are adjusted to the
+    /// packets buffered (see [`apply_retention`]).
+    fn packet_buffer(&self) -> Option<&PacketBuffer> {
+        None
+    }
+
+    /// Returns the number of consecutive packet encoding failures after which a station is
+    /// quarantined (see [`Quarantine`]). Must be greater than zero.
+    fn quarantine_threshold(&self) -> u32 {
+        quarantine::DEFAULT_QUARANTINE_THRESHOLD
+    }
+
+    /// Returns the time to live of cached `INFO STATIONS` and `INFO STREAMS` responses.
+    ///
+    /// Returns `None` if responses are not cached. Cached responses should be invalidated by means
+    /// of [`ServerHandle::invalidate_info_cache`] whenever the inventory changes.
+    fn info_cache_ttl(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns the limits protecting the server against slow or misbehaving backends, i.e. the
+    /// timeout applied to backend calls and the circuit breaker configuration.
+    fn backend_limits(&self) -> BackendLimits {
+        BackendLimits::default()
+    }
+
+    /// Returns the key SeedLink `v4` packets are signed with, if any.
+    ///
+    /// If a key is returned, the [`slink::PACKET_SIGNATURE_CAPABILITY_V4`] capability is advertised
+    /// in response to the `HELLO` command and each SeedLink `v4` packet sent is followed by its
+    /// signature (see [`slink::sign_packet_v4`]).
+    fn packet_signing_key(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Returns the maximum command line lengths per command category.
+    fn command_line_limits(&self) -> CommandLineLimits {
+        CommandLineLimits::default()
+    }
+
+    /// Returns the response to unknown commands which are not handled by
+    /// [`SeedLinkServer::handle_unknown_command`]. Applies to both SeedLink `v3` and `v4`
+    /// commands.
+    fn unknown_command_policy(&self) -> UnknownCommandPolicy {
+        UnknownCommandPolicy::default()
+    }
+
+    /// Handles commands unknown to the server. Allows to implement vendor specific extensions.
+    ///
+    /// Unknown SeedLink `v3` commands are passed as `v4` commands. Note that the names of `v3`
+    /// commands are lowercased.
+    ///
+    /// Returns `None` if the command is not handled.
+    async fn handle_unknown_command(
+        &self,
+        ctx: &RequestContext,
+        cmd: &UnknownCmdV4,
+    ) -> Option<Result<ExtensionResponse, ProtocolErrorV4>> {
+        None
+    }
+
     /// Returns the inventory without stream related data.
     async fn inventory_stations(
         &self,
+        ctx: &RequestContext,
         station_pattern: &str,
         stream_pattern: Option<String>,
         format_subformat_pattern: Option<String>,
@@ -59,11 +234,26 @@ pub trait SeedLinkServer: Send + Sync + 'static {
     /// Returns the inventory including stream related data.
     async fn inventory_streams(
         &self,
+        ctx: &RequestContext,
         station_pattern: &str,
         stream_pattern: Option<String>,
         format_subformat_pattern: Option<String>,
     ) -> Result<&Vec<Station>, ProtocolErrorV4>;
 
+    /// Notifies the server that a client requested the stations matching `station_pattern` and
+    /// the streams matching `select_patterns` (i.e. by means of the `STATION`, `SELECT` and `DATA`
+    /// commands), e.g. in order to subscribe to the streams upstream when proxying.
+    ///
+    /// Called once the negotiation of a station completed. If `select_patterns` is empty, all
+    /// streams are requested.
+    fn stations_requested(
+        &self,
+        ctx: &RequestContext,
+        station_pattern: &str,
+        select_patterns: &[SelectCmdPatternV4],
+    ) {
+    }
+
     // async fn initialize(&self) -> SeedLinkResult<()>;
 
     // async fn shutdown(&self) -> SeedLinkResult<()>;
@@ -3,8 +3,8 @@ use std::collections::HashMap;
 use tracing::info;
 use tracing_subscriber;
 
-use slink::Station;
-use slink_server::{ClientId, SeedLinkServer};
+use slink::{ProtocolErrorV4, Station};
+use slink_server::{ClientId, ListenerConfig, RequestContext, SeedLinkServer};
 
 use slink::DEFAULT_PORT;
 
@@ -33,19 +33,21 @@ impl SeedLinkServer for SeedLinkServerBackend {
 
     async fn inventory_stations(
         &self,
+        ctx: &RequestContext,
         station_pattern: &str,
         stream_pattern: Option<String>,
         format_subformat_pattern: Option<String>,
-    ) -> &Vec<Station> {
+    ) -> Result<&Vec<Station>, ProtocolErrorV4> {
         todo!()
     }
 
     async fn inventory_streams(
         &self,
+        ctx: &RequestContext,
         station_pattern: &str,
         stream_pattern: Option<String>,
         format_subformat_pattern: Option<String>,
-    ) -> &Vec<Station> {
+    ) -> Result<&Vec<Station>, ProtocolErrorV4> {
         todo!()
     }
 
@@ -62,10 +64,11 @@ async fn main() {
 
     let (server_handle, join_handle) = slink_server::spawn_main_loop(server);
 
-    tokio::spawn(async move {
-        let bind = ([0, 0, 0, 0], DEFAULT_PORT).into();
-        slink_server::start_accept(bind, server_handle).await;
-    });
+    slink_server::spawn_accept(
+        ([0, 0, 0, 0], DEFAULT_PORT).into(),
+        server_handle,
+        ListenerConfig::default(),
+    );
 
     info!("Starting on port {}", DEFAULT_PORT);
 
@@ -0,0 +1,317 @@
+use std::time::Duration;
+
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
+
+/// Length of the miniSEED 2 fixed section of data header.
+const MS2_FIXED_HEADER_LEN: usize = 48;
+/// Length of the miniSEED 3 fixed header (excluding the source identifier).
+const MS3_FIXED_HEADER_LEN: usize = 40;
+/// Prefix of FDSN source identifiers.
+const SID_PREFIX: &str = "FDSN:";
+
+/// Data extracted from the header of a miniSEED record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RecordHeader {
+    /// The station identifier (i.e. `NET_STA`).
+    pub station_id: String,
+    /// The stream identifier (i.e. `LOC_B_S_SS`).
+    pub stream_id: String,
+    pub start_time: OffsetDateTime,
+    pub end_time: OffsetDateTime,
+}
+
+/// Peeks at the fixed header of the miniSEED (version 2 or 3) record `buf`, i.e. without decoding
+/// the record.
+///
+/// Note that time corrections and the microsecond offset of blockette 1001 (miniSEED 2) are not
+/// taken into account.
+pub(crate) fn peek_header(buf: &[u8]) -> Result<RecordHeader, String> {
+    if buf.starts_with(b"MS") && buf.get(2) == Some(&3) {
+        peek_header_v3(buf)
+    } else {
+        peek_header_v2(buf)
+    }
+}
+
+/// Returns the length of the miniSEED (version 2 or 3) record starting at `buf` by means of its
+/// header. The length of miniSEED 2 records is determined by means of blockette 1000.
+pub(crate) fn record_len(buf: &[u8]) -> Result<usize, String> {
+    if buf.starts_with(b"MS") && buf.get(2) == Some(&3) {
+        record_len_v3(buf)
+    } else {
+        record_len_v2(buf)
+    }
+}
+
+fn record_len_v2(buf: &[u8]) -> Result<usize, String> {
+    if buf.len() < MS2_FIXED_HEADER_LEN {
+        return Err("record too short".to_string());
+    }
+
+    let year_be = u16::from_be_bytes([buf[20], buf[21]]);
+    let big_endian = (1900..=2100).contains(&year_be);
+    let read_u16 = |pos: usize| -> Result<u16, String> {
+        let bytes = buf
+            .get(pos..pos + 2)
+            .ok_or_else(|| "record too short".to_string())?;
+        let bytes = [bytes[0], bytes[1]];
+        Ok(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+
+    let mut pos = usize::from(read_u16(46)?);
+    while pos != 0 {
+        let next = usize::from(read_u16(pos + 2)?);
+        if read_u16(pos)? == 1000 {
+            let exponent = *buf
+                .get(pos + 6)
+                .ok_or_else(|| "record too short".to_string())?;
+            if !(7..=20).contains(&exponent) {
+                return Err(format!("invalid record length exponent: {}", exponent));
+            }
+            return Ok(1 << exponent);
+        }
+
+        // XXX(damb): blockettes are chained in ascending order
+        if next != 0 && next <= pos {
+            return Err("invalid blockette offset".to_string());
+        }
+        pos = next;
+    }
+
+    Err("missing blockette 1000".to_string())
+}
+
+fn record_len_v3(buf: &[u8]) -> Result<usize, String> {
+    if buf.len() < MS3_FIXED_HEADER_LEN {
+        return Err("record too short".to_string());
+    }
+
+    let sid_len = usize::from(buf[33]);
+    let extra_len = usize::from(u16::from_le_bytes([buf[34], buf[35]]));
+    let data_len = u32::from_le_bytes([buf[36], buf[37], buf[38], buf[39]]) as usize;
+
+    Ok(MS3_FIXED_HEADER_LEN + sid_len + extra_len + data_len)
+}
+
+fn peek_header_v2(buf: &[u8]) -> Result<RecordHeader, String> {
+    if buf.len() < MS2_FIXED_HEADER_LEN {
+        return Err("record too short".to_string());
+    }
+    if !matches!(buf[6], b'D' | b'R' | b'Q' | b'M') {
+        return Err("invalid data quality indicator".to_string());
+    }
+
+    let code = |range: std::ops::Range<usize>| {
+        std::str::from_utf8(&buf[range])
+            .map(|s| s.trim().to_string())
+            .map_err(|_| "invalid code".to_string())
+    };
+    let sta = code(8..13)?;
+    let loc = code(13..15)?;
+    let cha = code(15..18)?;
+    let net = code(18..20)?;
+
+    // XXX(damb): the byte order is detected by means of the year
+    let year_be = u16::from_be_bytes([buf[20], buf[21]]);
+    let big_endian = (1900..=2100).contains(&year_be);
+    let read_u16 = |pos: usize| {
+        let bytes = [buf[pos], buf[pos + 1]];
+        if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    };
+
+    let start_time = to_datetime(
+        read_u16(20),
+        read_u16(22),
+        buf[24],
+        buf[25],
+        buf[26],
+        u32::from(read_u16(28)) * 100_000,
+    )?;
+
+    let factor = f64::from(read_u16(32) as i16);
+    let multiplier = f64::from(read_u16(34) as i16);
+    let sample_rate = match (factor, multiplier) {
+        (f, m) if f == 0.0 || m == 0.0 => 0.0,
+        (f, m) if f > 0.0 && m > 0.0 => f * m,
+        (f, m) if f > 0.0 => -f / m,
+        (f, m) if m > 0.0 => -m / f,
+        (f, m) => 1.0 / (f * m),
+    };
+
+    let mut cha = cha.chars();
+    let stream_id = format!(
+        "{}_{}_{}_{}",
+        loc,
+        cha.next().unwrap_or_default(),
+        cha.next().unwrap_or_default(),
+        cha.as_str()
+    );
+
+    Ok(RecordHeader {
+        station_id: format!("{}_{}", net, sta),
+        stream_id,
+        start_time,
+        end_time: end_time(start_time, u32::from(read_u16(30)), sample_rate),
+    })
+}
+
+fn peek_header_v3(buf: &[u8]) -> Result<RecordHeader, String> {
+    if buf.len() < MS3_FIXED_HEADER_LEN {
+        return Err("record too short".to_string());
+    }
+
+    let sid_len = usize::from(buf[33]);
+    let sid = buf
+        .get(MS3_FIXED_HEADER_LEN..MS3_FIXED_HEADER_LEN + sid_len)
+        .ok_or_else(|| "record too short".to_string())?;
+    let sid = std::str::from_utf8(sid).map_err(|_| "invalid source identifier".to_string())?;
+    let (net, sta, stream_id) = sid
+        .strip_prefix(SID_PREFIX)
+        .and_then(|sid| {
+            let mut parts = sid.splitn(3, '_');
+            Some((parts.next()?, parts.next()?, parts.next()?))
+        })
+        .ok_or_else(|| format!("invalid source identifier: {}", sid))?;
+
+    let start_time = to_datetime(
+        u16::from_le_bytes([buf[8], buf[9]]),
+        u16::from_le_bytes([buf[10], buf[11]]),
+        buf[12],
+        buf[13],
+        buf[14],
+        u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]),
+    )?;
+
+    let mut sample_rate = f64::from_le_bytes(buf[16..24].try_into().unwrap());
+    if sample_rate < 0.0 {
+        // sample period
+        sample_rate = -1.0 / sample_rate;
+    }
+    let num_samples = u32::from_le_bytes([buf[24], buf[25], buf[26], buf[27]]);
+
+    Ok(RecordHeader {
+        station_id: format!("{}_{}", net, sta),
+        stream_id: stream_id.to_string(),
+        start_time,
+        end_time: end_time(start_time, num_samples, sample_rate),
+    })
+}
+
+fn to_datetime(
+    year: u16,
+    doy: u16,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nanosecond: u32,
+) -> Result<OffsetDateTime, String> {
+    let date = Date::from_ordinal_date(year as i32, doy).map_err(|e| e.to_string())?;
+    // XXX(damb): leap seconds are not supported
+    let time =
+        Time::from_hms_nano(hour, minute, second.min(59), nanosecond).map_err(|e| e.to_string())?;
+
+    Ok(PrimitiveDateTime::new(date, time).assume_utc())
+}
+
+fn end_time(start_time: OffsetDateTime, num_samples: u32, sample_rate: f64) -> OffsetDateTime {
+    if num_samples == 0 || sample_rate <= 0.0 || !sample_rate.is_finite() {
+        return start_time;
+    }
+
+    start_time + Duration::from_secs_f64(f64::from(num_samples - 1) / sample_rate)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use time::macros::datetime;
+
+    #[test]
+    fn peek_ms2_header() {
+        let mut buf = vec![0; 512];
+        buf[..20].copy_from_slice(b"000001D WLF  00BHZGE");
+        buf[20..22].copy_from_slice(&2023u16.to_be_bytes());
+        buf[22..24].copy_from_slice(&1u16.to_be_bytes());
+        buf[24..27].copy_from_slice(&[12, 30, 15]);
+        buf[28..30].copy_from_slice(&5000u16.to_be_bytes());
+        buf[30..32].copy_from_slice(&41u16.to_be_bytes());
+        buf[32..34].copy_from_slice(&20i16.to_be_bytes());
+        buf[34..36].copy_from_slice(&1i16.to_be_bytes());
+
+        let header = peek_header(&buf).unwrap();
+        assert_eq!(header.station_id, "GE_WLF");
+        assert_eq!(header.stream_id, "00_B_H_Z");
+        assert_eq!(header.start_time, datetime!(2023-01-01 12:30:15.5 UTC));
+        assert_eq!(header.end_time, datetime!(2023-01-01 12:30:17.5 UTC));
+
+        // little endian
+        buf[20..22].copy_from_slice(&2023u16.to_le_bytes());
+        buf[22..24].copy_from_slice(&1u16.to_le_bytes());
+        buf[28..30].copy_from_slice(&5000u16.to_le_bytes());
+        buf[30..32].copy_from_slice(&41u16.to_le_bytes());
+        buf[32..34].copy_from_slice(&20i16.to_le_bytes());
+        buf[34..36].copy_from_slice(&1i16.to_le_bytes());
+        assert_eq!(peek_header(&buf).unwrap(), header);
+
+        assert!(peek_header(&buf[..40]).is_err());
+    }
+
+    #[test]
+    fn peek_ms3_header() {
+        let sid = b"FDSN:GE_WLF_00_B_H_Z";
+        let mut buf = vec![0; MS3_FIXED_HEADER_LEN];
+        buf[..3].copy_from_slice(&[b'M', b'S', 3]);
+        buf[4..8].copy_from_slice(&500_000_000u32.to_le_bytes());
+        buf[8..10].copy_from_slice(&2023u16.to_le_bytes());
+        buf[10..12].copy_from_slice(&1u16.to_le_bytes());
+        buf[12..15].copy_from_slice(&[12, 30, 15]);
+        buf[16..24].copy_from_slice(&(-0.5f64).to_le_bytes());
+        buf[24..28].copy_from_slice(&3u32.to_le_bytes());
+        buf[33] = sid.len() as u8;
+        buf.extend_from_slice(sid);
+
+        let header = peek_header(&buf).unwrap();
+        assert_eq!(header.station_id, "GE_WLF");
+        assert_eq!(header.stream_id, "00_B_H_Z");
+        assert_eq!(header.start_time, datetime!(2023-01-01 12:30:15.5 UTC));
+        assert_eq!(header.end_time, datetime!(2023-01-01 12:30:16.5 UTC));
+
+        assert!(peek_header(&buf[..45]).is_err());
+    }
+
+    #[test]
+    fn record_lengths() {
+        let mut buf = vec![0; 512];
+        buf[..20].copy_from_slice(b"000001D WLF  00BHZGE");
+        buf[20..22].copy_from_slice(&2023u16.to_be_bytes());
+        buf[22..24].copy_from_slice(&1u16.to_be_bytes());
+        assert!(record_len(&buf).is_err());
+
+        // blockette 1000
+        buf[46..48].copy_from_slice(&48u16.to_be_bytes());
+        buf[48..50].copy_from_slice(&1000u16.to_be_bytes());
+        buf[54] = 9;
+        assert_eq!(record_len(&buf), Ok(512));
+
+        let sid = b"FDSN:GE_WLF_00_B_H_Z";
+        let mut buf = vec![0; MS3_FIXED_HEADER_LEN];
+        buf[..3].copy_from_slice(&[b'M', b'S', 3]);
+        buf[33] = sid.len() as u8;
+        buf[34..36].copy_from_slice(&2u16.to_le_bytes());
+        buf[36..40].copy_from_slice(&100u32.to_le_bytes());
+        assert_eq!(
+            record_len(&buf),
+            Ok(MS3_FIXED_HEADER_LEN + sid.len() + 2 + 100)
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use slink::ProtocolErrorV4;
-use slink::{CommandV4, SequenceNumberV4};
+use slink::{CommandV4, SelectCmdPatternV4, SequenceNumberV4};
 
 use crate::select::Select;
 
@@ -27,36 +27,51 @@ impl State {
 #[derive(Debug, Clone)]
 pub struct StationNegotiator {
     pub select: Select,
+    /// The station pattern of the `STATION` command.
+    pub station_pattern: String,
+    /// The `SELECT` patterns applied.
+    pub select_patterns: Vec<SelectCmdPatternV4>,
 
     state: State,
 }
 
 impl StationNegotiator {
-    /// Creates a new negotiator.
-    pub fn new(select: Select) -> Self {
+    /// Creates a new negotiator for the stations matching `station_pattern`.
+    pub fn new(station_pattern: &str, select: Select) -> Self {
         Self {
             select,
+            station_pattern: station_pattern.to_string(),
+            select_patterns: vec![],
             state: State::Station,
         }
     }
 
     /// Transitions the negotiator by feeding the next command.
+    ///
+    /// Commands with invalid arguments (e.g. invalid patterns) are rejected with an `ARGUMENTS`
+    /// error without transitioning the negotiator.
     pub fn next(&mut self, cmd: &CommandV4) -> Result<(), ProtocolErrorV4> {
-        self.state = self.state.next(cmd);
-        if self.state == State::Error {
+        let state = self.state.next(cmd);
+        if state == State::Error {
+            self.state = state;
             return Err(ProtocolErrorV4::unexpected_command());
         }
 
         match cmd {
             CommandV4::Select(cmd) => {
+                // XXX(damb): apply the patterns to a copy such that the selection is left
+                // unchanged if any pattern is rejected
+                let mut select = self.select.clone();
                 for select_pattern in cmd.iter() {
-                    self.select.apply(
+                    select.apply(
                         select_pattern.exclude,
                         &select_pattern.stream_pattern,
                         &select_pattern.format_subformat_pattern,
                         &select_pattern.filter,
-                    );
+                    )?;
                 }
+                self.select = select;
+                self.select_patterns.extend(cmd.iter().cloned());
             }
             CommandV4::Data(cmd) => {
                 if let Some(ref seq_num) = cmd.seq_num {
@@ -73,7 +88,33 @@ impl StationNegotiator {
             }
             _ => {}
         };
+        self.state = state;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use slink::ErrorCodeV4;
+
+    #[test]
+    fn reject_invalid_select() {
+        let mut negotiator = StationNegotiator::new("GE_WLF", Select::default());
+
+        let cmd = CommandV4::Select("00_B_H_Z 00_B_H_(".parse().unwrap());
+        assert_eq!(
+            negotiator.next(&cmd).unwrap_err().code,
+            ErrorCodeV4::IncorrectArguments
+        );
+        assert_eq!(negotiator.state, State::Station);
+
+        let cmd = CommandV4::Select("00_B_H_Z".parse().unwrap());
+        assert!(negotiator.next(&cmd).is_ok());
+        assert_eq!(negotiator.state, State::Select);
+        assert_eq!(negotiator.select_patterns.len(), 1);
+    }
+}
@@ -0,0 +1,512 @@
+//! Caching SeedLink proxy, i.e. a server mirroring the packets of an upstream SeedLink server to
+//! its packet buffer.
+//!
+//! The proxy serves the inventory of the upstream server. Streams requested by downstream clients
+//! (i.e. by means of `STATION` and `SELECT` commands) are subscribed to upstream by a single
+//! connection. Packets received are published to the packet buffer of the proxy, i.e. the buffer
+//! acts as cache shared by all downstream clients. Thus, the number of upstream connections is
+//! reduced to one, regardless of the number of local consumers.
+//!
+//! Downstream clients are streamed the packets cached. The sequence numbers and time windows
+//! advertised are adjusted to the packets cached (see [`apply_retention`](crate::apply_retention)).
+//!
+//! Example usage::
+//!
+//! ```rust,no_run
+//! use slink_server::proxy::{Proxy, ProxyConfig};
+//! use slink_server::{ListenerConfig, PacketBuffer, RetentionPolicy};
+//!
+//! let config = ProxyConfig::new("slink://geofon.gfz-potsdam.de/");
+//! let buffer = PacketBuffer::new(RetentionPolicy::default().with_max_packets(1000));
+//! let (proxy, relay) = Proxy::connect(config, buffer).await.unwrap();
+//!
+//! let (server_handle, _) = slink_server::spawn_main_loop(proxy);
+//! slink_server::spawn_accept(
+//!     ([0, 0, 0, 0], 18000).into(),
+//!     server_handle.clone(),
+//!     ListenerConfig::default(),
+//! );
+//! relay.run(server_handle).await;
+//! ```
+
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::StreamExt;
+use regex::Regex;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+use slink::{
+    Client, DataTransferMode, InventoryLevel, ProtocolErrorV4, SeedLinkPacket, SeedLinkPacketV3,
+    SeedLinkResult, SelectCmdPatternV4, Station, StreamConfig, StreamEnd, StreamItem,
+    StreamSelector,
+};
+
+use crate::select::validate_pattern;
+use crate::{PacketBuffer, RequestContext, SeedLinkServer, ServerHandle};
+
+/// Default delay before reconnecting to the upstream server.
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Maximum SeedLink v3 sequence number, i.e. sequence numbers wrap at `0xFFFFFF`.
+const MAX_SEQ_NUM_V3: u64 = 0xFFFFFF;
+
+/// Proxy configuration.
+#[derive(Clone, Debug)]
+pub struct ProxyConfig {
+    upstream: String,
+    data_center_description: Option<String>,
+    keep_alive_interval: Option<Duration>,
+    reconnect_delay: Duration,
+}
+
+impl ProxyConfig {
+    /// Creates the configuration of a proxy relaying from the upstream server `upstream` (e.g.
+    /// `slink://host:port/`).
+    pub fn new(upstream: impl Into<String>) -> Self {
+        Self {
+            upstream: upstream.into(),
+            data_center_description: None,
+            keep_alive_interval: None,
+            reconnect_delay: DEFAULT_RECONNECT_DELAY,
+        }
+    }
+
+    /// Sets the data center description announced to downstream clients. Defaults to the
+    /// description of the upstream server.
+    pub fn with_data_center_description(mut self, description: impl Into<String>) -> Self {
+        self.data_center_description = Some(description.into());
+        self
+    }
+
+    /// Sets the keepalive interval of the upstream connection.
+    pub fn with_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Sets the delay before reconnecting to the upstream server, e.g. after the upstream
+    /// connection failed.
+    pub fn with_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.reconnect_delay = delay;
+        self
+    }
+}
+
+/// Stream selectors requested upstream per station. `None` if all streams are requested.
+type Selectors = Option<BTreeSet<String>>;
+
+/// Streams requested by downstream clients per station (i.e. `NET_STA`).
+#[derive(Clone, Debug, Default)]
+struct Demand {
+    stations: Arc<Mutex<BTreeMap<String, Selectors>>>,
+    changed: Arc<Notify>,
+}
+
+impl Demand {
+    /// Adds the streams selected by `selectors` of the stations `station_ids`. Notifies the relay
+    /// if the demand changed.
+    fn request(&self, station_ids: impl IntoIterator<Item = String>, selectors: &Selectors) {
+        let mut stations = self.stations.lock().unwrap();
+        let mut changed = false;
+        for station_id in station_ids {
+            match stations.entry(station_id) {
+                Entry::Vacant(entry) => {
+                    entry.insert(selectors.clone());
+                    changed = true;
+                }
+                Entry::Occupied(mut entry) => match (entry.get_mut(), selectors) {
+                    (None, _) => {}
+                    (requested, None) => {
+                        *requested = None;
+                        changed = true;
+                    }
+                    (Some(requested), Some(selectors)) => {
+                        let len = requested.len();
+                        requested.extend(selectors.iter().cloned());
+                        changed |= requested.len() != len;
+                    }
+                },
+            }
+        }
+        if changed {
+            self.changed.notify_one();
+        }
+    }
+
+    /// Returns the stations requested together with their stream selectors.
+    fn stations(&self) -> Vec<(String, Selectors)> {
+        self.stations
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(station_id, selectors)| (station_id.clone(), selectors.clone()))
+            .collect()
+    }
+}
+
+/// Server implementation serving the inventory of the upstream server (see [`Proxy::connect`]).
+#[derive(Debug)]
+pub struct Proxy {
+    data_center_description: String,
+    stations: Vec<Station>,
+    packet_buffer: PacketBuffer,
+    demand: Demand,
+}
+
+impl Proxy {
+    /// Connects to the upstream server configured by `config` and requests its inventory.
+    /// Returns the proxy together with the relay of the upstream packets to `packet_buffer`.
+    ///
+    /// Note that the inventory is requested once, i.e. stations added upstream later on are not
+    /// served.
+    pub async fn connect(
+        config: ProxyConfig,
+        packet_buffer: PacketBuffer,
+    ) -> SeedLinkResult<(Self, Relay)> {
+        let client = Client::open(config.upstream.as_str())?;
+        let mut con = client.get_connection().await?;
+        let inventory = con
+            .request_inventory(None, None, InventoryLevel::Stream)
+            .await?;
+        info!(
+            "proxying {} ({} stations)",
+            config.upstream,
+            inventory.len()
+        );
+
+        let data_center_description = match config.data_center_description {
+            Some(ref description) => description.clone(),
+            None => con.greet_raw().await?.pop().unwrap_or_default(),
+        };
+        con.shutdown().await?;
+
+        let demand = Demand::default();
+        let proxy = Self {
+            data_center_description,
+            stations: inventory.iter().cloned().collect(),
+            packet_buffer,
+            demand: demand.clone(),
+        };
+        let relay = Relay {
+            client,
+            demand,
+            keep_alive_interval: config.keep_alive_interval,
+            reconnect_delay: config.reconnect_delay,
+            seq_nums: HashMap::new(),
+        };
+
+        Ok((proxy, relay))
+    }
+
+    /// Requests the streams matching `select_patterns` of the stations matching
+    /// `station_pattern` from the upstream server.
+    fn request_stations(
+        &self,
+        station_pattern: &str,
+        select_patterns: &[SelectCmdPatternV4],
+    ) -> Result<(), ProtocolErrorV4> {
+        let re = station_regex(station_pattern)?;
+        self.demand.request(
+            self.stations
+                .iter()
+                .map(|station| station.id().to_string())
+                .filter(|station_id| re.is_match(station_id)),
+            &upstream_selectors(select_patterns),
+        );
+
+        Ok(())
+    }
+}
+
+#[crate::async_trait]
+impl SeedLinkServer for Proxy {
+    fn implementation(&self) -> &str {
+        "slink-proxy"
+    }
+
+    fn implementation_version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn data_center_description(&self) -> &str {
+        &self.data_center_description
+    }
+
+    fn packet_buffer(&self) -> Option<&PacketBuffer> {
+        Some(&self.packet_buffer)
+    }
+
+    async fn inventory_stations(
+        &self,
+        _ctx: &RequestContext,
+        _station_pattern: &str,
+        _stream_pattern: Option<String>,
+        _format_subformat_pattern: Option<String>,
+    ) -> Result<&Vec<Station>, ProtocolErrorV4> {
+        Ok(&self.stations)
+    }
+
+    async fn inventory_streams(
+        &self,
+        _ctx: &RequestContext,
+        _station_pattern: &str,
+        _stream_pattern: Option<String>,
+        _format_subformat_pattern: Option<String>,
+    ) -> Result<&Vec<Station>, ProtocolErrorV4> {
+        Ok(&self.stations)
+    }
+
+    fn stations_requested(
+        &self,
+        _ctx: &RequestContext,
+        station_pattern: &str,
+        select_patterns: &[SelectCmdPatternV4],
+    ) {
+        // XXX(damb): the patterns were validated before
+        let _ = self.request_stations(station_pattern, select_patterns);
+    }
+}
+
+/// Relay of the packets of the upstream server to the packet buffer of the proxy.
+#[derive(Debug)]
+pub struct Relay {
+    client: Client,
+    demand: Demand,
+    keep_alive_interval: Option<Duration>,
+    reconnect_delay: Duration,
+    /// Sequence numbers to resume from per station, i.e. following the packets relayed most
+    /// recently.
+    seq_nums: HashMap<String, u64>,
+}
+
+impl Relay {
+    /// Relays the packets of the streams requested by downstream clients. Packets are published
+    /// by means of `server_handle` (see [`ServerHandle::publish_raw`]).
+    ///
+    /// Whenever the streams requested change, the upstream connection is re-established
+    /// resuming the stations relayed before. Runs until the proxy is shut down.
+    ///
+    /// Note that the future is not `Send`, i.e. it must be awaited rather than spawned.
+    pub async fn run(mut self, server_handle: ServerHandle) {
+        loop {
+            let stations = self.demand.stations();
+            if stations.is_empty() {
+                self.demand.changed.notified().await;
+                continue;
+            }
+
+            if let Err(e) = self.relay(&stations, &server_handle).await {
+                warn!("upstream connection failed: {}", e);
+                tokio::time::sleep(self.reconnect_delay).await;
+            }
+        }
+    }
+
+    /// Relays the packets of the streams requested for `stations` (i.e. the stations together with
+    /// their stream selectors) until the upstream connection terminates or the streams requested
+    /// change.
+    async fn relay(
+        &mut self,
+        stations: &[(String, Selectors)],
+        server_handle: &ServerHandle,
+    ) -> SeedLinkResult<()> {
+        let mut con = self.client.get_connection().await?;
+        for (station_id, selectors) in stations {
+            con.add_stream_config(self.stream_config(station_id, selectors)?)?;
+        }
+        con.configure(DataTransferMode::RealTime, None, false)
+            .await?;
+        debug!("relaying {} stations", stations.len());
+
+        let changed = self.demand.changed.clone();
+        let packets = con.packets(self.keep_alive_interval);
+        futures::pin_mut!(packets);
+        loop {
+            tokio::select! {
+                item = packets.next() => match item {
+                    Some(StreamItem::Packet(packet)) => self.publish(&packet, server_handle),
+                    Some(StreamItem::End(StreamEnd::Error(e))) => return Err(e),
+                    Some(StreamItem::End(end)) => {
+                        warn!("upstream connection terminated: {:?}", end);
+                        return Ok(());
+                    }
+                    None => return Ok(()),
+                },
+                _ = changed.notified() => {
+                    debug!("streams requested changed: reconnecting");
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Returns the upstream stream configuration of the station `station_id` requesting the streams
+    /// selected by `selectors`, resuming from the packet following the one relayed most recently.
+    fn stream_config(
+        &self,
+        station_id: &str,
+        selectors: &Selectors,
+    ) -> SeedLinkResult<StreamConfig> {
+        let (net, sta) = station_id.split_once('_').unwrap_or_default();
+        let mut stream_config = StreamConfig::try_new(net, sta)?;
+        for selector in selectors.iter().flatten() {
+            stream_config = stream_config.with_selector(StreamSelector::new(selector)?);
+        }
+        if let Some(seq_num) = self.seq_nums.get(station_id) {
+            stream_config = stream_config.with_seq_num(*seq_num);
+        }
+
+        Ok(stream_config)
+    }
+
+    /// Publishes the data packet `packet`. Other packets are ignored.
+    fn publish(&mut self, packet: &SeedLinkPacket, server_handle: &ServerHandle) {
+        let record = match packet {
+            SeedLinkPacket::V3(SeedLinkPacketV3::GenericData(packet)) => packet.raw_payload(),
+            SeedLinkPacket::V4(inner) if packet.is_data() => inner.payload_raw(),
+            _ => return,
+        };
+        let (sid, seq_num) = match packet.to_record() {
+            Some(Ok((sid, seq_num, _))) => (sid, seq_num),
+            Some(Err(e)) => {
+                warn!("failed to decode upstream packet: {}", e);
+                return;
+            }
+            None => return,
+        };
+
+        let max_seq_num = match packet {
+            SeedLinkPacket::V3(_) => MAX_SEQ_NUM_V3,
+            SeedLinkPacket::V4(_) => u64::MAX,
+        };
+        self.seq_nums.insert(
+            format!("{}_{}", sid.nslc.net, sid.nslc.sta),
+            next_seq_num(seq_num, max_seq_num),
+        );
+        if let Err(e) = server_handle.publish_raw(record) {
+            warn!("failed to publish upstream packet ({}): {}", sid, e);
+        }
+    }
+}
+
+/// Returns the sequence number following `seq_num`, wrapping at `max_seq_num`.
+fn next_seq_num(seq_num: u64, max_seq_num: u64) -> u64 {
+    if seq_num >= max_seq_num {
+        0
+    } else {
+        seq_num + 1
+    }
+}
+
+/// Returns the stream selectors requested upstream for the `SELECT` patterns `select_patterns`,
+/// or `None` if all streams are requested.
+fn upstream_selectors(select_patterns: &[SelectCmdPatternV4]) -> Selectors {
+    // XXX(damb): the upstream subscription of a station is shared by all downstream clients;
+    // thus, excluding patterns and filters are not forwarded, i.e. a superset of the streams is
+    // requested upstream
+    let selectors: BTreeSet<String> = select_patterns
+        .iter()
+        .filter(|pattern| !pattern.exclude)
+        .map(|pattern| {
+            SelectCmdPatternV4 {
+                filter: None,
+                ..pattern.clone()
+            }
+            .to_string()
+        })
+        .collect();
+
+    if selectors.is_empty() {
+        None
+    } else {
+        Some(selectors)
+    }
+}
+
+/// Creates an anchored regex matching station identifiers (i.e. `NET_STA`) from the station
+/// pattern `station_pattern`.
+fn station_regex(station_pattern: &str) -> Result<Regex, ProtocolErrorV4> {
+    validate_pattern(station_pattern)?;
+
+    let re = station_pattern.replace('*', ".*").replace('?', ".");
+    Regex::new(&format!("^{}$", re)).map_err(|_| ProtocolErrorV4::incorrect_arguments())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn select_patterns(patterns: &str) -> Vec<SelectCmdPatternV4> {
+        patterns
+            .split(' ')
+            .map(|pattern| pattern.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn request_stations() {
+        let demand = Demand::default();
+        let re = station_regex("GE_W*").unwrap();
+        demand.request(
+            ["GE_WLF", "GE_APE", "XGE_WLF"]
+                .into_iter()
+                .map(String::from)
+                .filter(|station_id| re.is_match(station_id)),
+            &None,
+        );
+        demand.request(["GE_WLF".to_string()], &None);
+
+        assert_eq!(demand.stations(), vec![("GE_WLF".to_string(), None)]);
+        assert!(station_regex("GE_(").is_err());
+    }
+
+    #[test]
+    fn request_streams() {
+        let demand = Demand::default();
+        let selectors = upstream_selectors(&select_patterns("*_B_H_Z !00_B_H_Z *_H_H_Z:native"));
+        demand.request(["GE_WLF".to_string()], &selectors);
+        let selectors = upstream_selectors(&select_patterns("*_B_H_?.2D"));
+        demand.request(["GE_WLF".to_string()], &selectors);
+        assert_eq!(
+            demand.stations(),
+            vec![(
+                "GE_WLF".to_string(),
+                Some(
+                    ["*_B_H_?.2D", "*_B_H_Z", "*_H_H_Z"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect()
+                )
+            )]
+        );
+
+        let stream_config = Relay {
+            client: Client::open("slink://localhost:18000/").unwrap(),
+            demand: demand.clone(),
+            keep_alive_interval: None,
+            reconnect_delay: DEFAULT_RECONNECT_DELAY,
+            seq_nums: HashMap::from([("GE_WLF".to_string(), 0x2a)]),
+        }
+        .stream_config("GE_WLF", &demand.stations()[0].1)
+        .unwrap();
+        assert_eq!(stream_config.selectors().len(), 3);
+        assert_eq!(stream_config.seq_num(), Some(0x2a));
+
+        // excluding patterns only, i.e. all streams are requested
+        let selectors = upstream_selectors(&select_patterns("!00_B_H_Z"));
+        assert!(selectors.is_none());
+        demand.request(["GE_WLF".to_string()], &selectors);
+        assert_eq!(demand.stations(), vec![("GE_WLF".to_string(), None)]);
+    }
+
+    #[test]
+    fn wrap_seq_num() {
+        assert_eq!(next_seq_num(0x2a, MAX_SEQ_NUM_V3), 0x2b);
+        assert_eq!(next_seq_num(MAX_SEQ_NUM_V3, MAX_SEQ_NUM_V3), 0);
+        assert_eq!(next_seq_num(MAX_SEQ_NUM_V3, u64::MAX), MAX_SEQ_NUM_V3 + 1);
+    }
+}
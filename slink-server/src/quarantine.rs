@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tracing::warn;
+
+use slink::wire;
+
+use crate::buffer::BufferedPacket;
+
+/// Default number of consecutive encoding failures quarantining a station.
+pub(crate) const DEFAULT_QUARANTINE_THRESHOLD: u32 = 3;
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Consecutive encoding failures per station.
+    failures: HashMap<String, u32>,
+}
+
+/// Quarantine of stations whose packets repeatedly fail encoding.
+///
+/// Once the packets of a station failed encoding `threshold` times in a row, the station is
+/// quarantined, i.e. its packets are skipped (and no longer published, see
+/// [`ServerHandle::publish_raw`]) until the station is released. Thus, a single station shipping
+/// bad payloads does not affect the sessions of the clients as a whole.
+///
+/// [`ServerHandle::publish_raw`]: crate::ServerHandle::publish_raw
+#[derive(Clone, Debug)]
+pub struct Quarantine {
+    threshold: u32,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Default for Quarantine {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUARANTINE_THRESHOLD)
+    }
+}
+
+impl Quarantine {
+    /// Creates a new quarantine. Panics if `threshold` is zero.
+    pub fn new(threshold: u32) -> Self {
+        assert_ne!(threshold, 0, "threshold must be greater than zero");
+        Self {
+            threshold,
+            inner: Arc::new(Mutex::new(Inner::default())),
+        }
+    }
+
+    /// Returns whether the station identified by `station_id` (i.e. `NET_STA`) is quarantined.
+    pub fn is_quarantined(&self, station_id: &str) -> bool {
+        self.inner
+            .lock()
+            .unwrap()
+            .failures
+            .get(station_id)
+            .is_some_and(|failures| *failures >= self.threshold)
+    }
+
+    /// Returns the identifiers of the stations quarantined ordered by station identifier.
+    pub fn quarantined(&self) -> Vec<String> {
+        let mut rv: Vec<String> = self
+            .inner
+            .lock()
+            .unwrap()
+            .failures
+            .iter()
+            .filter(|(_, failures)| **failures >= self.threshold)
+            .map(|(station_id, _)| station_id.clone())
+            .collect();
+        rv.sort();
+
+        rv
+    }
+
+    /// Releases the station identified by `station_id` from quarantine. Returns whether the
+    /// station was quarantined.
+    pub fn release(&self, station_id: &str) -> bool {
+        let failures = self.inner.lock().unwrap().failures.remove(station_id);
+        failures.is_some_and(|failures| failures >= self.threshold)
+    }
+
+    /// Encodes the buffered packet `packet` of the station identified by `station_id` as SeedLink
+    /// `v4` packet.
+    ///
+    /// Returns `None` if the station is quarantined or encoding failed.
+    pub fn encode_v4(&self, station_id: &str, packet: &BufferedPacket) -> Option<Vec<u8>> {
+        if self.is_quarantined(station_id) {
+            return None;
+        }
+
+        // TODO(damb): determine the subformat from the record
+        let format = if packet.data.starts_with(b"MS") && packet.data.get(2) == Some(&3) {
+            *b"3D"
+        } else {
+            *b"2D"
+        };
+        let mut buf = Vec::with_capacity(packet.data.len() + 32);
+        match wire::v4::write_packet(
+            format,
+            packet.seq_num,
+            station_id.as_bytes(),
+            &packet.data,
+            &mut buf,
+        ) {
+            Ok(()) => {
+                self.inner.lock().unwrap().failures.remove(station_id);
+                Some(buf)
+            }
+            Err(e) => {
+                self.record_failure(station_id, &e.to_string());
+                None
+            }
+        }
+    }
+
+    fn record_failure(&self, station_id: &str, reason: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        let failures = inner.failures.entry(station_id.to_string()).or_default();
+        *failures += 1;
+        if *failures == self.threshold {
+            warn!(
+                "quarantined station {} after {} encoding failures (last: {})",
+                station_id, failures, reason
+            );
+        } else {
+            warn!(
+                "failed to encode packet of station {}: {}",
+                station_id, reason
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use bytes::Bytes;
+    use time::OffsetDateTime;
+
+    fn packet(data: &'static [u8]) -> BufferedPacket {
+        BufferedPacket {
+            seq_num: 42,
+            stream_id: "00_B_H_Z".to_string(),
+            start_time: OffsetDateTime::UNIX_EPOCH,
+            end_time: OffsetDateTime::UNIX_EPOCH,
+            data: Bytes::from_static(data),
+        }
+    }
+
+    #[test]
+    fn quarantine_after_failures() {
+        let quarantine = Quarantine::new(2);
+
+        assert!(quarantine.encode_v4("GE_WLF", &packet(b"")).is_none());
+        assert!(quarantine.encode_v4("GE_WLF", &packet(b"foo")).is_some());
+        assert!(!quarantine.is_quarantined("GE_WLF"));
+
+        for _ in 0..2 {
+            assert!(quarantine.encode_v4("GE_WLF", &packet(b"")).is_none());
+        }
+        assert!(quarantine.is_quarantined("GE_WLF"));
+        assert!(quarantine.encode_v4("GE_WLF", &packet(b"foo")).is_none());
+        assert!(quarantine.encode_v4("GE_APE", &packet(b"foo")).is_some());
+        assert_eq!(quarantine.quarantined(), vec!["GE_WLF".to_string()]);
+
+        assert!(quarantine.release("GE_WLF"));
+        assert!(!quarantine.release("GE_WLF"));
+        assert!(quarantine.encode_v4("GE_WLF", &packet(b"foo")).is_some());
+    }
+}
@@ -0,0 +1,184 @@
+//! Time-shifted playback of buffered packets.
+//!
+//! Servers advertising the [`CAPABILITY_REPLAY`](crate::CAPABILITY_REPLAY) capability accept the
+//! `REPLAY <speed> <start_time> [<end_time>]` command after stations were selected. Instead of
+//! streaming in real-time, the packets buffered (see [`SeedLinkServer::packet_buffer`]) within the
+//! time window requested are played back at `END` (or `ENDFETCH`), paced by their start times. The
+//! speed factor `speed` allows playing back at real-time (i.e. `1`) or accelerated (e.g. `10`)
+//! speed, e.g. for exercise drills or testing algorithms against recorded sequences. Once played
+//! back, the connection is terminated with `END`.
+//!
+//! Note that this is a non-standard extension.
+//!
+//! [`SeedLinkServer::packet_buffer`]: crate::SeedLinkServer::packet_buffer
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use futures::stream::{self, Stream, StreamExt};
+use time::{format_description::well_known::Iso8601, OffsetDateTime};
+use tokio::time::{self as tokio_time, Instant};
+
+use slink::ProtocolErrorV4;
+
+use crate::{BufferedPacket, PacketBuffer, Select};
+
+/// Name of the command requesting a playback.
+pub const REPLAY_COMMAND: &str = "REPLAY";
+
+/// Playback requested by a client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayRequest {
+    /// Speed factor, e.g. `1` for real-time playback.
+    pub speed: f64,
+    /// Start of the time window played back.
+    pub start_time: OffsetDateTime,
+    /// End of the time window played back, if any.
+    pub end_time: Option<OffsetDateTime>,
+}
+
+impl ReplayRequest {
+    /// Returns whether the packet spanning from `start_time` to `end_time` is played back.
+    pub fn contains(&self, start_time: &OffsetDateTime, end_time: &OffsetDateTime) -> bool {
+        *end_time > self.start_time && self.end_time.map_or(true, |t| *start_time < t)
+    }
+}
+
+impl FromStr for ReplayRequest {
+    type Err = ProtocolErrorV4;
+
+    /// Parses the arguments of the `REPLAY` command.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split: Vec<&str> = s.split(' ').collect();
+        if split.len() < 2 || split.len() > 3 {
+            return Err(ProtocolErrorV4::incorrect_arguments());
+        }
+
+        let speed = split[0]
+            .parse::<f64>()
+            .map_err(|_| ProtocolErrorV4::incorrect_arguments())?;
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(ProtocolErrorV4::incorrect_arguments());
+        }
+
+        let start_time = parse_time(split[1])?;
+        let end_time = split.get(2).map(|s| parse_time(s)).transpose()?;
+        if end_time.is_some_and(|t| t <= start_time) {
+            return Err(ProtocolErrorV4::incorrect_arguments());
+        }
+
+        Ok(Self {
+            speed,
+            start_time,
+            end_time,
+        })
+    }
+}
+
+fn parse_time(s: &str) -> Result<OffsetDateTime, ProtocolErrorV4> {
+    let t = OffsetDateTime::parse(s, &Iso8601::DEFAULT)
+        .map_err(|_| ProtocolErrorV4::incorrect_arguments())?;
+    if !t.offset().is_utc() {
+        return Err(ProtocolErrorV4::incorrect_arguments());
+    }
+
+    Ok(t)
+}
+
+/// Returns the identifiers (i.e. `NET_STA`) of the stations selected by `selects`.
+pub fn selected_stations(selects: &[Select]) -> Vec<String> {
+    let mut station_ids: Vec<String> = selects
+        .iter()
+        .flat_map(|select| select.iter())
+        .filter(|station| station.has_selected())
+        .map(|station| format!("{}_{}", station.net_code(), station.sta_code()))
+        .collect();
+    station_ids.sort();
+    station_ids.dedup();
+
+    station_ids
+}
+
+/// Returns a stream playing back the packets of the stations `station_ids` buffered by `buffer`
+/// according to `request`. Items are the packets including the station identifiers.
+///
+/// Packets are ordered by start time. The first packet is produced immediately; subsequent
+/// packets are delayed by the time elapsed between their start times divided by the speed factor
+/// requested.
+pub fn replay(
+    buffer: &PacketBuffer,
+    station_ids: &[String],
+    request: &ReplayRequest,
+) -> impl Stream<Item = (String, BufferedPacket)> {
+    let mut packets: Vec<_> = station_ids
+        .iter()
+        .flat_map(|station_id| {
+            buffer
+                .packets_from(station_id, 0)
+                .into_iter()
+                .map(|packet| (station_id.clone(), packet))
+        })
+        .filter(|(_, packet)| request.contains(&packet.start_time, &packet.end_time))
+        .collect();
+    packets.sort_by_key(|(_, packet)| packet.start_time);
+
+    let origin = Instant::now();
+    let first = packets.first().map(|(_, packet)| packet.start_time);
+    let speed = request.speed;
+    stream::iter(packets).then(move |(station_id, packet)| async move {
+        if let Some(first) = first {
+            let offset = (packet.start_time - first).as_seconds_f64() / speed;
+            tokio_time::sleep_until(origin + Duration::from_secs_f64(offset)).await;
+        }
+
+        (station_id, packet)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use bytes::Bytes;
+    use time::macros::datetime;
+
+    use crate::RetentionPolicy;
+
+    #[tokio::test(start_paused = true)]
+    async fn replay_accelerated() {
+        assert!("0 2023-01-01T12:00:00Z".parse::<ReplayRequest>().is_err());
+        assert!("10 2023-01-01T12:00:00Z 2023-01-01T11:00:00Z"
+            .parse::<ReplayRequest>()
+            .is_err());
+        let request: ReplayRequest = "10 2023-01-01T12:00:30Z 2023-01-01T12:01:30Z"
+            .parse()
+            .unwrap();
+
+        let buffer = PacketBuffer::new(RetentionPolicy::default());
+        let t = datetime!(2023-01-01 12:00 UTC);
+        for (i, station_id) in ["GE_WLF", "GE_APE", "GE_WLF", "GE_APE"].iter().enumerate() {
+            let start_time = t + Duration::from_secs(30 * i as u64);
+            let end_time = start_time + Duration::from_secs(30);
+            buffer.push(station_id, "_B_H_Z", start_time, end_time, Bytes::new());
+        }
+
+        let start = Instant::now();
+        let packets = replay(
+            &buffer,
+            &["GE_APE".to_string(), "GE_WLF".to_string()],
+            &request,
+        );
+        futures::pin_mut!(packets);
+
+        let (station_id, packet) = packets.next().await.unwrap();
+        assert_eq!(station_id, "GE_APE");
+        assert_eq!(packet.start_time, datetime!(2023-01-01 12:00:30 UTC));
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        let (station_id, packet) = packets.next().await.unwrap();
+        assert_eq!(station_id, "GE_WLF");
+        assert_eq!(packet.start_time, datetime!(2023-01-01 12:01:00 UTC));
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+        assert!(packets.next().await.is_none());
+    }
+}
@@ -1,9 +1,201 @@
-/// Hello response information.
+use bytes::{BufMut, BytesMut};
+use tracing::warn;
+
+use slink::to_first_hello_resp_line_v4;
+use slink::wire::conformance::MAX_COMMAND_LINE_LENGTH;
+
+/// Versioned `HELLO` response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Hello {
+    V3(HelloV3),
+    V4(HelloV4),
+}
+
+impl Hello {
+    /// Encodes the response, i.e. writes the `<CR><LF>` terminated response lines to `dst`.
+    pub fn encode(&self, dst: &mut BytesMut) {
+        let (first_line, data_center_description) = match self {
+            Self::V3(hello) => (hello.first_line(), &hello.data_center_description),
+            Self::V4(hello) => (hello.first_line(), &hello.data_center_description),
+        };
+
+        dst.reserve(first_line.len() + data_center_description.len() + 4);
+        dst.put(first_line.as_bytes());
+        dst.put(&b"\r\n"[..]);
+        dst.put(data_center_description.as_bytes());
+        dst.put(&b"\r\n"[..]);
+    }
+}
+
+/// SeedLink `v3` `HELLO` response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HelloV3 {
+    pub implementation: String,
+    pub implementation_version: String,
+    /// Protocol version announced.
+    pub protocol_version: (u8, u8),
+
+    pub data_center_description: String,
+
+    pub capabilities: Option<Vec<String>>,
+}
+
+impl HelloV3 {
+    fn first_line(&self) -> String {
+        let mut line = format!(
+            "SeedLink v{}.{} ({}/{})",
+            self.protocol_version.0,
+            self.protocol_version.1,
+            self.implementation,
+            self.implementation_version
+        );
+        if let Some(ref capabilities) = self.capabilities {
+            line += &format!(" :: {}", capabilities.join(" "));
+        }
+
+        line
+    }
+}
+
+/// SeedLink `v4` `HELLO` response.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Hello {
+pub struct HelloV4 {
     pub implementation: String,
     pub implementation_version: String,
+    /// Protocol versions advertised (sorted in descending order).
+    pub protocol_versions: Vec<(u8, u8)>,
 
     pub data_center_description: String,
+
+    pub capabilities: Option<Vec<String>>,
 }
 
+impl HelloV4 {
+    fn first_line(&self) -> String {
+        to_first_hello_resp_line_v4(
+            &self.implementation,
+            &self.implementation_version,
+            &self.protocol_versions,
+            &self.capabilities,
+        )
+    }
+}
+
+/// Encoding of the data center description, i.e. the second line of the `HELLO` response.
+///
+/// Response lines must consist of printable ASCII characters, only.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct DescriptionEncoding {
+    /// Maximum length of the description (excluding the `<CR><LF>` terminator). Longer
+    /// descriptions are truncated.
+    pub max_len: usize,
+    /// Character replacing characters not allowed (i.e. non-printable or non-ASCII characters).
+    /// If `None` (or the replacement is not allowed itself), such characters are removed.
+    pub replacement: Option<char>,
+}
+
+impl Default for DescriptionEncoding {
+    fn default() -> Self {
+        Self {
+            // XXX(damb): keep the description as short as command lines are
+            max_len: MAX_COMMAND_LINE_LENGTH - 2,
+            replacement: Some('?'),
+        }
+    }
+}
+
+impl DescriptionEncoding {
+    /// Encodes the data center description `description`. Emits a warning if characters are
+    /// replaced or the description is truncated.
+    pub fn encode(&self, description: &str) -> String {
+        let replacement = self.replacement.filter(is_allowed);
+        let mut replaced = false;
+        let mut rv: String = description
+            .chars()
+            .filter_map(|c| {
+                if is_allowed(&c) {
+                    return Some(c);
+                }
+                replaced = true;
+                replacement
+            })
+            .collect();
+
+        if replaced {
+            warn!(
+                "data center description {:?} contains characters not allowed",
+                description
+            );
+        }
+        if rv.len() > self.max_len {
+            warn!(
+                "truncating data center description {:?} to {} characters",
+                description, self.max_len
+            );
+            rv.truncate(self.max_len);
+        }
+
+        rv
+    }
+}
+
+fn is_allowed(c: &char) -> bool {
+    c.is_ascii() && !c.is_ascii_control()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use slink::wire::conformance;
+
+    #[test]
+    fn encode_hello() {
+        let mut buf = BytesMut::new();
+        Hello::V4(HelloV4 {
+            implementation: "slink-server".to_string(),
+            implementation_version: "0.1".to_string(),
+            protocol_versions: vec![(4, 0), (3, 1)],
+            data_center_description: "GEOFON".to_string(),
+            capabilities: Some(vec!["TIME".to_string()]),
+        })
+        .encode(&mut buf);
+        assert_eq!(
+            &buf[..],
+            b"SeedLink v4.0 (slink-server/0.1) :: SLPROTO:3.1 SLPROTO:4.0 TIME\r\nGEOFON\r\n"
+        );
+        assert_eq!(conformance::check_lines(&buf), Ok(()));
+
+        let mut buf = BytesMut::new();
+        Hello::V3(HelloV3 {
+            implementation: "slink-server".to_string(),
+            implementation_version: "0.1".to_string(),
+            protocol_version: (3, 1),
+            data_center_description: "GEOFON".to_string(),
+            capabilities: None,
+        })
+        .encode(&mut buf);
+        assert_eq!(&buf[..], b"SeedLink v3.1 (slink-server/0.1)\r\nGEOFON\r\n");
+    }
+
+    #[test]
+    fn encode_description() {
+        let encoding = DescriptionEncoding {
+            max_len: 16,
+            replacement: Some('?'),
+        };
+        assert_eq!(encoding.encode("GEOFON DC"), "GEOFON DC");
+        assert_eq!(encoding.encode("Zürich\tDC"), "Z?rich?DC");
+        assert_eq!(
+            encoding.encode("GEOFON Data Centre, Potsdam"),
+            "GEOFON Data Cent"
+        );
+
+        let encoding = DescriptionEncoding {
+            replacement: Some('\n'),
+            ..Default::default()
+        };
+        assert_eq!(encoding.encode("Zürich\r\nDC"), "ZrichDC");
+    }
+}
@@ -1,3 +1,3 @@
-pub use hello::Hello;
+pub use hello::{DescriptionEncoding, Hello, HelloV3, HelloV4};
 
 mod hello;
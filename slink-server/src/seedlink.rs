@@ -6,13 +6,53 @@ use bytes::{Buf, BufMut, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 use tracing::trace;
 
-use slink::{CommandV4, ProtocolErrorV4};
+use slink::wire::conformance::MAX_AUTH_COMMAND_LINE_LENGTH;
+use slink::{AuthCmdV4, CommandV3, CommandV4, ProtocolErrorV3, ProtocolErrorV4};
 
 use crate::client::FromServer;
-use crate::{ClientId, DEFAULT_PROTO_VERSION};
+use crate::{ClientId, DEFAULT_PROTO_VERSION, HIGHEST_SUPPORTED_PROTO_VERSION};
+
+/// Default maximum length of the command line is 255 characters, including the `<CR><LF>`
+/// terminator.
+pub const DEFAULT_MAX_COMMAND_LINE_LENGTH: usize = 255;
+/// Default maximum length of `AUTH` command lines, including the `<CR><LF>` terminator. Allows
+/// for JSON Web Tokens (JWT) which easily exceed the default command line length.
+pub const DEFAULT_MAX_AUTH_COMMAND_LINE_LENGTH: usize = MAX_AUTH_COMMAND_LINE_LENGTH;
+
+/// Maximum command line lengths (including the `<CR><LF>` terminator) per command category.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct CommandLineLimits {
+    /// Maximum length of command lines not covered by a more specific limit.
+    pub default: usize,
+    /// Maximum length of `AUTH` command lines.
+    pub auth: usize,
+}
+
+impl Default for CommandLineLimits {
+    fn default() -> Self {
+        Self {
+            default: DEFAULT_MAX_COMMAND_LINE_LENGTH,
+            auth: DEFAULT_MAX_AUTH_COMMAND_LINE_LENGTH,
+        }
+    }
+}
 
-/// Maximum length of the command line is 255 characters, including the `<CR><LF>` terminator.
-const MAX_COMMAND_LINE_LENGTH: usize = 255;
+impl CommandLineLimits {
+    /// Returns the maximum length of the (possibly incomplete) command line `line`.
+    fn limit(&self, line: &[u8]) -> usize {
+        let name = line.split(|b| *b == b' ').next().unwrap_or_default();
+        if name.eq_ignore_ascii_case(AuthCmdV4::NAME.as_bytes()) {
+            self.auth
+        } else {
+            self.default
+        }
+    }
+
+    /// Returns the largest limit.
+    fn largest(&self) -> usize {
+        cmp::max(self.default, self.auth)
+    }
+}
 
 /// Enumeration of errors that can occur when parsing SeedLink commands.
 #[derive(thiserror::Error, Debug)]
@@ -22,9 +62,18 @@ pub enum ParseError {
     #[error(transparent)]
     ProtocolError(#[from] ProtocolErrorV4),
     #[error(transparent)]
+    ProtocolErrorV3(#[from] ProtocolErrorV3),
+    #[error(transparent)]
     IoError(#[from] io::Error),
 }
 
+/// SeedLink command decoded depending on the protocol version negotiated.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    V3(CommandV3),
+    V4(CommandV4),
+}
+
 /// SeedLink protocol version structure.
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct ProtocolVersion {
@@ -50,19 +99,27 @@ impl From<(u8, u8)> for ProtocolVersion {
 /// The codec also accepts a single `<CR>` or `<LF>` as a command terminator. Empty command lines
 /// are ignored.
 ///
-/// `SeedLinkCodec::decode` will return a `ParseError` when a line exceeds the length limit (i.e.
-/// 255 characters). Subsequent calls will discard up to 255 bytes from that line until a line
-/// ending character is reached, returning `None` until the line over the limit has been fully
-/// discarded. After that point, calls to `decode` will function as normal.
+/// `SeedLinkCodec::decode` will return a `ParseError` when a line exceeds the length limit of its
+/// command category (see [`CommandLineLimits`]). Subsequent calls will discard the remainder of
+/// that line in chunks of at most the largest limit until a line ending character is reached,
+/// returning `None` until the line over the limit has been fully discarded. After that point,
+/// calls to `decode` will function as normal.
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct SeedLinkCodec {
     client_id: ClientId,
 
+    limits: CommandLineLimits,
+
     next_index: usize,
     is_discarding: bool,
 
     protocol_version: ProtocolVersion,
     protocol_version_locked: bool,
+    // protocol versions clients may switch to
+    protocol_versions: Vec<ProtocolVersion>,
+
+    // total number of bytes consumed
+    bytes_decoded: u64,
 }
 
 impl SeedLinkCodec {
@@ -70,13 +127,37 @@ impl SeedLinkCodec {
     pub fn new(client_id: ClientId) -> Self {
         Self {
             client_id,
+            limits: CommandLineLimits::default(),
             next_index: 0,
             is_discarding: false,
             protocol_version: DEFAULT_PROTO_VERSION.into(),
             protocol_version_locked: false,
+            protocol_versions: vec![HIGHEST_SUPPORTED_PROTO_VERSION.into()],
+            bytes_decoded: 0,
         }
     }
 
+    /// Configures the protocol versions advertised to clients, i.e. the versions clients may
+    /// switch to.
+    ///
+    /// If the default protocol version is not advertised, the codec defaults to the highest
+    /// protocol version advertised.
+    pub fn with_protocol_versions(mut self, protocol_versions: &[(u8, u8)]) -> Self {
+        assert!(!protocol_versions.is_empty());
+
+        self.protocol_versions = protocol_versions.iter().map(|v| (*v).into()).collect();
+        if !self.protocol_versions.contains(&self.protocol_version) {
+            self.protocol_version = self.protocol_versions.iter().max().unwrap().clone();
+        }
+        self
+    }
+
+    /// Configures the command line length limits.
+    pub fn with_limits(mut self, limits: CommandLineLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Returns the configured SeedLink protocol version.
     pub fn protocol_version(&self) -> &ProtocolVersion {
         &self.protocol_version
@@ -102,6 +183,21 @@ impl SeedLinkCodec {
             return Err(err);
         }
 
+        if !self.protocol_versions.contains(&protocol_version) {
+            let mut err = ProtocolErrorV4::unsupported_command();
+            err.message = Some(
+                format!(
+                    "{}: unsupported protocol version {}.{}",
+                    err.code.description(),
+                    protocol_version.major,
+                    protocol_version.minor
+                )
+                .into(),
+            );
+
+            return Err(err);
+        }
+
         self.protocol_version = protocol_version;
 
         Ok(())
@@ -116,21 +212,26 @@ impl SeedLinkCodec {
     pub fn is_locked_protocol_version(&self) -> bool {
         self.protocol_version_locked
     }
-}
 
-impl Decoder for SeedLinkCodec {
-    type Item = CommandV4;
-    type Error = ParseError;
+    /// Returns the total number of bytes consumed (including command lines discarded).
+    pub fn bytes_decoded(&self) -> u64 {
+        self.bytes_decoded
+    }
 
-    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<CommandV4>, ParseError> {
+    fn decode_line(&mut self, buf: &mut BytesMut) -> Result<Option<Command>, ParseError> {
         // XXX(damb): slightly modified version of
         // https://docs.rs/tokio-util/latest/src/tokio_util/codec/lines_codec.rs.html#112-166
         // Reimplementing the decoder is required due to accepting a single `\r` as a line ending
         // is required.
         loop {
-            // Determine how far into the buffer we'll search for a newline. If
-            // there's no max_length set, we'll read to the end of the buffer.
-            let read_to = cmp::min(MAX_COMMAND_LINE_LENGTH, buf.len());
+            // Determine how far into the buffer we'll search for a newline. While discarding,
+            // the buffer doesn't start with a command name, i.e. the largest limit is applied.
+            let max_len = if self.is_discarding {
+                self.limits.largest()
+            } else {
+                self.limits.limit(buf)
+            };
+            let read_to = cmp::min(max_len, buf.len());
 
             let newline_offset = buf[self.next_index..read_to]
                 .iter()
@@ -177,13 +278,14 @@ impl Decoder for SeedLinkCodec {
 
                     trace!("{:?}: <- {:?}", self.client_id, line);
                     let cmd = match self.protocol_version.major {
-                        4 => CommandV4::parse(&line)?,
-                        0_u8..=3_u8 | 5_u8..=u8::MAX => todo!(),
+                        0_u8..=3_u8 => Command::V3(CommandV3::parse(line)?),
+                        4 => Command::V4(CommandV4::parse(line)?),
+                        5_u8..=u8::MAX => todo!(),
                     };
 
                     return Ok(Some(cmd));
                 }
-                (false, None) if buf.len() > MAX_COMMAND_LINE_LENGTH => {
+                (false, None) if buf.len() > max_len => {
                     // Reached the maximum length without finding a
                     // newline, return an error and start discarding on the
                     // next call.
@@ -201,6 +303,19 @@ impl Decoder for SeedLinkCodec {
     }
 }
 
+impl Decoder for SeedLinkCodec {
+    type Item = Command;
+    type Error = ParseError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Command>, ParseError> {
+        let len = buf.len();
+        let res = self.decode_line(buf);
+        self.bytes_decoded += (len - buf.len()) as u64;
+
+        res
+    }
+}
+
 fn without_carriage_return(s: &[u8]) -> &[u8] {
     if let Some(&b'\r') = s.last() {
         &s[..s.len() - 1]
@@ -213,10 +328,10 @@ impl Encoder<FromServer> for SeedLinkCodec {
     type Error = io::Error;
 
     fn encode(&mut self, item: FromServer, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        match self.protocol_version.major {
-            4 => match item {
-                _ => todo!()
-            },
+        match item {
+            // XXX(damb): responses are versioned themselves
+            FromServer::Hello(hello) => hello.encode(dst),
+            // TODO(damb): encode responses depending on the protocol version negotiated
             _ => todo!(),
         }
 
@@ -228,7 +343,7 @@ impl Encoder<FromServer> for SeedLinkCodec {
 mod tests {
     use bytes::BufMut;
 
-    use slink::{CommandV4, HelloCmdV4};
+    use slink::{AuthCmdMethodV4, AuthCmdV4, CommandV3, CommandV4, HelloCmdV3, HelloCmdV4};
 
     use super::*;
 
@@ -239,6 +354,79 @@ mod tests {
         let mut codec = SeedLinkCodec::new(ClientId(42));
         let mut buffer = BytesMut::from("HELLO\r\n");
         let cmd = codec.decode(&mut buffer).unwrap();
-        assert_eq!(cmd, Some(CommandV4::Hello(HelloCmdV4)));
+        assert_eq!(cmd, Some(Command::V4(CommandV4::Hello(HelloCmdV4))));
+        assert_eq!(codec.bytes_decoded(), 7);
+    }
+
+    #[test]
+    fn decode_v3() {
+        let mut codec = SeedLinkCodec::new(ClientId(42)).with_protocol_versions(&[(3, 1)]);
+        let mut buffer = BytesMut::from("HELLO\r\nFOO bar\r\n");
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some(Command::V3(CommandV3::Hello(HelloCmdV3)))
+        );
+        let cmd = codec.decode(&mut buffer).unwrap();
+        assert!(matches!(
+            cmd,
+            Some(Command::V3(CommandV3::Unknown(ref unknown_cmd)))
+                if unknown_cmd.command_name == "foo" && unknown_cmd.args.as_deref() == Some("bar")
+        ));
+    }
+
+    #[test]
+    fn advertised_protocol_versions() {
+        let mut codec = SeedLinkCodec::new(ClientId(42)).with_protocol_versions(&[(3, 1)]);
+        assert_eq!(codec.protocol_version(), &(3, 1).into());
+        assert!(codec.try_set_protocol_version((4, 0).into()).is_err());
+        assert!(codec.try_set_protocol_version((3, 1).into()).is_ok());
+
+        let codec = SeedLinkCodec::new(ClientId(42)).with_protocol_versions(&[(4, 0), (3, 1)]);
+        assert_eq!(codec.protocol_version(), &DEFAULT_PROTO_VERSION.into());
+    }
+
+    #[test]
+    fn decode_long_auth() {
+        let token = "x".repeat(1024);
+        let mut codec = SeedLinkCodec::new(ClientId(42));
+        let mut buffer = BytesMut::from(format!("AUTH TOKEN {}\r\n", token).as_str());
+        let cmd = codec.decode(&mut buffer).unwrap();
+        assert_eq!(
+            cmd,
+            Some(Command::V4(CommandV4::Auth(AuthCmdV4::new(
+                AuthCmdMethodV4::JWT(token)
+            ))))
+        );
+    }
+
+    #[test]
+    fn decode_too_long() {
+        let mut codec = SeedLinkCodec::new(ClientId(42)).with_limits(CommandLineLimits {
+            default: 16,
+            auth: 32,
+        });
+
+        let mut buffer =
+            BytesMut::from(format!("AUTH TOKEN {}\r\nHELLO\r\n", "x".repeat(32)).as_str());
+        assert!(matches!(
+            codec.decode(&mut buffer),
+            Err(ParseError::CommandLineTooLong)
+        ));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some(Command::V4(CommandV4::Hello(HelloCmdV4)))
+        );
+
+        let mut buffer = BytesMut::from(format!("HELLO {}\r\nHELLO\r\n", "x".repeat(16)).as_str());
+        assert!(matches!(
+            codec.decode(&mut buffer),
+            Err(ParseError::CommandLineTooLong)
+        ));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some(Command::V4(CommandV4::Hello(HelloCmdV4)))
+        );
     }
 }
@@ -1,10 +1,15 @@
 use std::convert::From;
 use std::ops::Deref;
 
-use regex::{Error, Regex};
+use regex::Regex;
 use time::OffsetDateTime;
 
-use slink::{Format, SequenceNumberV4, Station, StationId, Stream, StreamId, SubFormat};
+use slink::{
+    Format, ProtocolErrorV4, SequenceNumberV4, Station, StationId, Stream, StreamId, SubFormat,
+};
+
+/// Maximum length of station, stream and format patterns.
+pub(crate) const MAX_PATTERN_LENGTH: usize = 64;
 
 /// Station selection.
 #[derive(Debug, Clone)]
@@ -17,6 +22,11 @@ pub struct StationSelect {
 }
 
 impl StationSelect {
+    /// Returns the station identifier.
+    pub fn id(&self) -> &StationId {
+        &self.id
+    }
+
     /// Returns the network code.
     pub fn net_code(&self) -> &str {
         self.id.net_code()
@@ -93,6 +103,11 @@ pub struct StreamSelect {
 }
 
 impl StreamSelect {
+    /// Returns the stream identifier.
+    pub fn id(&self) -> &StreamId {
+        &self.id
+    }
+
     /// Returns the location code.
     pub fn loc_code(&self) -> &str {
         self.id.loc_code()
@@ -170,9 +185,13 @@ impl Select {
         Self(select)
     }
 
-    /// Creates a new `Select` from stations matching pattern.
-    pub fn with_pattern(stations: &Vec<Station>, station_pattern: &str) -> Self {
-        let re = create_regex(station_pattern).unwrap();
+    /// Creates a new `Select` from stations matching pattern. Fails with an `ARGUMENTS` error if
+    /// the pattern is invalid (see [`validate_pattern`]).
+    pub fn with_pattern(
+        stations: &Vec<Station>,
+        station_pattern: &str,
+    ) -> Result<Self, ProtocolErrorV4> {
+        let re = create_regex(station_pattern)?;
 
         let mut select = Vec::new();
         for sta in stations.iter() {
@@ -182,7 +201,7 @@ impl Select {
             }
         }
 
-        Self(select)
+        Ok(Self(select))
     }
 
     /// Returns whether there are any selected stations.
@@ -204,20 +223,26 @@ impl Select {
         }
     }
 
-    /// Applies rules to the selection.
+    /// Applies rules to the selection. Fails with an `ARGUMENTS` error if a pattern is invalid
+    /// (see [`validate_pattern`]) or `filter` is used together with `exclude`, leaving the
+    /// selection unchanged.
     pub fn apply(
         &mut self,
         exclude: bool,
         stream_pattern: &str,
         format_subformat_pattern: &Option<String>,
         filter: &Option<String>,
-    ) {
-        assert!(filter.is_none() || (filter.is_some() && !exclude));
+    ) -> Result<(), ProtocolErrorV4> {
+        if exclude && filter.is_some() {
+            let mut err = ProtocolErrorV4::incorrect_arguments();
+            err.message = Some("filter must not be used together with exclusion".into());
+            return Err(err);
+        }
 
-        let stream_re = create_regex(stream_pattern).unwrap();
+        let stream_re = create_regex(stream_pattern)?;
 
         let format_subformat_re = if let Some(ref pattern) = format_subformat_pattern {
-            Some(create_regex(pattern).unwrap())
+            Some(create_regex(pattern)?)
         } else {
             None
         };
@@ -251,6 +276,8 @@ impl Select {
                 }
             }
         }
+
+        Ok(())
     }
 
     /// Sets the sequence number for selected stations.
@@ -302,11 +329,47 @@ impl Deref for Select {
     }
 }
 
-/// Creates a regex from a pattern.
-fn create_regex(pattern: &str) -> Result<Regex, Error> {
-    let pattern = pattern.replace('*', ".*");
-    let pattern = pattern.replace('?', ".");
-    Regex::new(&pattern)
+/// Validates the pattern `pattern`, i.e. checks its length and characters. Patterns may consist
+/// of alphanumeric characters, `_`, `-` and the wildcards `*` and `?`.
+///
+/// Returns an `ARGUMENTS` error echoing the offending pattern if the pattern is invalid.
+pub(crate) fn validate_pattern(pattern: &str) -> Result<(), ProtocolErrorV4> {
+    let reason = if pattern.is_empty() {
+        "empty pattern".to_string()
+    } else if pattern.len() > MAX_PATTERN_LENGTH {
+        format!("pattern exceeds {} characters", MAX_PATTERN_LENGTH)
+    } else if let Some(c) = pattern
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '*' | '?')))
+    {
+        format!("invalid character '{}'", c.escape_default())
+    } else {
+        return Ok(());
+    };
+
+    // XXX(damb): echo the pattern escaped and truncated in order to keep the response line valid
+    let echoed: String = pattern
+        .chars()
+        .take(MAX_PATTERN_LENGTH)
+        .flat_map(char::escape_default)
+        .collect();
+    let mut err = ProtocolErrorV4::incorrect_arguments();
+    err.message = Some(format!("invalid pattern '{}': {}", echoed, reason).into());
+    Err(err)
+}
+
+/// Creates a regex from a pattern. Fails with an `ARGUMENTS` error if the pattern is invalid
+/// (see [`validate_pattern`]).
+fn create_regex(pattern: &str) -> Result<Regex, ProtocolErrorV4> {
+    validate_pattern(pattern)?;
+
+    let re = pattern.replace('*', ".*");
+    let re = re.replace('?', ".");
+    Regex::new(&re).map_err(|e| {
+        let mut err = ProtocolErrorV4::incorrect_arguments();
+        err.message = Some(format!("invalid pattern '{}': {}", pattern, e).into());
+        err
+    })
 }
 
 /// Returns a compound station identifier.
@@ -325,6 +388,33 @@ mod tests {
         assert!(!select.has_selected());
     }
 
+    #[test]
+    fn invalid_patterns() {
+        assert!(validate_pattern("GE_W?F").is_ok());
+        assert!(validate_pattern("00_B_H_*").is_ok());
+
+        let err = validate_pattern("GE_(WLF").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERROR ARGUMENTS: invalid pattern 'GE_(WLF': invalid character '('"
+        );
+        assert_eq!(
+            validate_pattern("GE\r\nWLF").unwrap_err().to_string(),
+            "ERROR ARGUMENTS: invalid pattern 'GE\\r\\nWLF': invalid character '\\r'"
+        );
+        assert!(validate_pattern("").is_err());
+        assert!(validate_pattern(&"*".repeat(MAX_PATTERN_LENGTH + 1)).is_err());
+
+        let mut select = Select::default();
+        assert!(select.apply(false, "[", &None, &None).is_err());
+        assert!(select
+            .apply(true, "*", &None, &Some("lp".to_string()))
+            .is_err());
+        assert!(select
+            .apply(false, "*", &Some("2D".to_string()), &None)
+            .is_ok());
+    }
+
     #[test]
     fn select_single_station_no_streams() {
         todo!()
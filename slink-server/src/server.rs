@@ -4,23 +4,54 @@ use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
 };
+use std::time::Duration;
 
+use bytes::Bytes;
+use tokio::select;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
-use tracing::{debug, error};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
-use slink::{CommandV4, ErrorInfoV4, InfoV4, ProtocolErrorV4};
+use slink::{
+    CommandV3, CommandV4, ConnectionsInfoV4, ErrorInfoV4, InfoCmdItemV4, InfoV4, ProtocolErrorV4,
+};
 
 use crate::client::{ClientHandle, FromServer};
 use crate::dispatch::Dispatcher;
+use crate::mseed::peek_header;
+use crate::task::{Subsystem, TaskPanic, TaskRegistry};
+use crate::trace;
+use crate::traffic::TrafficStats;
 use crate::util::to_id_info_v4;
-use crate::HIGHEST_SUPPORTED_PROTO_VERSION;
-use crate::{ClientId, SeedLinkServer};
+use crate::{
+    ClientId, CommandLineLimits, DescriptionEncoding, InfoCacheStats, PacketBuffer, Quarantine,
+    RequestContext, RequestId, SeedLinkServer,
+};
+
+/// Enumeration of errors that can occur when publishing packets.
+#[derive(thiserror::Error, Debug)]
+pub enum PublishError {
+    #[error("no packet buffer configured")]
+    NoPacketBuffer,
+    #[error("invalid miniSEED record: {0}")]
+    InvalidRecord(String),
+    #[error("station quarantined: {0}")]
+    Quarantined(String),
+}
 
 #[derive(Clone, Debug)]
 pub struct ServerHandle {
     chan: Sender<ToServer>,
     next_id: Arc<AtomicUsize>,
+    tasks: TaskRegistry,
+    client_panics: Arc<AtomicUsize>,
+    packet_buffer: Option<PacketBuffer>,
+    quarantine: Quarantine,
+
+    command_line_limits: CommandLineLimits,
+    description_encoding: DescriptionEncoding,
+    packet_signing_key: Option<Arc<[u8]>>,
 }
 
 impl ServerHandle {
@@ -34,15 +65,148 @@ impl ServerHandle {
         let id = self.next_id.fetch_add(1, Ordering::Relaxed);
         ClientId(id)
     }
+
+    /// Returns the registry of the tasks spawned by the server.
+    pub(crate) fn tasks(&self) -> &TaskRegistry {
+        &self.tasks
+    }
+
+    /// Returns the panic of a critical server task (e.g. the accept loop), if any.
+    ///
+    /// A panicking critical task shuts down the server.
+    pub fn task_panic(&self) -> Option<TaskPanic> {
+        self.tasks.panic()
+    }
+
+    /// Returns the number of client actors which panicked.
+    ///
+    /// Note that a panicking client actor does not affect other clients.
+    pub fn client_panics(&self) -> usize {
+        self.client_panics.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_client_panic(&self) {
+        self.client_panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Shuts down the server.
+    ///
+    /// Tasks are shut down in order, i.e. first the accept loops, then the client actors, the
+    /// streaming tasks and finally the packet buffer maintenance tasks.
+    pub async fn shutdown(&mut self) {
+        self.send(ToServer::Shutdown).await
+    }
+
+    /// Drains the server, e.g. before a rolling upgrade behind a load balancer.
+    ///
+    /// The accept loops are shut down and new connections are rejected. Connected clients receive
+    /// the data queued so far (streaming clients the packets buffered so far), followed by `END`.
+    /// Playbacks are cut short. Waits until all clients disconnected or, if
+    /// `timeout` is not `None`, until the timeout elapsed. Clients connected still are
+    /// disconnected. Returns whether all clients disconnected in time.
+    ///
+    /// Note that the server keeps running, i.e. it must be shut down subsequently (see
+    /// [`ServerHandle::shutdown`]).
+    pub async fn drain(&mut self, timeout: Option<Duration>) -> bool {
+        self.tasks.shutdown_subsystem(Subsystem::Accept).await;
+
+        let (send, recv) = oneshot::channel();
+        self.send(ToServer::Drain(send)).await;
+        let drained = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, recv).await.is_ok(),
+            None => {
+                let _ = recv.await;
+                true
+            }
+        };
+
+        if !drained {
+            self.send(ToServer::DisconnectClients).await;
+        }
+
+        drained
+    }
+
+    /// Returns the command line length limits configured.
+    pub fn command_line_limits(&self) -> CommandLineLimits {
+        self.command_line_limits
+    }
+
+    /// Returns the encoding applied to data center descriptions.
+    pub fn description_encoding(&self) -> DescriptionEncoding {
+        self.description_encoding
+    }
+
+    /// Returns the key SeedLink `v4` packets are signed with, if any.
+    pub(crate) fn packet_signing_key(&self) -> Option<&Arc<[u8]>> {
+        self.packet_signing_key.as_ref()
+    }
+
+    /// Returns the quarantine of stations whose packets repeatedly fail encoding.
+    pub fn quarantine(&self) -> &Quarantine {
+        &self.quarantine
+    }
+
+    /// Publishes the raw miniSEED record `record` to the packet buffer of the server (see
+    /// [`SeedLinkServer::packet_buffer`]) and returns the sequence number assigned.
+    ///
+    /// The station is determined by peeking at the record header, i.e. the record is neither
+    /// decoded nor validated. Thus, records should originate from trusted sources, only. Records of
+    /// quarantined stations are rejected (see [`ServerHandle::quarantine`]).
+    pub fn publish_raw(&self, record: &[u8]) -> Result<u64, PublishError> {
+        let buffer = self
+            .packet_buffer
+            .as_ref()
+            .ok_or(PublishError::NoPacketBuffer)?;
+        let header = peek_header(record).map_err(PublishError::InvalidRecord)?;
+        if self.quarantine.is_quarantined(&header.station_id) {
+            return Err(PublishError::Quarantined(header.station_id));
+        }
+
+        Ok(buffer.push(
+            &header.station_id,
+            &header.stream_id,
+            header.start_time,
+            header.end_time,
+            Bytes::copy_from_slice(record),
+        ))
+    }
+
+    /// Invalidates the cached `INFO` responses, e.g. on inventory changes.
+    pub async fn invalidate_info_cache(&mut self) {
+        self.send(ToServer::InvalidateInfoCache).await
+    }
+
+    /// Returns the `INFO` response cache statistics.
+    pub async fn info_cache_stats(&mut self) -> InfoCacheStats {
+        let (send, recv) = oneshot::channel();
+        self.send(ToServer::InfoCacheStats(send)).await;
+        recv.await.expect("Main loop has shut down.")
+    }
+
+    /// Returns the byte accounting of the clients connected ordered by client identifier.
+    pub async fn client_traffic(&mut self) -> Vec<(ClientId, TrafficStats)> {
+        let (send, recv) = oneshot::channel();
+        self.send(ToServer::ClientTraffic(send)).await;
+        recv.await.expect("Main loop has shut down.")
+    }
 }
 
 /// The message type used when a client actor sends messages to the main server loop.
 pub enum ToServer {
     NewClient(ClientHandle),
     DisconnectClient(ClientId),
+    ClientPanicked(ClientId, String),
     Command(ClientId, CommandV4),
+    CommandV3(ClientId, CommandV3),
     ErrorInfo(ClientId, ProtocolErrorV4),
+    InvalidateInfoCache,
+    InfoCacheStats(oneshot::Sender<InfoCacheStats>),
+    ClientTraffic(oneshot::Sender<Vec<(ClientId, TrafficStats)>>),
     FatalError(io::Error),
+    Drain(oneshot::Sender<()>),
+    DisconnectClients,
+    Shutdown,
 }
 
 /// Spawns the main server loop.
@@ -55,15 +219,24 @@ where
     let server_handle = ServerHandle {
         chan: send,
         next_id: Default::default(),
+        tasks: TaskRegistry::default(),
+        client_panics: Default::default(),
+        packet_buffer: service.packet_buffer().cloned(),
+        quarantine: Quarantine::new(service.quarantine_threshold()),
+        command_line_limits: service.command_line_limits(),
+        description_encoding: service.description_encoding(),
+        packet_signing_key: service.packet_signing_key().map(Arc::from),
     };
 
+    let tasks = server_handle.tasks.clone();
+    let quarantine = server_handle.quarantine.clone();
     let server_join_handle = tokio::spawn(async move {
-        let res = main_loop(service, recv).await;
+        let res = main_loop(service, recv, tasks, quarantine).await;
         match res {
             Ok(()) => {}
             Err(err) => {
                 // TODO(damb): handle error approriately
-                error!("Failed to spawn main server loop: {}.", err);
+                error!("Main server loop terminated: {}.", err);
             }
         }
     });
@@ -77,9 +250,27 @@ struct ServerData<T> {
     clients: HashMap<ClientId, ClientHandle>,
 
     router: Dispatcher<T>,
+
+    next_request_id: u64,
+
+    /// Whether the server is draining, i.e. rejects new clients.
+    draining: bool,
+    /// Waiting for all clients to disconnect while draining.
+    drain_waiters: Vec<oneshot::Sender<()>>,
 }
 
 impl<T: SeedLinkServer> ServerData<T> {
+    /// Returns the context of a new request issued by the client `client_id`.
+    fn new_request(&mut self, client_id: ClientId) -> RequestContext {
+        let request_id = RequestId(self.next_request_id);
+        self.next_request_id = self.next_request_id.wrapping_add(1);
+
+        RequestContext {
+            client_id,
+            request_id,
+        }
+    }
+
     /// Adds a client.
     fn add_client(&mut self, client_handle: ClientHandle) {
         let client_id = client_handle.id;
@@ -91,6 +282,65 @@ impl<T: SeedLinkServer> ServerData<T> {
         self.clients.remove(client_id)
     }
 
+    /// Returns the byte accounting of the clients connected ordered by client identifier.
+    fn client_traffic(&self) -> Vec<(ClientId, TrafficStats)> {
+        let mut rv: Vec<(ClientId, TrafficStats)> = self
+            .clients
+            .iter()
+            .map(|(client_id, client_handle)| (*client_id, client_handle.traffic()))
+            .collect();
+        rv.sort_by_key(|(client_id, _)| *client_id);
+
+        rv
+    }
+
+    /// Returns the `INFO CONNECTIONS` response information requested by the client `client_id`.
+    fn connections_info(&self, client_id: &ClientId) -> Option<ConnectionsInfoV4> {
+        let client_handle = self.clients.get(client_id)?;
+        Some(ConnectionsInfoV4 {
+            id: to_id_info_v4(
+                self.router.server(),
+                self.router.data_center_description(client_handle),
+                client_handle.protocol_versions(),
+                &self.router.capabilities(),
+            ),
+            client: self
+                .client_traffic()
+                .into_iter()
+                .map(|(client_id, traffic)| traffic.to_info_v4(client_id))
+                .collect(),
+        })
+    }
+
+    /// Terminates the connections of all clients with `END` (see [`ServerHandle::drain`]).
+    fn drain(&mut self) {
+        self.draining = true;
+
+        // XXX(damb): `END` is queued behind the data pending, i.e. clients receive the data
+        // queued before
+        let client_ids: Vec<ClientId> = self.clients.keys().copied().collect();
+        for client_id in client_ids {
+            let res = match self.clients.get_mut(&client_id) {
+                Some(client_handle) => client_handle.drain(),
+                None => continue,
+            };
+            if res.is_err() {
+                self.log_remove_client(&client_id);
+            }
+        }
+    }
+
+    /// Notifies the drain waiters once all clients disconnected.
+    fn notify_drained(&mut self) {
+        if !self.draining || !self.clients.is_empty() {
+            return;
+        }
+
+        for send in self.drain_waiters.drain(..) {
+            let _ = send.send(());
+        }
+    }
+
     fn log_remove_client(&mut self, client_id: &ClientId) {
         if let Some(client_handle) = self.remove_client(&client_id) {
             debug!(
@@ -102,17 +352,49 @@ impl<T: SeedLinkServer> ServerData<T> {
     }
 }
 
-async fn main_loop<T>(mut service: T, mut recv: Receiver<ToServer>) -> Result<(), io::Error>
+async fn main_loop<T>(
+    service: T,
+    recv: Receiver<ToServer>,
+    tasks: TaskRegistry,
+    quarantine: Quarantine,
+) -> Result<(), io::Error>
 where
     T: SeedLinkServer,
 {
     let mut data = ServerData {
         clients: HashMap::default(),
-        router: Dispatcher::new(service),
+        router: Dispatcher::new(service, tasks.clone(), quarantine),
+        next_request_id: 0,
+        draining: false,
+        drain_waiters: Vec::new(),
     };
 
+    let res = select! {
+        res = serve(&mut data, recv) => res,
+        task_panic = tasks.panicked() => Err(io::Error::new(io::ErrorKind::Other, task_panic)),
+    };
+
+    // XXX(damb): shut down tasks before dropping the client handles, i.e. before aborting the
+    // client actors
+    tasks.shutdown().await;
+
+    res
+}
+
+async fn serve<T>(data: &mut ServerData<T>, mut recv: Receiver<ToServer>) -> Result<(), io::Error>
+where
+    T: SeedLinkServer,
+{
     while let Some(msg) = recv.recv().await {
         match msg {
+            ToServer::NewClient(client_handle) if data.draining => {
+                // XXX(damb): dropping the handle aborts the client actor
+                debug!(
+                    "{:?}: rejecting client connection while draining (ip={})",
+                    client_handle.id,
+                    client_handle.addr()
+                );
+            }
             ToServer::NewClient(client_handle) => {
                 debug!(
                     "{:?}: new client connection (ip={})",
@@ -122,12 +404,40 @@ where
                 data.add_client(client_handle);
             }
             ToServer::Command(client_id, cmd) => {
+                let ctx = data.new_request(client_id);
+                let span = info_span!(
+                    target: trace::DISPATCH,
+                    "command",
+                    client_id = ?client_id,
+                    request_id = %ctx.request_id
+                );
+                span.in_scope(|| debug!("{:?}: command: '{}'", client_id, cmd));
+
+                // XXX(damb): `INFO CONNECTIONS` responses cover all clients and thus, are
+                // prepared before borrowing the handle of the client requesting
+                let mut connections_info = match cmd {
+                    CommandV4::Info(ref info_cmd)
+                        if info_cmd.item == InfoCmdItemV4::Connections =>
+                    {
+                        data.connections_info(&client_id)
+                    }
+                    _ => None,
+                };
+
                 let mut disconnect = false;
                 if let Some(client_handle) = data.clients.get_mut(&client_id) {
                     match cmd {
                         CommandV4::Bye(_) => {
                             disconnect = true;
                         }
+                        CommandV4::Info(_) if connections_info.is_some() => {
+                            let connections_info = connections_info.take().unwrap();
+                            if let Err(_) = client_handle
+                                .send(FromServer::Info(InfoV4::Connections(connections_info)))
+                            {
+                                disconnect = true;
+                            }
+                        }
                         CommandV4::UserAgent(inner_cmd) => {
                             client_handle.useragent_info = inner_cmd
                                 .info
@@ -140,7 +450,12 @@ where
                             }
                         }
                         _ => {
-                            if let Err(_) = data.router.dispatch(&cmd, client_handle).await {
+                            if let Err(_) = data
+                                .router
+                                .dispatch(&cmd, client_handle, &ctx)
+                                .instrument(span)
+                                .await
+                            {
                                 disconnect = true;
                             }
                         }
@@ -151,18 +466,57 @@ where
                     data.log_remove_client(&client_id);
                 }
             }
+            ToServer::CommandV3(client_id, cmd) => {
+                let ctx = data.new_request(client_id);
+                let span = info_span!(
+                    target: trace::DISPATCH,
+                    "command",
+                    client_id = ?client_id,
+                    request_id = %ctx.request_id
+                );
+                span.in_scope(|| debug!("{:?}: command: '{}'", client_id, cmd));
+
+                let mut disconnect = false;
+                if let Some(client_handle) = data.clients.get_mut(&client_id) {
+                    match cmd {
+                        CommandV3::Bye(_) => {
+                            disconnect = true;
+                        }
+                        _ => {
+                            disconnect = data
+                                .router
+                                .dispatch_v3(&cmd, client_handle, &ctx)
+                                .instrument(span)
+                                .await
+                                .is_err();
+                        }
+                    }
+                }
+
+                if disconnect {
+                    data.log_remove_client(&client_id);
+                }
+            }
             ToServer::ErrorInfo(client_id, err) => {
+                let ctx = data.new_request(client_id);
+                info_span!(
+                    target: trace::DISPATCH,
+                    "command",
+                    client_id = ?client_id,
+                    request_id = %ctx.request_id
+                )
+                .in_scope(|| debug!("{:?}: invalid info request: {}", client_id, err));
+
                 if let Some(client_handle) = data.clients.get_mut(&client_id) {
                     let error_info = ErrorInfoV4 {
                         id: to_id_info_v4(
                             data.router.server(),
-                            &vec![(
-                                HIGHEST_SUPPORTED_PROTO_VERSION.0,
-                                HIGHEST_SUPPORTED_PROTO_VERSION.1,
-                            )],
-                            &None,
+                            data.router.data_center_description(client_handle),
+                            client_handle.protocol_versions(),
+                            &data.router.capabilities(),
                         ),
                         error: err,
+                        request_id: Some(ctx.request_id.to_string()),
                     };
 
                     if let Err(_) = client_handle.send(FromServer::Info(InfoV4::Error(error_info)))
@@ -174,8 +528,41 @@ where
             ToServer::DisconnectClient(client_id) => {
                 data.log_remove_client(&client_id);
             }
+            ToServer::ClientPanicked(client_id, msg) => {
+                if let Some(client_handle) = data.remove_client(&client_id) {
+                    error!(
+                        "{:?}: client actor panicked (ip={}): {}",
+                        client_handle.id,
+                        client_handle.addr(),
+                        msg
+                    );
+                }
+            }
+            ToServer::InvalidateInfoCache => {
+                debug!("invalidating info cache");
+                data.router.info_cache_mut().invalidate();
+            }
+            ToServer::InfoCacheStats(send) => {
+                let _ = send.send(data.router.info_cache().stats());
+            }
+            ToServer::ClientTraffic(send) => {
+                let _ = send.send(data.client_traffic());
+            }
+            ToServer::Drain(send) => {
+                info!("draining server (clients={})", data.clients.len());
+                data.drain_waiters.push(send);
+                data.drain();
+            }
+            ToServer::DisconnectClients => {
+                if !data.clients.is_empty() {
+                    warn!("disconnecting clients (num={})", data.clients.len());
+                }
+                data.clients.clear();
+            }
             ToServer::FatalError(err) => return Err(err),
+            ToServer::Shutdown => break,
         }
+        data.notify_drained();
         println!("Number of clients: {}", data.clients.len());
     }
 
@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Duration;
+
+use futures::stream::StreamExt;
+use time::OffsetDateTime;
+use tokio::select;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+use tokio::task::AbortHandle;
+use tracing::debug;
+
+use slink::{SequenceNumberV4, StationId};
+
+use crate::client::FromServer;
+use crate::holdback::is_withheld;
+use crate::replay::{replay, ReplayRequest};
+use crate::task::{Subsystem, TaskRegistry};
+use crate::{BufferedPacket, ClientId, PacketBuffer, Quarantine, Select};
+
+/// Data transfer mode of a streaming session.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TransferMode {
+    /// Streams the packets buffered and waits for new packets (i.e. `END`).
+    RealTime,
+    /// Transfers the packets buffered and terminates with `END` (i.e. `ENDFETCH`).
+    DialUp,
+    /// Plays back the packets buffered and terminates with `END` (see [`replay`]).
+    Replay(ReplayRequest),
+}
+
+/// Stream selected by a client.
+#[derive(Clone, Debug)]
+struct StreamFilter {
+    stream_id: String,
+    start_time: Option<OffsetDateTime>,
+    end_time: Option<OffsetDateTime>,
+}
+
+impl StreamFilter {
+    /// Returns whether the packet `packet` passes the filter.
+    fn matches(&self, packet: &BufferedPacket) -> bool {
+        self.stream_id == packet.stream_id
+            && self.start_time.map_or(true, |t| packet.end_time > t)
+            && self.end_time.map_or(true, |t| packet.start_time < t)
+    }
+}
+
+/// Station selected by a client.
+#[derive(Clone, Debug)]
+struct StationStream {
+    /// The station identifier (i.e. `NET_STA`).
+    station_id: String,
+    /// The sequence number of the next packet transferred.
+    next_seq: u64,
+    /// The holdback window the client is subject to, if any.
+    holdback: Option<Duration>,
+    streams: Vec<StreamFilter>,
+}
+
+impl StationStream {
+    /// Returns whether the packet `packet` was selected.
+    fn selects(&self, packet: &BufferedPacket) -> bool {
+        self.streams.iter().any(|stream| stream.matches(packet))
+    }
+}
+
+/// Returns the stations selected by `selects` including the sequence numbers the transfer starts
+/// at with regard to the packets buffered by `buffer`.
+///
+/// Stations selected more than once are merged, i.e. the transfer starts at the lowest sequence
+/// number requested.
+fn station_streams(
+    selects: &[Select],
+    buffer: &PacketBuffer,
+    holdback: impl Fn(&StationId) -> Option<Duration>,
+) -> Vec<StationStream> {
+    let mut stations: BTreeMap<String, StationStream> = BTreeMap::new();
+    for station_select in selects.iter().flat_map(|select| select.iter()) {
+        if !station_select.has_selected() {
+            continue;
+        }
+
+        let station_id = station_select.id().to_string();
+        let next_seq = match station_select.seq_num() {
+            SequenceNumberV4::Number(seq_num) => *seq_num,
+            SequenceNumberV4::All => 0,
+            SequenceNumberV4::Next => buffer
+                .extent(&station_id)
+                .map_or(0, |extent| extent.end_seq),
+        };
+        let streams = station_select
+            .iter()
+            .filter(|stream_select| stream_select.is_selected())
+            .map(|stream_select| StreamFilter {
+                stream_id: stream_select.id().to_string(),
+                start_time: *stream_select.start_time(),
+                end_time: *stream_select.end_time(),
+            });
+
+        let station = stations
+            .entry(station_id.clone())
+            .or_insert_with(|| StationStream {
+                station_id,
+                next_seq,
+                holdback: holdback(station_select.id()),
+                streams: vec![],
+            });
+        station.next_seq = station.next_seq.min(next_seq);
+        station.streams.extend(streams);
+    }
+
+    stations.into_values().collect()
+}
+
+/// Handle to a streaming session, used by the client handle.
+#[derive(Debug)]
+pub(crate) struct StreamingHandle {
+    abort: AbortHandle,
+    drain: Option<oneshot::Sender<()>>,
+}
+
+impl StreamingHandle {
+    /// Drains the session, i.e. the packets available are transferred, followed by `END`.
+    /// Playbacks are terminated with `END` immediately.
+    pub fn drain(&mut self) {
+        if let Some(drain) = self.drain.take() {
+            let _ = drain.send(());
+        }
+    }
+}
+
+impl Drop for StreamingHandle {
+    fn drop(&mut self) {
+        self.abort.abort()
+    }
+}
+
+/// Streaming session transferring the packets buffered of the stations selected by a client.
+pub(crate) struct Session {
+    client_id: ClientId,
+    chan: Sender<FromServer>,
+
+    buffer: PacketBuffer,
+    quarantine: Quarantine,
+
+    stations: Vec<StationStream>,
+    mode: TransferMode,
+}
+
+impl Session {
+    /// Creates a new session transferring the packets of the stations selected by `selects` by
+    /// means of `chan`. Packets younger than the holdback window of a station (see `holdback`) are
+    /// withheld until released.
+    pub fn new(
+        client_id: ClientId,
+        chan: Sender<FromServer>,
+        buffer: PacketBuffer,
+        quarantine: Quarantine,
+        selects: &[Select],
+        holdback: impl Fn(&StationId) -> Option<Duration>,
+        mode: TransferMode,
+    ) -> Self {
+        let stations = station_streams(selects, &buffer, holdback);
+        Self {
+            client_id,
+            chan,
+            buffer,
+            quarantine,
+            stations,
+            mode,
+        }
+    }
+
+    /// Spawns the session as part of the streaming subsystem. Returns `None` if the server is
+    /// shutting down.
+    pub fn spawn(self, tasks: &TaskRegistry) -> Option<StreamingHandle> {
+        let (drain, drained) = oneshot::channel();
+        let abort = tasks.spawn(Subsystem::Streaming, self.run(drained))?;
+        Some(StreamingHandle {
+            abort,
+            drain: Some(drain),
+        })
+    }
+
+    async fn run(mut self, mut drained: oneshot::Receiver<()>) {
+        if let TransferMode::Replay(request) = self.mode.clone() {
+            let terminate = select! {
+                res = self.play_back(&request) => res.is_ok(),
+                _ = &mut drained => true,
+            };
+            if terminate {
+                let _ = self.chan.send(FromServer::End).await;
+            }
+
+            debug!("{:?}: playback terminated", self.client_id);
+            return;
+        }
+
+        let buffer = self.buffer.clone();
+        loop {
+            // XXX(damb): create the future before transferring such that packets pushed meanwhile
+            // are not missed
+            let notified = buffer.notified();
+            let release_time = match self.transfer().await {
+                Ok(release_time) => release_time,
+                Err(_) => break,
+            };
+
+            // XXX(damb): packets withheld are not transferred in dial-up mode
+            if self.mode == TransferMode::DialUp {
+                let _ = self.chan.send(FromServer::End).await;
+                break;
+            }
+
+            let released = async {
+                match release_time {
+                    Some(release_time) => {
+                        let delay = Duration::try_from(release_time - OffsetDateTime::now_utc())
+                            .unwrap_or_default();
+                        tokio::time::sleep(delay).await
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            let drain = select! {
+                _ = notified => false,
+                _ = released => false,
+                _ = &mut drained => true,
+            };
+            if drain {
+                if self.transfer().await.is_ok() {
+                    let _ = self.chan.send(FromServer::End).await;
+                }
+                break;
+            }
+        }
+
+        debug!("{:?}: streaming session terminated", self.client_id);
+    }
+
+    /// Transfers the packets available. Packets failing encoding are skipped (see
+    /// [`Quarantine::encode_v4`]). The transfer of a station stops at the first packet withheld,
+    /// i.e. packets are transferred in order.
+    ///
+    /// Returns the time the next packet withheld is released, if any. Fails if the client
+    /// disconnected.
+    async fn transfer(&mut self) -> Result<Option<OffsetDateTime>, io::Error> {
+        let now = OffsetDateTime::now_utc();
+        let mut next_release_time: Option<OffsetDateTime> = None;
+        for station in self.stations.iter_mut() {
+            for packet in self
+                .buffer
+                .packets_from(&station.station_id, station.next_seq)
+            {
+                if !station.selects(&packet) {
+                    station.next_seq = packet.seq_num + 1;
+                    continue;
+                }
+                if let Some(holdback) = station.holdback {
+                    if is_withheld(&packet.end_time, Some(holdback), now) {
+                        let release_time = packet.end_time + holdback;
+                        next_release_time =
+                            Some(next_release_time.map_or(release_time, |t| t.min(release_time)));
+                        break;
+                    }
+                }
+                station.next_seq = packet.seq_num + 1;
+
+                let Some(encoded) = self.quarantine.encode_v4(&station.station_id, &packet) else {
+                    continue;
+                };
+                self.chan
+                    .send(FromServer::Packet(encoded))
+                    .await
+                    .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
+            }
+        }
+
+        Ok(next_release_time)
+    }
+
+    /// Plays back the packets buffered according to `request`. Contrary to [`Session::transfer`],
+    /// packets are selected by time rather than by sequence number. Packets withheld are skipped.
+    ///
+    /// Fails if the client disconnected.
+    async fn play_back(&mut self, request: &ReplayRequest) -> Result<(), io::Error> {
+        let now = OffsetDateTime::now_utc();
+        let station_ids: Vec<String> = self
+            .stations
+            .iter()
+            .map(|station| station.station_id.clone())
+            .collect();
+        let packets = replay(&self.buffer, &station_ids, request);
+        futures::pin_mut!(packets);
+        while let Some((station_id, packet)) = packets.next().await {
+            let Some(station) = self
+                .stations
+                .iter()
+                .find(|station| station.station_id == station_id)
+            else {
+                continue;
+            };
+            if !station.selects(&packet) || is_withheld(&packet.end_time, station.holdback, now) {
+                continue;
+            }
+
+            let Some(encoded) = self.quarantine.encode_v4(&station_id, &packet) else {
+                continue;
+            };
+            self.chan
+                .send(FromServer::Packet(encoded))
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+
+use futures::future::FutureExt;
+use tokio::sync::watch;
+use tokio::task::{AbortHandle, JoinSet};
+use tracing::{debug, error};
+
+/// Enumeration of server subsystems spawning tasks.
+///
+/// On shutdown, subsystems are shut down in declaration order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Subsystem {
+    /// Tasks accepting client connections.
+    Accept,
+    /// Client actors.
+    Client,
+    /// Tasks streaming data to clients.
+    Streaming,
+    /// Tasks maintaining packet buffers (e.g. eviction).
+    Buffer,
+}
+
+impl Subsystem {
+    const ALL: [Subsystem; 4] = [
+        Subsystem::Accept,
+        Subsystem::Client,
+        Subsystem::Streaming,
+        Subsystem::Buffer,
+    ];
+
+    /// Returns whether a panicking task of this subsystem takes down the server.
+    ///
+    /// Panicking client actors only affect the client concerned.
+    fn is_critical(&self) -> bool {
+        !matches!(self, Subsystem::Client)
+    }
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Subsystem::Accept => "accept",
+            Subsystem::Client => "client",
+            Subsystem::Streaming => "streaming",
+            Subsystem::Buffer => "buffer",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A panic of a task spawned by the server.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TaskPanic {
+    /// The subsystem the task belongs to.
+    pub subsystem: Subsystem,
+    /// The panic message.
+    pub message: String,
+}
+
+impl fmt::Display for TaskPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} task panicked: {}", self.subsystem, self.message)
+    }
+}
+
+impl std::error::Error for TaskPanic {}
+
+type Tasks = BTreeMap<Subsystem, JoinSet<()>>;
+
+/// Registry keeping track of the tasks spawned by the server, keyed by subsystem.
+#[derive(Clone, Debug)]
+pub(crate) struct TaskRegistry {
+    // `None` once shutting down
+    tasks: Arc<Mutex<Option<Tasks>>>,
+    panic: Arc<watch::Sender<Option<TaskPanic>>>,
+}
+
+impl Default for TaskRegistry {
+    fn default() -> Self {
+        let (panic, _) = watch::channel(None);
+        Self {
+            tasks: Arc::new(Mutex::new(Some(Tasks::new()))),
+            panic: Arc::new(panic),
+        }
+    }
+}
+
+impl TaskRegistry {
+    /// Spawns `task` as part of `subsystem`.
+    ///
+    /// Returns `None` (and drops `task`) if the registry is shutting down.
+    pub fn spawn<F>(&self, subsystem: Subsystem, task: F) -> Option<AbortHandle>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut guard = self.tasks.lock().unwrap();
+        let set = guard.as_mut()?.entry(subsystem).or_default();
+
+        // reap finished tasks
+        while let Some(Some(_)) = set.join_next().now_or_never() {}
+
+        let tasks = self.tasks.clone();
+        let panic = self.panic.clone();
+        Some(set.spawn(async move {
+            if let Err(payload) = AssertUnwindSafe(task).catch_unwind().await {
+                let task_panic = TaskPanic {
+                    subsystem,
+                    message: panic_message(payload.as_ref()),
+                };
+
+                // XXX(damb): tasks may panic while the server is shutting down (e.g. when sending
+                // to the main loop)
+                if tasks.lock().map(|tasks| tasks.is_none()).unwrap_or(true) {
+                    debug!("{} (shutting down)", task_panic);
+                    return;
+                }
+
+                error!("{}", task_panic);
+                if subsystem.is_critical() {
+                    panic.send_if_modified(|p| {
+                        if p.is_none() {
+                            *p = Some(task_panic);
+                            return true;
+                        }
+                        false
+                    });
+                }
+            }
+        }))
+    }
+
+    /// Returns the first panic of a critical task, if any.
+    pub fn panic(&self) -> Option<TaskPanic> {
+        self.panic.borrow().clone()
+    }
+
+    /// Waits until a critical task panicked.
+    pub async fn panicked(&self) -> TaskPanic {
+        let mut recv = self.panic.subscribe();
+        loop {
+            if let Some(task_panic) = recv.borrow_and_update().clone() {
+                return task_panic;
+            }
+            // XXX(damb): the sender is kept alive by `self`
+            let _ = recv.changed().await;
+        }
+    }
+
+    /// Shuts down the tasks of the subsystem `subsystem`, only.
+    pub async fn shutdown_subsystem(&self, subsystem: Subsystem) {
+        let set = match self.tasks.lock().unwrap().as_mut() {
+            Some(tasks) => tasks.remove(&subsystem),
+            None => return,
+        };
+
+        if let Some(mut set) = set {
+            debug!("shutting down {} tasks (num={})", subsystem, set.len());
+            set.shutdown().await;
+        }
+    }
+
+    /// Shuts down the tasks registered subsystem by subsystem (see [`Subsystem`]).
+    ///
+    /// Subsequently spawned tasks are dropped.
+    pub async fn shutdown(&self) {
+        let mut tasks = match self.tasks.lock().unwrap().take() {
+            Some(tasks) => tasks,
+            None => return,
+        };
+
+        for subsystem in Subsystem::ALL {
+            if let Some(mut set) = tasks.remove(&subsystem) {
+                debug!("shutting down {} tasks (num={})", subsystem, set.len());
+                set.shutdown().await;
+            }
+        }
+    }
+}
+
+/// Returns the message of a panic `payload`.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        return s.to_string();
+    }
+    if let Some(s) = payload.downcast_ref::<String>() {
+        return s.clone();
+    }
+
+    "unknown panic".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn propagate_critical_panics() {
+        let registry = TaskRegistry::default();
+
+        registry.spawn(Subsystem::Client, async { panic!("client") });
+        registry.spawn(Subsystem::Accept, async { panic!("accept") });
+
+        let task_panic = registry.panicked().await;
+        assert_eq!(
+            task_panic,
+            TaskPanic {
+                subsystem: Subsystem::Accept,
+                message: "accept".to_string()
+            }
+        );
+        assert_eq!(registry.panic(), Some(task_panic));
+    }
+
+    #[tokio::test]
+    async fn shutdown_in_order() {
+        let registry = TaskRegistry::default();
+
+        let (send, recv) = tokio::sync::oneshot::channel::<()>();
+        registry.spawn(Subsystem::Streaming, async move {
+            let _send = send;
+            tokio::time::sleep(Duration::from_secs(3600)).await
+        });
+
+        registry.shutdown().await;
+        assert!(recv.await.is_err());
+        assert!(registry.spawn(Subsystem::Accept, async {}).is_none());
+        assert_eq!(registry.panic(), None);
+    }
+}
@@ -0,0 +1,29 @@
+//! Tracing targets of the server.
+//!
+//! Events are emitted with the targets below (i.e. the module paths), i.e. subsystems may be
+//! debugged individually, e.g. by means of the `RUST_LOG=info,slink_server::dispatch=debug`
+//! directives. See also [`slink::trace`] regarding the targets of the protocol library.
+//!
+//! Spans:
+//!
+//! - `client` (target [`CLIENT`]): client actor, with the fields `client_id` and `peer`.
+//! - `command` (target [`DISPATCH`]): command processing, with the fields `client_id` and
+//!   `request_id` (see [`RequestContext`](crate::RequestContext)).
+
+/// Target of accepting client connections.
+pub const ACCEPT: &str = "slink_server::accept";
+/// Target of the packet buffer (e.g. retention and eviction).
+pub const BUFFER: &str = "slink_server::buffer";
+/// Target of the client actors (i.e. reading commands and writing responses).
+pub const CLIENT: &str = "slink_server::client";
+/// Target of dispatching commands to the server implementation.
+pub const DISPATCH: &str = "slink_server::dispatch";
+/// Target of the station negotiation.
+pub const NEGOTIATE: &str = "slink_server::negotiate";
+/// Target of the proxy relaying packets from an upstream server (see [`proxy`](crate::proxy)).
+pub const PROXY: &str = "slink_server::proxy";
+/// Target of the server main loop.
+pub const SERVER: &str = "slink_server::server";
+
+/// All targets of the server.
+pub const TARGETS: &[&str] = &[ACCEPT, BUFFER, CLIENT, DISPATCH, NEGOTIATE, PROXY, SERVER];
@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use slink::ClientTrafficInfoV4;
+
+use crate::ClientId;
+
+/// Byte accounting of a client connection.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct TrafficStats {
+    /// Number of bytes received from the client.
+    pub bytes_received: u64,
+    /// Number of bytes sent to the client.
+    pub bytes_sent: u64,
+    /// Number of packet header bytes sent to the client.
+    pub header_bytes_sent: u64,
+    /// Number of packet payload bytes sent to the client.
+    pub payload_bytes_sent: u64,
+}
+
+impl TrafficStats {
+    /// Returns the number of bytes transferred in both directions not carrying packet payload,
+    /// i.e. commands, packet headers and responses other than packets.
+    pub fn protocol_overhead(&self) -> u64 {
+        (self.bytes_received + self.bytes_sent).saturating_sub(self.payload_bytes_sent)
+    }
+
+    pub(crate) fn to_info_v4(&self, client_id: ClientId) -> ClientTrafficInfoV4 {
+        ClientTrafficInfoV4 {
+            id: client_id.0 as u64,
+            bytes_received: self.bytes_received,
+            bytes_sent: self.bytes_sent,
+            header_bytes_sent: self.header_bytes_sent,
+            payload_bytes_sent: self.payload_bytes_sent,
+        }
+    }
+}
+
+/// Records the byte accounting of a client connection. Shared between the client actor and the
+/// [`ClientHandle`](crate::client::ClientHandle).
+#[derive(Debug, Default)]
+pub(crate) struct TrafficRecorder {
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    header_bytes_sent: AtomicU64,
+    payload_bytes_sent: AtomicU64,
+}
+
+impl TrafficRecorder {
+    /// Sets the total number of bytes received.
+    pub fn set_bytes_received(&self, bytes: u64) {
+        self.bytes_received.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Records `bytes` bytes sent other than packets.
+    pub fn add_bytes_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records a packet sent made up of `len_header` header bytes and `len_payload` payload bytes.
+    pub fn add_packet_sent(&self, len_header: usize, len_payload: usize) {
+        self.add_bytes_sent(len_header + len_payload);
+        self.header_bytes_sent
+            .fetch_add(len_header as u64, Ordering::Relaxed);
+        self.payload_bytes_sent
+            .fetch_add(len_payload as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the byte accounting recorded so far.
+    pub fn snapshot(&self) -> TrafficStats {
+        TrafficStats {
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            header_bytes_sent: self.header_bytes_sent.load(Ordering::Relaxed),
+            payload_bytes_sent: self.payload_bytes_sent.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn account_traffic() {
+        let recorder = TrafficRecorder::default();
+        recorder.set_bytes_received(7);
+        recorder.add_bytes_sent(4);
+        recorder.add_packet_sent(17, 512);
+
+        let stats = recorder.snapshot();
+        assert_eq!(
+            stats,
+            TrafficStats {
+                bytes_received: 7,
+                bytes_sent: 4 + 17 + 512,
+                header_bytes_sent: 17,
+                payload_bytes_sent: 512,
+            }
+        );
+        assert_eq!(stats.protocol_overhead(), 7 + 4 + 17);
+    }
+}
@@ -4,9 +4,11 @@ use crate::SeedLinkServer;
 
 /// Returns an `INFO ID` response object.
 ///
-/// Note that `protocol_versions` must be sorted in descending order.
+/// Note that `protocol_versions` must be sorted in descending order and `data_center_description`
+/// must be encoded already (see [`DescriptionEncoding`](crate::DescriptionEncoding)).
 pub fn to_id_info_v4(
     server: &impl SeedLinkServer,
+    data_center_description: &str,
     protocol_versions: &Vec<(u8, u8)>,
     capabilities: &Option<Vec<String>>,
 ) -> IdInfoV4 {
@@ -14,7 +16,7 @@ pub fn to_id_info_v4(
         server.implementation(),
         server.implementation_version(),
         protocol_versions,
-        server.data_center_description(),
+        data_center_description,
         capabilities,
     )
 }
@@ -0,0 +1,735 @@
+//! End-to-end tests wiring the crate's own client to an in-process server.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use time::macros::datetime;
+use time::OffsetDateTime;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+use slink::{
+    AuthV4, Connection, Credentials, CredentialsProvider, DataTransferMode, InventoryLevel,
+    ProtocolErrorV4, SeedLinkConnectionInfo, SeedLinkError, SeedLinkPacket, SeedLinkResult,
+    Station, StationId, StationV4, StreamEnd, StreamItem, PACKET_SIGNATURE_CAPABILITY_V4,
+};
+use slink_server::file::{publish_file, FileBackend};
+use slink_server::{
+    ListenerConfig, PacketBuffer, RequestContext, SeedLinkServer, CAPABILITY_AUTH_REFRESH,
+    CAPABILITY_REPLAY,
+};
+
+const STATIONS: &str = r#"
+    [
+        {
+            "id": "GE_WLF",
+            "description": "GEOFON Station Walferdange",
+            "start_seq": 0,
+            "end_seq": 42,
+            "stream": [
+                {
+                    "id": "_B_H_Z",
+                    "format": "2",
+                    "subformat": "D",
+                    "start_time": "2023-01-01T00:00:00.0Z",
+                    "end_time": "2023-01-01T12:00:00.0Z"
+                }
+            ]
+        },
+        {
+            "id": "GE_APE",
+            "description": "GEOFON Station Apirathos",
+            "start_seq": 10,
+            "end_seq": 20,
+            "stream": [
+                {
+                    "id": "_B_H_Z",
+                    "format": "2",
+                    "subformat": "D",
+                    "start_time": "2023-01-01T00:00:00.0Z",
+                    "end_time": "2023-01-01T12:00:00.0Z"
+                }
+            ]
+        }
+    ]
+"#;
+
+#[derive(Debug)]
+struct Backend {
+    stations: Vec<Station>,
+    capabilities: Option<Vec<String>>,
+    packet_signing_key: Option<Vec<u8>>,
+    info_cache_ttl: Option<Duration>,
+    packet_buffer: Option<PacketBuffer>,
+    holdback: Option<Duration>,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        let stations: Vec<StationV4> = serde_json::from_str(STATIONS).unwrap();
+        Self {
+            stations: stations.into_iter().map(Station::from).collect(),
+            capabilities: None,
+            packet_signing_key: None,
+            info_cache_ttl: None,
+            packet_buffer: None,
+            holdback: None,
+        }
+    }
+}
+
+#[slink_server::async_trait]
+impl SeedLinkServer for Backend {
+    fn implementation(&self) -> &str {
+        "slink-server"
+    }
+
+    fn implementation_version(&self) -> &str {
+        "0.1"
+    }
+
+    fn data_center_description(&self) -> &str {
+        "Test DC"
+    }
+
+    fn capabilities(&self) -> Option<Vec<String>> {
+        self.capabilities.clone()
+    }
+
+    async fn authenticate(
+        &self,
+        _ctx: &RequestContext,
+        auth: &AuthV4,
+    ) -> Result<Option<OffsetDateTime>, ProtocolErrorV4> {
+        match auth {
+            AuthV4::JWT(token) if token == "valid" => {
+                Ok(Some(OffsetDateTime::now_utc() + time::Duration::hours(1)))
+            }
+            _ => Err(ProtocolErrorV4::authentication_failed()),
+        }
+    }
+
+    fn packet_signing_key(&self) -> Option<Vec<u8>> {
+        self.packet_signing_key.clone()
+    }
+
+    fn info_cache_ttl(&self) -> Option<Duration> {
+        self.info_cache_ttl
+    }
+
+    fn packet_buffer(&self) -> Option<&PacketBuffer> {
+        self.packet_buffer.as_ref()
+    }
+
+    fn holdback(&self, _station_id: &StationId) -> Option<Duration> {
+        self.holdback
+    }
+
+    async fn inventory_stations(
+        &self,
+        _ctx: &RequestContext,
+        _station_pattern: &str,
+        _stream_pattern: Option<String>,
+        _format_subformat_pattern: Option<String>,
+    ) -> Result<&Vec<Station>, ProtocolErrorV4> {
+        Ok(&self.stations)
+    }
+
+    async fn inventory_streams(
+        &self,
+        _ctx: &RequestContext,
+        _station_pattern: &str,
+        _stream_pattern: Option<String>,
+        _format_subformat_pattern: Option<String>,
+    ) -> Result<&Vec<Station>, ProtocolErrorV4> {
+        Ok(&self.stations)
+    }
+}
+
+#[tokio::test]
+async fn negotiate_and_request_info() {
+    let (mut server_handle, _) = slink_server::spawn_main_loop(Backend::default());
+
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let mut con = Connection::from_duplex(stream, &SeedLinkConnectionInfo::default())
+        .await
+        .unwrap();
+    assert_eq!(con.protocol_version(), 4);
+
+    let id = con.request_id_info_raw().await.unwrap();
+    assert!(id.contains("slink-server"));
+
+    let inventory = con
+        .request_inventory(None, None, InventoryLevel::Stream)
+        .await
+        .unwrap();
+    assert_eq!(inventory.len(), 2);
+    let wlf = inventory
+        .iter()
+        .find(|station| station.sta_code() == "WLF")
+        .unwrap();
+    assert_eq!((wlf.start_seq(), wlf.end_seq()), (0, 42));
+    assert_eq!(wlf.len(), 1);
+
+    let stats = con.stats();
+    assert!(stats.payload_bytes_received > 0);
+    let traffic = server_handle.client_traffic().await;
+    assert_eq!(traffic.len(), 1);
+    assert!(traffic[0].1.payload_bytes_sent > 0);
+    assert!(traffic[0].1.bytes_received > 0);
+
+    con.shutdown().await.unwrap();
+    server_handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn cached_info_per_listener() {
+    let backend = Backend {
+        info_cache_ttl: Some(Duration::from_secs(60)),
+        ..Backend::default()
+    };
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+
+    for description in ["GEOFON DC", "ORFEUS DC", "GEOFON DC"] {
+        let stream = slink_server::accept_mem_with_config(
+            server_handle.clone(),
+            ListenerConfig::default().with_data_center_description(description),
+        );
+        let mut con = Connection::from_duplex(stream, &SeedLinkConnectionInfo::default())
+            .await
+            .unwrap();
+
+        let stations = con.request_stations_info().await.unwrap();
+        assert_eq!(stations.id.organization, description);
+
+        con.shutdown().await.unwrap();
+    }
+
+    let stats = server_handle.info_cache_stats().await;
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+
+    server_handle.shutdown().await;
+}
+
+/// Returns a miniSEED 2 record (i.e. its fixed header and blockette 1000) of the `GE` station
+/// `sta` starting at `start_time`.
+fn ms2_record(sta: &str, start_time: OffsetDateTime) -> Vec<u8> {
+    let mut buf = vec![0u8; 512];
+    buf[..20].copy_from_slice(format!("000001D {:<5}  BHZGE", sta).as_bytes());
+    buf[20..22].copy_from_slice(&(start_time.year() as u16).to_be_bytes());
+    buf[22..24].copy_from_slice(&start_time.ordinal().to_be_bytes());
+    buf[24..27].copy_from_slice(&[start_time.hour(), start_time.minute(), start_time.second()]);
+    buf[30..32].copy_from_slice(&100u16.to_be_bytes());
+    buf[32..34].copy_from_slice(&20i16.to_be_bytes());
+    buf[34..36].copy_from_slice(&1i16.to_be_bytes());
+    // blockette 1000 (record length 512 bytes)
+    buf[46..48].copy_from_slice(&48u16.to_be_bytes());
+    buf[48..50].copy_from_slice(&1000u16.to_be_bytes());
+    buf[54] = 9;
+    buf
+}
+
+/// Returns the sequence number of the next packet streamed.
+async fn next_seq_num<S>(packets: &mut S) -> u64
+where
+    S: futures::Stream<Item = StreamItem> + Unpin,
+{
+    match packets.next().await {
+        Some(StreamItem::Packet(SeedLinkPacket::V4(packet))) => packet.sequence_number(),
+        item => panic!("unexpected stream item: {:?}", item),
+    }
+}
+
+#[tokio::test]
+async fn stream_published_packets() {
+    let backend = Backend {
+        packet_buffer: Some(PacketBuffer::default()),
+        ..Backend::default()
+    };
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+
+    let now = OffsetDateTime::now_utc();
+    assert_eq!(
+        server_handle.publish_raw(&ms2_record("WLF", now)).unwrap(),
+        0
+    );
+
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let mut con = Connection::from_duplex(stream, &SeedLinkConnectionInfo::default())
+        .await
+        .unwrap();
+    con.add_stream("GE", "WLF", &None, &Some("0".to_string()), &None)
+        .unwrap();
+    con.configure(DataTransferMode::RealTime, None, false)
+        .await
+        .unwrap();
+    let mut packets = con.packets(None);
+    assert_eq!(next_seq_num(&mut packets).await, 0);
+
+    // published while streaming
+    assert_eq!(
+        server_handle.publish_raw(&ms2_record("WLF", now)).unwrap(),
+        1
+    );
+    assert_eq!(next_seq_num(&mut packets).await, 1);
+
+    drop(packets);
+    server_handle.shutdown().await;
+}
+
+/// Fetches the packets of the `GE` stations `stations` in dial-up mode and returns the station
+/// identifiers and sequence numbers of the packets received.
+async fn fetch_packets(
+    server_handle: &slink_server::ServerHandle,
+    slink_connection_info: &SeedLinkConnectionInfo,
+    stations: &[&str],
+) -> Vec<(String, u64)> {
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let mut con = Connection::from_duplex(stream, slink_connection_info)
+        .await
+        .unwrap();
+    for sta in stations {
+        con.add_stream("GE", sta, &None, &Some("0".to_string()), &None)
+            .unwrap();
+    }
+    con.configure(DataTransferMode::DialUp, None, false)
+        .await
+        .unwrap();
+
+    let mut rv = vec![];
+    let mut packets = con.packets(None);
+    loop {
+        match packets.next().await {
+            Some(StreamItem::Packet(SeedLinkPacket::V4(packet))) => rv.push((
+                packet.sta_id().clone().unwrap_or_default(),
+                packet.sequence_number(),
+            )),
+            Some(StreamItem::End(StreamEnd::Completed)) => break,
+            item => panic!("unexpected stream item: {:?}", item),
+        }
+    }
+
+    rv
+}
+
+#[tokio::test]
+async fn file_backend() {
+    let dir = std::env::temp_dir().join(format!("slink-server-e2e-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let inventory_path = dir.join("inventory.json");
+    std::fs::write(&inventory_path, STATIONS).unwrap();
+    let t = datetime!(2023-01-01 06:00 UTC);
+    let records: Vec<u8> = (0..5)
+        .flat_map(|i| ms2_record("WLF", t + time::Duration::seconds(10 * i)))
+        .collect();
+    let records_path = dir.join("records.mseed");
+    std::fs::write(&records_path, records).unwrap();
+
+    let backend = FileBackend::open(&inventory_path, PacketBuffer::default())
+        .unwrap()
+        .with_data_center_description("File DC");
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+    assert_eq!(
+        publish_file(&server_handle, &records_path).await.unwrap(),
+        5
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let mut con = Connection::from_duplex(stream, &SeedLinkConnectionInfo::default())
+        .await
+        .unwrap();
+    assert_eq!(con.protocol_version(), 4);
+
+    // the inventory advertised reflects the packets buffered
+    let stations = con.request_stations_info().await.unwrap();
+    assert_eq!(stations.id.organization, "File DC");
+    let inventory = con
+        .request_inventory(None, None, InventoryLevel::Stream)
+        .await
+        .unwrap();
+    let wlf = inventory
+        .iter()
+        .find(|station| station.sta_code() == "WLF")
+        .unwrap();
+    assert_eq!(wlf.start_seq(), 0);
+    assert_eq!(*wlf[0].start_time(), t);
+
+    con.add_stream("GE", "WLF", &None, &Some("0".to_string()), &None)
+        .unwrap();
+    con.configure(DataTransferMode::DialUp, None, false)
+        .await
+        .unwrap();
+    let mut packets = con.packets(None);
+    for seq_num in 0..5 {
+        assert_eq!(next_seq_num(&mut packets).await, seq_num);
+    }
+    assert!(matches!(
+        packets.next().await,
+        Some(StreamItem::End(StreamEnd::Completed))
+    ));
+
+    server_handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn withhold_packets() {
+    let backend = Backend {
+        capabilities: Some(vec!["AUTH:TOKEN".to_string()]),
+        packet_buffer: Some(PacketBuffer::default()),
+        holdback: Some(Duration::from_secs(60 * 60)),
+        ..Backend::default()
+    };
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+
+    let now = OffsetDateTime::now_utc();
+    let past = now - time::Duration::hours(2);
+    for start_time in [past, now, past] {
+        server_handle
+            .publish_raw(&ms2_record("WLF", start_time))
+            .unwrap();
+    }
+
+    // packets are transferred in order, i.e. the transfer stops at the first packet withheld
+    let packets = fetch_packets(&server_handle, &SeedLinkConnectionInfo::default(), &["WLF"]).await;
+    assert_eq!(packets, vec![("GE_WLF".to_string(), 0)]);
+
+    // authenticated clients are exempt
+    let slink_connection_info = SeedLinkConnectionInfo {
+        token: Some("valid".to_string()),
+        ..SeedLinkConnectionInfo::default()
+    };
+    let packets = fetch_packets(&server_handle, &slink_connection_info, &["WLF"]).await;
+    let seq_nums: Vec<u64> = packets.into_iter().map(|(_, seq_num)| seq_num).collect();
+    assert_eq!(seq_nums, vec![0, 1, 2]);
+
+    server_handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn quarantine_failing_station() {
+    let buffer = PacketBuffer::default();
+    let backend = Backend {
+        packet_buffer: Some(buffer.clone()),
+        ..Backend::default()
+    };
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+
+    let now = OffsetDateTime::now_utc();
+    for _ in 0..3 {
+        server_handle.publish_raw(&ms2_record("WLF", now)).unwrap();
+        // empty payloads fail encoding
+        buffer.push("GE_APE", "_B_H_Z", now, now, Bytes::new());
+    }
+
+    let packets = fetch_packets(
+        &server_handle,
+        &SeedLinkConnectionInfo::default(),
+        &["APE", "WLF"],
+    )
+    .await;
+    let expected: Vec<(String, u64)> = (0..3)
+        .map(|seq_num| ("GE_WLF".to_string(), seq_num))
+        .collect();
+    assert_eq!(packets, expected);
+    assert!(server_handle.quarantine().is_quarantined("GE_APE"));
+    assert!(!server_handle.quarantine().is_quarantined("GE_WLF"));
+
+    server_handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn drain_ends_clients() {
+    let (mut server_handle, _) = slink_server::spawn_main_loop(Backend::default());
+
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let con = Connection::from_duplex(stream, &SeedLinkConnectionInfo::default())
+        .await
+        .unwrap();
+
+    let mut packets = con.packets(None);
+    let (drained, item) = tokio::join!(
+        server_handle.drain(Some(Duration::from_secs(10))),
+        packets.next()
+    );
+    assert!(drained);
+    assert!(matches!(item, Some(StreamItem::End(StreamEnd::Completed))));
+    assert!(server_handle.client_traffic().await.is_empty());
+
+    server_handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn drain_streaming_clients() {
+    let backend = Backend {
+        packet_buffer: Some(PacketBuffer::default()),
+        ..Backend::default()
+    };
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+
+    let now = OffsetDateTime::now_utc();
+    server_handle.publish_raw(&ms2_record("WLF", now)).unwrap();
+
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let mut con = Connection::from_duplex(stream, &SeedLinkConnectionInfo::default())
+        .await
+        .unwrap();
+    con.add_stream("GE", "WLF", &None, &Some("0".to_string()), &None)
+        .unwrap();
+    con.configure(DataTransferMode::RealTime, None, false)
+        .await
+        .unwrap();
+    let mut packets = con.packets(None);
+    assert_eq!(next_seq_num(&mut packets).await, 0);
+
+    // published while draining
+    for _ in 0..2 {
+        server_handle.publish_raw(&ms2_record("WLF", now)).unwrap();
+    }
+    let (drained, seq_nums) =
+        tokio::join!(server_handle.drain(Some(Duration::from_secs(10))), async {
+            let seq_nums = vec![
+                next_seq_num(&mut packets).await,
+                next_seq_num(&mut packets).await,
+            ];
+            assert!(matches!(
+                packets.next().await,
+                Some(StreamItem::End(StreamEnd::Completed))
+            ));
+            seq_nums
+        });
+    assert!(drained);
+    assert_eq!(seq_nums, vec![1, 2]);
+
+    server_handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn signed_packets() {
+    let backend = Backend {
+        packet_signing_key: Some(b"secret".to_vec()),
+        ..Backend::default()
+    };
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+
+    let slink_connection_info = SeedLinkConnectionInfo {
+        packet_signing_key: Some(b"secret".to_vec()),
+        ..SeedLinkConnectionInfo::default()
+    };
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let mut con = Connection::from_duplex(stream, &slink_connection_info)
+        .await
+        .unwrap();
+
+    let id = con.request_id_info_raw().await.unwrap();
+    assert!(id.contains("slink-server"));
+    assert!(id.contains(PACKET_SIGNATURE_CAPABILITY_V4));
+    con.shutdown().await.unwrap();
+
+    // wrong key
+    let slink_connection_info = SeedLinkConnectionInfo {
+        packet_signing_key: Some(b"other".to_vec()),
+        ..SeedLinkConnectionInfo::default()
+    };
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let mut con = Connection::from_duplex(stream, &slink_connection_info)
+        .await
+        .unwrap();
+    assert!(con.request_id_info_raw().await.is_err());
+
+    server_handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn signed_packets_unsupported() {
+    let (mut server_handle, _) = slink_server::spawn_main_loop(Backend::default());
+
+    let slink_connection_info = SeedLinkConnectionInfo {
+        packet_signing_key: Some(b"secret".to_vec()),
+        ..SeedLinkConnectionInfo::default()
+    };
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let res = Connection::from_duplex(stream, &slink_connection_info).await;
+    assert!(matches!(res, Err(SeedLinkError::InvalidClientConfig(_))));
+
+    server_handle.shutdown().await;
+}
+
+#[derive(Debug)]
+struct StaticCredentials;
+
+impl CredentialsProvider for StaticCredentials {
+    fn credentials(&self) -> BoxFuture<'_, SeedLinkResult<Credentials>> {
+        Box::pin(async {
+            Ok(Credentials {
+                auth: AuthV4::JWT("valid".to_string()),
+                expires: Some(OffsetDateTime::now_utc() + time::Duration::hours(1)),
+            })
+        })
+    }
+}
+
+#[tokio::test]
+async fn authenticate_with_credentials_provider() {
+    let backend = Backend {
+        capabilities: Some(vec![
+            "AUTH:TOKEN".to_string(),
+            CAPABILITY_AUTH_REFRESH.to_string(),
+        ]),
+        ..Backend::default()
+    };
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+
+    let slink_connection_info = SeedLinkConnectionInfo {
+        credentials_provider: Some(Arc::new(StaticCredentials)),
+        ..SeedLinkConnectionInfo::default()
+    };
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let mut con = Connection::from_duplex(stream, &slink_connection_info)
+        .await
+        .unwrap();
+    assert!(con.request_id_info_raw().await.is_ok());
+
+    con.shutdown().await.unwrap();
+    server_handle.shutdown().await;
+}
+
+/// Sends the command lines `cmds` after selecting SeedLink `v4` and returns the response lines.
+async fn responses(backend: Backend, cmds: &[&str]) -> Vec<String> {
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read).lines();
+
+    write.write_all(b"SLPROTO 4.0\r\n").await.unwrap();
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "OK");
+
+    let mut rv = Vec::new();
+    for cmd in cmds {
+        write
+            .write_all(format!("{}\r\n", cmd).as_bytes())
+            .await
+            .unwrap();
+        rv.push(lines.next_line().await.unwrap().unwrap());
+    }
+
+    server_handle.shutdown().await;
+
+    rv
+}
+
+#[tokio::test]
+async fn replay_at_end() {
+    let backend = Backend {
+        capabilities: Some(vec![CAPABILITY_REPLAY.to_string()]),
+        packet_buffer: Some(PacketBuffer::default()),
+        ..Backend::default()
+    };
+    let (mut server_handle, _) = slink_server::spawn_main_loop(backend);
+
+    let t = OffsetDateTime::now_utc().replace_nanosecond(0).unwrap() - time::Duration::hours(2);
+    for i in 0..3 {
+        server_handle
+            .publish_raw(&ms2_record("WLF", t + time::Duration::seconds(10 * i)))
+            .unwrap();
+    }
+
+    let stream = slink_server::accept_mem(server_handle.clone());
+    let (read, mut write) = tokio::io::split(stream);
+    let mut read = BufReader::new(read);
+
+    // the first packet ends before the time window played back
+    let start_time = t + time::Duration::seconds(6);
+    let replay_cmd = format!(
+        "REPLAY 1000 {:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        start_time.year(),
+        u8::from(start_time.month()),
+        start_time.day(),
+        start_time.hour(),
+        start_time.minute(),
+        start_time.second()
+    );
+    for cmd in ["SLPROTO 4.0", "STATION GE_WLF", "DATA", replay_cmd.as_str()] {
+        write
+            .write_all(format!("{}\r\n", cmd).as_bytes())
+            .await
+            .unwrap();
+        let mut line = String::new();
+        read.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "OK\r\n", "{}", cmd);
+    }
+    write.write_all(b"END\r\n").await.unwrap();
+
+    let mut seq_nums = vec![];
+    for _ in 0..2 {
+        let mut header = [0u8; 17];
+        read.read_exact(&mut header).await.unwrap();
+        assert_eq!(&header[..2], b"SE");
+        seq_nums.push(u64::from_le_bytes(header[8..16].try_into().unwrap()));
+        let len =
+            u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize + header[16] as usize;
+        read.read_exact(&mut vec![0u8; len]).await.unwrap();
+    }
+    assert_eq!(seq_nums, vec![1, 2]);
+
+    // the connection is terminated once played back
+    let mut rest = String::new();
+    read.read_to_string(&mut rest).await.unwrap();
+    assert_eq!(rest, "END\r\n");
+
+    server_handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn reauthenticate() {
+    let backend = Backend {
+        capabilities: Some(vec![CAPABILITY_AUTH_REFRESH.to_string()]),
+        ..Backend::default()
+    };
+    let resps = responses(
+        backend,
+        &[
+            "AUTH TOKEN valid",
+            "AUTH TOKEN valid",
+            "AUTH TOKEN invalid",
+            "AUTH TOKEN valid",
+        ],
+    )
+    .await;
+    assert_eq!(resps[0], "OK");
+    assert_eq!(resps[1], "OK");
+    assert!(resps[2].starts_with("ERROR AUTH"));
+    // a failed re-authentication revokes the credentials
+    assert_eq!(resps[3], "OK");
+}
+
+#[tokio::test]
+async fn reauthenticate_unsupported() {
+    let resps = responses(
+        Backend::default(),
+        &["AUTH TOKEN valid", "AUTH TOKEN valid"],
+    )
+    .await;
+    assert_eq!(resps[0], "OK");
+    assert!(resps[1].starts_with("ERROR UNEXPECTED"));
+}
+
+#[tokio::test]
+async fn unknown_command_v3() {
+    let (mut server_handle, _) = slink_server::spawn_main_loop(Backend::default());
+
+    let stream = slink_server::accept_mem_with_config(
+        server_handle.clone(),
+        ListenerConfig::default().with_protocol_versions(vec![(3, 1)]),
+    );
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read).lines();
+
+    write.write_all(b"FOO bar\r\n").await.unwrap();
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "ERROR");
+
+    server_handle.shutdown().await;
+}
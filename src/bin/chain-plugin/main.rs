@@ -8,7 +8,7 @@ use std::time::Duration;
 
 use anyhow::bail;
 use daemonize::Daemonize;
-use futures::TryStreamExt;
+use futures::StreamExt;
 use tokio::fs::{self, OpenOptions};
 use tokio::io::AsyncWriteExt;
 // use tokio::net::unix::pipe;
@@ -17,7 +17,7 @@ use tracing_subscriber;
 
 use clap::Parser;
 
-use slink::{Client, DataTransferMode, SeedLinkPacket, SeedLinkPacketV3};
+use slink::{Client, DataTransferMode, SeedLinkPacket, SeedLinkPacketV3, StreamEnd, StreamItem};
 
 const DEFAULT_PATH_FIFO: &str = "/var/tmp/slink/plugin.fifo";
 
@@ -38,8 +38,19 @@ fn slink_url(url: &str) -> Result<String, String> {
     Ok(url.to_string())
 }
 
+fn network_timeout(s: &str) -> Result<Duration, String> {
+    let secs = s
+        .parse::<u64>()
+        .map_err(|_| "invalid value for network timeout".to_string())?;
+    let rv = Duration::from_secs(secs);
+    if rv.is_zero() {
+        return Err("network timeout must be non-zero".to_string());
+    }
+
+    Ok(rv)
+}
+
 // TODO(damb):
-// - handle network timeout
 // - handle different SeedLink protocol versions (allow the user to force the protocol version
 // specified)
 // - allow plugin to be configured via with a configuration file (see: https://crates.io/crates/clap-serde-derive/)
@@ -49,7 +60,7 @@ fn slink_url(url: &str) -> Result<String, String> {
 #[command(version = "0.1")]
 #[command(about = "slink chain-plugin", long_about=None)]
 struct Args {
-    /// FIFO (named pipe) path SeedLink packets are written to
+    /// FIFO (named pipe) path the records of SeedLink data packets are written to
     #[arg(default_value = DEFAULT_PATH_FIFO)]
     #[arg(value_name = "FIFO")]
     #[arg(short = 'o', long)]
@@ -74,6 +85,11 @@ struct Args {
     #[arg(short = 'b', long = "batch")]
     batch: bool,
 
+    /// Terminate if no data is received this long (seconds).
+    #[arg(long = "network-timeout", value_name = "SECONDS")]
+    #[arg(value_parser = network_timeout)]
+    network_timeout: Option<Duration>,
+
     /// Run as daemon
     #[arg(short = 'D', long)]
     daemonize: bool,
@@ -126,6 +142,7 @@ async fn tokio_main(args: &Args) -> anyhow::Result<()> {
     con.configure(DataTransferMode::RealTime, None, args.batch)
         .await
         .unwrap();
+    con.set_idle_timeout(args.network_timeout);
 
     // create fifo directory
     if let Some(fifo_dir) = args.fifo.parent() {
@@ -154,13 +171,24 @@ async fn tokio_main(args: &Args) -> anyhow::Result<()> {
 
     tokio::pin!(packet_stream);
 
-    while let Some(packet) = packet_stream.try_next().await? {
+    while let Some(item) = packet_stream.next().await {
+        let packet = match item {
+            StreamItem::Packet(packet) => packet,
+            StreamItem::End(StreamEnd::Error(err)) => return Err(err.into()),
+            StreamItem::End(reason) => {
+                debug!("data transfer terminated: {:?}", reason);
+                break;
+            }
+        };
+
+        // XXX(damb): the records are written without the SeedLink header such that the framing
+        // is independent of the protocol version negotiated
         match &packet {
             SeedLinkPacket::V3(packet) => {
                 match &packet {
                     SeedLinkPacketV3::GenericData(packet) => {
                         debug!("received packet: seq {}", packet.sequence_number()?);
-                        tx.write(packet.raw()).await?;
+                        tx.write_all(packet.raw_payload()).await?;
                     }
                     _ => {
                         debug!("received info packet");
@@ -168,6 +196,15 @@ async fn tokio_main(args: &Args) -> anyhow::Result<()> {
                     }
                 }
             }
+            SeedLinkPacket::V4(packet_v4) => {
+                if packet.is_data() {
+                    debug!("received packet: seq {}", packet_v4.sequence_number());
+                    tx.write_all(packet_v4.payload_raw()).await?;
+                } else {
+                    debug!("received info packet");
+                    // ignore
+                }
+            }
         }
     }
 
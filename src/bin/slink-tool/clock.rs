@@ -0,0 +1,80 @@
+use time::{Duration, OffsetDateTime};
+
+/// Estimates the clock offset between the server (i.e. the data source) and the local system
+/// clock.
+///
+/// Records cannot be received before their last sample was recorded. Thus, the minimum latency
+/// (local receive time minus record end time) observed over the session is an upper bound of the
+/// offset of the local clock relative to the data source clock. A negative minimum latency
+/// indicates that the data source clock is ahead of the local clock.
+#[derive(Clone, Debug, Default)]
+pub struct ClockOffset {
+    samples: u64,
+    min: Option<Duration>,
+    max: Option<Duration>,
+    sum: Duration,
+}
+
+/// Clock offset estimate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockOffsetEstimate {
+    /// Number of records the estimate is based on.
+    pub samples: u64,
+    /// Estimated offset, i.e. the minimum latency observed.
+    pub offset: Duration,
+    /// Mean latency observed.
+    pub mean_latency: Duration,
+    /// Maximum latency observed.
+    pub max_latency: Duration,
+}
+
+impl ClockOffset {
+    /// Observes a record with end time `end_time` received at `received`.
+    pub fn observe(&mut self, end_time: OffsetDateTime, received: OffsetDateTime) {
+        let latency = received - end_time;
+
+        self.samples += 1;
+        self.sum += latency;
+        self.min = Some(self.min.map_or(latency, |min| min.min(latency)));
+        self.max = Some(self.max.map_or(latency, |max| max.max(latency)));
+    }
+
+    /// Returns the current estimate, if any record was observed at all.
+    pub fn estimate(&self) -> Option<ClockOffsetEstimate> {
+        Some(ClockOffsetEstimate {
+            samples: self.samples,
+            offset: self.min?,
+            mean_latency: self.sum / self.samples as f64,
+            max_latency: self.max?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use time::macros::datetime;
+
+    #[test]
+    fn estimate() {
+        let mut clock_offset = ClockOffset::default();
+        assert_eq!(clock_offset.estimate(), None);
+
+        let end_time = datetime!(2023-01-01 00:00:00 UTC);
+        clock_offset.observe(end_time, end_time + Duration::seconds(3));
+        clock_offset.observe(end_time, end_time - Duration::seconds(1));
+        clock_offset.observe(end_time, end_time + Duration::seconds(7));
+
+        assert_eq!(
+            clock_offset.estimate(),
+            Some(ClockOffsetEstimate {
+                samples: 3,
+                offset: Duration::seconds(-1),
+                mean_latency: Duration::seconds(3),
+                max_latency: Duration::seconds(7),
+            })
+        );
+    }
+}
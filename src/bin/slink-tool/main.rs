@@ -1,14 +1,14 @@
 // use std::fs::File;
 use std::ops::RangeInclusive;
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use futures::TryStreamExt;
+use futures::StreamExt;
 use quick_xml::events::Event;
 use quick_xml::reader::Reader;
 use quick_xml::writer::Writer;
-use tokio::fs::OpenOptions;
-use tokio::io::{self, AsyncWrite, AsyncWriteExt};
+use time::OffsetDateTime;
+use tokio::io::{self, AsyncWrite};
 use tracing::{info, warn};
 use tracing_subscriber;
 
@@ -16,10 +16,20 @@ use clap::{Parser, ValueEnum};
 
 use mseed::MSControlFlags;
 use slink::DEFAULT_PORT;
-use slink::{Client, DataTransferMode, FDSNSourceId, SeedLinkPacket, SeedLinkPacketV3, StateDB};
+use slink::{
+    Client, DataTransferMode, FDSNSourceId, PacketEnvelope, SeedLinkPacket, SeedLinkPacketV3,
+    StateDB, StateTrackingExt, StreamEnd, StreamItem,
+};
+
+use clock::ClockOffset;
+use writer::{OutputMode, RecordWriter};
+
+mod clock;
+mod writer;
 
 const DEFAULT_HOSTNAME: &str = "localhost";
 const PORT_RANGE: RangeInclusive<usize> = 1..=65535;
+const STATE_DB_SNAPSHOTS: usize = 3;
 
 async fn write_xml<W: AsyncWrite + Unpin>(xml: String, writer: W) -> anyhow::Result<()> {
     let mut reader = Reader::from_str(&xml);
@@ -71,8 +81,55 @@ fn keep_alive_interval(s: &str) -> Result<Duration, String> {
     Ok(rv)
 }
 
+/// Parses and validates the given network timeout.
+fn network_timeout(s: &str) -> Result<Duration, String> {
+    let secs = s
+        .parse::<u64>()
+        .map_err(|_| format!("invalid value for network timeout"))?;
+    let rv = Duration::from_secs(secs);
+    if rv.is_zero() {
+        return Err(format!("network timeout must be non-zero"));
+    }
+
+    Ok(rv)
+}
+
+/// Parses and validates the given clock offset report interval.
+fn clock_offset_interval(s: &str) -> Result<Duration, String> {
+    let secs = s
+        .parse::<u64>()
+        .map_err(|_| format!("invalid value for clock offset report interval"))?;
+    let rv = Duration::from_secs(secs);
+    if rv.is_zero() {
+        return Err(format!("clock offset report interval must be non-zero"));
+    }
+
+    Ok(rv)
+}
+
+/// Parses the given number of days.
+fn state_db_max_age(s: &str) -> Result<Duration, String> {
+    let days = s
+        .parse::<u64>()
+        .map_err(|_| format!("invalid value for state db max age"))?;
+
+    Ok(Duration::from_secs(days * 24 * 60 * 60))
+}
+
+/// Parses and validates the given duration.
+fn state_db_snapshot_interval(s: &str) -> Result<Duration, String> {
+    let secs = s
+        .parse::<u64>()
+        .map_err(|_| format!("invalid value for state db snapshot interval"))?;
+    let rv = Duration::from_secs(secs);
+    if rv.is_zero() {
+        return Err(format!("state db snapshot interval must be non-zero"));
+    }
+
+    Ok(rv)
+}
+
 // TODO(damb):
-// - handle network timeout (-> must be handled by the client)
 // - allow the user to force the seedlink protocol version used
 // - Print packet header details (`-p` flag)
 // - Unpack packet samples (`-u` flag)
@@ -112,10 +169,30 @@ struct Args {
     #[arg(value_parser = keep_alive_interval)]
     keep_alive: Option<Duration>,
 
+    /// Terminate if no data (including keepalive responses) is received this long (seconds).
+    #[arg(long = "network-timeout", value_name = "SECONDS")]
+    #[arg(value_parser = network_timeout)]
+    network_timeout: Option<Duration>,
+
     /// Save and restore stream state information to and from this file
     #[arg(short = 'x', long = "state-db", value_name = "FILE")]
     state_db: Option<PathBuf>,
 
+    /// Prune stream state information not updated for this many days.
+    #[arg(long = "state-db-max-age", value_name = "DAYS", requires = "state_db")]
+    #[arg(value_parser = state_db_max_age)]
+    state_db_max_age: Option<Duration>,
+
+    /// Periodically snapshot stream state information to this file. The state db is recovered
+    /// from the most recent valid snapshot if corrupted.
+    #[arg(long = "state-db-snapshot", value_name = "FILE", requires = "state_db")]
+    state_db_snapshot: Option<PathBuf>,
+
+    /// Snapshot stream state information this often (seconds).
+    #[arg(long = "state-db-snapshot-interval", value_name = "SECONDS")]
+    #[arg(value_parser = state_db_snapshot_interval, default_value = "300")]
+    state_db_snapshot_interval: Duration,
+
     /// Configure the connection in dial-up mode.
     #[arg(short = 'd', long = "dial-up")]
     dial_up: bool,
@@ -137,6 +214,29 @@ struct Args {
     #[arg(short = 'o', long = "output", value_name = "FILE")]
     output: Option<PathBuf>,
 
+    /// Do not write to FILE if it exists already.
+    #[arg(
+        long = "no-clobber",
+        requires = "output",
+        conflicts_with = "unique_suffix"
+    )]
+    no_clobber: bool,
+
+    /// Write to FILE.N (with N being the smallest number available) if FILE exists already.
+    #[arg(long = "unique-suffix", requires = "output")]
+    unique_suffix: bool,
+
+    /// Estimate the clock offset between the data source and the local system clock from the
+    /// record end times received and report it this often (seconds).
+    #[arg(long = "clock-offset", value_name = "SECONDS")]
+    #[arg(value_parser = clock_offset_interval)]
+    clock_offset: Option<Duration>,
+
+    /// Print the data packets received as JSON envelopes (one per line) instead of their sequence
+    /// numbers.
+    #[arg(long)]
+    json: bool,
+
     /// Request information of type TYPE (case insensitive)
     #[arg(value_enum)]
     #[arg(short = 'i', long = "info", ignore_case = true, value_name = "TYPE")]
@@ -168,7 +268,33 @@ async fn main() {
 
     let mut state_db = {
         if let Some(p) = args.state_db {
-            Some(StateDB::open(p).await.unwrap())
+            let db = if let Some(ref snapshot) = args.state_db_snapshot {
+                let db = StateDB::open_or_recover(p, snapshot).await.unwrap();
+                db.spawn_snapshots(
+                    snapshot,
+                    args.state_db_snapshot_interval,
+                    STATE_DB_SNAPSHOTS,
+                );
+                db
+            } else {
+                StateDB::open(p).await.unwrap()
+            };
+
+            let mut db = db.with_namespace(&con.state_namespace());
+            // state db files created before namespaces were introduced
+            let adopted = db.adopt_namespace("").await.unwrap();
+            if adopted > 0 {
+                info!(
+                    "adopted {} state db entries of the default namespace",
+                    adopted
+                );
+            }
+            if let Some(max_age) = args.state_db_max_age {
+                let pruned = db.prune_older_than(max_age).await.unwrap();
+                info!("pruned {} stale state db entries", pruned);
+            }
+
+            Some(db)
         } else {
             None
         }
@@ -185,6 +311,8 @@ async fn main() {
                         if con.protocol_version() == 3 {
                             write_xml(resp, io::stdout()).await.unwrap();
                             println!();
+                        } else {
+                            println!("{}", resp);
                         }
                     }
                     Err(e) => {
@@ -199,6 +327,8 @@ async fn main() {
                         if con.protocol_version() == 3 {
                             write_xml(resp, io::stdout()).await.unwrap();
                             println!();
+                        } else {
+                            println!("{}", resp);
                         }
                     }
                     Err(e) => {
@@ -213,6 +343,8 @@ async fn main() {
                         if con.protocol_version() == 3 {
                             write_xml(resp, io::stdout()).await.unwrap();
                             println!();
+                        } else {
+                            println!("{}", resp);
                         }
                     }
                     Err(e) => {
@@ -227,6 +359,8 @@ async fn main() {
                         if con.protocol_version() == 3 {
                             write_xml(resp, io::stdout()).await.unwrap();
                             println!();
+                        } else {
+                            println!("{}", resp);
                         }
                     }
                     Err(e) => {
@@ -290,49 +424,123 @@ async fn main() {
     con.configure(data_transfer_mode, None, args.batch)
         .await
         .unwrap();
+    con.set_idle_timeout(args.network_timeout);
+
+    let mut record_writer = if let Some(output) = args.output {
+        let mode = if args.no_clobber {
+            OutputMode::NoClobber
+        } else if args.unique_suffix {
+            OutputMode::UniqueSuffix
+        } else {
+            OutputMode::Append
+        };
 
-    let mut ofs_dump;
-    if let Some(output) = args.output {
-        ofs_dump = Some(
-            OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(output)
-                .await
-                .unwrap(),
-        );
+        let record_writer = RecordWriter::open(&output, mode).await.unwrap();
+        info!("writing records to {}", record_writer.path().display());
+        Some(record_writer)
     } else {
-        ofs_dump = None;
-    }
+        None
+    };
 
-    let packet_stream = con.packets(args.keep_alive);
+    let mut clock_offset = args
+        .clock_offset
+        .map(|interval| (ClockOffset::default(), interval, Instant::now()));
 
-    tokio::pin!(packet_stream);
+    let mut packet_stream = match state_db {
+        Some(state_db) => con
+            .packets(args.keep_alive)
+            .with_state_tracking(state_db)
+            .boxed_local(),
+        None => con.packets(args.keep_alive).boxed_local(),
+    };
+
+    while let Some(item) = packet_stream.next().await {
+        let packet = match item {
+            StreamItem::Packet(ref packet) => packet,
+            StreamItem::End(StreamEnd::Error(err)) => panic!("data transfer failed: {}", err),
+            StreamItem::End(reason) => {
+                info!("data transfer terminated: {:?}", reason);
+                break;
+            }
+        };
+
+        if let Some((ref mut clock_offset, interval, ref mut last_report)) = clock_offset {
+            if let SeedLinkPacket::V3(SeedLinkPacketV3::GenericData(packet)) = packet {
+                let received = OffsetDateTime::now_utc();
+                match packet
+                    .payload(MSControlFlags::empty())
+                    .and_then(|ms_record| ms_record.end_time().map_err(Into::into))
+                {
+                    Ok(end_time) => clock_offset.observe(end_time, received),
+                    Err(e) => warn!("failed to determine record end time ({})", e),
+                }
+            }
+
+            if last_report.elapsed() >= interval {
+                report_clock_offset(clock_offset);
+                *last_report = Instant::now();
+            }
+        }
+
+        if args.json {
+            match PacketEnvelope::new(packet, OffsetDateTime::now_utc()) {
+                Some(Ok(envelope)) => println!("{}", envelope.to_json().unwrap()),
+                Some(Err(e)) => warn!("failed to decode packet ({})", e),
+                None => {}
+            }
+        }
 
-    while let Some(ref packet) = packet_stream.try_next().await.unwrap() {
         match packet {
             SeedLinkPacket::V3(packet) => match packet {
                 SeedLinkPacketV3::GenericData(packet) => {
                     let seq_num = packet.sequence_number().unwrap();
-                    println!("seq {}", seq_num);
-                    if let Some(ref mut ofs) = ofs_dump {
-                        // dump to file
-                        ofs.write(packet.raw_payload()).await.unwrap();
+                    if !args.json {
+                        println!("seq {}", seq_num);
                     }
-
-                    if let Some(ref mut state_db) = state_db {
-                        let ms_record = packet.payload(MSControlFlags::empty()).unwrap();
-                        let sid = ms_record.sid().unwrap();
-
-                        state_db.store(&sid, seq_num as i64).await.unwrap();
+                    if let Some(ref mut record_writer) = record_writer {
+                        // dump to file
+                        record_writer.write(packet.raw_payload()).await.unwrap();
                     }
                 }
                 SeedLinkPacketV3::Info(_) => {
                     // ignore keepalive packets
                 }
             },
+            SeedLinkPacket::V4(packet_v4) => {
+                if !packet.is_data() {
+                    // ignore keepalive packets
+                    continue;
+                }
+
+                let seq_num = packet_v4.sequence_number();
+                if !args.json {
+                    println!("seq {}", seq_num);
+                }
+                if let Some(ref mut record_writer) = record_writer {
+                    // dump to file
+                    record_writer.write(packet_v4.payload_raw()).await.unwrap();
+                }
+            }
         }
     }
+
+    if let Some((ref clock_offset, _, _)) = clock_offset {
+        report_clock_offset(clock_offset);
+    }
+}
+
+/// Reports the current clock offset estimate.
+fn report_clock_offset(clock_offset: &ClockOffset) {
+    match clock_offset.estimate() {
+        Some(estimate) => info!(
+            "clock offset estimate: {:.3}s (mean latency: {:.3}s, max latency: {:.3}s, records: {})",
+            estimate.offset.as_seconds_f64(),
+            estimate.mean_latency.as_seconds_f64(),
+            estimate.max_latency.as_seconds_f64(),
+            estimate.samples
+        ),
+        None => info!("clock offset estimate: no records received"),
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,204 @@
+use std::ffi::OsString;
+use std::fs::{File as StdFile, OpenOptions as StdOpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+use nix::fcntl::{flock, FlockArg};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+/// Maximum number of unique suffixes tried.
+const MAX_UNIQUE_SUFFIX: usize = 1000;
+
+/// Enumeration of output file modes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputMode {
+    /// Append to the output file if it exists already.
+    #[default]
+    Append,
+    /// Refuse to write to the output file if it exists already.
+    NoClobber,
+    /// Write to a new file with a unique numeric suffix if the output file exists already.
+    UniqueSuffix,
+}
+
+/// Writes records to an output file guarded by an advisory lockfile.
+///
+/// The lock is held as long as the writer is alive, i.e. other writers sharing the same output
+/// file fail instead of interleaving records.
+#[derive(Debug)]
+pub struct RecordWriter {
+    path: PathBuf,
+    file: File,
+
+    _lock: StdFile,
+}
+
+impl RecordWriter {
+    /// Opens the output file `path` according to `mode`.
+    pub async fn open(path: &Path, mode: OutputMode) -> io::Result<Self> {
+        match mode {
+            OutputMode::Append => {
+                let lock = lock(path)?;
+                let file = OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(path)
+                    .await?;
+
+                Ok(Self::new(path.to_path_buf(), file, lock))
+            }
+            OutputMode::NoClobber => {
+                let lock = lock(path)?;
+                let file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(path)
+                    .await?;
+
+                Ok(Self::new(path.to_path_buf(), file, lock))
+            }
+            OutputMode::UniqueSuffix => {
+                for n in 0..MAX_UNIQUE_SUFFIX {
+                    let candidate = if n == 0 {
+                        path.to_path_buf()
+                    } else {
+                        with_suffix(path, &n.to_string())
+                    };
+                    if candidate.exists() {
+                        continue;
+                    }
+
+                    let lock = match lock(&candidate) {
+                        Ok(lock) => lock,
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                        Err(e) => return Err(e),
+                    };
+                    match OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&candidate)
+                        .await
+                    {
+                        Ok(file) => return Ok(Self::new(candidate, file, lock)),
+                        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                        Err(e) => return Err(e),
+                    }
+                }
+
+                Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("no unique output file available: {}", path.display()),
+                ))
+            }
+        }
+    }
+
+    fn new(path: PathBuf, file: File, lock: StdFile) -> Self {
+        Self {
+            path,
+            file,
+            _lock: lock,
+        }
+    }
+
+    /// Returns the path of the output file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the record `buf`.
+    pub async fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.file.write_all(buf).await
+    }
+}
+
+/// Returns `path` with `suffix` appended, separated by a dot.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut p = OsString::from(path.as_os_str());
+    p.push(".");
+    p.push(suffix);
+    p.into()
+}
+
+/// Acquires an advisory lock on the lockfile of `path`.
+///
+/// Note that the lockfile is not removed once the lock is released.
+fn lock(path: &Path) -> io::Result<StdFile> {
+    let lock_path = with_suffix(path, "lock");
+    let file = StdOpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(&lock_path)?;
+
+    flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).map_err(|e| {
+        if e == Errno::EWOULDBLOCK {
+            io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!(
+                    "output file locked by another process: {} (lockfile: {})",
+                    path.display(),
+                    lock_path.display()
+                ),
+            )
+        } else {
+            io::Error::from(e)
+        }
+    })?;
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+    use std::process;
+
+    fn tmp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("slink-tool-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("out.mseed")
+    }
+
+    #[tokio::test]
+    async fn locked() {
+        let path = tmp_path("locked");
+
+        let _writer = RecordWriter::open(&path, OutputMode::Append).await.unwrap();
+        let err = RecordWriter::open(&path, OutputMode::Append)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[tokio::test]
+    async fn no_clobber() {
+        let path = tmp_path("no-clobber");
+        fs::write(&path, b"foo").unwrap();
+
+        let err = RecordWriter::open(&path, OutputMode::NoClobber)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[tokio::test]
+    async fn unique_suffix() {
+        let path = tmp_path("unique-suffix");
+
+        let first = RecordWriter::open(&path, OutputMode::UniqueSuffix)
+            .await
+            .unwrap();
+        assert_eq!(first.path(), path);
+        let second = RecordWriter::open(&path, OutputMode::UniqueSuffix)
+            .await
+            .unwrap();
+        assert_eq!(second.path(), with_suffix(&path, "1"));
+    }
+}
@@ -0,0 +1,537 @@
+#[cfg(feature = "v4-client")]
+use std::sync::Arc;
+use std::time::Duration;
+
+use time::PrimitiveDateTime;
+use tokio::io::DuplexStream;
+
+use crate::connection::NegotiationProgressCallback;
+#[cfg(feature = "v4-client")]
+use crate::CredentialsProvider;
+#[cfg(feature = "state-sqlite")]
+use crate::StateDB;
+use crate::{
+    connect, Connection, ConnectionInfo, DataTransferMode, Format, HelloFallback,
+    IntoConnectionInfo, InventoryValidation, NegotiationProgress, SeedLinkError, SeedLinkResult,
+    TcpSocketOptions, UserAgentCmdInfoV4,
+};
+
+/// Stream request of a station declared by means of [`ConnectionBuilder::stream`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamRequest {
+    net: String,
+    sta: String,
+    selectors: Vec<String>,
+    seq_num: Option<String>,
+    start_time: Option<PrimitiveDateTime>,
+    end_time: Option<PrimitiveDateTime>,
+    data_transfer_mode: Option<DataTransferMode>,
+    format: Option<Format>,
+}
+
+impl StreamRequest {
+    /// Creates a new request for all streams of the station `net`, `sta`.
+    pub fn new(net: &str, sta: &str) -> Self {
+        Self {
+            net: net.to_string(),
+            sta: sta.to_string(),
+            selectors: vec![],
+            seq_num: None,
+            start_time: None,
+            end_time: None,
+            data_transfer_mode: None,
+            format: None,
+        }
+    }
+
+    /// Adds the `SELECT` command argument `selector` (e.g. `BHZ` or `*_B_H_Z` depending on the
+    /// protocol version).
+    pub fn select(mut self, selector: &str) -> Self {
+        self.selectors.push(selector.to_string());
+        self
+    }
+
+    /// Sets the (hexadecimal) sequence number data is requested from.
+    pub fn seq_num(mut self, seq_num: &str) -> Self {
+        self.seq_num = Some(seq_num.to_string());
+        self
+    }
+
+    /// Sets the time data is requested from.
+    pub fn start_time(mut self, start_time: PrimitiveDateTime) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+
+    /// Requests data for the time window from `start_time` to `end_time` (see
+    /// [`Connection::add_stream_with_time_window`]).
+    pub fn time_window(
+        mut self,
+        start_time: PrimitiveDateTime,
+        end_time: PrimitiveDateTime,
+    ) -> Self {
+        self.start_time = Some(start_time);
+        self.end_time = Some(end_time);
+        self
+    }
+
+    /// Sets the station specific data transfer mode (see [`Connection::add_stream_with_mode`]).
+    pub fn data_transfer_mode(mut self, data_transfer_mode: DataTransferMode) -> Self {
+        self.data_transfer_mode = Some(data_transfer_mode);
+        self
+    }
+
+    /// Requests streams in the format `format`, only. The format is appended to the selectors
+    /// as format/subformat pattern (e.g. `*_B_H_Z.3?`).
+    ///
+    /// Note that format selection is supported by SeedLink `v4` connections, only. Connecting
+    /// fails if the server does not support the format (see [`Connection::request_formats`]).
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Returns the `SELECT` command arguments, i.e. the selectors including the format pattern
+    /// (if any).
+    fn select_args(&self) -> SeedLinkResult<Vec<Option<String>>> {
+        let format = match self.format {
+            Some(ref format) => format,
+            None if self.selectors.is_empty() => return Ok(vec![None]),
+            None => return Ok(self.selectors.iter().cloned().map(Some).collect()),
+        };
+
+        let mut rv = vec![];
+        for selector in &self.selectors {
+            if selector.contains('.') {
+                return Err(SeedLinkError::InvalidClientConfig(format!(
+                    "selector {} conflicts with format {}",
+                    selector, format
+                )));
+            }
+            // XXX(damb): excluding selectors apply regardless of the format
+            if selector.starts_with('!') {
+                rv.push(Some(selector.clone()));
+            } else {
+                rv.push(Some(format!("{}.{}?", selector, format)));
+            }
+        }
+        if rv
+            .iter()
+            .flatten()
+            .all(|selector| selector.starts_with('!'))
+        {
+            rv.push(Some(format!("*.{}?", format)));
+        }
+
+        Ok(rv)
+    }
+}
+
+/// Builder declaring the configuration of a connection before connecting (see
+/// [`Connection::builder`]).
+///
+/// Example usage:
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// use slink::{Connection, DataTransferMode, StreamRequest};
+///
+/// let con = Connection::builder("slink://127.0.0.1/")
+///     .unwrap()
+///     .stream(StreamRequest::new("GE", "WLF").select("BHZ"))
+///     .data_transfer_mode(DataTransferMode::DialUp)
+///     .idle_timeout(Duration::from_secs(300))
+///     .connect()
+///     .await
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ConnectionBuilder {
+    connection_info: ConnectionInfo,
+    connect_timeout: Option<Duration>,
+    streams: Vec<StreamRequest>,
+    data_transfer_mode: DataTransferMode,
+    time_window: Option<(PrimitiveDateTime, bool)>,
+    batch_cmd_mode: bool,
+    #[cfg(feature = "state-sqlite")]
+    state_db: Option<(StateDB, bool)>,
+    keep_alive_interval: Option<Duration>,
+    keep_alive_timeout: Option<Duration>,
+    max_unanswered_keep_alives: Option<u32>,
+    idle_timeout: Option<Duration>,
+    negotiation_progress: Option<NegotiationProgressCallback>,
+}
+
+impl ConnectionBuilder {
+    /// Creates a new builder connecting to `params` (e.g. `slink://127.0.0.1/`).
+    pub fn new<T: IntoConnectionInfo>(params: T) -> SeedLinkResult<Self> {
+        Ok(Self {
+            connection_info: params.into_connection_info()?,
+            connect_timeout: None,
+            streams: vec![],
+            data_transfer_mode: DataTransferMode::RealTime,
+            time_window: None,
+            batch_cmd_mode: false,
+            #[cfg(feature = "state-sqlite")]
+            state_db: None,
+            keep_alive_interval: None,
+            keep_alive_timeout: None,
+            max_unanswered_keep_alives: None,
+            idle_timeout: None,
+            negotiation_progress: None,
+        })
+    }
+
+    /// Pins the SeedLink protocol version (e.g. `4`). By default, the most recent protocol version
+    /// implemented by both the library and the server is used.
+    pub fn protocol_version(mut self, protocol_version: u8) -> Self {
+        self.connection_info.slink.protocol_version = Some(protocol_version);
+        self
+    }
+
+    /// Sets the user agent information sent to SeedLink `v4` servers (see
+    /// [`SeedLinkConnectionInfo::user_agent`](crate::SeedLinkConnectionInfo::user_agent)).
+    pub fn user_agent(mut self, user_agent: Vec<UserAgentCmdInfoV4>) -> Self {
+        self.connection_info.slink.user_agent = user_agent;
+        self
+    }
+
+    /// Enables or disables strict handshaking (see
+    /// [`SeedLinkConnectionInfo::strict_handshake`](crate::SeedLinkConnectionInfo::strict_handshake)).
+    pub fn strict_handshake(mut self, strict: bool) -> Self {
+        self.connection_info.slink.strict_handshake = strict;
+        self
+    }
+
+    /// Sets the assumptions applied to nonstandard responses to `HELLO` (see
+    /// [`SeedLinkConnectionInfo::hello_fallback`](crate::SeedLinkConnectionInfo::hello_fallback)).
+    pub fn hello_fallback(mut self, fallback: HelloFallback) -> Self {
+        self.connection_info.slink.hello_fallback = fallback;
+        self
+    }
+
+    /// Enables or disables falling back to non-batch negotiation (see
+    /// [`SeedLinkConnectionInfo::batch_fallback`](crate::SeedLinkConnectionInfo::batch_fallback)).
+    pub fn batch_fallback(mut self, batch_fallback: bool) -> Self {
+        self.connection_info.slink.batch_fallback = batch_fallback;
+        self
+    }
+
+    /// Sets the provider of the credentials used to authenticate (see
+    /// [`SeedLinkConnectionInfo::credentials_provider`](crate::SeedLinkConnectionInfo::credentials_provider)).
+    #[cfg(feature = "v4-client")]
+    pub fn credentials_provider(mut self, provider: Arc<dyn CredentialsProvider>) -> Self {
+        self.connection_info.slink.credentials_provider = Some(provider);
+        self
+    }
+
+    /// Sets the key the signatures of SeedLink `v4` packets are verified with (see
+    /// [`SeedLinkConnectionInfo::packet_signing_key`](crate::SeedLinkConnectionInfo::packet_signing_key)).
+    pub fn packet_signing_key(mut self, key: Vec<u8>) -> Self {
+        self.connection_info.slink.packet_signing_key = Some(key);
+        self
+    }
+
+    /// Sets the validation of the stations requested against the server's inventory (see
+    /// [`SeedLinkConnectionInfo::inventory_validation`](crate::SeedLinkConnectionInfo::inventory_validation)).
+    pub fn inventory_validation(mut self, validation: InventoryValidation) -> Self {
+        self.connection_info.slink.inventory_validation = validation;
+        self
+    }
+
+    /// Sets the maximum number of stations negotiated ahead (see
+    /// [`SeedLinkConnectionInfo::negotiation_concurrency`](crate::SeedLinkConnectionInfo::negotiation_concurrency)).
+    pub fn negotiation_concurrency(mut self, negotiation_concurrency: usize) -> Self {
+        self.connection_info.slink.negotiation_concurrency = negotiation_concurrency;
+        self
+    }
+
+    /// Sets the callback notified about the negotiation progress (see
+    /// [`Connection::set_negotiation_progress`]).
+    pub fn on_negotiation_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&NegotiationProgress) + Send + Sync + 'static,
+    {
+        self.negotiation_progress = Some(NegotiationProgressCallback::new(callback));
+        self
+    }
+
+    /// Sets the timeout establishing the connection.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the TCP socket options (see
+    /// [`SeedLinkConnectionInfo::tcp`](crate::SeedLinkConnectionInfo::tcp)).
+    pub fn tcp_socket_options(mut self, options: TcpSocketOptions) -> Self {
+        self.connection_info.slink.tcp = options;
+        self
+    }
+
+    /// Sets the capacity of the read buffer (see
+    /// [`SeedLinkConnectionInfo::read_buffer_capacity`](crate::SeedLinkConnectionInfo::read_buffer_capacity)).
+    pub fn read_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.connection_info.slink.read_buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets the capacity of the write buffer (see
+    /// [`SeedLinkConnectionInfo::write_buffer_capacity`](crate::SeedLinkConnectionInfo::write_buffer_capacity)).
+    pub fn write_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.connection_info.slink.write_buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Declares the stream request `stream`. Requests of the same station are merged.
+    pub fn stream(mut self, stream: StreamRequest) -> Self {
+        self.streams.push(stream);
+        self
+    }
+
+    /// Sets the data transfer mode. Defaults to [`DataTransferMode::RealTime`].
+    pub fn data_transfer_mode(mut self, data_transfer_mode: DataTransferMode) -> Self {
+        self.data_transfer_mode = data_transfer_mode;
+        self
+    }
+
+    /// Requests data in time window mode, i.e. until `end_time` (see
+    /// [`Connection::configure_time_window`]). Takes precedence over the data transfer mode.
+    pub fn time_window(mut self, end_time: PrimitiveDateTime, wait_for_completion: bool) -> Self {
+        self.time_window = Some((end_time, wait_for_completion));
+        self
+    }
+
+    /// Enables or disables batch command mode (i.e. pipelining) while handshaking.
+    pub fn batch_cmd_mode(mut self, batch_cmd_mode: bool) -> Self {
+        self.batch_cmd_mode = batch_cmd_mode;
+        self
+    }
+
+    /// Recovers the sequence numbers of the streams declared from `state_db` (see
+    /// [`Connection::recover_state`]).
+    #[cfg(feature = "state-sqlite")]
+    pub fn recover_state(mut self, state_db: StateDB, add_select_args: bool) -> Self {
+        self.state_db = Some((state_db, add_select_args));
+        self
+    }
+
+    /// Sets the keepalive interval of the packet stream (see
+    /// [`Connection::keep_alive_interval`]). Panics if `interval` is zero.
+    pub fn keep_alive_interval(mut self, interval: Duration) -> Self {
+        assert!(!interval.is_zero(), "interval must be greater than zero");
+        self.keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Sets the time within which keepalives must be acknowledged (see
+    /// [`Connection::set_keep_alive_timeout`]).
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of consecutive unanswered keepalives (see
+    /// [`Connection::set_max_unanswered_keep_alives`]). Panics if `max` is zero.
+    pub fn max_unanswered_keep_alives(mut self, max: u32) -> Self {
+        assert_ne!(max, 0, "max must be greater than zero");
+        self.max_unanswered_keep_alives = Some(max);
+        self
+    }
+
+    /// Sets the time within which data must be received while streaming packets (see
+    /// [`Connection::set_idle_timeout`]).
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Connects to the SeedLink server and configures the connection, i.e. completes
+    /// handshaking.
+    pub async fn connect(self) -> SeedLinkResult<Connection> {
+        let con = connect(&self.connection_info, self.connect_timeout).await?;
+        self.configure(con).await
+    }
+
+    /// Creates the connection from the in-memory stream `stream` and configures it (see
+    /// [`Connection::from_duplex`]).
+    pub async fn connect_duplex(self, stream: DuplexStream) -> SeedLinkResult<Connection> {
+        let con = Connection::from_duplex(stream, &self.connection_info.slink).await?;
+        self.configure(con).await
+    }
+
+    async fn configure(self, mut con: Connection) -> SeedLinkResult<Connection> {
+        if self.streams.iter().any(|stream| stream.format.is_some()) {
+            self.check_formats(&mut con).await?;
+        }
+
+        for stream in &self.streams {
+            let time = stream.start_time;
+            let selectors = stream.select_args()?;
+            for selector in &selectors {
+                match (stream.end_time, stream.data_transfer_mode) {
+                    (Some(end_time), _) => con.add_stream_with_time_window(
+                        &stream.net,
+                        &stream.sta,
+                        selector,
+                        &stream.seq_num,
+                        // XXX(damb): the start time is set along with the end time
+                        time.unwrap(),
+                        end_time,
+                    )?,
+                    (None, Some(mode)) => con.add_stream_with_mode(
+                        &stream.net,
+                        &stream.sta,
+                        selector,
+                        &stream.seq_num,
+                        &time,
+                        mode,
+                    )?,
+                    (None, None) => {
+                        con.add_stream(&stream.net, &stream.sta, selector, &stream.seq_num, &time)?
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "state-sqlite")]
+        if let Some((mut state_db, add_select_args)) = self.state_db {
+            con.recover_state(&mut state_db, add_select_args).await?;
+        }
+
+        con.set_keep_alive_interval(self.keep_alive_interval);
+        con.set_keep_alive_timeout(self.keep_alive_timeout);
+        con.set_max_unanswered_keep_alives(self.max_unanswered_keep_alives);
+        con.set_idle_timeout(self.idle_timeout);
+        if let Some(callback) = self.negotiation_progress {
+            con.set_negotiation_progress_callback(callback);
+        }
+
+        match self.time_window {
+            Some((end_time, wait_for_completion)) => {
+                con.configure_time_window(end_time, wait_for_completion, self.batch_cmd_mode)
+                    .await?
+            }
+            None => {
+                con.configure(self.data_transfer_mode, None, self.batch_cmd_mode)
+                    .await?
+            }
+        }
+
+        Ok(con)
+    }
+
+    /// Checks the formats requested against the formats supported by the server.
+    async fn check_formats(&self, con: &mut Connection) -> SeedLinkResult<()> {
+        if con.protocol_version() < 4 {
+            return Err(SeedLinkError::InvalidClientConfig(
+                "format selection requires seedlink protocol version v4".to_string(),
+            ));
+        }
+
+        let supported = con.request_formats().await?;
+        for format in self
+            .streams
+            .iter()
+            .filter_map(|stream| stream.format.as_ref())
+        {
+            if !supported.contains(format) {
+                return Err(SeedLinkError::InvalidClientConfig(format!(
+                    "format {} not supported by the server",
+                    format
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    #[test]
+    fn select_args_with_format() {
+        let stream = StreamRequest::new("GE", "WLF").format(Format::MiniSeed3);
+        assert_eq!(
+            stream.select_args().unwrap(),
+            vec![Some("*.3?".to_string())]
+        );
+
+        let stream = stream.select("*_B_H_Z").select("!00_*");
+        assert_eq!(
+            stream.select_args().unwrap(),
+            vec![Some("*_B_H_Z.3?".to_string()), Some("!00_*".to_string())]
+        );
+
+        let stream = StreamRequest::new("GE", "WLF")
+            .select("*_B_H_Z.2D")
+            .format(Format::MiniSeed3);
+        assert!(stream.select_args().is_err());
+    }
+
+    #[tokio::test]
+    async fn configure_v3() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let server = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "batch");
+            write.write_all(b"OK\r\n").await.unwrap();
+
+            let mut cmds = vec![];
+            loop {
+                let line = lines.next_line().await.unwrap().unwrap();
+                if line == "end" {
+                    break;
+                }
+                cmds.push(line);
+            }
+            cmds
+        };
+
+        let builder = ConnectionBuilder::new("slink://127.0.0.1/")
+            .unwrap()
+            .stream(
+                StreamRequest::new("GE", "WLF")
+                    .select("BHZ")
+                    .select("BHN")
+                    .seq_num("1a"),
+            )
+            .stream(StreamRequest::new("GE", "APE").data_transfer_mode(DataTransferMode::DialUp))
+            .batch_cmd_mode(true)
+            .keep_alive_interval(Duration::from_secs(60));
+        let (con, cmds) = tokio::join!(builder.connect_duplex(client_stream), server);
+        let con = con.unwrap();
+
+        assert_eq!(con.keep_alive_interval(), Some(Duration::from_secs(60)));
+
+        // stations are configured in arbitrary order
+        let mut stations: Vec<Vec<String>> = vec![];
+        for cmd in cmds {
+            if cmd.starts_with("station") {
+                stations.push(vec![]);
+            }
+            stations.last_mut().unwrap().push(cmd);
+        }
+        stations.sort();
+        assert_eq!(
+            stations,
+            vec![
+                vec!["station APE GE", "fetch"],
+                vec!["station WLF GE", "select BHZ", "select BHN", "data 1a"],
+            ]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::io;
+
+use quick_xml::de;
+use serde::Deserialize;
+
+use crate::SeedLinkResult;
+
+/// Capabilities of a SeedLink server (see [`Connection::capabilities`]).
+///
+/// [`Connection::capabilities`]: crate::Connection::capabilities
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Protocol versions supported (e.g. `4.0`).
+    pub protocol_versions: Vec<String>,
+    /// Whether dial-up mode is supported.
+    pub dialup: bool,
+    /// Whether multi-station mode is supported.
+    pub multistation: bool,
+    /// Whether time window requests are supported.
+    pub time_window: bool,
+    /// WebSocket protocol version supported, if any.
+    pub websocket: Option<String>,
+    /// `INFO` levels supported (e.g. `stations`), if advertised.
+    pub info: Vec<String>,
+    /// Remaining capabilities advertised (e.g. `BATCH` or `INFO:GZIP`).
+    pub extensions: Vec<String>,
+}
+
+impl Capabilities {
+    /// Creates the capabilities from the protocol versions `protocol_versions` and the
+    /// capabilities `capabilities` advertised in response to `HELLO`.
+    pub(crate) fn from_hello(protocol_versions: &[String], capabilities: &[String]) -> Self {
+        let mut rv = Self {
+            protocol_versions: protocol_versions.to_vec(),
+            ..Default::default()
+        };
+        for cap in capabilities {
+            rv.insert(cap);
+        }
+
+        rv
+    }
+
+    /// Marks the capabilities implied by SeedLink `v4`, i.e. dial-up and multi-station mode as
+    /// well as time window requests.
+    pub(crate) fn with_v4_defaults(mut self) -> Self {
+        self.dialup = true;
+        self.multistation = true;
+        self.time_window = true;
+        self
+    }
+
+    /// Merges the capabilities of the `v3` `INFO CAPABILITIES` response `capabilities_xml`.
+    pub(crate) fn merge_xml_v3(mut self, capabilities_xml: &str) -> SeedLinkResult<Self> {
+        let parsed: CapabilitiesXml = de::from_str(capabilities_xml).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid INFO CAPABILITIES response: {}", e),
+            )
+        })?;
+        for cap in parsed.capability {
+            self.insert(&cap.name);
+        }
+
+        Ok(self)
+    }
+
+    /// Returns whether the capability `capability` (e.g. `INFO:GZIP`) was advertised as
+    /// extension.
+    pub fn has_extension(&self, capability: &str) -> bool {
+        self.extensions.iter().any(|ext| ext == capability)
+    }
+
+    fn insert(&mut self, cap: &str) {
+        if let Some(version) = cap.strip_prefix("SLPROTO:") {
+            if !self.protocol_versions.iter().any(|v| v == version) {
+                self.protocol_versions.push(version.to_string());
+            }
+        } else if let Some(version) = cap.strip_prefix("WS:") {
+            self.websocket = Some(version.to_string());
+        } else if let Some(level) = cap.strip_prefix("info:") {
+            if !self.info.iter().any(|l| l == level) {
+                self.info.push(level.to_string());
+            }
+        } else {
+            match cap {
+                "dialup" => self.dialup = true,
+                "multistation" | "MULTISTATION" => self.multistation = true,
+                "window-extraction" | "TIME" => self.time_window = true,
+                cap if !self.has_extension(cap) => self.extensions.push(cap.to_string()),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CapabilitiesXml {
+    #[serde(default)]
+    capability: Vec<CapabilityXml>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CapabilityXml {
+    #[serde(rename = "@name")]
+    name: String,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_capabilities() {
+        let capabilities = Capabilities::from_hello(
+            &["3.1".to_string()],
+            &["SLPROTO:3.1", "CAP", "EXTREPLY", "NSWILDCARD", "BATCH", "WS:13"]
+                .map(String::from),
+        )
+        .merge_xml_v3(
+            r#"<?xml version="1.0"?>
+<seedlink software="SeedLink v3.3 (2020.122)" organization="GEOFON" started="2023/05/01 00:00:00.0000">
+  <capability name="dialup"/>
+  <capability name="multistation"/>
+  <capability name="window-extraction"/>
+  <capability name="info:id"/>
+  <capability name="info:stations"/>
+</seedlink>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            capabilities,
+            Capabilities {
+                protocol_versions: vec!["3.1".to_string()],
+                dialup: true,
+                multistation: true,
+                time_window: true,
+                websocket: Some("13".to_string()),
+                info: vec!["id".to_string(), "stations".to_string()],
+                extensions: ["CAP", "EXTREPLY", "NSWILDCARD", "BATCH"]
+                    .map(String::from)
+                    .to_vec(),
+            }
+        );
+        assert!(capabilities.has_extension("BATCH"));
+
+        let capabilities = Capabilities::from_hello(&["4.0".to_string()], &[]).with_v4_defaults();
+        assert!(capabilities.dialup && capabilities.multistation && capabilities.time_window);
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use pin_project_lite::pin_project;
+use tracing::warn;
+
+use crate::trace;
+use crate::{Inventory, StreamEnd, StreamItem};
+
+/// Sequence numbers of the packets most recently buffered by the server per station (i.e.
+/// `NET_STA`), e.g. when starting to fetch the backlog.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Watermarks(HashMap<String, u64>);
+
+impl Watermarks {
+    /// Creates the watermarks from the end sequence numbers of the stations of `inventory` (see
+    /// [`Connection::request_inventory`](crate::Connection::request_inventory)).
+    ///
+    /// Note that stations not subscribed to never catch up, i.e. the inventory should be
+    /// restricted to the stations subscribed to (see [`Inventory::filter`]).
+    pub fn from_inventory(inventory: &Inventory) -> Self {
+        Self(
+            inventory
+                .iter()
+                .map(|sta| {
+                    (
+                        format!("{}_{}", sta.net_code(), sta.sta_code()),
+                        sta.end_seq(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Sets the watermark of the station `sta` of network `net` to `seq_num`.
+    pub fn insert(&mut self, net: &str, sta: &str, seq_num: u64) {
+        self.0.insert(format!("{}_{}", net, sta), seq_num);
+    }
+
+    /// Returns the number of stations.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether there are no watermarks.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Notification that a station caught up, i.e. the packet buffered most recently by the server
+/// (when the watermarks were taken) was received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaughtUp {
+    /// Station identifier (i.e. `NET_STA`).
+    pub station_id: String,
+    /// Watermark the station caught up with.
+    pub watermark: u64,
+    /// Number of stations not yet caught up. If zero, all stations caught up.
+    pub remaining: usize,
+}
+
+/// Extension trait for packet streams (see [`Connection::packets`](crate::Connection::packets)).
+pub trait CatchUpTrackingExt: Stream<Item = StreamItem> + Sized {
+    /// Wraps the packet stream keeping track of the stations catching up with `watermarks` when
+    /// fetching backlog (i.e. in dial-up mode or when resuming in real-time mode).
+    /// `on_caught_up` is called once per station caught up.
+    ///
+    /// Once the server completed the data transfer (see [`StreamEnd::Completed`]), the
+    /// stations remaining are considered caught up. Items are passed through unchanged.
+    fn with_catch_up_tracking<F>(
+        self,
+        watermarks: Watermarks,
+        on_caught_up: F,
+    ) -> CatchUpTracking<Self, F>
+    where
+        F: FnMut(&CaughtUp),
+    {
+        CatchUpTracking {
+            packets: self,
+            watermarks,
+            on_caught_up,
+        }
+    }
+}
+
+impl<S: Stream<Item = StreamItem>> CatchUpTrackingExt for S {}
+
+pin_project! {
+    /// Stream adapter for [`CatchUpTrackingExt::with_catch_up_tracking`].
+    #[must_use = "streams do nothing unless polled"]
+    pub struct CatchUpTracking<S, F> {
+        #[pin]
+        packets: S,
+        watermarks: Watermarks,
+        on_caught_up: F,
+    }
+}
+
+impl<S, F> Stream for CatchUpTracking<S, F>
+where
+    S: Stream<Item = StreamItem>,
+    F: FnMut(&CaughtUp),
+{
+    type Item = StreamItem;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let item = match this.packets.poll_next(cx) {
+            Poll::Ready(Some(item)) => item,
+            other => return other,
+        };
+        if this.watermarks.is_empty() {
+            return Poll::Ready(Some(item));
+        }
+
+        match item {
+            StreamItem::Packet(ref packet) => match packet.to_record() {
+                Some(Ok((sid, seq_num, _))) => {
+                    let station_id = format!("{}_{}", sid.nslc.net, sid.nslc.sta);
+                    if let Some(caught_up) = observe(this.watermarks, station_id, seq_num) {
+                        (this.on_caught_up)(&caught_up);
+                    }
+                }
+                Some(Err(e)) => warn!(target: trace::STREAM, "failed to track catch up: {}", e),
+                None => {}
+            },
+            StreamItem::End(StreamEnd::Completed) => {
+                let mut remaining: Vec<(String, u64)> = this.watermarks.0.drain().collect();
+                remaining.sort();
+                let mut len = remaining.len();
+                for (station_id, watermark) in remaining {
+                    len -= 1;
+                    (this.on_caught_up)(&CaughtUp {
+                        station_id,
+                        watermark,
+                        remaining: len,
+                    });
+                }
+            }
+            StreamItem::End(_) => {}
+        }
+
+        Poll::Ready(Some(item))
+    }
+}
+
+fn observe(watermarks: &mut Watermarks, station_id: String, seq_num: u64) -> Option<CaughtUp> {
+    let watermark = *watermarks.0.get(&station_id)?;
+    if seq_num < watermark {
+        return None;
+    }
+
+    watermarks.0.remove(&station_id);
+    Some(CaughtUp {
+        station_id,
+        watermark,
+        remaining: watermarks.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn observe_caught_up() {
+        let mut watermarks = Watermarks::default();
+        watermarks.insert("GE", "WLF", 42);
+        watermarks.insert("GE", "APE", 7);
+
+        assert_eq!(observe(&mut watermarks, "GE_WLF".to_string(), 41), None);
+        assert_eq!(observe(&mut watermarks, "GE_MOX".to_string(), 41), None);
+        assert_eq!(
+            observe(&mut watermarks, "GE_WLF".to_string(), 42),
+            Some(CaughtUp {
+                station_id: "GE_WLF".to_string(),
+                watermark: 42,
+                remaining: 1,
+            })
+        );
+        assert_eq!(observe(&mut watermarks, "GE_WLF".to_string(), 43), None);
+        assert_eq!(
+            observe(&mut watermarks, "GE_APE".to_string(), 8),
+            Some(CaughtUp {
+                station_id: "GE_APE".to_string(),
+                watermark: 7,
+                remaining: 0,
+            })
+        );
+        assert!(watermarks.is_empty());
+    }
+}
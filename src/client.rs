@@ -1,10 +1,10 @@
 use std::time::Duration;
 
-use crate::{connect, Connection, ConnectionInfo, IntoConnectionInfo, SeedLinkResult};
+use crate::{
+    connect, Connection, ConnectionInfo, IntoConnectionInfo, SeedLinkResult, UserAgentCmdInfoV4,
+};
 
 // TODO(damb):
-// - allow the user to make use of certain protocol versions e.g. by means of using the URL syntax
-// `slink+v3://`, `slink+v4://` (check out the syntax for tls/unix socket connections)
 // - allow to switch the protocol version (if still possible)
 
 /// The client acts as connector to the SeedLink server. By itself it does not
@@ -17,6 +17,9 @@ use crate::{connect, Connection, ConnectionInfo, IntoConnectionInfo, SeedLinkRes
 /// slink://host:port/
 /// ```
 ///
+/// The client protocol version may be pinned by means of the `slink+v3://` and `slink+v4://`
+/// schemes (e.g. `slinks+v4://` for TLS connections).
+///
 /// Example usage::
 ///
 /// ```rust,no_run
@@ -38,6 +41,13 @@ impl Client {
         })
     }
 
+    /// Sets the user agent information connections identify with to SeedLink `v4` servers (e.g.
+    /// `myapp/1.0`). By default, the library identifies itself (i.e. `slink/<version>`).
+    pub fn with_user_agent(mut self, user_agent: Vec<UserAgentCmdInfoV4>) -> Self {
+        self.connection_info.slink.user_agent = user_agent;
+        self
+    }
+
     /// Instructs the client to actually connect to SeedLink and returns a connection object. The
     /// connection object can be used to communicate with the server. This can fail with a variety
     /// of errors (like unreachable host) so it's important that you handle those errors.
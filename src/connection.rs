@@ -1,26 +1,49 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::future;
 use std::io;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::task::{Context, Poll};
 use std::time::Duration;
 
-use futures::stream::{self, Stream, StreamExt, TryStream};
-use time::PrimitiveDateTime;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use mseed::MSRecord;
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, DuplexStream};
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time as tokio_time;
 use tokio_stream::wrappers::IntervalStream;
 use tracing::{debug, info, instrument, warn};
 
+#[cfg(feature = "v4-client")]
+use crate::credentials::CredentialsRefresh;
+use crate::metrics;
+use crate::socket;
+use crate::warning::{self, WarningObserver, WarningSink};
+#[cfg(feature = "gzip")]
+use crate::CAPABILITY_INFO_GZIP_V3;
 use crate::{
-    util, Frame, Inventory, SeedLinkConnectionV3, SeedLinkDataTransferModeV3,
-    SeedLinkError, SeedLinkGenericDataPacketV3, SeedLinkInfoPacketV3, SeedLinkPacket,
-    SeedLinkPacketV3, SeedLinkResult, StateDB, StreamConfig, AVAILABLE_CLIENT_PROTO_VERSIONS,
-    DEFAULT_PORT,
+    util, Capabilities, ConnectionBuilder, ConnectionsInfoV4, ConnectionsV3, FDSNSourceId, Format,
+    FormatsInfoV4, Frame, GapsV3, IdInfoV4, InfoCmdItemV3, Inventory, InventoryLevel,
+    LatencyMonitor, SeedLinkConnectionV3, SeedLinkDataTransferModeV3, SeedLinkError,
+    SeedLinkGenericDataPacketV3, SeedLinkInfoPacketV3, SeedLinkPacket, SeedLinkPacketV3,
+    SeedLinkResult, SeedLinkWarning, Stations, StationsInfoV4, StreamConfig, StreamsInfoV4,
+    SubscribedStation, TcpSocketOptions, UserAgentCmdInfoV4, AVAILABLE_CLIENT_PROTO_VERSIONS,
+    CAPABILITY_CAP_V3, CAPABILITY_EXTREPLY_V3, DEFAULT_PORT,
 };
+#[cfg(feature = "v4-client")]
+use crate::{
+    AuthCmdMethodV4, CredentialsProvider, ErrorCodeV4, FrameV4, InfoCmdItemV4, InfoCmdV4,
+    SeedLinkConnectionV4, SeedLinkDataTransferModeV4, SlProtoCmdV4, CAPABILITY_AUTH_REFRESH_V4,
+    PACKET_SIGNATURE_CAPABILITY_V4,
+};
+#[cfg(feature = "state-sqlite")]
+use crate::{StateDB, StreamState};
 
 #[derive(Debug)]
 pub(crate) struct TcpConnection {
@@ -28,48 +51,110 @@ pub(crate) struct TcpConnection {
     pub open: bool,
 }
 
+/// TLS connection on top of TCP.
+#[cfg(feature = "tls")]
+#[derive(Debug)]
+pub(crate) struct TlsConnection {
+    pub rw: tokio_rustls::client::TlsStream<TcpStream>,
+    pub open: bool,
+}
+
+/// In-memory connection e.g. wired to an in-process server.
+#[derive(Debug)]
+pub(crate) struct MemConnection {
+    pub rw: DuplexStream,
+    pub open: bool,
+}
+
 /// Enumerations of actual raw connections.
 #[derive(Debug)]
 pub(crate) enum ActualConnection {
     Tcp(TcpConnection),
+    #[cfg(feature = "tls")]
+    Tls(TlsConnection),
+    Mem(MemConnection),
 }
 
 impl ActualConnection {
-    pub async fn new(addr: &ConnectionAddr, timeout: Option<Duration>) -> SeedLinkResult<Self> {
+    pub async fn new(
+        addr: &ConnectionAddr,
+        slink_connection_info: &SeedLinkConnectionInfo,
+        timeout: Option<Duration>,
+    ) -> SeedLinkResult<Self> {
         Ok(match *addr {
             ConnectionAddr::Tcp(ref host, ref port) => {
-                let addr = (host.as_str(), *port);
-                if let Some(timeout) = timeout {
-                    let socket = tokio_time::timeout(timeout, TcpStream::connect(addr))
-                        .await
-                        .map_err(|_| {
-                            io::Error::new(io::ErrorKind::Other, "connection timeout")
-                        })??;
-
-                    Self::Tcp(TcpConnection {
-                        rw: socket,
-                        open: true,
-                    })
-                } else {
-                    let socket = TcpStream::connect(addr).await?;
-                    Self::Tcp(TcpConnection {
-                        rw: socket,
-                        open: true,
-                    })
-                }
+                let socket = connect_tcp(host, *port, &slink_connection_info.tcp, timeout).await?;
+                Self::Tcp(TcpConnection {
+                    rw: socket,
+                    open: true,
+                })
+            }
+            #[cfg(feature = "tls")]
+            ConnectionAddr::TcpTls { ref host, port } => {
+                let socket = connect_tcp(host, port, &slink_connection_info.tcp, timeout).await?;
+                let handshake = crate::tls::connect(host, socket, slink_connection_info);
+                let stream = match timeout {
+                    Some(timeout) => {
+                        tokio_time::timeout(timeout, handshake)
+                            .await
+                            .map_err(|_| SeedLinkError::ConnectTimeout {
+                                addr: addr.to_string(),
+                                timeout,
+                            })??
+                    }
+                    None => handshake.await?,
+                };
+
+                Self::Tls(TlsConnection {
+                    rw: stream,
+                    open: true,
+                })
+            }
+            #[cfg(not(feature = "tls"))]
+            ConnectionAddr::TcpTls { .. } => {
+                return Err(SeedLinkError::InvalidClientConfig(
+                    "TLS connections require the `tls` feature".to_string(),
+                ));
+            }
+            ConnectionAddr::Mem => {
+                return Err(SeedLinkError::ClientError(
+                    "in-memory connections cannot be established by address".to_string(),
+                ))
             }
         })
     }
 }
 
+/// Establishes a TCP connection to `host` and `port` and applies the socket options `options`.
+async fn connect_tcp(
+    host: &str,
+    port: u16,
+    options: &TcpSocketOptions,
+    timeout: Option<Duration>,
+) -> SeedLinkResult<TcpStream> {
+    let socket = match timeout {
+        Some(timeout) => tokio_time::timeout(timeout, socket::connect(host, port))
+            .await
+            .map_err(|_| SeedLinkError::ConnectTimeout {
+                addr: format!("{}:{}", host, port),
+                timeout,
+            })??,
+        None => socket::connect(host, port).await?,
+    };
+    options.apply(&socket)?;
+
+    Ok(socket)
+}
+
 #[derive(Debug)]
 pub(crate) enum ActualSeedLinkConnection {
     V3(SeedLinkConnectionV3),
-    // V4(),
+    #[cfg(feature = "v4-client")]
+    V4(SeedLinkConnectionV4),
 }
 
 /// Enumeration of possible data transfer modes.
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum DataTransferMode {
     /// Real-time mode.
     RealTime,
@@ -77,6 +162,260 @@ pub enum DataTransferMode {
     DialUp,
 }
 
+/// Outcome of negotiating the connection.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct NegotiationReport {
+    /// Whether pipelining (i.e. batch command mode) was requested but is unavailable, e.g.
+    /// because the server rejected it (see [`SeedLinkConnectionInfo::batch_fallback`]).
+    pub pipelining_unavailable: bool,
+    /// Stations (i.e. `NET_STA`) requested but absent from the server's inventory (see
+    /// [`SeedLinkConnectionInfo::inventory_validation`]).
+    pub missing_stations: Vec<String>,
+}
+
+/// Validation of the stations requested against the server's inventory (i.e. `INFO STATIONS`)
+/// before negotiating.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum InventoryValidation {
+    /// The stations requested are not validated (default). Recommended for servers with huge
+    /// inventories.
+    #[default]
+    Disabled,
+    /// Stations absent from the inventory are logged as warnings (and negotiated regardless).
+    Warn,
+    /// Configuring the connection fails if any station is absent from the inventory.
+    Fail,
+}
+
+/// Assumptions applied to nonstandard responses to `HELLO` (e.g. `SeedLink Server` without a
+/// protocol version) unless strict handshaking is enabled (see
+/// [`SeedLinkConnectionInfo::strict_handshake`]).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HelloFallback {
+    /// Protocol version assumed if the server does not advertise a valid protocol version.
+    /// Defaults to `3.0`.
+    pub protocol_version: String,
+}
+
+impl Default for HelloFallback {
+    fn default() -> Self {
+        Self {
+            protocol_version: "3.0".to_string(),
+        }
+    }
+}
+
+/// Progress of negotiating the stations configured (see
+/// [`Connection::set_negotiation_progress`]).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NegotiationProgress {
+    /// Number of stations negotiated so far.
+    pub negotiated: usize,
+    /// Number of stations rejected so far.
+    pub rejected: usize,
+    /// Total number of stations to be negotiated.
+    pub total: usize,
+    /// Time elapsed since negotiating started.
+    pub elapsed: Duration,
+}
+
+impl NegotiationProgress {
+    /// Returns the estimated time remaining until all stations are negotiated, based on the
+    /// average time negotiating a station took so far.
+    pub fn estimated_remaining(&self) -> Option<Duration> {
+        if self.negotiated == 0 {
+            return None;
+        }
+
+        let remaining = u32::try_from(self.total - self.negotiated).ok()?;
+        let negotiated = u32::try_from(self.negotiated).ok()?;
+        Some(self.elapsed / negotiated * remaining)
+    }
+}
+
+/// Tracks the negotiation progress and notifies the callback configured.
+pub(crate) struct NegotiationProgressTracker<'a> {
+    progress: NegotiationProgress,
+    started: tokio_time::Instant,
+    callback: Option<&'a NegotiationProgressCallback>,
+}
+
+impl<'a> NegotiationProgressTracker<'a> {
+    pub fn new(total: usize, callback: Option<&'a NegotiationProgressCallback>) -> Self {
+        Self {
+            progress: NegotiationProgress {
+                negotiated: 0,
+                rejected: 0,
+                total,
+                elapsed: Duration::ZERO,
+            },
+            started: tokio_time::Instant::now(),
+            callback,
+        }
+    }
+
+    /// Records a station negotiated. Returns the number of stations accepted so far.
+    pub fn advance(&mut self, accepted: bool) -> usize {
+        self.progress.negotiated += 1;
+        if !accepted {
+            self.progress.rejected += 1;
+        }
+        self.progress.elapsed = self.started.elapsed();
+
+        if let Some(callback) = self.callback {
+            (callback.0)(&self.progress);
+        }
+
+        self.progress.negotiated - self.progress.rejected
+    }
+}
+
+/// Callback notified about the negotiation progress.
+#[derive(Clone)]
+pub(crate) struct NegotiationProgressCallback(Arc<dyn Fn(&NegotiationProgress) + Send + Sync>);
+
+impl NegotiationProgressCallback {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&NegotiationProgress) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for NegotiationProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NegotiationProgressCallback")
+            .finish_non_exhaustive()
+    }
+}
+
+/// Statistics of a connection (see [`Connection::stats`]).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct ConnectionStats {
+    /// Number of bytes received.
+    pub bytes_received: u64,
+    /// Number of bytes sent.
+    pub bytes_sent: u64,
+    /// Number of data packets received.
+    pub data_packets_received: u64,
+    /// Number of `INFO` packets received (including the responses to keepalives).
+    pub info_packets_received: u64,
+    /// Number of packet header bytes received.
+    pub header_bytes_received: u64,
+    /// Number of packet payload bytes received.
+    pub payload_bytes_received: u64,
+    /// Number of keepalives sent.
+    pub keep_alives_sent: u64,
+    /// Time negotiating the stations configured took, if the connection was configured.
+    pub negotiation_duration: Option<Duration>,
+    /// Time elapsed since the connection was established.
+    pub uptime: Duration,
+}
+
+impl ConnectionStats {
+    /// Returns the number of bytes transferred in both directions not carrying packet payload,
+    /// i.e. packet headers, command lines and responses.
+    pub fn protocol_overhead(&self) -> u64 {
+        (self.bytes_received + self.bytes_sent).saturating_sub(self.payload_bytes_received)
+    }
+}
+
+/// Default capacity of the read buffer of connections.
+const DEFAULT_READ_BUFFER_CAPACITY: usize = 8 * 1024;
+/// Default capacity of the write buffer of connections.
+const DEFAULT_WRITE_BUFFER_CAPACITY: usize = 255;
+
+/// Capacities of the buffers of a framed connection.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BufferCapacities {
+    pub read: usize,
+    pub write: usize,
+}
+
+/// Records the statistics of a connection. Shared between the framed connection and the
+/// [`ConnectionControl`] handles.
+#[derive(Debug)]
+pub(crate) struct StatsRecorder {
+    established: tokio_time::Instant,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    data_packets_received: AtomicU64,
+    info_packets_received: AtomicU64,
+    header_bytes_received: AtomicU64,
+    payload_bytes_received: AtomicU64,
+    keep_alives_sent: AtomicU64,
+    negotiation_duration: std::sync::Mutex<Option<Duration>>,
+}
+
+impl Default for StatsRecorder {
+    fn default() -> Self {
+        Self {
+            established: tokio_time::Instant::now(),
+            bytes_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            data_packets_received: AtomicU64::new(0),
+            info_packets_received: AtomicU64::new(0),
+            header_bytes_received: AtomicU64::new(0),
+            payload_bytes_received: AtomicU64::new(0),
+            keep_alives_sent: AtomicU64::new(0),
+            negotiation_duration: std::sync::Mutex::new(None),
+        }
+    }
+}
+
+impl StatsRecorder {
+    /// Sets the total number of bytes received.
+    pub fn set_bytes_received(&self, bytes: u64) {
+        self.bytes_received.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records a packet received made up of `len_header` header bytes and `len_payload` payload
+    /// bytes.
+    pub fn add_packet(&self, info: bool, len_header: usize, len_payload: usize) {
+        metrics::packet_received(info);
+        let counter = if info {
+            &self.info_packets_received
+        } else {
+            &self.data_packets_received
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.header_bytes_received
+            .fetch_add(len_header as u64, Ordering::Relaxed);
+        self.payload_bytes_received
+            .fetch_add(len_payload as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_keep_alive(&self) {
+        self.keep_alives_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_negotiation_duration(&self, duration: Duration) {
+        *self.negotiation_duration.lock().unwrap() = Some(duration);
+    }
+
+    /// Returns the statistics recorded so far.
+    pub fn snapshot(&self) -> ConnectionStats {
+        ConnectionStats {
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            data_packets_received: self.data_packets_received.load(Ordering::Relaxed),
+            info_packets_received: self.info_packets_received.load(Ordering::Relaxed),
+            header_bytes_received: self.header_bytes_received.load(Ordering::Relaxed),
+            payload_bytes_received: self.payload_bytes_received.load(Ordering::Relaxed),
+            keep_alives_sent: self.keep_alives_sent.load(Ordering::Relaxed),
+            negotiation_duration: *self.negotiation_duration.lock().unwrap(),
+            uptime: self.established.elapsed(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 struct StreamConfigs(pub HashMap<String, StreamConfig>);
 
@@ -106,6 +445,63 @@ impl StreamConfigs {
         Ok(())
     }
 
+    pub fn add_stream_config(&mut self, stream_config: StreamConfig) {
+        let key = format!("{}{}", stream_config.network, stream_config.station);
+        match self.0.get_mut(&key) {
+            Some(existing) => {
+                for select_arg in stream_config.iter() {
+                    existing.add_select_arg(select_arg);
+                }
+            }
+            None => {
+                self.0.insert(key, stream_config);
+            }
+        }
+    }
+
+    pub fn set_data_transfer_mode(&mut self, net: &str, sta: &str, mode: DataTransferMode) {
+        if let Some(stream_config) = self.0.get_mut(&format!("{}{}", net, sta)) {
+            stream_config.data_transfer_mode = Some(mode);
+        }
+    }
+
+    pub fn set_time_window(
+        &mut self,
+        net: &str,
+        sta: &str,
+        start_time: PrimitiveDateTime,
+        end_time: PrimitiveDateTime,
+    ) {
+        if let Some(stream_config) = self.0.get_mut(&format!("{}{}", net, sta)) {
+            stream_config.time = Some(start_time);
+            stream_config.end_time = Some(end_time);
+        }
+    }
+
+    pub fn remove_stream(&mut self, net: &str, sta: &str) -> bool {
+        self.0.remove(&format!("{}{}", net, sta)).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn streams(&self) -> Vec<(&str, &str, &[String])> {
+        let mut rv: Vec<(&str, &str, &[String])> = self
+            .0
+            .values()
+            .map(|stream_config| {
+                (
+                    stream_config.network.as_str(),
+                    stream_config.station.as_str(),
+                    stream_config.as_slice(),
+                )
+            })
+            .collect();
+        rv.sort_unstable_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        rv
+    }
+
     pub fn seq_num(&self, net: &str, sta: &str) -> Option<&str> {
         let key = format!("{}{}", net, sta);
 
@@ -127,22 +523,296 @@ impl StreamConfigs {
 pub struct Connection {
     /// The actual underlying SeedLink connection handle.
     con: ActualSeedLinkConnection,
+    /// The address of the remote peer.
+    addr: ConnectionAddr,
 
     stream_configs: StreamConfigs,
+
+    /// End of the time window the packet stream waits for (see
+    /// [`Connection::configure_time_window`]).
+    window_end: Option<PrimitiveDateTime>,
+
+    /// Keepalive interval configured by means of [`ConnectionBuilder::keep_alive_interval`].
+    keep_alive_interval: Option<Duration>,
+    /// Detection of dead connections by means of unacknowledged keepalives.
+    keep_alive_check: KeepAliveCheck,
+    /// Time within which data must be received while streaming packets.
+    idle_timeout: Option<Duration>,
+
+    /// Channel of the control requests issued by means of [`ConnectionControl`] handles.
+    control: Option<(mpsc::Sender<ControlRequest>, mpsc::Receiver<ControlRequest>)>,
+    /// Latencies of the records received (see [`Connection::latency_monitor`]).
+    latency_monitor: Option<LatencyMonitor>,
+
+    /// Validation of the stations requested before negotiating.
+    inventory_validation: InventoryValidation,
+    /// Stations requested but absent from the server's inventory.
+    missing_stations: Vec<String>,
+    /// Non-fatal conditions observed (see [`Connection::warnings`]).
+    warnings: WarningSink,
+    /// Capabilities advertised in response to `HELLO`.
+    capabilities: Capabilities,
+    /// Protocol version (i.e. major and minor version) negotiated.
+    negotiated_protocol_version: (u8, u8),
+    /// Server software identifier received in response to `HELLO`.
+    server_software: String,
+    /// Station or data center description received in response to `HELLO`.
+    data_center_description: String,
+    /// Refresh of the credentials provided by the credentials provider, if any.
+    #[cfg(feature = "v4-client")]
+    credentials_refresh: CredentialsRefresh,
 }
 
 impl Connection {
-    pub(crate) fn new(con: ActualSeedLinkConnection) -> Self {
+    /// Returns a builder declaring the configuration of a connection to `params` (e.g.
+    /// `slink://127.0.0.1/`).
+    pub fn builder<T: IntoConnectionInfo>(params: T) -> SeedLinkResult<ConnectionBuilder> {
+        ConnectionBuilder::new(params)
+    }
+
+    pub(crate) fn new(con: ActualSeedLinkConnection, addr: ConnectionAddr) -> Self {
         Self {
             con,
+            addr,
             stream_configs: StreamConfigs::default(),
+            window_end: None,
+            keep_alive_interval: None,
+            keep_alive_check: KeepAliveCheck::default(),
+            idle_timeout: None,
+            control: None,
+            latency_monitor: None,
+            inventory_validation: InventoryValidation::Disabled,
+            missing_stations: Vec::new(),
+            warnings: WarningSink::default(),
+            capabilities: Capabilities::default(),
+            negotiated_protocol_version: (0, 0),
+            server_software: String::new(),
+            data_center_description: String::new(),
+            #[cfg(feature = "v4-client")]
+            credentials_refresh: CredentialsRefresh::default(),
+        }
+    }
+
+    /// Returns the keepalive interval configured by means of
+    /// [`ConnectionBuilder::keep_alive_interval`], i.e. the interval to be passed to
+    /// [`Connection::packets`].
+    pub fn keep_alive_interval(&self) -> Option<Duration> {
+        self.keep_alive_interval
+    }
+
+    pub(crate) fn set_keep_alive_interval(&mut self, interval: Option<Duration>) {
+        self.keep_alive_interval = interval;
+    }
+
+    /// Sets the time within which keepalives must be acknowledged by the server (see
+    /// [`Connection::packets`]). By default, there is no timeout.
+    ///
+    /// If a keepalive is not acknowledged in time, the packet stream terminates with an
+    /// [`io::ErrorKind::TimedOut`] error, i.e. callers may reconnect instead of waiting for a dead
+    /// connection.
+    pub fn set_keep_alive_timeout(&mut self, timeout: Option<Duration>) {
+        self.keep_alive_check.timeout = timeout;
+    }
+
+    /// Sets the time within which data must be received from the server while streaming packets
+    /// (see [`Connection::packets`]). By default, there is no timeout.
+    ///
+    /// The timeout is measured from the creation of the packet stream and reset whenever data
+    /// (including keepalive responses) is received. If it expires, the packet stream terminates
+    /// with an [`io::ErrorKind::TimedOut`] error, e.g. on half-open TCP connections.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Sets the maximum number of consecutive keepalives which may remain unanswered (see
+    /// [`Connection::packets`]). By default, there is no limit. Panics if `max` is zero.
+    ///
+    /// If `max` keepalives are still unanswered when the next keepalive is due, the packet stream
+    /// terminates with an [`io::ErrorKind::TimedOut`] error.
+    pub fn set_max_unanswered_keep_alives(&mut self, max: Option<u32>) {
+        assert_ne!(max, Some(0), "max must be greater than zero");
+        self.keep_alive_check.max_unanswered = max;
+    }
+
+    /// Returns a handle allowing to control the connection while streaming packets (see
+    /// [`Connection::packets`]), e.g. in order to issue `INFO` requests or to shut down the
+    /// connection gracefully from another task.
+    ///
+    /// Note that the `INFO` packets requested by means of the handle are not returned by the packet
+    /// stream.
+    pub fn control(&mut self) -> ConnectionControl {
+        let (send, _) = self
+            .control
+            .get_or_insert_with(|| mpsc::channel(CONTROL_CHANNEL_SIZE));
+
+        ConnectionControl {
+            chan: send.clone(),
+            stats: self.stats_recorder().clone(),
+            protocol_version: self.protocol_version(),
         }
     }
 
+    /// Returns a handle providing a rolling summary of the latencies of the records received per
+    /// stream while streaming packets (see [`Connection::packets`]), e.g. in order to monitor the
+    /// acquisition lag.
+    ///
+    /// Latencies are tracked only once the handle was requested.
+    pub fn latency_monitor(&mut self) -> LatencyMonitor {
+        self.latency_monitor
+            .get_or_insert_with(LatencyMonitor::default)
+            .clone()
+    }
+
+    /// Returns a receiver of the non-fatal conditions observed, e.g. in order to print or to count
+    /// them. Previously requested receivers do not receive warnings anymore.
+    ///
+    /// Warnings observed while establishing the connection are passed to the receiver requested
+    /// first. Packets are checked (e.g. for [`SeedLinkWarning::ReorderedPacket`]) while streaming
+    /// packets (see [`Connection::packets`]) only once a receiver was requested. Note that the
+    /// receiver is unbounded, i.e. it should be drained continuously.
+    pub fn warnings(&mut self) -> mpsc::UnboundedReceiver<SeedLinkWarning> {
+        self.warnings.subscribe()
+    }
+
+    /// Creates a new connection from the in-memory stream `stream` and negotiates the protocol
+    /// version.
+    ///
+    /// The remote end of `stream` is usually wired to an in-process server, which allows testing
+    /// without sockets.
+    pub async fn from_duplex(
+        stream: DuplexStream,
+        slink_connection_info: &SeedLinkConnectionInfo,
+    ) -> SeedLinkResult<Self> {
+        let con = ActualConnection::Mem(MemConnection {
+            rw: stream,
+            open: true,
+        });
+        let connection_info = ConnectionInfo {
+            addr: ConnectionAddr::Mem,
+            slink: slink_connection_info.clone(),
+        };
+
+        setup_connection(con, &connection_info).await
+    }
+
+    /// Returns the address of the remote peer.
+    pub fn addr(&self) -> &ConnectionAddr {
+        &self.addr
+    }
+
+    /// Returns the `StateDB` namespace identifying the remote peer, e.g.
+    /// `slink://geofon.gfz-potsdam.de:18000`.
+    ///
+    /// See also [`StateDB::with_namespace`].
+    pub fn state_namespace(&self) -> String {
+        format!("slink://{}", self.addr)
+    }
+
     /// Returns the SeedLink protocol version used.
     pub fn protocol_version(&self) -> u8 {
         match self.con {
             ActualSeedLinkConnection::V3(_) => 3,
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(_) => 4,
+        }
+    }
+
+    /// Returns the protocol version negotiated, i.e. the major and minor version (e.g. `(3, 1)`).
+    ///
+    /// The minor version is the highest minor version advertised by the server for the major
+    /// version selected (see [`Connection::protocol_version`]).
+    pub fn negotiated_protocol_version(&self) -> (u8, u8) {
+        self.negotiated_protocol_version
+    }
+
+    /// Returns the raw server software identifier received in response to `HELLO` (e.g.
+    /// `SeedLink v4.0 (2023.123 RingServer)`).
+    pub fn server_software(&self) -> &str {
+        &self.server_software
+    }
+
+    /// Returns the station or data center description received in response to `HELLO`.
+    pub fn data_center_description(&self) -> &str {
+        &self.data_center_description
+    }
+
+    /// Sets the callback notified each time a station was negotiated while configuring the
+    /// connection (see [`Connection::configure`]).
+    ///
+    /// This allows to e.g. render progress bars for selections of thousands of stations.
+    pub fn set_negotiation_progress<F>(&mut self, callback: F)
+    where
+        F: Fn(&NegotiationProgress) + Send + Sync + 'static,
+    {
+        self.set_negotiation_progress_callback(NegotiationProgressCallback::new(callback));
+    }
+
+    pub(crate) fn set_negotiation_progress_callback(
+        &mut self,
+        callback: NegotiationProgressCallback,
+    ) {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(con) => con
+                .get_framed_connection_mut()
+                .set_negotiation_progress(callback),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con
+                .get_framed_connection_mut()
+                .set_negotiation_progress(callback),
+        }
+    }
+
+    /// Returns the effective subscription, i.e. the stations negotiated successfully while
+    /// configuring the connection, sorted by network and station code.
+    ///
+    /// Note that selectors rejected by the server are not taken into account.
+    pub fn subscription(&self) -> Vec<SubscribedStation> {
+        let mut rv = match &self.con {
+            ActualSeedLinkConnection::V3(con) => {
+                con.get_framed_connection().subscription().to_vec()
+            }
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => {
+                con.get_framed_connection().subscription().to_vec()
+            }
+        };
+        rv.sort_unstable_by(|a, b| (&a.network, &a.station).cmp(&(&b.network, &b.station)));
+        rv
+    }
+
+    /// Returns the stream configurations added.
+    pub(crate) fn stream_configs(&self) -> Vec<StreamConfig> {
+        self.stream_configs.0.values().cloned().collect()
+    }
+
+    /// Returns the report of negotiating the connection.
+    pub fn negotiation_report(&self) -> NegotiationReport {
+        match &self.con {
+            ActualSeedLinkConnection::V3(con) => NegotiationReport {
+                pipelining_unavailable: con.get_framed_connection().batch_cmd_mode_unavailable(),
+                missing_stations: self.missing_stations.clone(),
+            },
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(_) => NegotiationReport {
+                missing_stations: self.missing_stations.clone(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns the statistics of the connection, e.g. the number of bytes and packets received.
+    ///
+    /// While streaming packets, the statistics are available by means of
+    /// [`ConnectionControl::stats`].
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats_recorder().snapshot()
+    }
+
+    fn stats_recorder(&self) -> &Arc<StatsRecorder> {
+        match &self.con {
+            ActualSeedLinkConnection::V3(con) => con.get_framed_connection().stats(),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.get_framed_connection().stats(),
         }
     }
 
@@ -150,6 +820,8 @@ impl Connection {
     pub fn is_open(&self) -> bool {
         match &self.con {
             ActualSeedLinkConnection::V3(con) => con.is_open(),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.is_open(),
         }
     }
 
@@ -166,7 +838,50 @@ impl Connection {
             .add_stream(net, sta, select_arg, seq_num, time)
     }
 
+    /// Configures the connection with the provided stream specific data. Data of the station is
+    /// transferred in `data_transfer_mode` regardless of the mode the connection is configured
+    /// with (see [`Connection::configure`]).
+    ///
+    /// This allows to e.g. fetch the backlog of some stations while streaming others in real-time.
+    /// Note that SeedLink `v4` connections do not support mixing data transfer modes.
+    pub fn add_stream_with_mode(
+        &mut self,
+        net: &str,
+        sta: &str,
+        select_arg: &Option<String>,
+        seq_num: &Option<String>,
+        time: &Option<PrimitiveDateTime>,
+        data_transfer_mode: DataTransferMode,
+    ) -> SeedLinkResult<()> {
+        self.stream_configs
+            .add_stream(net, sta, select_arg, seq_num, time)?;
+        self.stream_configs
+            .set_data_transfer_mode(net, sta, data_transfer_mode);
+        Ok(())
+    }
+
+    /// Removes the station previously added by means of [`Connection::add_stream`] (including its
+    /// selectors). Returns whether the station was configured.
+    pub fn remove_stream(&mut self, net: &str, sta: &str) -> bool {
+        self.stream_configs.remove_stream(net, sta)
+    }
+
+    /// Removes all stations previously added by means of [`Connection::add_stream`].
+    pub fn clear_streams(&mut self) {
+        self.stream_configs.clear()
+    }
+
+    /// Returns the currently configured stations (i.e. network and station codes) together with
+    /// their selectors, sorted by network and station code.
+    pub fn streams(&self) -> Vec<(&str, &str, &[String])> {
+        self.stream_configs.streams()
+    }
+
     /// Recovers the `StateDB` and updates the streams previously added by `Connection::add_stream`.
+    ///
+    /// Only state information within the namespace of `db` is taken into account.
+    #[cfg(feature = "state-sqlite")]
+    #[instrument(target = "slink::state", skip(self, db), fields(namespace = db.namespace()))]
     pub async fn recover_state(
         &mut self,
         db: &mut StateDB,
@@ -174,7 +889,21 @@ impl Connection {
     ) -> SeedLinkResult<()> {
         let protocol_version = self.protocol_version();
 
-        for (sid, seq_num) in db.state().await? {
+        for StreamState {
+            sid,
+            seq_num,
+            protocol_version: stored_protocol_version,
+            ..
+        } in db.state().await?
+        {
+            if stored_protocol_version != protocol_version {
+                warn!(
+                    "ignoring state of {} (sequence number refers to protocol version v{}, negotiated v{})",
+                    sid, stored_protocol_version, protocol_version
+                );
+                continue;
+            }
+
             if let Some(stream_config) = self
                 .stream_configs
                 .0
@@ -183,6 +912,8 @@ impl Connection {
                 if add_select_args {
                     if protocol_version == 3 {
                         stream_config.add_select_arg(&util::get_select_arg_v3(&sid));
+                    } else if protocol_version == 4 {
+                        stream_config.add_select_arg(&util::get_select_arg_v4(&sid));
                     }
                 }
 
@@ -199,8 +930,64 @@ impl Connection {
         Ok(())
     }
 
+    /// Recovers the `StateDB` and updates the streams previously added by `Connection::add_stream`
+    /// such that data is requested starting from the end time of the most recent record received
+    /// (i.e. `DATA`/`FETCH` with a time argument on `v3` and a start time on `v4`).
+    ///
+    /// Contrary to [`Connection::recover_state`], resuming by time is independent of the server
+    /// and the protocol version the state was recorded with, e.g. when failing over to a server
+    /// providing different sequence numbers. Since the start time applies to all streams of a
+    /// station, data is requested from the earliest end time of the station's streams, i.e.
+    /// records of streams ahead may be received again. Sequence numbers configured are not
+    /// modified. Streams without end time stored are not updated.
+    ///
+    /// Only state information within the namespace of `db` is taken into account.
+    #[cfg(feature = "state-sqlite")]
+    #[instrument(target = "slink::state", skip(self, db), fields(namespace = db.namespace()))]
+    pub async fn recover_state_by_time(
+        &mut self,
+        db: &mut StateDB,
+        add_select_args: bool,
+    ) -> SeedLinkResult<()> {
+        let protocol_version = self.protocol_version();
+
+        let mut recovered: HashMap<String, PrimitiveDateTime> = HashMap::new();
+        for StreamState { sid, end_time, .. } in db.state().await? {
+            let end_time = match end_time {
+                Some(end_time) => end_time.to_offset(time::UtcOffset::UTC),
+                None => continue,
+            };
+
+            let key = format!("{}{}", sid.nslc.net, sid.nslc.sta);
+            if let Some(stream_config) = self.stream_configs.0.get_mut(&key) {
+                if add_select_args {
+                    if protocol_version == 3 {
+                        stream_config.add_select_arg(&util::get_select_arg_v3(&sid));
+                    } else if protocol_version == 4 {
+                        stream_config.add_select_arg(&util::get_select_arg_v4(&sid));
+                    }
+                }
+
+                // XXX(damb): resume from the stream lagging behind most
+                let start_time = PrimitiveDateTime::new(end_time.date(), end_time.time());
+                if let Some(prev_start_time) = recovered.get(&key) {
+                    if start_time > *prev_start_time {
+                        continue;
+                    }
+                }
+                recovered.insert(key, start_time);
+                stream_config.time.replace(start_time);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Directly configures the connection from a `StateDB` and completes handshaking.
-    #[instrument(skip(self))]
+    ///
+    /// Only state information within the namespace of `db` is taken into account.
+    #[cfg(feature = "state-sqlite")]
+    #[instrument(target = "slink::state", skip(self, db), fields(namespace = db.namespace()))]
     pub async fn configure_from_state_db(
         &mut self,
         db: &mut StateDB,
@@ -210,7 +997,21 @@ impl Connection {
         let protocol_version = self.protocol_version();
 
         let mut stream_configs = StreamConfigs::default();
-        for (sid, seq_num) in db.state().await? {
+        for StreamState {
+            sid,
+            seq_num,
+            protocol_version: stored_protocol_version,
+            ..
+        } in db.state().await?
+        {
+            if stored_protocol_version != protocol_version {
+                warn!(
+                    "ignoring state of {} (sequence number refers to protocol version v{}, negotiated v{})",
+                    sid, stored_protocol_version, protocol_version
+                );
+                continue;
+            }
+
             let seq_num = {
                 let seq_num = format!("{:x}", seq_num);
                 if let Some(prev_seq_num) = stream_configs.seq_num(&sid.nslc.net, &sid.nslc.sta) {
@@ -227,6 +1028,8 @@ impl Connection {
             let select_arg = {
                 if protocol_version == 3 {
                     Some(util::get_select_arg_v3(&sid))
+                } else if protocol_version == 4 {
+                    Some(util::get_select_arg_v4(&sid))
                 } else {
                     None
                 }
@@ -241,9 +1044,9 @@ impl Connection {
             )?;
         }
 
-        let stream_configs: Vec<StreamConfig> = self.stream_configs.0.values().cloned().collect();
+        let stream_configs: Vec<StreamConfig> = stream_configs.0.values().cloned().collect();
 
-        match &mut self.con {
+        let rv = match &mut self.con {
             ActualSeedLinkConnection::V3(con) => {
                 let v3_data_transfer_mode = match data_transfer_mode {
                     DataTransferMode::RealTime => SeedLinkDataTransferModeV3::RealTime,
@@ -253,33 +1056,233 @@ impl Connection {
                 con.configure(&stream_configs, &v3_data_transfer_mode, pipelining)
                     .await
             }
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => {
+                let v4_data_transfer_mode = match data_transfer_mode {
+                    DataTransferMode::RealTime => SeedLinkDataTransferModeV4::RealTime,
+                    DataTransferMode::DialUp => SeedLinkDataTransferModeV4::DialUp,
+                };
+
+                con.configure(&stream_configs, &v4_data_transfer_mode, pipelining)
+                    .await
+            }
+        };
+        if rv.is_err() {
+            metrics::negotiation_failed();
         }
+
+        rv
+    }
+
+    /// Configures the connection with the provided stream specific data. Data of the station is
+    /// requested for the time window from `start_time` to `end_time` regardless of the mode the
+    /// connection is configured with (see [`Connection::configure`]).
+    ///
+    /// This allows to request different historical time windows per station.
+    pub fn add_stream_with_time_window(
+        &mut self,
+        net: &str,
+        sta: &str,
+        select_arg: &Option<String>,
+        seq_num: &Option<String>,
+        start_time: PrimitiveDateTime,
+        end_time: PrimitiveDateTime,
+    ) -> SeedLinkResult<()> {
+        if end_time <= start_time {
+            return Err(SeedLinkError::InvalidClientConfig(format!(
+                "invalid time window (station: {}_{}): end time must be after start time",
+                net, sta
+            )));
+        }
+
+        self.stream_configs
+            .add_stream(net, sta, select_arg, seq_num, &Some(start_time))?;
+        self.stream_configs
+            .set_time_window(net, sta, start_time, end_time);
+        Ok(())
+    }
+
+    /// Configures the connection with the stream configuration `stream_config`. Selectors of
+    /// stations already configured are merged.
+    pub fn add_stream_config(&mut self, stream_config: StreamConfig) -> SeedLinkResult<()> {
+        if let (Some(start_time), Some(end_time)) = stream_config.time_window() {
+            if end_time <= start_time {
+                return Err(SeedLinkError::InvalidClientConfig(format!(
+                    "invalid time window (station: {}_{}): end time must be after start time",
+                    stream_config.network, stream_config.station
+                )));
+            }
+        }
+
+        self.stream_configs.add_stream_config(stream_config);
+        Ok(())
     }
 
     /// Configures the connection and completes handshaking.
-    #[instrument(skip(self))]
+    ///
+    /// `data_transfer_mode` applies to the streams without a station specific data transfer mode
+    /// (see [`Connection::add_stream_with_mode`]).
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn configure(
         &mut self,
         data_transfer_mode: DataTransferMode,
         end_time: Option<PrimitiveDateTime>,
         pipelining: bool,
     ) -> SeedLinkResult<()> {
+        if let Some(end_time) = end_time {
+            return self
+                .configure_time_window(end_time, false, pipelining)
+                .await;
+        }
+        self.validate_inventory().await?;
+
         let stream_configs: Vec<StreamConfig> = self.stream_configs.0.values().cloned().collect();
 
-        match &mut self.con {
+        let rv = match &mut self.con {
+            ActualSeedLinkConnection::V3(con) => {
+                let v3_data_transfer_mode = match data_transfer_mode {
+                    DataTransferMode::RealTime => SeedLinkDataTransferModeV3::RealTime,
+                    DataTransferMode::DialUp => SeedLinkDataTransferModeV3::DialUp,
+                };
+                con.configure(&stream_configs, &v3_data_transfer_mode, pipelining)
+                    .await
+            }
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => {
+                let v4_data_transfer_mode = match data_transfer_mode {
+                    DataTransferMode::RealTime => SeedLinkDataTransferModeV4::RealTime,
+                    DataTransferMode::DialUp => SeedLinkDataTransferModeV4::DialUp,
+                };
+                con.configure(&stream_configs, &v4_data_transfer_mode, pipelining)
+                    .await
+            }
+        };
+        if rv.is_err() {
+            metrics::negotiation_failed();
+        }
+
+        rv
+    }
+
+    /// Configures the connection in time window mode, i.e. data is requested until `end_time`,
+    /// and completes handshaking.
+    ///
+    /// If `wait_for_window_completion` is `true` and the time window extends beyond the present,
+    /// the connection is configured in real-time mode instead and the packet stream terminates
+    /// once the time window is closed. Otherwise, the server terminates the connection after
+    /// having transferred the data available.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn configure_time_window(
+        &mut self,
+        end_time: PrimitiveDateTime,
+        wait_for_window_completion: bool,
+        pipelining: bool,
+    ) -> SeedLinkResult<()> {
+        self.validate_inventory().await?;
+        let stream_configs: Vec<StreamConfig> = self.stream_configs.0.values().cloned().collect();
+
+        let wait = wait_for_window_completion && end_time.assume_utc() > OffsetDateTime::now_utc();
+        if wait {
+            debug!("time window not yet closed: waiting for window completion in real-time mode");
+            self.window_end = Some(end_time);
+        }
+
+        let rv = match &mut self.con {
             ActualSeedLinkConnection::V3(con) => {
-                let v3_data_transfer_mode;
-                if let Some(end_time) = end_time {
-                    v3_data_transfer_mode = SeedLinkDataTransferModeV3::TimeWindow(end_time);
+                let v3_data_transfer_mode = if wait {
+                    SeedLinkDataTransferModeV3::RealTime
                 } else {
-                    v3_data_transfer_mode = match data_transfer_mode {
-                        DataTransferMode::RealTime => SeedLinkDataTransferModeV3::RealTime,
-                        DataTransferMode::DialUp => SeedLinkDataTransferModeV3::DialUp,
-                    };
-                }
+                    SeedLinkDataTransferModeV3::TimeWindow(end_time)
+                };
                 con.configure(&stream_configs, &v3_data_transfer_mode, pipelining)
                     .await
             }
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => {
+                let v4_data_transfer_mode = if wait {
+                    SeedLinkDataTransferModeV4::RealTime
+                } else {
+                    SeedLinkDataTransferModeV4::TimeWindow(end_time)
+                };
+                con.configure(&stream_configs, &v4_data_transfer_mode, pipelining)
+                    .await
+            }
+        };
+        if rv.is_err() {
+            metrics::negotiation_failed();
+        }
+
+        rv
+    }
+
+    /// Validates the stations configured against the server's inventory (see
+    /// [`SeedLinkConnectionInfo::inventory_validation`]).
+    async fn validate_inventory(&mut self) -> SeedLinkResult<()> {
+        if self.inventory_validation == InventoryValidation::Disabled
+            || self.stream_configs.0.is_empty()
+        {
+            return Ok(());
+        }
+
+        let inventory = self.request_station_info().await?;
+        self.missing_stations = self
+            .stream_configs
+            .streams()
+            .into_iter()
+            .map(|(net, sta, _)| format!("{}_{}", net, sta))
+            .filter(|station_id| !inventory.contains_match(station_id))
+            .collect();
+        if self.missing_stations.is_empty() {
+            return Ok(());
+        }
+
+        let missing = self.missing_stations.join(", ");
+        match self.inventory_validation {
+            InventoryValidation::Fail => Err(SeedLinkError::InvalidClientConfig(format!(
+                "stations absent from the server's inventory: {}",
+                missing
+            ))),
+            _ => {
+                warn!("stations absent from the server's inventory: {}", missing);
+                Ok(())
+            }
+        }
+    }
+
+    /// Authenticates by means of the JSON Web Token (JWT) `token`.
+    ///
+    /// Note that authentication is supported by SeedLink `v4` connections, only. Connections are
+    /// authenticated automatically if [`SeedLinkConnectionInfo::token`] is configured.
+    #[instrument(skip_all)]
+    pub async fn authenticate_token(&mut self, token: &str) -> SeedLinkResult<()> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(_) => Err(SeedLinkError::UnsupportedCommand(
+                "authentication not supported by seedlink protocol version v3".to_string(),
+            )),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => {
+                con.authenticate(AuthCmdMethodV4::JWT(token.to_string()))
+                    .await
+            }
+        }
+    }
+
+    /// Identifies the client by means of the user agent information `user_agent`.
+    ///
+    /// Note that the `USERAGENT` command is supported by SeedLink `v4` connections, only.
+    /// Connections identify automatically during handshaking (see
+    /// [`SeedLinkConnectionInfo::user_agent`]).
+    #[instrument(skip(self))]
+    pub async fn send_user_agent(
+        &mut self,
+        user_agent: &[UserAgentCmdInfoV4],
+    ) -> SeedLinkResult<()> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(_) => Err(SeedLinkError::UnsupportedCommand(
+                "useragent not supported by seedlink protocol version v3".to_string(),
+            )),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.send_user_agent(user_agent).await,
         }
     }
 
@@ -293,16 +1296,46 @@ impl Connection {
                 let (first_resp_line, second_resp_line) = con.say_hello_raw().await?;
                 rv = vec![first_resp_line, second_resp_line];
             }
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => {
+                let (first_resp_line, second_resp_line) = con.say_hello_raw().await?;
+                rv = vec![first_resp_line, second_resp_line];
+            }
         }
 
         Ok(rv)
     }
 
+    /// Requests the capabilities of the SeedLink server.
+    ///
+    /// The capabilities advertised in response to `HELLO` are complemented by the capabilities
+    /// listed in response to `INFO CAPABILITIES`. Note that SeedLink `v4` servers do not list
+    /// capabilities in response to `INFO CAPABILITIES`, i.e. the capabilities implied by the
+    /// protocol version are assumed.
+    #[instrument(skip(self))]
+    pub async fn capabilities(&mut self) -> SeedLinkResult<Capabilities> {
+        let capabilities = self.capabilities.clone();
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(con) => {
+                let capabilities_xml = con.request_capability_info_raw().await?;
+                capabilities.merge_xml_v3(&capabilities_xml)
+            }
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => {
+                // TODO(damb): merge the capabilities listed, once specified
+                con.request_capabilities_info().await?;
+                Ok(capabilities.with_v4_defaults())
+            }
+        }
+    }
+
     /// Requests raw id information from the SeedLink server.
     #[instrument(skip(self))]
     pub async fn request_id_info_raw(&mut self) -> SeedLinkResult<String> {
         match &mut self.con {
             ActualSeedLinkConnection::V3(con) => con.request_id_info_raw().await,
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_id_info_raw().await,
         }
     }
 
@@ -311,6 +1344,8 @@ impl Connection {
     pub async fn request_station_info_raw(&mut self) -> SeedLinkResult<String> {
         match &mut self.con {
             ActualSeedLinkConnection::V3(con) => con.request_station_info_raw().await,
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_station_info_raw().await,
         }
     }
 
@@ -319,6 +1354,8 @@ impl Connection {
     pub async fn request_stream_info_raw(&mut self) -> SeedLinkResult<String> {
         match &mut self.con {
             ActualSeedLinkConnection::V3(con) => con.request_stream_info_raw().await,
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_stream_info_raw().await,
         }
     }
 
@@ -327,172 +1364,1157 @@ impl Connection {
     pub async fn request_connection_info_raw(&mut self) -> SeedLinkResult<String> {
         match &mut self.con {
             ActualSeedLinkConnection::V3(con) => con.request_connection_info_raw().await,
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_connection_info_raw().await,
         }
     }
 
-    /// Requests stream information from the SeedLink server.
+    /// Requests the connection information from the SeedLink server.
+    ///
+    /// Note that `INFO CONNECTIONS` XML responses are provided by SeedLink `v3` connections, only
+    /// (see [`Connection::request_connections_info`] for SeedLink `v4` connections).
     #[instrument(skip(self))]
-    pub async fn request_station_info(&mut self) -> SeedLinkResult<Inventory> {
+    pub async fn request_connection_info(&mut self) -> SeedLinkResult<ConnectionsV3> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(con) => con.request_connection_info().await,
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(_) => Err(SeedLinkError::UnsupportedCommand(
+                "info connections xml not supported by seedlink protocol version v4".to_string(),
+            )),
+        }
+    }
+
+    /// Requests raw gap information from the SeedLink server.
+    ///
+    /// Note that the `INFO GAPS` request is supported by SeedLink `v3` connections, only.
+    #[instrument(skip(self))]
+    pub async fn request_gap_info_raw(&mut self) -> SeedLinkResult<String> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(con) => con.request_gap_info_raw().await,
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(_) => Err(SeedLinkError::UnsupportedCommand(
+                "info gaps not supported by seedlink protocol version v4".to_string(),
+            )),
+        }
+    }
+
+    /// Requests the gap information from the SeedLink server, i.e. the gaps in the data of the
+    /// streams buffered (e.g. in order to backfill the gaps from other sources).
+    ///
+    /// Note that the `INFO GAPS` request is supported by SeedLink `v3` connections, only.
+    #[instrument(skip(self))]
+    pub async fn request_gap_info(&mut self) -> SeedLinkResult<GapsV3> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(con) => con.request_gap_info().await,
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(_) => Err(SeedLinkError::UnsupportedCommand(
+                "info gaps not supported by seedlink protocol version v4".to_string(),
+            )),
+        }
+    }
+
+    /// Requests raw format information from the SeedLink server.
+    ///
+    /// Note that the `INFO FORMATS` request is supported by SeedLink `v4` connections, only.
+    #[instrument(skip(self))]
+    pub async fn request_formats_info_raw(&mut self) -> SeedLinkResult<String> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(_) => Err(SeedLinkError::UnsupportedCommand(
+                "info formats not supported by seedlink protocol version v3".to_string(),
+            )),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_formats_info_raw().await,
+        }
+    }
+
+    /// Requests the formats supported by the SeedLink server.
+    ///
+    /// Note that the `INFO FORMATS` request is supported by SeedLink `v4` connections, only.
+    #[instrument(skip(self))]
+    pub async fn request_formats(&mut self) -> SeedLinkResult<Vec<Format>> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(_) => Err(SeedLinkError::UnsupportedCommand(
+                "info formats not supported by seedlink protocol version v3".to_string(),
+            )),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_formats().await,
+        }
+    }
+
+    /// Requests the id information from the SeedLink server.
+    ///
+    /// Note that typed `INFO ID` responses are supported by SeedLink `v4` connections, only.
+    #[instrument(skip(self))]
+    pub async fn request_id_info(&mut self) -> SeedLinkResult<IdInfoV4> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(_) => Err(SeedLinkError::UnsupportedCommand(
+                "typed info id not supported by seedlink protocol version v3".to_string(),
+            )),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_id_info().await,
+        }
+    }
+
+    /// Requests the format information from the SeedLink server, i.e. the formats and subformats
+    /// supported, e.g. in order to decide whether to request miniSEED 2 or 3 before configuring
+    /// the connection.
+    ///
+    /// Note that the `INFO FORMATS` request is supported by SeedLink `v4` connections, only.
+    #[instrument(skip(self))]
+    pub async fn request_formats_info(&mut self) -> SeedLinkResult<FormatsInfoV4> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(_) => Err(SeedLinkError::UnsupportedCommand(
+                "info formats not supported by seedlink protocol version v3".to_string(),
+            )),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_formats_info().await,
+        }
+    }
+
+    /// Requests the station information from the SeedLink server.
+    ///
+    /// Note that typed `INFO STATIONS` responses are supported by SeedLink `v4` connections, only.
+    #[instrument(skip(self))]
+    pub async fn request_stations_info(&mut self) -> SeedLinkResult<StationsInfoV4> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(_) => Err(SeedLinkError::UnsupportedCommand(
+                "typed info stations not supported by seedlink protocol version v3".to_string(),
+            )),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_stations_info().await,
+        }
+    }
+
+    /// Requests the stream information from the SeedLink server.
+    ///
+    /// Note that typed `INFO STREAMS` responses are supported by SeedLink `v4` connections, only.
+    #[instrument(skip(self))]
+    pub async fn request_streams_info(&mut self) -> SeedLinkResult<StreamsInfoV4> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(_) => Err(SeedLinkError::UnsupportedCommand(
+                "typed info streams not supported by seedlink protocol version v3".to_string(),
+            )),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_streams_info().await,
+        }
+    }
+
+    /// Requests the connection information from the SeedLink server.
+    ///
+    /// Note that typed `INFO CONNECTIONS` responses are supported by SeedLink `v4` connections, only.
+    #[instrument(skip(self))]
+    pub async fn request_connections_info(&mut self) -> SeedLinkResult<ConnectionsInfoV4> {
+        match &mut self.con {
+            ActualSeedLinkConnection::V3(_) => Err(SeedLinkError::UnsupportedCommand(
+                "typed info connections not supported by seedlink protocol version v3".to_string(),
+            )),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.request_connections_info().await,
+        }
+    }
+
+    /// Requests the inventory from the SeedLink server.
+    ///
+    /// The inventory is filtered by means of `station_pattern` (i.e. `NET_STA`) and
+    /// `stream_pattern` (i.e. `LOC_B_S_SS`). Patterns may contain the wildcards `*` and `?`. Note
+    /// that SeedLink `v3` servers do not support filtering, i.e. the inventory is filtered on the
+    /// client side.
+    #[instrument(skip(self))]
+    pub async fn request_inventory(
+        &mut self,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+        level: InventoryLevel,
+    ) -> SeedLinkResult<Inventory> {
         match &mut self.con {
             ActualSeedLinkConnection::V3(con) => {
-                con.request_station_info().await.map(|inv_v3| inv_v3.into())
+                con.request_inventory(station_pattern, stream_pattern, level)
+                    .await
+            }
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => {
+                con.request_inventory(station_pattern, stream_pattern, level)
+                    .await
             }
         }
     }
 
-    /// Requests stream information from the SeedLink server.
+    /// Requests the inventory from the SeedLink server, returning an iterator parsing the
+    /// stations lazily.
+    ///
+    /// In contrast to [`Connection::request_inventory`] the inventory is not materialized at
+    /// once, i.e. callers may stop early (e.g. when looking for a single station).
     #[instrument(skip(self))]
-    pub async fn request_stream_info(&mut self) -> SeedLinkResult<Inventory> {
+    pub async fn request_stations(
+        &mut self,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+        level: InventoryLevel,
+    ) -> SeedLinkResult<Stations> {
         match &mut self.con {
             ActualSeedLinkConnection::V3(con) => {
-                con.request_stream_info().await.map(|inv_v3| inv_v3.into())
+                con.request_stations(station_pattern, stream_pattern, level)
+                    .await
+            }
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => {
+                con.request_stations(station_pattern, stream_pattern, level)
+                    .await
             }
         }
     }
 
+    /// Requests station information from the SeedLink server.
+    ///
+    /// Shorthand for [`Connection::request_inventory`] without any patterns.
+    #[instrument(skip(self))]
+    pub async fn request_station_info(&mut self) -> SeedLinkResult<Inventory> {
+        self.request_inventory(None, None, InventoryLevel::Station)
+            .await
+    }
+
+    /// Requests stream information from the SeedLink server.
+    ///
+    /// Shorthand for [`Connection::request_inventory`] without any patterns.
+    #[instrument(skip(self))]
+    pub async fn request_stream_info(&mut self) -> SeedLinkResult<Inventory> {
+        self.request_inventory(None, None, InventoryLevel::Stream)
+            .await
+    }
+
     // TODO(damb): provide an example (i.e. code snippet)
     /// Returns a stream producing SeedLink version dependent packets asynchronously.
     ///
     /// If `keep_alive_interval` is not `None` the stream sents keepalive packets to the remote
     /// peer SeedLink server backed by the specified `Duration`. Panics if the `Duration` is zero.
     ///
-    /// Note that keepalive packets are returned, too.
+    /// Note that keepalive packets are returned, too. If gzip compressed `INFO` responses were
+    /// negotiated (SeedLink v3, `gzip` feature) their payload is compressed.
+    ///
+    /// The stream terminates with a [`StreamItem::End`] item indicating the reason of the
+    /// termination. E.g. if the connection was configured to wait for the completion of a time
+    /// window (see [`Connection::configure_time_window`]) the stream terminates with
+    /// [`StreamEnd::TimeWindowDone`] once the time window is closed.
+    ///
+    /// Keepalive intervals are driven by `tokio::time`, i.e. idle behavior may be simulated with
+    /// virtual time (see `tokio::time::pause`). Unacknowledged keepalives terminate the stream if
+    /// configured (see [`Connection::set_keep_alive_timeout`] and
+    /// [`Connection::set_max_unanswered_keep_alives`]).
     /// ```
-    pub fn packets(
-        self,
-        keep_alive_interval: Option<Duration>,
-    ) -> impl TryStream<Item = SeedLinkResult<SeedLinkPacket>> {
-        let keep_alive_stream: Arc<Mutex<Pin<Box<dyn Stream<Item = tokio_time::Instant>>>>>;
+    pub fn packets(self, keep_alive_interval: Option<Duration>) -> PacketStream {
+        let keep_alive: Pin<Box<dyn Stream<Item = tokio_time::Instant> + Send>>;
         if let Some(duration) = keep_alive_interval {
             assert!(
                 !duration.is_zero(),
                 "keep_alive_interval must be greater than zero"
             );
             let interval = tokio_time::interval(duration);
-            keep_alive_stream = Arc::new(Mutex::new(Box::pin(IntervalStream::new(interval))));
+            keep_alive = Box::pin(IntervalStream::new(interval));
         } else {
-            keep_alive_stream = Arc::new(Mutex::new(Box::pin(stream::pending::<
-                tokio_time::Instant,
-            >())));
-        }
-
-        let inner_con = match self.con {
-            ActualSeedLinkConnection::V3(con) => con,
-        };
-        let inner_con = Arc::new(Mutex::new(inner_con));
-
-        stream::try_unfold((), move |_| {
-            let cloned_inner_con = inner_con.clone();
-            let cloned_keep_alive = keep_alive_stream.clone();
-            async move {
-                loop {
-                    let mut inner_con = cloned_inner_con.lock().await;
-                    let mut keep_alive = cloned_keep_alive.lock().await;
-                    tokio::select! {
-                        frame = inner_con.get_framed_connection_mut().read_frame() => match frame? {
-                            Frame::GenericDataPacket(buf) => {
-                                return Ok(Some((SeedLinkPacket::V3(SeedLinkPacketV3::GenericData(SeedLinkGenericDataPacketV3::new(buf))), ())));
-                            }
-                            Frame::InfoPacket(buf) => {
-                                inner_con.get_framed_connection_mut().ack_keep_alive();
-                                return Ok(Some((SeedLinkPacket::V3(SeedLinkPacketV3::Info(SeedLinkInfoPacketV3::new(buf))), ())));
-                            }
-                            Frame::End => {
-                                inner_con.shutdown().await?;
-                                return Ok(None)
-                            },
-                            frame => {
-                                return Err(io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    format!("unexpected frame received: {:?}", frame),
-                                )
-                                .into());
-                            }
-                        },
-                        _  = keep_alive.next() => {
-                            inner_con.get_framed_connection_mut().try_send_keep_alive().await?;
-                        },
-                    }
-                }
+            keep_alive = Box::pin(stream::pending::<tokio_time::Instant>());
+        }
+
+        let window_end: Pin<Box<dyn Stream<Item = ()> + Send>>;
+        if let Some(t) = self.window_end {
+            let remaining = t.assume_utc() - OffsetDateTime::now_utc();
+            let sleep = tokio_time::sleep(remaining.try_into().unwrap_or(Duration::ZERO));
+            window_end = Box::pin(stream::once(sleep));
+        } else {
+            window_end = Box::pin(stream::pending::<()>());
+        }
+
+        let control = ControlState {
+            recv: self.control.map(|(_, recv)| recv),
+            queue: VecDeque::new(),
+            pending: None,
+        };
+
+        let keep_alive_check = self.keep_alive_check;
+        let idle_check = IdleCheck::new(self.idle_timeout);
+        let latency_monitor = self.latency_monitor;
+        let warning_observer = self.warnings.into_observer();
+        #[cfg(feature = "v4-client")]
+        let credentials_refresh = self.credentials_refresh;
+        let inner = match self.con {
+            ActualSeedLinkConnection::V3(con) => PacketStreamState {
+                con,
+                keep_alive,
+                window_end,
+                control,
+                keep_alive_check,
+                idle_check,
+                #[cfg(feature = "v4-client")]
+                credentials_refresh: CredentialsRefresh::default(),
             }
-        })
+            .into_stream()
+            .boxed(),
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => PacketStreamState {
+                con,
+                keep_alive,
+                window_end,
+                control,
+                keep_alive_check,
+                idle_check,
+                credentials_refresh,
+            }
+            .into_stream()
+            .boxed(),
+        };
+
+        PacketStream {
+            inner: inner
+                .inspect(observe_latency(latency_monitor))
+                .inspect(observe_warnings(warning_observer))
+                .boxed(),
+        }
+    }
+
+    /// Returns a stream producing the miniSEED records received together with their FDSN source
+    /// identifier and packet sequence number (see [`SeedLinkPacket::to_record`]).
+    ///
+    /// Packets other than data packets (e.g. keepalive packets) are skipped. Records failing to
+    /// decode are yielded as errors. The stream terminates once the packet stream terminates (see
+    /// [`Connection::packets`]), i.e. yielding an error if the data transfer failed.
+    pub fn records(
+        self,
+        keep_alive_interval: Option<Duration>,
+    ) -> impl Stream<Item = SeedLinkResult<(FDSNSourceId, u64, MSRecord)>> {
+        self.packets(keep_alive_interval)
+            .filter_map(|item| {
+                future::ready(match item {
+                    StreamItem::Packet(packet) => packet.to_record(),
+                    StreamItem::End(StreamEnd::Error(err)) => Some(Err(err)),
+                    StreamItem::End(_) => None,
+                })
+            })
+            .boxed_local()
     }
 
     pub async fn shutdown(&mut self) -> SeedLinkResult<()> {
         match &mut self.con {
             ActualSeedLinkConnection::V3(con) => con.shutdown().await,
+            #[cfg(feature = "v4-client")]
+            ActualSeedLinkConnection::V4(con) => con.shutdown().await,
         }
     }
 }
 
-/// This function takes a SeedLink URL string and parses it into a URL
-/// as used by rust-url. This is necessary as the default parser does
-/// not understand how SeedLink URLs function.
-pub fn parse_slink_url(input: &str) -> Option<url::Url> {
-    match url::Url::parse(input) {
-        Ok(result) => match result.scheme() {
-            "slink" | "slinkv3" => Some(result),
-            _ => None,
-        },
-        Err(_) => None,
-    }
+/// Stream producing the packets received by a [`Connection`] (see [`Connection::packets`]).
+///
+/// The stream owns the connection, i.e. the connection is dropped together with the stream.
+#[must_use = "streams do nothing unless polled"]
+pub struct PacketStream {
+    inner: BoxStream<'static, StreamItem>,
 }
 
-/// Defines the connection address.
-#[derive(Clone, Debug)]
-pub enum ConnectionAddr {
-    /// Format for this is `(host, port)`.
-    Tcp(String, u16),
-    ///// Format for this is `(host, port)`.
-    //TcpTls {
-    //    /// Hostname
-    //    host: String,
-    //    /// Port
-    //    port: u16,
-    //    /// Disable hostname verification when connecting.
-    //    ///
-    //    /// # Warning
-    //    ///
-    //    /// You should think very carefully before you use this method. If hostname
-    //    /// verification is not used, any valid certificate for any site will be
-    //    /// trusted for use from any other. This introduces a significant
-    //    /// vulnerability to man-in-the-middle attacks.
-    //    insecure: bool,
-    //},
-    ///// Format for this is the path to the unix socket.
-    //Unix(PathBuf),
-}
+impl Stream for PacketStream {
+    type Item = StreamItem;
 
-impl fmt::Display for ConnectionAddr {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // Cluster::get_connection_info depends on the return value from this function
-        match *self {
-            ConnectionAddr::Tcp(ref host, port) => write!(f, "{host}:{port}"),
-            // ConnectionAddr::TcpTls { ref host, port, .. } => write!(f, "{host}:{port}"),
-            // ConnectionAddr::Unix(ref path) => write!(f, "{}", path.display()),
-        }
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
 }
 
-/// Holds the connection information that SeedLink should use for connecting.
-#[derive(Clone, Debug)]
-pub struct ConnectionInfo {
-    /// A connection address for where to connect to.
-    pub addr: ConnectionAddr,
+impl fmt::Debug for PacketStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketStream").finish_non_exhaustive()
+    }
+}
 
-    /// SeedLink specific connection information.
-    pub slink: SeedLinkConnectionInfo,
+/// State owned by a [`PacketStream`] while streaming packets from the connection `con`.
+struct PacketStreamState<C> {
+    con: C,
+    keep_alive: Pin<Box<dyn Stream<Item = tokio_time::Instant> + Send>>,
+    window_end: Pin<Box<dyn Stream<Item = ()> + Send>>,
+    control: ControlState,
+    keep_alive_check: KeepAliveCheck,
+    idle_check: IdleCheck,
+    /// Refresh of expiring credentials (SeedLink `v4` only).
+    #[cfg(feature = "v4-client")]
+    credentials_refresh: CredentialsRefresh,
 }
 
-/// SeedLink specific/connection independent information used to establish a connection to redis.
-#[derive(Clone, Debug, Default)]
-pub struct SeedLinkConnectionInfo {
-    /// The SeedLink protocol to be used.
-    pub protocol_version: Option<u8>,
-    /// Optionally a username that should be used for connection.
-    pub username: Option<String>,
+impl PacketStreamState<SeedLinkConnectionV3> {
+    fn into_stream(self) -> impl Stream<Item = StreamItem> + Send {
+        stream::unfold(Some(self), |state| async move {
+            let mut state = state?;
+            let res = state.next_item().await;
+            let last_message = state
+                .con
+                .get_framed_connection()
+                .last_message()
+                .map(ToString::to_string);
+            let item = to_stream_item(res, last_message);
+            let state = (!matches!(item, StreamItem::End(_))).then_some(state);
+
+            Some((item, state))
+        })
+    }
+
+    async fn next_item(&mut self) -> SeedLinkResult<StreamItem> {
+        let Self {
+            con: inner_con,
+            keep_alive,
+            window_end,
+            control,
+            keep_alive_check,
+            idle_check,
+            ..
+        } = self;
+
+        loop {
+            let keep_alive_sent = inner_con.get_framed_connection().keep_alive_sent();
+            let last_frame = inner_con.get_framed_connection().last_frame();
+            tokio::select! {
+                frame = inner_con.get_framed_connection_mut().read_frame() => match frame? {
+                    Frame::GenericDataPacket(buf) => {
+                        return Ok(StreamItem::Packet(SeedLinkPacket::V3(SeedLinkPacketV3::GenericData(SeedLinkGenericDataPacketV3::new(buf)))));
+                    }
+                    Frame::InfoPacket(buf) => {
+                        let packet = SeedLinkInfoPacketV3::new(buf);
+                        if let Some((send, mut payload)) = control.pending.take() {
+                            // demultiplex the response to a control request
+                            let res = if packet.is_err() {
+                                Err(SeedLinkError::UnsupportedCommand(
+                                    "INFO level request is not supported.".to_string(),
+                                ))
+                            } else {
+                                packet.payload_bytes().map(|p| payload.extend(p))
+                            };
+
+                            match res {
+                                Ok(()) if !packet.is_last() => {
+                                    control.pending = Some((send, payload));
+                                }
+                                res => {
+                                    inner_con.get_framed_connection_mut().ack_keep_alive();
+                                    let res = res.and_then(|_| inner_con.get_framed_connection().decode_info_payload(payload));
+                                    let _ = send.send(res);
+                                    dispatch_control_request(inner_con, control).await?;
+                                }
+                            }
+                            continue;
+                        }
+
+                        inner_con.get_framed_connection_mut().ack_keep_alive();
+                        dispatch_control_request(inner_con, control).await?;
+                        return Ok(StreamItem::Packet(SeedLinkPacket::V3(SeedLinkPacketV3::Info(packet))));
+                    }
+                    Frame::End => {
+                        inner_con.shutdown().await?;
+                        return Ok(StreamItem::End(StreamEnd::Completed))
+                    },
+                    Frame::Line(_) | Frame::Error(_) => {
+                        // XXX(damb): servers may respond with an error before disconnecting
+                        warn!(
+                            "server message received: '{}'",
+                            inner_con.get_framed_connection().last_message().unwrap_or_default()
+                        );
+                    },
+                    frame => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("unexpected frame received: {:?}", frame),
+                        )
+                        .into());
+                    }
+                },
+                _  = keep_alive.next() => {
+                    keep_alive_check.check_unanswered(inner_con.get_framed_connection().unanswered_keep_alives())?;
+                    inner_con.get_framed_connection_mut().try_send_keep_alive().await?;
+                },
+                _ = keep_alive_check.expired(keep_alive_sent) => {
+                    return Err(keep_alive_check.timeout_error());
+                },
+                _ = idle_check.expired(last_frame) => {
+                    return Err(idle_check.timeout_error());
+                },
+                Some(_) = window_end.next() => {
+                    debug!("time window closed");
+                    inner_con.shutdown().await?;
+                    return Ok(StreamItem::End(StreamEnd::TimeWindowDone))
+                },
+                Some(req) = control.next_request() => match req {
+                    ControlRequest::Shutdown(send) => {
+                        control.close();
+                        let res = inner_con.shutdown().await;
+                        return shutdown_requested(res, send);
+                    }
+                    req => {
+                        control.queue.push_back(req);
+                        dispatch_control_request(inner_con, control).await?;
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(feature = "v4-client")]
+impl PacketStreamState<SeedLinkConnectionV4> {
+    fn into_stream(self) -> impl Stream<Item = StreamItem> + Send {
+        stream::unfold(Some(self), |state| async move {
+            let mut state = state?;
+            let res = state.next_item().await;
+            let last_message = state
+                .con
+                .get_framed_connection_mut()
+                .last_message()
+                .map(ToString::to_string);
+            let item = to_stream_item(res, last_message);
+            let state = (!matches!(item, StreamItem::End(_))).then_some(state);
+
+            Some((item, state))
+        })
+    }
+
+    async fn next_item(&mut self) -> SeedLinkResult<StreamItem> {
+        let Self {
+            con: inner_con,
+            keep_alive,
+            window_end,
+            control,
+            keep_alive_check,
+            idle_check,
+            credentials_refresh,
+        } = self;
+
+        loop {
+            let keep_alive_sent = inner_con.get_framed_connection().keep_alive_sent();
+            let last_frame = inner_con.get_framed_connection().last_frame();
+            tokio::select! {
+                frame = inner_con.get_framed_connection_mut().read_frame() => match frame? {
+                    FrameV4::Packet(packet) => {
+                        let packet = SeedLinkPacket::V4(packet);
+                        if packet.is_info() {
+                            inner_con.get_framed_connection_mut().ack_keep_alive();
+                            if let (Some((send, _)), SeedLinkPacket::V4(info)) = (control.pending.take(), &packet) {
+                                // demultiplex the response to a control request
+                                let _ = send.send(SeedLinkConnectionV4::decode_info_packet(info));
+                                dispatch_control_request_v4(inner_con, control).await?;
+                                continue;
+                            }
+                            dispatch_control_request_v4(inner_con, control).await?;
+                        }
+                        return Ok(StreamItem::Packet(packet));
+                    }
+                    FrameV4::Ok if credentials_refresh.is_pending() => {
+                        debug!("response: auth is OK (credentials refreshed)");
+                        credentials_refresh.ack();
+                    }
+                    // XXX(damb): other errors do not answer the re-authentication
+                    FrameV4::Error(err)
+                        if credentials_refresh.is_pending()
+                            && err.code == ErrorCodeV4::AuthenticationFailed =>
+                    {
+                        return Err(SeedLinkError::AuthenticationFailed(err));
+                    }
+                    FrameV4::Error(err) if control.pending.is_some() => {
+                        // demultiplex the error response to a control request
+                        inner_con.get_framed_connection_mut().ack_keep_alive();
+                        if let Some((send, _)) = control.pending.take() {
+                            let _ = send.send(Err(err.into()));
+                        }
+                        dispatch_control_request_v4(inner_con, control).await?;
+                    }
+                    FrameV4::End => {
+                        inner_con.shutdown().await?;
+                        return Ok(StreamItem::End(StreamEnd::Completed))
+                    }
+                    FrameV4::Lines(_) | FrameV4::Error(_) => {
+                        // XXX(damb): servers may respond with an error before disconnecting
+                        warn!(
+                            "server message received: '{}'",
+                            inner_con.get_framed_connection_mut().last_message().unwrap_or_default()
+                        );
+                    }
+                    frame => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("unexpected frame received: {:?}", frame),
+                        )
+                        .into());
+                    }
+                },
+                _  = keep_alive.next() => {
+                    keep_alive_check.check_unanswered(inner_con.get_framed_connection().unanswered_keep_alives())?;
+                    inner_con.get_framed_connection_mut().try_send_keep_alive().await?;
+                },
+                _ = credentials_refresh.due() => {
+                    let auth = credentials_refresh.refresh().await?;
+                    inner_con.get_framed_connection_mut().send_auth(auth.into()).await?;
+                },
+                _ = keep_alive_check.expired(keep_alive_sent) => {
+                    return Err(keep_alive_check.timeout_error());
+                },
+                _ = idle_check.expired(last_frame) => {
+                    return Err(idle_check.timeout_error());
+                },
+                Some(_) = window_end.next() => {
+                    debug!("time window closed");
+                    inner_con.shutdown().await?;
+                    return Ok(StreamItem::End(StreamEnd::TimeWindowDone))
+                },
+                Some(req) = control.next_request() => match req {
+                    ControlRequest::Shutdown(send) => {
+                        control.close();
+                        let res = inner_con.shutdown().await;
+                        return shutdown_requested(res, send);
+                    }
+                    req => {
+                        control.queue.push_back(req);
+                        dispatch_control_request_v4(inner_con, control).await?;
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Detection of dead connections by means of unacknowledged keepalives.
+#[derive(Clone, Copy, Debug, Default)]
+struct KeepAliveCheck {
+    timeout: Option<Duration>,
+    max_unanswered: Option<u32>,
+}
+
+impl KeepAliveCheck {
+    /// Completes once the keepalive sent at `sent` (if any) was not acknowledged in time.
+    async fn expired(&self, sent: Option<tokio_time::Instant>) {
+        expire(sent, self.timeout).await
+    }
+
+    /// Fails if the maximum number of keepalives are unanswered, i.e. before sending yet another
+    /// keepalive.
+    fn check_unanswered(&self, unanswered: u32) -> SeedLinkResult<()> {
+        match self.max_unanswered {
+            Some(max) if unanswered >= max => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} consecutive keepalives not acknowledged", max),
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn timeout_error(&self) -> SeedLinkError {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "keepalive not acknowledged within {:?}",
+                self.timeout.unwrap_or_default()
+            ),
+        )
+        .into()
+    }
+}
+
+/// Detection of idle connections, i.e. connections not receiving any data.
+#[derive(Clone, Copy, Debug)]
+struct IdleCheck {
+    timeout: Option<Duration>,
+    /// Time the packet stream was created.
+    started: tokio_time::Instant,
+}
+
+impl IdleCheck {
+    fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            started: tokio_time::Instant::now(),
+        }
+    }
+
+    /// Completes once no data was received in time. `last_frame` is the time the last frame was
+    /// received, if any.
+    async fn expired(&self, last_frame: Option<tokio_time::Instant>) {
+        let since = last_frame.map_or(self.started, |t| t.max(self.started));
+        expire(Some(since), self.timeout).await
+    }
+
+    fn timeout_error(&self) -> SeedLinkError {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "no data received within {:?}",
+                self.timeout.unwrap_or_default()
+            ),
+        )
+        .into()
+    }
+}
+
+/// Completes `timeout` after `since`. Never completes if either is `None`.
+async fn expire(since: Option<tokio_time::Instant>, timeout: Option<Duration>) {
+    match (since, timeout) {
+        (Some(since), Some(timeout)) => tokio_time::sleep_until(since + timeout).await,
+        _ => future::pending().await,
+    }
+}
+
+/// Acknowledges the shutdown requested by means of [`ConnectionControl::shutdown`] with the result
+/// `res` of shutting down the connection.
+fn shutdown_requested(
+    res: SeedLinkResult<()>,
+    send: oneshot::Sender<SeedLinkResult<()>>,
+) -> SeedLinkResult<StreamItem> {
+    match res {
+        Ok(()) => {
+            let _ = send.send(Ok(()));
+            Ok(StreamItem::End(StreamEnd::Shutdown))
+        }
+        Err(err) => {
+            let _ = send.send(Err(SeedLinkError::ClientError(err.to_string())));
+            Err(err)
+        }
+    }
+}
+
+/// Maps the result `res` of polling the packet stream to a stream item. `last_message` is the
+/// last response line received, if any.
+fn to_stream_item(res: SeedLinkResult<StreamItem>, last_message: Option<String>) -> StreamItem {
+    match res {
+        Ok(item) => item,
+        Err(SeedLinkError::Io(err)) if err.kind() == io::ErrorKind::BrokenPipe => {
+            debug!("connection closed by server: {}", err);
+            StreamItem::End(StreamEnd::ServerClosed(last_message))
+        }
+        Err(err) => StreamItem::End(StreamEnd::Error(err)),
+    }
+}
+
+/// Item produced by the packet stream (see [`Connection::packets`]).
+#[derive(Debug)]
+pub enum StreamItem {
+    /// A SeedLink packet.
+    Packet(SeedLinkPacket),
+    /// The terminal item of the stream.
+    End(StreamEnd),
+}
+
+/// Enumeration of reasons for the termination of the packet stream.
+#[derive(Debug)]
+pub enum StreamEnd {
+    /// The server completed the data transfer (i.e. sent `END`), e.g. in dial-up mode.
+    Completed,
+    /// The server closed the connection without completing the data transfer. Holds the last
+    /// message (e.g. an error response) received from the server, if any.
+    ServerClosed(Option<String>),
+    /// The time window the connection was waiting for closed (see
+    /// [`Connection::configure_time_window`]).
+    TimeWindowDone,
+    /// The connection was shut down gracefully by means of [`ConnectionControl::shutdown`].
+    Shutdown,
+    /// The data transfer failed.
+    Error(SeedLinkError),
+}
+
+/// Size of the control request channel.
+const CONTROL_CHANNEL_SIZE: usize = 16;
+
+/// Control request issued by means of a [`ConnectionControl`] handle.
+#[derive(Debug)]
+pub(crate) enum ControlRequest {
+    Info(InfoCmdItemV3, oneshot::Sender<SeedLinkResult<String>>),
+    Shutdown(oneshot::Sender<SeedLinkResult<()>>),
+}
+
+/// Handle allowing to control a [`Connection`] while streaming packets.
+///
+/// See also [`Connection::control`].
+#[derive(Clone, Debug)]
+pub struct ConnectionControl {
+    chan: mpsc::Sender<ControlRequest>,
+    stats: Arc<StatsRecorder>,
+    /// The major protocol version of the connection.
+    protocol_version: u8,
+}
+
+impl ConnectionControl {
+    /// Returns the statistics of the connection (see [`Connection::stats`]).
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats.snapshot()
+    }
+
+    /// Requests the raw id information from the SeedLink server, i.e. XML for SeedLink v3 and
+    /// JSON for SeedLink v4 connections.
+    pub async fn request_id_info_raw(&self) -> SeedLinkResult<String> {
+        self.request_info_raw(InfoCmdItemV3::Id).await
+    }
+
+    /// Requests the raw station information from the SeedLink server, i.e. XML for SeedLink v3 and
+    /// JSON for SeedLink v4 connections.
+    pub async fn request_station_info_raw(&self) -> SeedLinkResult<String> {
+        self.request_info_raw(InfoCmdItemV3::Stations).await
+    }
+
+    /// Requests the raw stream information from the SeedLink server, i.e. XML for SeedLink v3 and
+    /// JSON for SeedLink v4 connections.
+    pub async fn request_stream_info_raw(&self) -> SeedLinkResult<String> {
+        self.request_info_raw(InfoCmdItemV3::Streams).await
+    }
+
+    /// Requests the raw connection information from the SeedLink server, i.e. XML for SeedLink v3 and
+    /// JSON for SeedLink v4 connections.
+    pub async fn request_connection_info_raw(&self) -> SeedLinkResult<String> {
+        self.request_info_raw(InfoCmdItemV3::Connections).await
+    }
+
+    /// Requests the inventory from the SeedLink server while streaming (see
+    /// [`Connection::request_inventory`]).
+    ///
+    /// The `INFO` response packets are reassembled within the packet stream (i.e. they are not
+    /// yielded) and the parsed inventory is returned once the response is complete. Note that the
+    /// packet stream must be polled in order to make progress.
+    pub async fn request_inventory(
+        &self,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+        level: InventoryLevel,
+    ) -> SeedLinkResult<Inventory> {
+        // XXX(damb): filtering by stream pattern requires stream information
+        let resp = if level == InventoryLevel::Stream || stream_pattern.is_some() {
+            self.request_stream_info_raw().await?
+        } else {
+            self.request_station_info_raw().await?
+        };
+
+        let stations = match self.protocol_version {
+            #[cfg(feature = "v4-client")]
+            4 => SeedLinkConnectionV4::parse_stations(resp, station_pattern, stream_pattern, level),
+            _ => SeedLinkConnectionV3::parse_stations(resp, station_pattern, stream_pattern, level),
+        };
+        stations.collect()
+    }
+
+    /// Requests station information from the SeedLink server while streaming.
+    ///
+    /// Shorthand for [`ConnectionControl::request_inventory`] without any patterns.
+    pub async fn request_station_info(&self) -> SeedLinkResult<Inventory> {
+        self.request_inventory(None, None, InventoryLevel::Station)
+            .await
+    }
+
+    /// Requests stream information from the SeedLink server while streaming.
+    ///
+    /// Shorthand for [`ConnectionControl::request_inventory`] without any patterns.
+    pub async fn request_stream_info(&self) -> SeedLinkResult<Inventory> {
+        self.request_inventory(None, None, InventoryLevel::Stream)
+            .await
+    }
+
+    /// Requests a graceful shutdown of the connection, i.e. `BYE` is sent and the connection is
+    /// closed. The packet stream terminates with [`StreamEnd::Shutdown`].
+    ///
+    /// Pending `INFO` requests issued by means of the handle fail.
+    pub async fn shutdown(&self) -> SeedLinkResult<()> {
+        let (send, recv) = oneshot::channel();
+        self.chan
+            .send(ControlRequest::Shutdown(send))
+            .await
+            .map_err(|_| control_closed())?;
+
+        recv.await.map_err(|_| control_closed())?
+    }
+
+    async fn request_info_raw(&self, item: InfoCmdItemV3) -> SeedLinkResult<String> {
+        let (send, recv) = oneshot::channel();
+        self.chan
+            .send(ControlRequest::Info(item, send))
+            .await
+            .map_err(|_| control_closed())?;
+
+        recv.await.map_err(|_| control_closed())?
+    }
+}
+
+fn control_closed() -> SeedLinkError {
+    io::Error::new(io::ErrorKind::BrokenPipe, "packet stream closed").into()
+}
+
+/// Returns a function recording the latencies of the packets streamed by means of `monitor`, if
+/// any.
+fn observe_latency(monitor: Option<LatencyMonitor>) -> impl FnMut(&StreamItem) {
+    move |item| {
+        if let Some(ref monitor) = monitor {
+            monitor.observe(item);
+        }
+    }
+}
+
+/// Returns a function checking the packets streamed for non-fatal conditions by means of
+/// `observer`, if any.
+fn observe_warnings(mut observer: Option<WarningObserver>) -> impl FnMut(&StreamItem) {
+    move |item| {
+        if let Some(ref mut observer) = observer {
+            observer.observe(item);
+        }
+    }
+}
+
+/// Returns the error of a connection closed by the remote peer including the last message
+/// received, if any.
+pub(crate) fn disconnected(last_message: Option<&str>) -> SeedLinkError {
+    let msg = match last_message {
+        Some(last_message) => format!("disconnected (last message: '{}')", last_message),
+        None => "disconnected".to_string(),
+    };
+
+    io::Error::new(io::ErrorKind::BrokenPipe, msg).into()
+}
+
+/// State of the control requests issued while streaming packets.
+#[derive(Debug)]
+struct ControlState {
+    recv: Option<mpsc::Receiver<ControlRequest>>,
+    /// Control requests waiting to be issued.
+    queue: VecDeque<ControlRequest>,
+    /// The `INFO` request awaiting its response including the payload received, so far.
+    pending: Option<(oneshot::Sender<SeedLinkResult<String>>, Vec<u8>)>,
+}
+
+impl ControlState {
+    /// Closes the control channel. Pending requests fail.
+    fn close(&mut self) {
+        self.recv = None;
+        self.queue.clear();
+        self.pending = None;
+    }
+
+    async fn next_request(&mut self) -> Option<ControlRequest> {
+        match self.recv {
+            Some(ref mut recv) => recv.recv().await,
+            None => future::pending().await,
+        }
+    }
+}
+
+/// Issues the next control request queued unless an `INFO` response is outstanding.
+async fn dispatch_control_request(
+    con: &mut SeedLinkConnectionV3,
+    control: &mut ControlState,
+) -> SeedLinkResult<()> {
+    if control.pending.is_some() || con.get_framed_connection_mut().expects_info_resp() {
+        return Ok(());
+    }
+
+    if let Some(ControlRequest::Info(item, send)) = control.queue.pop_front() {
+        con.get_framed_connection_mut()
+            .send_info_request(item)
+            .await?;
+        control.pending = Some((send, Vec::new()));
+    }
+
+    Ok(())
+}
+
+/// Issues the next control request queued unless an `INFO` response is outstanding. Requests
+/// not available by means of SeedLink v4 fail immediately.
+#[cfg(feature = "v4-client")]
+async fn dispatch_control_request_v4(
+    con: &mut SeedLinkConnectionV4,
+    control: &mut ControlState,
+) -> SeedLinkResult<()> {
+    while control.pending.is_none() && !con.get_framed_connection().expects_info_resp() {
+        let (item, send) = match control.queue.pop_front() {
+            Some(ControlRequest::Info(item, send)) => (item, send),
+            _ => break,
+        };
+
+        match info_cmd_v4(&item) {
+            Ok(cmd) => {
+                con.get_framed_connection_mut()
+                    .send_info_request(cmd)
+                    .await?;
+                control.pending = Some((send, Vec::new()));
+            }
+            Err(err) => {
+                let _ = send.send(Err(err));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the SeedLink v4 `INFO` command corresponding to the SeedLink v3 `INFO` item `item`.
+#[cfg(feature = "v4-client")]
+fn info_cmd_v4(item: &InfoCmdItemV3) -> SeedLinkResult<InfoCmdV4> {
+    let item = match item {
+        InfoCmdItemV3::Id => InfoCmdItemV4::Id,
+        InfoCmdItemV3::Capabilities => InfoCmdItemV4::Capabilities,
+        InfoCmdItemV3::Stations => InfoCmdItemV4::Stations,
+        InfoCmdItemV3::Streams => InfoCmdItemV4::Streams,
+        InfoCmdItemV3::Connections => InfoCmdItemV4::Connections,
+        item => {
+            return Err(SeedLinkError::UnsupportedCommand(format!(
+                "INFO {} is not supported by SeedLink v4 connections",
+                item
+            )))
+        }
+    };
+
+    Ok(InfoCmdV4::new(item))
+}
+
+/// This function takes a SeedLink URL string and parses it into a URL
+/// as used by rust-url. This is necessary as the default parser does
+/// not understand how SeedLink URLs function.
+pub fn parse_slink_url(input: &str) -> Option<url::Url> {
+    match url::Url::parse(input) {
+        Ok(result) => parse_slink_scheme(result.scheme()).map(|_| result),
+        Err(_) => None,
+    }
+}
+
+/// Parses the SeedLink URL `scheme` into whether TLS is used and the major protocol version
+/// forced (if any).
+///
+/// Besides the plain `slink` and `slinks` schemes the protocol version may be forced by means of
+/// a `+v3` or `+v4` suffix (e.g. `slink+v4`). The legacy `slinkv3` and `slinkv4` schemes are
+/// still supported.
+fn parse_slink_scheme(scheme: &str) -> Option<(bool, Option<u8>)> {
+    let (base, protocol_version) = match scheme.split_once('+') {
+        Some((base, "v3")) => (base, Some(3)),
+        Some((base, "v4")) => (base, Some(4)),
+        Some(_) => return None,
+        None => match scheme {
+            "slinkv3" => ("slink", Some(3)),
+            "slinkv4" => ("slink", Some(4)),
+            scheme => (scheme, None),
+        },
+    };
+
+    match base {
+        "slink" => Some((false, protocol_version)),
+        "slinks" => Some((true, protocol_version)),
+        _ => None,
+    }
+}
+
+/// Defines the connection address.
+#[derive(Clone, Debug)]
+pub enum ConnectionAddr {
+    /// Format for this is `(host, port)`.
+    Tcp(String, u16),
+    /// TLS on top of TCP (requires the `tls` feature). See also
+    /// [`SeedLinkConnectionInfo::tls_ca_file`] and [`SeedLinkConnectionInfo::tls_insecure`].
+    TcpTls {
+        /// Hostname
+        host: String,
+        /// Port
+        port: u16,
+    },
+    ///// Format for this is the path to the unix socket.
+    //Unix(PathBuf),
+    /// In-memory connection (see [`Connection::from_duplex`]).
+    Mem,
+}
+
+impl fmt::Display for ConnectionAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Cluster::get_connection_info depends on the return value from this function
+        match *self {
+            ConnectionAddr::Tcp(ref host, port) => write!(f, "{host}:{port}"),
+            ConnectionAddr::Mem => write!(f, "mem"),
+            ConnectionAddr::TcpTls { ref host, port } => write!(f, "{host}:{port}"),
+            // ConnectionAddr::Unix(ref path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Holds the connection information that SeedLink should use for connecting.
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    /// A connection address for where to connect to.
+    pub addr: ConnectionAddr,
+
+    /// SeedLink specific connection information.
+    pub slink: SeedLinkConnectionInfo,
+}
+
+/// SeedLink specific/connection independent information used to establish a connection to redis.
+#[derive(Clone, Debug, Default)]
+pub struct SeedLinkConnectionInfo {
+    /// The SeedLink protocol to be used.
+    pub protocol_version: Option<u8>,
+    /// Optionally a username that should be used for connection.
+    pub username: Option<String>,
     /// Optionally a password that should be used for connection.
     pub password: Option<String>,
+    /// Optionally a JSON Web Token (JWT) that should be used for connection. Takes precedence
+    /// over `username` and `password`.
+    pub token: Option<String>,
+    /// Optionally a file of PEM encoded CA certificates used to verify the server certificate of
+    /// TLS connections. By default, the Mozilla root certificates are used.
+    pub tls_ca_file: Option<PathBuf>,
+    /// Disable the server certificate verification of TLS connections.
+    ///
+    /// # Warning
+    ///
+    /// If certificate verification is disabled, any certificate for any site is trusted. This
+    /// introduces a significant vulnerability to man-in-the-middle attacks.
+    pub tls_insecure: bool,
+    /// Whether unsolicited server messages received during handshaking and nonstandard responses
+    /// to `HELLO` fail the handshake. By default, such messages are logged as warnings and
+    /// skipped, while the assumptions of `hello_fallback` are applied to nonstandard responses to
+    /// `HELLO`.
+    pub strict_handshake: bool,
+    /// Assumptions applied to nonstandard responses to `HELLO` unless `strict_handshake` is
+    /// enabled.
+    pub hello_fallback: HelloFallback,
+    /// Whether to fall back to non-batch negotiation if a SeedLink `v3` server rejects batch
+    /// command mode (see [`NegotiationReport::pipelining_unavailable`]). By default, configuring
+    /// the connection fails.
+    pub batch_fallback: bool,
+    /// Maximum number of stations a SeedLink `v3` connection negotiates ahead (i.e. without
+    /// awaiting the responses) if batch command mode is disabled. Values smaller than or equal
+    /// to `1` (default) disable pipelining.
+    pub negotiation_concurrency: usize,
+    /// User agent information sent to SeedLink `v4` servers during handshaking. If empty, the
+    /// library identifies itself (i.e. `slink/<version>`).
+    pub user_agent: Vec<UserAgentCmdInfoV4>,
+    /// TCP socket options applied to the connections established (including TLS connections).
+    pub tcp: TcpSocketOptions,
+    /// Capacity of the read buffer (in bytes). Large buffers may improve the throughput of e.g.
+    /// archive pulls, while small buffers reduce the memory footprint. If `None`, defaults to
+    /// 8 KiB.
+    pub read_buffer_capacity: Option<usize>,
+    /// Capacity of the write buffer (in bytes). If `None`, defaults to 255 bytes.
+    pub write_buffer_capacity: Option<usize>,
+    /// Validation of the stations requested against the server's inventory before negotiating.
+    /// Disabled by default.
+    pub inventory_validation: InventoryValidation,
+    /// Optionally a provider of (e.g. short-lived) credentials used to authenticate SeedLink `v4`
+    /// connections. Takes precedence over `token`, `username` and `password`.
+    ///
+    /// Credentials are requested whenever connecting. While streaming packets, credentials are
+    /// refreshed before they expire if the server advertises the `AUTH:REFRESH` capability.
+    #[cfg(feature = "v4-client")]
+    pub credentials_provider: Option<Arc<dyn CredentialsProvider>>,
+    /// Optionally a key the signatures of SeedLink `v4` packets are verified with. If set, the
+    /// server must advertise the (non-standard) `SIGN:HMAC-SHA256` capability and packets with
+    /// invalid signatures fail the connection.
+    pub packet_signing_key: Option<Vec<u8>>,
+}
+
+impl SeedLinkConnectionInfo {
+    /// Returns the buffer capacities of connections (falling back to the defaults).
+    pub(crate) fn buffer_capacities(&self) -> BufferCapacities {
+        BufferCapacities {
+            read: self
+                .read_buffer_capacity
+                .unwrap_or(DEFAULT_READ_BUFFER_CAPACITY),
+            write: self
+                .write_buffer_capacity
+                .unwrap_or(DEFAULT_WRITE_BUFFER_CAPACITY),
+        }
+    }
 }
 
 impl FromStr for ConnectionInfo {
@@ -552,6 +2574,15 @@ impl IntoConnectionInfo for String {
 }
 
 fn url_to_tcp_connection_info(url: url::Url) -> SeedLinkResult<ConnectionInfo> {
+    let (tls, protocol_version) = match parse_slink_scheme(url.scheme()) {
+        Some(parsed) => parsed,
+        None => {
+            return Err(SeedLinkError::InvalidClientConfig(
+                "URL provided is not a SeedLink URL".to_string(),
+            ));
+        }
+    };
+
     let host = match url.host() {
         Some(host) => {
             // Here we manually match host's enum arms and call their to_string().
@@ -580,16 +2611,16 @@ fn url_to_tcp_connection_info(url: url::Url) -> SeedLinkResult<ConnectionInfo> {
 
     let port = url.port().unwrap_or(DEFAULT_PORT);
 
-    let addr = ConnectionAddr::Tcp(host, port);
+    let addr = if tls {
+        ConnectionAddr::TcpTls { host, port }
+    } else {
+        ConnectionAddr::Tcp(host, port)
+    };
 
     Ok(ConnectionInfo {
         addr,
         slink: SeedLinkConnectionInfo {
-            protocol_version: if url.scheme() == "slinkv3" {
-                Some(3)
-            } else {
-                None
-            },
+            protocol_version,
             username: if url.username().is_empty() {
                 None
             } else {
@@ -613,18 +2644,31 @@ fn url_to_tcp_connection_info(url: url::Url) -> SeedLinkResult<ConnectionInfo> {
                 },
                 None => None,
             },
+            token: url
+                .query_pairs()
+                .find(|(k, _)| k == "token")
+                .map(|(_, v)| v.into_owned()),
+            strict_handshake: false,
+            hello_fallback: HelloFallback::default(),
+            batch_fallback: false,
+            negotiation_concurrency: 1,
+            tls_ca_file: None,
+            tls_insecure: false,
+            user_agent: Vec::new(),
+            tcp: TcpSocketOptions::default(),
+            read_buffer_capacity: None,
+            write_buffer_capacity: None,
+            inventory_validation: InventoryValidation::Disabled,
+            #[cfg(feature = "v4-client")]
+            credentials_provider: None,
+            packet_signing_key: None,
         },
     })
 }
 
 impl IntoConnectionInfo for url::Url {
     fn into_connection_info(self) -> SeedLinkResult<ConnectionInfo> {
-        match self.scheme() {
-            "slink" | "slinkv3" => url_to_tcp_connection_info(self),
-            _ => Err(SeedLinkError::InvalidClientConfig(
-                "URL provided is not a SeedLink URL".to_string(),
-            )),
-        }
+        url_to_tcp_connection_info(self)
     }
 }
 
@@ -632,12 +2676,20 @@ pub async fn connect(
     connection_info: &ConnectionInfo,
     timeout: Option<Duration>,
 ) -> SeedLinkResult<Connection> {
-    let con = ActualConnection::new(&connection_info.addr, timeout).await?;
-    setup_connection(con, &connection_info.slink).await
+    let rv = async {
+        let con =
+            ActualConnection::new(&connection_info.addr, &connection_info.slink, timeout).await?;
+        setup_connection(con, connection_info).await
+    }
+    .await;
+    metrics::connect_attempt(rv.is_ok());
+
+    rv
 }
 
 async fn make_preflight_request(
     con: &mut ActualConnection,
+    fallback_protocol_version: Option<&str>,
 ) -> SeedLinkResult<util::ParsedHelloResponse> {
     let mut buf = Vec::new();
 
@@ -651,9 +2703,24 @@ async fn make_preflight_request(
             read_line(rw, &mut buf).await?;
             read_line(rw, &mut buf).await?;
         }
-    };
+        #[cfg(feature = "tls")]
+        ActualConnection::Tls(TlsConnection { ref mut rw, .. }) => {
+            rw.write_all(b"hello\r\n").await?;
+            rw.flush().await?;
 
-    let buf = String::from_utf8(buf)
+            read_line(rw, &mut buf).await?;
+            read_line(rw, &mut buf).await?;
+        }
+        ActualConnection::Mem(MemConnection { ref mut rw, .. }) => {
+            rw.write_all(b"hello\r\n").await?;
+            rw.flush().await?;
+
+            read_line(rw, &mut buf).await?;
+            read_line(rw, &mut buf).await?;
+        }
+    };
+
+    let buf = String::from_utf8(buf)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
 
     let mut lines = buf.lines();
@@ -687,7 +2754,8 @@ async fn make_preflight_request(
         .into());
     }
 
-    let rv = util::parse_hello_response(first_resp_line, second_resp_line)?;
+    let rv =
+        util::parse_hello_response(first_resp_line, second_resp_line, fallback_protocol_version)?;
 
     info!("[preflight request] connected to: {}", first_resp_line);
     debug!(
@@ -706,6 +2774,82 @@ async fn make_preflight_request(
     Ok(rv)
 }
 
+/// Creates a new SeedLink `v4` connection and selects the most recent `v4` protocol version
+/// advertised by the remote peer.
+#[cfg(feature = "v4-client")]
+async fn new_connection_v4(
+    con: ActualConnection,
+    protocol_versions: &[String],
+    capabilities: &[String],
+    slink_connection_info: &SeedLinkConnectionInfo,
+) -> SeedLinkResult<(ActualSeedLinkConnection, CredentialsRefresh)> {
+    let version = protocol_versions
+        .iter()
+        .filter_map(|v| v.parse::<SlProtoCmdV4>().ok())
+        .filter(|v| v.major == 4)
+        .max_by_key(|v| v.minor)
+        .unwrap_or(SlProtoCmdV4 { major: 4, minor: 0 });
+
+    let mut con = SeedLinkConnectionV4::new(con, slink_connection_info.buffer_capacities());
+    con.get_framed_connection_mut()
+        .set_strict(slink_connection_info.strict_handshake);
+    if let Some(ref key) = slink_connection_info.packet_signing_key {
+        if !capabilities
+            .iter()
+            .any(|cap| cap == PACKET_SIGNATURE_CAPABILITY_V4)
+        {
+            return Err(SeedLinkError::InvalidClientConfig(
+                "packet signatures not supported by server".to_string(),
+            ));
+        }
+        con.get_framed_connection_mut()
+            .set_signing_key(Some(key.clone()));
+    }
+    con.negotiate(&version, &slink_connection_info.user_agent)
+        .await?;
+
+    let mut credentials_refresh = CredentialsRefresh::default();
+    let auth_method = match (
+        &slink_connection_info.credentials_provider,
+        &slink_connection_info.token,
+        &slink_connection_info.username,
+        &slink_connection_info.password,
+    ) {
+        (Some(provider), _, _, _) => {
+            let credentials = provider.credentials().await?;
+            if capabilities
+                .iter()
+                .any(|cap| cap == CAPABILITY_AUTH_REFRESH_V4)
+            {
+                credentials_refresh =
+                    CredentialsRefresh::new(provider.clone(), credentials.expires);
+            } else if credentials.expires.is_some() {
+                warn!(
+                    "credentials not refreshed while streaming (server does not advertise {})",
+                    CAPABILITY_AUTH_REFRESH_V4
+                );
+            }
+            Some(AuthCmdMethodV4::from(credentials.auth))
+        }
+        (None, Some(token), _, _) => Some(AuthCmdMethodV4::JWT(token.clone())),
+        (None, None, Some(username), Some(password)) => Some(AuthCmdMethodV4::UserPass(
+            username.clone(),
+            password.clone(),
+        )),
+        (None, None, None, None) => None,
+        _ => {
+            return Err(SeedLinkError::InvalidClientConfig(
+                "authentication requires both username and password".to_string(),
+            ));
+        }
+    };
+    if let Some(auth_method) = auth_method {
+        con.authenticate(auth_method).await?;
+    }
+
+    Ok((ActualSeedLinkConnection::V4(con), credentials_refresh))
+}
+
 async fn read_line<R: AsyncRead + Unpin>(read: &mut R, buf: &mut Vec<u8>) -> SeedLinkResult<()> {
     loop {
         let byte = read.read_u8().await?;
@@ -720,13 +2864,28 @@ async fn read_line<R: AsyncRead + Unpin>(read: &mut R, buf: &mut Vec<u8>) -> See
 
 async fn setup_connection(
     mut con: ActualConnection,
-    slink_connection_info: &SeedLinkConnectionInfo,
+    connection_info: &ConnectionInfo,
 ) -> SeedLinkResult<Connection> {
-    let hello_resp = make_preflight_request(&mut con).await?;
+    let slink_connection_info = &connection_info.slink;
+    if let Some(proto_version) = slink_connection_info.protocol_version {
+        if !AVAILABLE_CLIENT_PROTO_VERSIONS.contains(&proto_version) {
+            return Err(SeedLinkError::InvalidClientConfig(format!(
+                "seedlink protocol version not implemented by client: v{}",
+                proto_version
+            )));
+        }
+    }
+
+    let fallback_protocol_version = &slink_connection_info.hello_fallback.protocol_version;
+    let hello_resp = make_preflight_request(
+        &mut con,
+        (!slink_connection_info.strict_handshake).then_some(fallback_protocol_version.as_str()),
+    )
+    .await?;
 
     let mut major_proto_versions = HashSet::new();
     for proto_version_str in &hello_resp.protocol_versions {
-        if let Some(major_proto_version) = proto_version_str.splitn(2, '.').next() {
+        if let Some(major_proto_version) = proto_version_str.split('.').next() {
             let parsed_major_proto_version = major_proto_version.parse::<u8>().map_err(|_| {
                 io::Error::new(
                     io::ErrorKind::InvalidData,
@@ -743,60 +2902,1250 @@ async fn setup_connection(
 
     let mut selected_proto_version: Option<u8> = None;
     if let Some(proto_version) = slink_connection_info.protocol_version {
-        if major_proto_versions.get(&proto_version).is_none() {
-            return Err(SeedLinkError::ClientError("incompatible seedlink protocol versions: protocol version not implemented by remote peer".to_string()));
+        if !major_proto_versions.contains(&proto_version) {
+            return Err(SeedLinkError::UnsupportedProtocolVersion {
+                offered: hello_resp.protocol_versions.clone(),
+                requested: Some(proto_version),
+            });
         }
 
         selected_proto_version = Some(proto_version);
     }
 
-    // try most recent protocol version implemented by both the library and the remote peer
-    for avail_proto_version in AVAILABLE_CLIENT_PROTO_VERSIONS.into_iter().rev() {
-        if major_proto_versions.get(&avail_proto_version).is_none() {
-            continue;
-        }
-        selected_proto_version = Some(avail_proto_version);
+    if selected_proto_version.is_none() {
+        // try most recent protocol version implemented by both the library and the remote peer
+        selected_proto_version = AVAILABLE_CLIENT_PROTO_VERSIONS
+            .into_iter()
+            .rev()
+            .find(|v| major_proto_versions.contains(v));
     }
 
+    #[cfg(feature = "v4-client")]
+    let mut credentials_refresh = CredentialsRefresh::default();
     let con = match selected_proto_version {
-        Some(v) => {
-            debug!("using seedlink protocol version: v{}", v);
-            if v == 3 {
-                ActualSeedLinkConnection::V3(SeedLinkConnectionV3::new(con))
-            } else {
-                return Err(SeedLinkError::ClientError(
-                    "incompatible seedlink protocol versions".to_string(),
+        Some(3) => {
+            debug!("using seedlink protocol version: v3");
+            let has_credentials =
+                slink_connection_info.username.is_some() || slink_connection_info.token.is_some();
+            #[cfg(feature = "v4-client")]
+            let has_credentials =
+                has_credentials || slink_connection_info.credentials_provider.is_some();
+            if has_credentials {
+                warn!("authentication not supported by seedlink protocol version v3 (credentials ignored)");
+            }
+            if slink_connection_info.packet_signing_key.is_some() {
+                return Err(SeedLinkError::InvalidClientConfig(
+                    "packet signatures not supported by seedlink protocol version v3".to_string(),
                 ));
             }
+            let mut con = SeedLinkConnectionV3::new(con, slink_connection_info.buffer_capacities());
+            con.get_framed_connection_mut()
+                .set_strict(slink_connection_info.strict_handshake);
+            con.get_framed_connection_mut()
+                .set_batch_fallback(slink_connection_info.batch_fallback);
+            con.get_framed_connection_mut()
+                .set_negotiation_concurrency(slink_connection_info.negotiation_concurrency);
+            let has_capability =
+                |capability: &str| hello_resp.capabilities.iter().any(|cap| cap == capability);
+            if has_capability(CAPABILITY_CAP_V3) && has_capability(CAPABILITY_EXTREPLY_V3) {
+                if let Err(e) = con
+                    .get_framed_connection_mut()
+                    .enable_extended_replies()
+                    .await
+                {
+                    match e {
+                        SeedLinkError::UnsupportedCommand(_) => warn!("{}", e),
+                        e => return Err(e),
+                    }
+                }
+            }
+            #[cfg(feature = "gzip")]
+            if hello_resp
+                .capabilities
+                .iter()
+                .any(|cap| cap == CAPABILITY_INFO_GZIP_V3)
+            {
+                if let Err(e) = con.get_framed_connection_mut().enable_info_gzip().await {
+                    match e {
+                        SeedLinkError::UnsupportedCommand(_) => {
+                            warn!("{} (INFO responses uncompressed)", e)
+                        }
+                        e => return Err(e),
+                    }
+                }
+            }
+            ActualSeedLinkConnection::V3(con)
         }
-        None => {
-            return Err(SeedLinkError::ClientError(
-                "incompatible seedlink protocol versions".to_string(),
-            ));
+        #[cfg(feature = "v4-client")]
+        Some(4) => {
+            debug!("using seedlink protocol version: v4");
+            let (con, refresh) = new_connection_v4(
+                con,
+                &hello_resp.protocol_versions,
+                &hello_resp.capabilities,
+                slink_connection_info,
+            )
+            .await?;
+            credentials_refresh = refresh;
+            con
+        }
+        _ => {
+            return Err(SeedLinkError::UnsupportedProtocolVersion {
+                offered: hello_resp.protocol_versions.clone(),
+                requested: None,
+            });
         }
     };
 
-    let rv = Connection::new(con);
+    let mut rv = Connection::new(con, connection_info.addr.clone());
+    rv.inventory_validation = slink_connection_info.inventory_validation;
+    #[cfg(feature = "v4-client")]
+    {
+        rv.credentials_refresh = credentials_refresh;
+    }
+    rv.capabilities =
+        Capabilities::from_hello(&hello_resp.protocol_versions, &hello_resp.capabilities);
+    let major_proto_version = rv.protocol_version();
+    let minor_proto_version = hello_resp
+        .protocol_versions
+        .iter()
+        .filter_map(|v| util::parse_protocol_version(v))
+        .filter(|(major, _)| *major == major_proto_version)
+        .map(|(_, minor)| minor)
+        .max()
+        .unwrap_or(0);
+    rv.negotiated_protocol_version = (major_proto_version, minor_proto_version);
+    rv.server_software = hello_resp.software.clone();
+    rv.data_center_description = hello_resp.station_or_datacenter_desc.clone();
+    if hello_resp.station_or_datacenter_desc.is_empty() {
+        rv.warnings.emit(SeedLinkWarning::MissingDescription);
+    }
+    for cap in hello_resp
+        .capabilities
+        .iter()
+        .filter(|cap| !warning::is_known_capability(cap))
+    {
+        rv.warnings
+            .emit(SeedLinkWarning::UnknownCapability(cap.to_string()));
+    }
+
+    Ok(rv)
+}
 
-    // TODO(damb):
-    // - perform authentication
+#[cfg(test)]
+mod tests {
 
-    // if connection_info.password.is_some() {
-    //     connect_auth(&mut rv, connection_info)?;
-    // }
+    use super::*;
 
-    // if connection_info.db != 0 {
-    //     match cmd("SELECT")
-    //         .arg(connection_info.db)
-    //         .query::<Value>(&mut rv)
-    //     {
-    //         Ok(Value::Okay) => {}
-    //         _ => fail!((
-    //             ErrorKind::ResponseError,
-    //             "Redis server refused to switch database"
-    //         )),
-    //     }
-    // }
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::time::Instant;
 
-    Ok(rv)
+    #[tokio::test(start_paused = true)]
+    async fn keep_alive_virtual_time() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), hello);
+        let mut con = con.unwrap();
+        assert_eq!(con.protocol_version(), 3);
+        assert_eq!(con.negotiated_protocol_version(), (3, 1));
+        assert_eq!(con.server_software(), "SeedLink v3.1 (2020.075)");
+        assert_eq!(con.data_center_description(), "GEOFON");
+        let control = con.control();
+
+        let packets = con.packets(Some(Duration::from_secs(60)));
+        tokio::pin!(packets);
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            tokio::select! {
+                _ = packets.next() => panic!("unexpected packet"),
+                line = lines.next_line() => assert_eq!(line.unwrap().unwrap(), "info id"),
+            }
+
+            // acknowledge keepalive
+            write.write_all(b"SLINFO  ").await.unwrap();
+            write.write_all(&[0; 512]).await.unwrap();
+            let packet = packets.next().await;
+            assert!(matches!(
+                packet,
+                Some(StreamItem::Packet(SeedLinkPacket::V3(
+                    SeedLinkPacketV3::Info(_)
+                )))
+            ));
+        }
+
+        // the first keepalive is sent immediately
+        assert_eq!(start.elapsed(), Duration::from_secs(120));
+
+        let stats = control.stats();
+        assert_eq!(stats.keep_alives_sent, 3);
+        assert_eq!(stats.info_packets_received, 3);
+        assert_eq!(stats.data_packets_received, 0);
+        assert_eq!(stats.bytes_sent, 7 + 3 * 9);
+        assert_eq!(stats.bytes_received, 34 + 3 * 520);
+        assert_eq!(stats.header_bytes_received, 3 * 8);
+        assert_eq!(stats.payload_bytes_received, 3 * 512);
+        assert_eq!(stats.protocol_overhead(), 7 + 3 * 9 + 34 + 3 * 8);
+        assert_eq!(stats.negotiation_duration, None);
+        assert_eq!(stats.uptime, Duration::from_secs(120));
+    }
+
+    #[tokio::test]
+    async fn shutdown_by_control_handle() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), hello);
+        let mut con = con.unwrap();
+        let control = con.control();
+
+        let packets = con.packets(None);
+        tokio::pin!(packets);
+
+        let (item, res) = tokio::join!(packets.next(), control.shutdown());
+        res.unwrap();
+        assert!(matches!(item, Some(StreamItem::End(StreamEnd::Shutdown))));
+        assert!(packets.next().await.is_none());
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "bye");
+
+        // the packet stream terminated
+        assert!(control.shutdown().await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keep_alive_not_acknowledged() {
+        for (timeout, max_unanswered, elapsed) in [
+            (Some(Duration::from_secs(30)), None, Duration::from_secs(30)),
+            (None, Some(2), Duration::from_secs(120)),
+        ] {
+            let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+            let (read, mut write) = tokio::io::split(server_stream);
+            let mut lines = BufReader::new(read).lines();
+
+            let hello = async {
+                assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+                write
+                    .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                    .await
+                    .unwrap();
+            };
+            let info = SeedLinkConnectionInfo::default();
+            let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), hello);
+            let mut con = con.unwrap();
+            con.set_keep_alive_timeout(timeout);
+            con.set_max_unanswered_keep_alives(max_unanswered);
+
+            let packets = con.packets(Some(Duration::from_secs(60)));
+            tokio::pin!(packets);
+
+            let start = Instant::now();
+            let item = packets.next().await;
+            assert!(matches!(
+                item,
+                Some(StreamItem::End(StreamEnd::Error(SeedLinkError::Io(ref err))))
+                    if err.kind() == io::ErrorKind::TimedOut
+            ));
+            assert_eq!(start.elapsed(), elapsed);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_timeout() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), hello);
+        let mut con = con.unwrap();
+        con.set_idle_timeout(Some(Duration::from_secs(30)));
+
+        let packets = con.packets(None);
+        tokio::pin!(packets);
+
+        let start = Instant::now();
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        write.write_all(b"SLINFO  ").await.unwrap();
+        write.write_all(&[0; 512]).await.unwrap();
+        assert!(matches!(
+            packets.next().await,
+            Some(StreamItem::Packet(SeedLinkPacket::V3(
+                SeedLinkPacketV3::Info(_)
+            )))
+        ));
+
+        let item = packets.next().await;
+        assert!(matches!(
+            item,
+            Some(StreamItem::End(StreamEnd::Error(SeedLinkError::Io(ref err))))
+                if err.kind() == io::ErrorKind::TimedOut
+        ));
+        assert_eq!(start.elapsed(), Duration::from_secs(50));
+    }
+
+    #[tokio::test]
+    async fn stream_end_server_closed() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), hello);
+        let con = con.unwrap();
+        drop(lines);
+        drop(write);
+
+        let packets = con.packets(None);
+        tokio::pin!(packets);
+
+        assert!(matches!(
+            packets.next().await,
+            Some(StreamItem::End(StreamEnd::ServerClosed(None)))
+        ));
+        assert!(packets.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn stream_end_server_closed_with_reason() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), hello);
+        let con = con.unwrap();
+        write.write_all(b"ERROR\r\n").await.unwrap();
+        drop(lines);
+        drop(write);
+
+        let packets = con.packets(None);
+        tokio::pin!(packets);
+
+        assert!(matches!(
+            packets.next().await,
+            Some(StreamItem::End(StreamEnd::ServerClosed(Some(reason)))) if reason == "ERROR"
+        ));
+    }
+
+    #[tokio::test]
+    async fn small_buffer_capacities() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let slink_connection_info = SeedLinkConnectionInfo {
+            read_buffer_capacity: Some(4),
+            write_buffer_capacity: Some(1),
+            ..Default::default()
+        };
+        let (con, ()) = tokio::join!(
+            Connection::from_duplex(client_stream, &slink_connection_info),
+            hello
+        );
+        let mut con = con.unwrap();
+
+        con.add_stream("GE", "WLF", &None, &None, &None).unwrap();
+        let configure = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "station WLF GE");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "data");
+            write.write_all(b"OK\r\n").await.unwrap();
+        };
+        let (res, ()) = tokio::join!(
+            con.configure(DataTransferMode::RealTime, None, false),
+            configure
+        );
+        assert!(res.is_ok());
+    }
+
+    async fn configure_with_unsolicited_message(strict_handshake: bool) -> SeedLinkResult<()> {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let slink_connection_info = SeedLinkConnectionInfo {
+            strict_handshake,
+            ..Default::default()
+        };
+        let (con, ()) = tokio::join!(
+            Connection::from_duplex(client_stream, &slink_connection_info),
+            hello
+        );
+        let mut con = con.unwrap();
+
+        con.add_stream("GE", "WLF", &None, &None, &None).unwrap();
+        let configure = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "station WLF GE");
+            write
+                .write_all(b"maintenance scheduled\r\nOK\r\n")
+                .await
+                .unwrap();
+            if !strict_handshake {
+                assert_eq!(lines.next_line().await.unwrap().unwrap(), "data");
+                write.write_all(b"OK\r\n").await.unwrap();
+            }
+        };
+        let (res, ()) = tokio::join!(
+            con.configure(DataTransferMode::RealTime, None, false),
+            configure
+        );
+
+        res
+    }
+
+    #[tokio::test]
+    async fn unsolicited_handshake_message() {
+        assert!(configure_with_unsolicited_message(false).await.is_ok());
+        assert!(matches!(
+            configure_with_unsolicited_message(true).await,
+            Err(SeedLinkError::Io(err)) if err.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+
+    async fn configure_with_batch_rejected(batch_fallback: bool) -> SeedLinkResult<Connection> {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let slink_connection_info = SeedLinkConnectionInfo {
+            batch_fallback,
+            ..Default::default()
+        };
+        let (con, ()) = tokio::join!(
+            Connection::from_duplex(client_stream, &slink_connection_info),
+            hello
+        );
+        let mut con = con.unwrap();
+
+        con.add_stream("GE", "WLF", &None, &None, &None).unwrap();
+        let configure = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "batch");
+            write.write_all(b"ERROR\r\n").await.unwrap();
+            if batch_fallback {
+                assert_eq!(lines.next_line().await.unwrap().unwrap(), "station WLF GE");
+                write.write_all(b"OK\r\n").await.unwrap();
+                assert_eq!(lines.next_line().await.unwrap().unwrap(), "data");
+                write.write_all(b"OK\r\n").await.unwrap();
+            }
+        };
+        let (res, ()) = tokio::join!(
+            con.configure(DataTransferMode::RealTime, None, true),
+            configure
+        );
+
+        res.map(|_| con)
+    }
+
+    #[tokio::test]
+    async fn batch_fallback() {
+        let con = configure_with_batch_rejected(true).await.unwrap();
+        assert!(con.negotiation_report().pipelining_unavailable);
+
+        assert!(matches!(
+            configure_with_batch_rejected(false).await,
+            Err(SeedLinkError::UnsupportedCommand(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn negotiation_concurrency() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let slink_connection_info = SeedLinkConnectionInfo {
+            negotiation_concurrency: 2,
+            ..Default::default()
+        };
+        let (con, ()) = tokio::join!(
+            Connection::from_duplex(client_stream, &slink_connection_info),
+            hello
+        );
+        let mut con = con.unwrap();
+
+        for sta in ["WLF", "APE", "XXX"] {
+            con.add_stream("GE", sta, &Some("BHZ".to_string()), &None, &None)
+                .unwrap();
+        }
+        let progress = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let progress_cloned = progress.clone();
+        con.set_negotiation_progress(move |p| {
+            progress_cloned
+                .lock()
+                .unwrap()
+                .push((p.negotiated, p.rejected, p.total))
+        });
+        let configure = async {
+            // the unknown station is rejected including its subsequent commands
+            let mut reject = false;
+            let mut stations = Vec::new();
+            loop {
+                let line = lines.next_line().await.unwrap().unwrap();
+                if line == "end" {
+                    break;
+                }
+                if let Some(sta) = line.strip_prefix("station ") {
+                    reject = sta == "XXX GE";
+                    stations.push(sta.to_string());
+                }
+                let resp: &[u8] = if reject { b"ERROR\r\n" } else { b"OK\r\n" };
+                write.write_all(resp).await.unwrap();
+            }
+            stations.sort();
+            stations
+        };
+        let (res, stations) = tokio::join!(
+            con.configure(DataTransferMode::RealTime, None, false),
+            configure
+        );
+        res.unwrap();
+        assert_eq!(stations, vec!["APE GE", "WLF GE", "XXX GE"]);
+
+        let progress = progress.lock().unwrap();
+        assert_eq!(progress.len(), 3);
+        assert_eq!(progress.last(), Some(&(3, 1, 3)));
+
+        let subscription = con.subscription();
+        assert_eq!(
+            subscription
+                .iter()
+                .map(|s| (s.station.as_str(), s.data_transfer_mode))
+                .collect::<Vec<_>>(),
+            vec![
+                ("APE", DataTransferMode::RealTime),
+                ("WLF", DataTransferMode::RealTime)
+            ]
+        );
+        assert_eq!(subscription[0].selectors, vec!["BHZ".to_string()]);
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[tokio::test]
+    async fn negotiate_v4() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let handshake = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v4.0 (2023.1) :: SLPROTO:4.0 SLPROTO:3.1\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "slproto 4.0");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert!(lines
+                .next_line()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with("useragent slink/"));
+            write.write_all(b"OK\r\n").await.unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), handshake);
+        let mut con = con.unwrap();
+        assert_eq!(con.protocol_version(), 4);
+
+        con.add_stream("GE", "WLF", &None, &Some("2a".to_string()), &None)
+            .unwrap();
+        let configure = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "station GE_WLF");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "data 42");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "end");
+        };
+        let (res, ()) = tokio::join!(
+            con.configure(DataTransferMode::RealTime, None, false),
+            configure
+        );
+        res.unwrap();
+
+        let mut buf = Vec::new();
+        crate::wire::v4::write_packet(*b"2D", 42, b"GE_WLF", &[0; 512], &mut buf).unwrap();
+        write.write_all(&buf).await.unwrap();
+        drop(lines);
+        drop(write);
+
+        let packets = con.packets(None);
+        tokio::pin!(packets);
+
+        assert!(matches!(
+            packets.next().await,
+            Some(StreamItem::Packet(SeedLinkPacket::V4(packet))) if packet.sequence_number() == 42
+        ));
+        assert!(matches!(
+            packets.next().await,
+            Some(StreamItem::End(StreamEnd::ServerClosed(None)))
+        ));
+    }
+
+    #[test]
+    fn remove_streams() {
+        let mut stream_configs = StreamConfigs::default();
+        stream_configs
+            .add_stream("GE", "WLF", &Some("BHZ".to_string()), &None, &None)
+            .unwrap();
+        stream_configs
+            .add_stream("GE", "WLF", &Some("BHN".to_string()), &None, &None)
+            .unwrap();
+        stream_configs
+            .add_stream("GE", "APE", &None, &None, &None)
+            .unwrap();
+        assert_eq!(
+            stream_configs.streams(),
+            vec![
+                ("GE", "APE", &[][..]),
+                ("GE", "WLF", &["BHZ".to_string(), "BHN".to_string()][..])
+            ]
+        );
+
+        assert!(stream_configs.remove_stream("GE", "WLF"));
+        assert!(!stream_configs.remove_stream("GE", "WLF"));
+        assert_eq!(stream_configs.streams(), vec![("GE", "APE", &[][..])]);
+
+        stream_configs.clear();
+        assert!(stream_configs.streams().is_empty());
+    }
+
+    #[test]
+    fn slink_url_schemes() {
+        for (url, tls, protocol_version) in [
+            ("slink://localhost", false, None),
+            ("slinkv3://localhost", false, Some(3)),
+            ("slink+v3://localhost", false, Some(3)),
+            ("slink+v4://localhost", false, Some(4)),
+            ("slinks+v4://localhost", true, Some(4)),
+        ] {
+            let connection_info = url.into_connection_info().unwrap();
+            assert_eq!(connection_info.slink.protocol_version, protocol_version);
+            assert_eq!(
+                matches!(connection_info.addr, ConnectionAddr::TcpTls { .. }),
+                tls
+            );
+        }
+
+        let connection_info = "slink+v4://localhost?token=abc.def.ghi"
+            .into_connection_info()
+            .unwrap();
+        assert_eq!(connection_info.slink.token, Some("abc.def.ghi".to_string()));
+
+        assert!(parse_slink_url("slink+v5://localhost").is_none());
+        assert!("http+v4://localhost".into_connection_info().is_err());
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[tokio::test]
+    async fn forced_protocol_version_not_offered() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let slink_connection_info = SeedLinkConnectionInfo {
+            protocol_version: Some(4),
+            ..Default::default()
+        };
+        let (con, ()) = tokio::join!(
+            Connection::from_duplex(client_stream, &slink_connection_info),
+            hello
+        );
+        assert!(matches!(
+            con,
+            Err(SeedLinkError::UnsupportedProtocolVersion {
+                requested: Some(4),
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "state-sqlite")]
+    #[tokio::test]
+    async fn recover_state_protocol_version() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), hello);
+        let mut con = con.unwrap();
+        con.add_stream("GE", "WLF", &None, &None, &None).unwrap();
+        con.add_stream("GE", "APE", &None, &None, &None).unwrap();
+
+        let mut db = StateDB::open(":memory:").await.unwrap();
+        db.store("FDSN:GE_WLF_00_B_H_Z", 0x2a, 3).await.unwrap();
+        db.store("FDSN:GE_APE_00_B_H_Z", 0x7, 4).await.unwrap();
+        con.recover_state(&mut db, false).await.unwrap();
+
+        assert_eq!(con.stream_configs.seq_num("GE", "WLF"), Some("2a"));
+        // sequence numbers of other protocol versions are ignored
+        assert_eq!(con.stream_configs.seq_num("GE", "APE"), None);
+    }
+
+    #[cfg(feature = "state-sqlite")]
+    #[tokio::test]
+    async fn recover_state_by_time() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), hello);
+        let mut con = con.unwrap();
+        con.add_stream("GE", "WLF", &None, &None, &None).unwrap();
+        con.add_stream("GE", "APE", &None, &None, &None).unwrap();
+
+        let stream_state = |sid: &str, protocol_version, end_time| StreamState {
+            sid: sid.parse().unwrap(),
+            seq_num: 42,
+            protocol_version,
+            end_time,
+        };
+        let mut db = StateDB::open(":memory:").await.unwrap();
+        db.store_batch(vec![
+            stream_state(
+                "FDSN:GE_WLF_00_B_H_Z",
+                4,
+                Some(time::macros::datetime!(2023-01-01 12:10 UTC)),
+            ),
+            stream_state(
+                "FDSN:GE_WLF_00_B_H_N",
+                4,
+                Some(time::macros::datetime!(2023-01-01 12:00 UTC)),
+            ),
+            stream_state(
+                "FDSN:GE_WLF_00_B_H_E",
+                4,
+                Some(time::macros::datetime!(2023-01-01 12:05 UTC)),
+            ),
+            stream_state("FDSN:GE_APE_00_B_H_Z", 3, None),
+        ])
+        .await
+        .unwrap();
+        con.recover_state_by_time(&mut db, false).await.unwrap();
+
+        let start_time = |net, sta| {
+            let key = format!("{}{}", net, sta);
+            con.stream_configs.0[&key].time_window().0
+        };
+        // resumed from the stream lagging behind most, regardless of the protocol version
+        assert_eq!(
+            start_time("GE", "WLF"),
+            Some(time::macros::datetime!(2023-01-01 12:00))
+        );
+        assert_eq!(start_time("GE", "APE"), None);
+        assert_eq!(con.stream_configs.seq_num("GE", "WLF"), None);
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[tokio::test]
+    async fn send_user_agent_v4() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let handshake = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v4.0 (2023.1) :: SLPROTO:4.0\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "slproto 4.0");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(
+                lines.next_line().await.unwrap().unwrap(),
+                "useragent myapp/1.0 slink/0.1"
+            );
+            write.write_all(b"OK\r\n").await.unwrap();
+        };
+        let slink_connection_info = SeedLinkConnectionInfo {
+            user_agent: vec![
+                UserAgentCmdInfoV4::new("myapp".to_string(), "1.0".to_string()),
+                UserAgentCmdInfoV4::new("slink".to_string(), "0.1".to_string()),
+            ],
+            ..Default::default()
+        };
+        let (con, ()) = tokio::join!(
+            Connection::from_duplex(client_stream, &slink_connection_info),
+            handshake
+        );
+        assert!(con.is_ok());
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[tokio::test]
+    async fn control_info_v4() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let handshake = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v4.0 (2023.1) :: SLPROTO:4.0\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "slproto 4.0");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert!(lines
+                .next_line()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with("useragent slink/"));
+            write.write_all(b"OK\r\n").await.unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), handshake);
+        let mut con = con.unwrap();
+        let control = con.control();
+
+        let packets = con.packets(None);
+        tokio::pin!(packets);
+
+        // `INFO` responses to control requests are demultiplexed from the packet stream
+        let server = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "info stations");
+            let json = r#"{"station": [
+                {"id": "GE_WLF", "description": "GEOFON Station Walferdange", "start_seq": 0, "end_seq": 42},
+                {"id": "GE_APE", "description": "GEOFON Station Apirathos", "start_seq": 0, "end_seq": 42}
+            ]}"#;
+            write
+                .write_all(&crate::pack_info_ok_v4(json).unwrap())
+                .await
+                .unwrap();
+            let mut buf = Vec::new();
+            crate::wire::v4::write_packet(*b"2D", 42, b"GE_WLF", &[0; 512], &mut buf).unwrap();
+            write.write_all(&buf).await.unwrap();
+        };
+        let (item, inventory, ()) = tokio::join!(
+            packets.next(),
+            control.request_inventory(Some("GE_W*"), None, InventoryLevel::Station),
+            server
+        );
+        assert!(matches!(
+            item,
+            Some(StreamItem::Packet(SeedLinkPacket::V4(packet))) if packet.sequence_number() == 42
+        ));
+        let inventory = inventory.unwrap();
+        assert_eq!(inventory.len(), 1);
+        assert_eq!(inventory.iter().next().unwrap().id().to_string(), "GE_WLF");
+
+        // `INFO` items not available by means of SeedLink v4 fail immediately
+        let (item, res) = tokio::join!(
+            tokio_time::timeout(Duration::from_millis(100), packets.next()),
+            control.request_info_raw(InfoCmdItemV3::Gaps)
+        );
+        assert!(item.is_err());
+        assert!(matches!(res, Err(SeedLinkError::UnsupportedCommand(_))));
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[tokio::test]
+    async fn handshake_rejected_v4() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let hello = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v4.0 (2023.1) :: SLPROTO:4.0\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "slproto 4.0");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert!(lines
+                .next_line()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with("useragent slink/"));
+            write.write_all(b"OK\r\n").await.unwrap();
+        };
+        let info = SeedLinkConnectionInfo::default();
+        let (con, ()) = tokio::join!(Connection::from_duplex(client_stream, &info), hello);
+        let mut con = con.unwrap();
+        con.add_stream("GE", "WLF", &None, &None, &None).unwrap();
+
+        let configure = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "station GE_WLF");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert!(lines
+                .next_line()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with("data"));
+            write
+                .write_all(b"ERROR ARGUMENTS invalid sequence number\r\n")
+                .await
+                .unwrap();
+        };
+        let (res, ()) = tokio::join!(
+            con.configure(DataTransferMode::RealTime, None, false),
+            configure
+        );
+        assert!(matches!(
+            res,
+            Err(SeedLinkError::HandshakeRejected { ref station, reason: Some(_) })
+                if station == "GE_WLF"
+        ));
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[tokio::test]
+    async fn authenticate_token_v4() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        // JWTs usually exceed the default command line length
+        let token = format!("{}.{}.{}", "a".repeat(36), "b".repeat(300), "c".repeat(43));
+        let handshake = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v4.0 (2023.1) :: SLPROTO:4.0 AUTH:TOKEN\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "slproto 4.0");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert!(lines
+                .next_line()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with("useragent slink/"));
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(
+                lines.next_line().await.unwrap().unwrap(),
+                format!("auth token {}", token)
+            );
+            write.write_all(b"OK\r\n").await.unwrap();
+        };
+        let slink_connection_info = SeedLinkConnectionInfo {
+            token: Some(token.clone()),
+            ..Default::default()
+        };
+        let (con, ()) = tokio::join!(
+            Connection::from_duplex(client_stream, &slink_connection_info),
+            handshake
+        );
+        assert!(con.is_ok());
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[tokio::test]
+    async fn authenticate_v4() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let handshake = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v4.0 (2023.1) :: SLPROTO:4.0 AUTH:USERPASS\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "slproto 4.0");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert!(lines
+                .next_line()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with("useragent slink/"));
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(
+                lines.next_line().await.unwrap().unwrap(),
+                "auth userpass foo bar"
+            );
+            write
+                .write_all(b"ERROR AUTH invalid credentials\r\n")
+                .await
+                .unwrap();
+        };
+        let slink_connection_info = SeedLinkConnectionInfo {
+            username: Some("foo".to_string()),
+            password: Some("bar".to_string()),
+            ..Default::default()
+        };
+        let (con, ()) = tokio::join!(
+            Connection::from_duplex(client_stream, &slink_connection_info),
+            handshake
+        );
+        let err = con.err().unwrap();
+        assert!(matches!(err, SeedLinkError::AuthenticationFailed(_)));
+        assert_eq!(
+            err.protocol_error_code(),
+            Some(&crate::ErrorCodeV4::AuthenticationFailed)
+        );
+        assert_eq!(err.to_string(), "ERROR AUTH: invalid credentials");
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[derive(Debug, Default)]
+    struct TokenProvider {
+        issued: AtomicU64,
+    }
+
+    #[cfg(feature = "v4-client")]
+    impl CredentialsProvider for TokenProvider {
+        fn credentials(
+            &self,
+        ) -> futures::future::BoxFuture<'_, SeedLinkResult<crate::Credentials>> {
+            let issued = self.issued.fetch_add(1, Ordering::Relaxed) + 1;
+            Box::pin(future::ready(Ok(crate::Credentials {
+                auth: crate::AuthV4::JWT(format!("token{}", issued)),
+                expires: Some(OffsetDateTime::now_utc() + time::Duration::minutes(10)),
+            })))
+        }
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[tokio::test(start_paused = true)]
+    async fn refresh_credentials_v4() {
+        let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+        let (read, mut write) = tokio::io::split(server_stream);
+        let mut lines = BufReader::new(read).lines();
+
+        let handshake = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(
+                    b"SeedLink v4.0 (2023.1) :: SLPROTO:4.0 AUTH:TOKEN AUTH:REFRESH\r\nGEOFON\r\n",
+                )
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "slproto 4.0");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert!(lines
+                .next_line()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with("useragent slink/"));
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(
+                lines.next_line().await.unwrap().unwrap(),
+                "auth token token1"
+            );
+            write.write_all(b"OK\r\n").await.unwrap();
+        };
+        let provider = Arc::new(TokenProvider::default());
+        let slink_connection_info = SeedLinkConnectionInfo {
+            credentials_provider: Some(provider.clone()),
+            ..Default::default()
+        };
+        let (con, ()) = tokio::join!(
+            Connection::from_duplex(client_stream, &slink_connection_info),
+            handshake
+        );
+        let mut con = con.unwrap();
+
+        con.add_stream("GE", "WLF", &None, &Some("2a".to_string()), &None)
+            .unwrap();
+        let configure = async {
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "station GE_WLF");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "data 42");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "end");
+        };
+        let (res, ()) = tokio::join!(
+            con.configure(DataTransferMode::RealTime, None, false),
+            configure
+        );
+        res.unwrap();
+
+        let packets = con.packets(None);
+        tokio::pin!(packets);
+
+        // credentials are refreshed before they expire
+        let refresh = async {
+            assert_eq!(
+                lines.next_line().await.unwrap().unwrap(),
+                "auth token token2"
+            );
+            write.write_all(b"OK\r\n").await.unwrap();
+            let mut buf = Vec::new();
+            crate::wire::v4::write_packet(*b"2D", 42, b"GE_WLF", &[0; 512], &mut buf).unwrap();
+            write.write_all(&buf).await.unwrap();
+        };
+        let (item, ()) = tokio::join!(packets.next(), refresh);
+        assert!(matches!(
+            item,
+            Some(StreamItem::Packet(SeedLinkPacket::V4(packet))) if packet.sequence_number() == 42
+        ));
+        assert_eq!(provider.issued.load(Ordering::Relaxed), 2);
+
+        // errors other than authentication failures do not answer the refresh
+        let refresh = async {
+            assert_eq!(
+                lines.next_line().await.unwrap().unwrap(),
+                "auth token token3"
+            );
+            write
+                .write_all(b"ERROR LIMIT too many requests\r\n")
+                .await
+                .unwrap();
+            let mut buf = Vec::new();
+            crate::wire::v4::write_packet(*b"2D", 43, b"GE_WLF", &[0; 512], &mut buf).unwrap();
+            write.write_all(&buf).await.unwrap();
+        };
+        let (item, ()) = tokio::join!(packets.next(), refresh);
+        assert!(matches!(
+            item,
+            Some(StreamItem::Packet(SeedLinkPacket::V4(packet))) if packet.sequence_number() == 43
+        ));
+
+        // a rejected refresh terminates the stream
+        let refresh = async {
+            write
+                .write_all(b"ERROR AUTH token expired\r\n")
+                .await
+                .unwrap();
+        };
+        let (item, ()) = tokio::join!(packets.next(), refresh);
+        assert!(matches!(
+            item,
+            Some(StreamItem::End(StreamEnd::Error(
+                SeedLinkError::AuthenticationFailed(_)
+            )))
+        ));
+    }
+
+    #[cfg(feature = "v4-client")]
+    #[tokio::test]
+    async fn reconnect_credentials_v4() {
+        let provider = Arc::new(TokenProvider::default());
+        let slink_connection_info = SeedLinkConnectionInfo {
+            credentials_provider: Some(provider.clone()),
+            ..Default::default()
+        };
+
+        // credentials are requested again when reconnecting
+        for token in ["token1", "token2"] {
+            let (client_stream, server_stream) = tokio::io::duplex(4 * 1024);
+            let (read, mut write) = tokio::io::split(server_stream);
+            let mut lines = BufReader::new(read).lines();
+
+            let handshake = async {
+                assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+                write
+                    .write_all(b"SeedLink v4.0 (2023.1) :: SLPROTO:4.0 AUTH:TOKEN\r\nGEOFON\r\n")
+                    .await
+                    .unwrap();
+                assert_eq!(lines.next_line().await.unwrap().unwrap(), "slproto 4.0");
+                write.write_all(b"OK\r\n").await.unwrap();
+                assert!(lines
+                    .next_line()
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .starts_with("useragent slink/"));
+                write.write_all(b"OK\r\n").await.unwrap();
+                assert_eq!(
+                    lines.next_line().await.unwrap().unwrap(),
+                    format!("auth token {}", token)
+                );
+                write.write_all(b"OK\r\n").await.unwrap();
+            };
+            let (con, ()) = tokio::join!(
+                Connection::from_duplex(client_stream, &slink_connection_info),
+                handshake
+            );
+            assert!(con.is_ok());
+        }
+        assert_eq!(provider.issued.load(Ordering::Relaxed), 2);
+    }
 }
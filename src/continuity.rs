@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::stream::Stream;
+use pin_project_lite::pin_project;
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::trace;
+use crate::{FDSNSourceId, SeedLinkPacket, SeedLinkResult, StreamItem};
+
+/// Maximum `v3` sequence number (i.e. `FFFFFF`), wrapping to `0`.
+const MAX_SEQ_NUM_V3: u64 = 0xFFFFFF;
+
+/// Position within a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamPosition {
+    /// Packet sequence number.
+    SequenceNumber(u64),
+    /// Record start time.
+    Time(OffsetDateTime),
+}
+
+/// Discontinuity detected within a stream.
+///
+/// Sequence numbers are checked per station, while record times are checked per stream.
+#[derive(Debug, Clone)]
+pub struct GapDetected {
+    /// Source identifier of the record received.
+    pub sid: FDSNSourceId,
+    /// Position expected.
+    pub expected: StreamPosition,
+    /// Position received.
+    pub actual: StreamPosition,
+}
+
+impl GapDetected {
+    /// Returns whether the record received precedes the expected position, i.e. the record was
+    /// received out of order (or was received before).
+    pub fn is_out_of_order(&self) -> bool {
+        match (self.expected, self.actual) {
+            (StreamPosition::SequenceNumber(expected), StreamPosition::SequenceNumber(actual)) => {
+                actual < expected
+            }
+            (StreamPosition::Time(expected), StreamPosition::Time(actual)) => actual < expected,
+            _ => false,
+        }
+    }
+}
+
+/// Extension trait for packet streams (see [`Connection::packets`](crate::Connection::packets)).
+pub trait ContinuityTrackingExt: Stream<Item = StreamItem> + Sized {
+    /// Wraps the packet stream keeping track of the continuity of the data packets passing
+    /// through. `on_gap` is called for each gap detected.
+    ///
+    /// Items are passed through unchanged.
+    fn with_continuity_tracking<F>(self, on_gap: F) -> ContinuityTracking<Self, F>
+    where
+        F: FnMut(&GapDetected),
+    {
+        ContinuityTracking {
+            packets: self,
+            tracker: ContinuityTracker::default(),
+            on_gap,
+        }
+    }
+}
+
+impl<S: Stream<Item = StreamItem>> ContinuityTrackingExt for S {}
+
+pin_project! {
+    /// Stream adapter for [`ContinuityTrackingExt::with_continuity_tracking`].
+    #[must_use = "streams do nothing unless polled"]
+    pub struct ContinuityTracking<S, F> {
+        #[pin]
+        packets: S,
+        tracker: ContinuityTracker,
+        on_gap: F,
+    }
+}
+
+impl<S, F> Stream for ContinuityTracking<S, F>
+where
+    S: Stream<Item = StreamItem>,
+    F: FnMut(&GapDetected),
+{
+    type Item = StreamItem;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let item = match this.packets.poll_next(cx) {
+            Poll::Ready(Some(item)) => item,
+            other => return other,
+        };
+
+        if let StreamItem::Packet(ref packet) = item {
+            match this.tracker.track(packet) {
+                Ok(gaps) => gaps.iter().for_each(|gap| (this.on_gap)(gap)),
+                Err(e) => warn!(target: trace::STREAM, "failed to track stream continuity: {}", e),
+            }
+        }
+
+        Poll::Ready(Some(item))
+    }
+}
+
+/// Keeps track of the sequence numbers per station and the record end times per stream.
+#[derive(Debug, Default)]
+struct ContinuityTracker {
+    // last sequence number received per station
+    stations: HashMap<String, u64>,
+    // expected start time of the next record per stream
+    streams: HashMap<String, (OffsetDateTime, Duration)>,
+}
+
+impl ContinuityTracker {
+    /// Tracks the data packet `packet`. Packets other than data packets are ignored.
+    fn track(&mut self, packet: &SeedLinkPacket) -> SeedLinkResult<Vec<GapDetected>> {
+        let (sid, seq_num, ms_record) = match packet.to_record() {
+            Some(res) => res?,
+            None => return Ok(vec![]),
+        };
+
+        let max_seq_num = match packet {
+            SeedLinkPacket::V3(_) => MAX_SEQ_NUM_V3,
+            SeedLinkPacket::V4(_) => u64::MAX,
+        };
+
+        Ok(self.observe(
+            sid,
+            seq_num,
+            max_seq_num,
+            ms_record.start_time()?,
+            ms_record.end_time()?,
+            ms_record.sample_rate_hz(),
+        ))
+    }
+
+    fn observe(
+        &mut self,
+        sid: FDSNSourceId,
+        seq_num: u64,
+        max_seq_num: u64,
+        start_time: OffsetDateTime,
+        end_time: OffsetDateTime,
+        sample_rate: f64,
+    ) -> Vec<GapDetected> {
+        let mut gaps = vec![];
+
+        let station_id = format!("{}_{}", sid.nslc.net, sid.nslc.sta);
+        if let Some(last) = self.stations.insert(station_id, seq_num) {
+            let expected = if last >= max_seq_num { 0 } else { last + 1 };
+            if seq_num != expected {
+                gaps.push(GapDetected {
+                    sid: sid.clone(),
+                    expected: StreamPosition::SequenceNumber(expected),
+                    actual: StreamPosition::SequenceNumber(seq_num),
+                });
+            }
+        }
+
+        let next = (sample_rate > 0.0 && sample_rate.is_finite()).then(|| {
+            let sample_period = Duration::from_secs_f64(1.0 / sample_rate);
+            // XXX(damb): tolerate half a sample period (as libmseed does)
+            (end_time + sample_period, sample_period / 2)
+        });
+        let last = match next {
+            Some(next) => self.streams.insert(sid.to_string(), next),
+            None => self.streams.remove(&sid.to_string()),
+        };
+        if let Some((expected, tolerance)) = last {
+            if (start_time - expected).abs() > tolerance {
+                gaps.push(GapDetected {
+                    sid,
+                    expected: StreamPosition::Time(expected),
+                    actual: StreamPosition::Time(start_time),
+                });
+            }
+        }
+
+        gaps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use time::macros::datetime;
+
+    #[test]
+    fn detect_gaps() {
+        let sid: FDSNSourceId = "FDSN:GE_WLF_00_B_H_Z".parse().unwrap();
+        let mut tracker = ContinuityTracker::default();
+
+        let gaps = tracker.observe(
+            sid.clone(),
+            MAX_SEQ_NUM_V3,
+            MAX_SEQ_NUM_V3,
+            datetime!(2023-01-01 00:00:00 UTC),
+            datetime!(2023-01-01 00:00:09.95 UTC),
+            20.0,
+        );
+        assert!(gaps.is_empty());
+
+        // sequence numbers wrap
+        let gaps = tracker.observe(
+            sid.clone(),
+            0,
+            MAX_SEQ_NUM_V3,
+            datetime!(2023-01-01 00:00:10 UTC),
+            datetime!(2023-01-01 00:00:19.95 UTC),
+            20.0,
+        );
+        assert!(gaps.is_empty());
+
+        let gaps = tracker.observe(
+            sid.clone(),
+            2,
+            MAX_SEQ_NUM_V3,
+            datetime!(2023-01-01 00:00:30 UTC),
+            datetime!(2023-01-01 00:00:39.95 UTC),
+            20.0,
+        );
+        assert_eq!(gaps.len(), 2);
+        assert_eq!(gaps[0].expected, StreamPosition::SequenceNumber(1));
+        assert_eq!(gaps[0].actual, StreamPosition::SequenceNumber(2));
+        assert_eq!(
+            gaps[1].expected,
+            StreamPosition::Time(datetime!(2023-01-01 00:00:20 UTC))
+        );
+        assert!(!gaps[1].is_out_of_order());
+
+        let gaps = tracker.observe(
+            sid,
+            1,
+            MAX_SEQ_NUM_V3,
+            datetime!(2023-01-01 00:00:20 UTC),
+            datetime!(2023-01-01 00:00:29.95 UTC),
+            20.0,
+        );
+        assert_eq!(gaps.len(), 2);
+        assert!(gaps.iter().all(GapDetected::is_out_of_order));
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{self, BoxFuture};
+use time::OffsetDateTime;
+use tokio::time as tokio_time;
+
+use crate::{AuthV4, SeedLinkResult};
+
+/// Margin before the credentials expire within which credentials are refreshed while streaming.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Credentials authenticating SeedLink `v4` connections (see [`CredentialsProvider`]).
+#[derive(Clone)]
+pub struct Credentials {
+    /// The authentication method, e.g. a JSON Web Token.
+    pub auth: AuthV4,
+    /// The time the credentials expire, if any.
+    pub expires: Option<OffsetDateTime>,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // XXX(damb): credentials are never logged
+        f.debug_struct("Credentials")
+            .field("expires", &self.expires)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Trait implemented by providers of credentials, e.g. of short-lived JSON Web Tokens issued by an
+/// identity provider.
+///
+/// Connections request credentials whenever authenticating, i.e. when connecting (including
+/// reconnects) and, while streaming packets, before the credentials expire if the server
+/// advertises the [`CAPABILITY_AUTH_REFRESH_V4`](crate::CAPABILITY_AUTH_REFRESH_V4) capability.
+pub trait CredentialsProvider: fmt::Debug + Send + Sync {
+    /// Returns the current credentials.
+    fn credentials(&self) -> BoxFuture<'_, SeedLinkResult<Credentials>>;
+}
+
+/// Refresh of expiring credentials while streaming packets.
+#[derive(Debug, Default)]
+pub(crate) struct CredentialsRefresh {
+    provider: Option<Arc<dyn CredentialsProvider>>,
+    /// Time the credentials are refreshed, if any.
+    due: Option<tokio_time::Instant>,
+    /// Time the credentials requested most recently expire while awaiting the response to the
+    /// re-authentication.
+    pending: Option<Option<OffsetDateTime>>,
+}
+
+impl CredentialsRefresh {
+    /// Creates a refresh of the credentials provided by `provider` which expire at `expires`.
+    pub fn new(provider: Arc<dyn CredentialsProvider>, expires: Option<OffsetDateTime>) -> Self {
+        let mut rv = Self {
+            provider: Some(provider),
+            due: None,
+            pending: None,
+        };
+        rv.schedule(expires);
+
+        rv
+    }
+
+    /// Completes once the credentials are due to be refreshed. Never completes while awaiting the
+    /// response to a re-authentication.
+    pub async fn due(&self) {
+        match self.due {
+            Some(due) if self.pending.is_none() => tokio_time::sleep_until(due).await,
+            _ => future::pending().await,
+        }
+    }
+
+    /// Requests fresh credentials from the provider and marks the re-authentication as pending.
+    pub async fn refresh(&mut self) -> SeedLinkResult<AuthV4> {
+        let provider = self
+            .provider
+            .as_ref()
+            .expect("credentials refresh without provider");
+        let credentials = provider.credentials().await?;
+        self.pending = Some(credentials.expires);
+
+        Ok(credentials.auth)
+    }
+
+    /// Returns whether a re-authentication awaits the server's response.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Acknowledges the pending re-authentication and schedules the next refresh.
+    pub fn ack(&mut self) {
+        if let Some(expires) = self.pending.take() {
+            self.schedule(expires);
+        }
+    }
+
+    /// Schedules the refresh of the credentials expiring at `expires`, i.e. [`REFRESH_MARGIN`]
+    /// before expiry but not before half of the remaining lifetime elapsed.
+    fn schedule(&mut self, expires: Option<OffsetDateTime>) {
+        self.due = expires.map(|expires| {
+            let remaining: Duration = (expires - OffsetDateTime::now_utc())
+                .try_into()
+                .unwrap_or(Duration::ZERO);
+            tokio_time::Instant::now() + remaining - REFRESH_MARGIN.min(remaining / 2)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use futures::FutureExt;
+
+    #[derive(Debug)]
+    struct StaticProvider;
+
+    impl CredentialsProvider for StaticProvider {
+        fn credentials(&self) -> BoxFuture<'_, SeedLinkResult<Credentials>> {
+            future::ready(Ok(Credentials {
+                auth: AuthV4::JWT("token".to_string()),
+                expires: None,
+            }))
+            .boxed()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn schedule_refresh() {
+        let expires = OffsetDateTime::now_utc() + time::Duration::minutes(10);
+        let mut refresh = CredentialsRefresh::new(Arc::new(StaticProvider), Some(expires));
+        let due = refresh.due.unwrap() - tokio_time::Instant::now();
+        assert!(due <= Duration::from_secs(9 * 60) && due > Duration::from_secs(8 * 60));
+
+        // short-lived credentials are refreshed after half of their lifetime
+        refresh.schedule(Some(
+            OffsetDateTime::now_utc() + time::Duration::seconds(30),
+        ));
+        let due = refresh.due.unwrap() - tokio_time::Instant::now();
+        assert!(due <= Duration::from_secs(15) && due > Duration::from_secs(14));
+
+        refresh.due().await;
+        assert_eq!(
+            refresh.refresh().await.unwrap(),
+            AuthV4::JWT("token".to_string())
+        );
+        assert!(refresh.is_pending());
+        refresh.ack();
+        assert!(!refresh.is_pending());
+        assert!(refresh.due.is_none());
+    }
+}
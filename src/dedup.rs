@@ -0,0 +1,138 @@
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::Stream;
+use pin_project_lite::pin_project;
+use tracing::debug;
+
+use crate::trace;
+use crate::StreamItem;
+
+/// Default number of sequence numbers kept per station.
+const DEFAULT_WINDOW: usize = 16;
+
+/// Sequence numbers of the packets delivered most recently per station (i.e. `NET_STA`), e.g.
+/// in order to suppress the packets resent by servers when resuming.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deduplication {
+    window: usize,
+    delivered: HashMap<String, VecDeque<u64>>,
+}
+
+impl Default for Deduplication {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl Deduplication {
+    /// Creates the deduplication keeping the sequence numbers of up to `window` packets per
+    /// station.
+    ///
+    /// Panics if `window` is zero.
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "window must be greater than zero");
+
+        Self {
+            window,
+            delivered: HashMap::new(),
+        }
+    }
+
+    /// Marks the packet `seq_num` of the station `sta` of network `net` as delivered, e.g. the
+    /// sequence number stored when resuming from a statefile.
+    pub fn with_delivered(mut self, net: &str, sta: &str, seq_num: u64) -> Self {
+        self.deliver(format!("{}_{}", net, sta), seq_num);
+        self
+    }
+
+    /// Records the delivery of the packet `seq_num` of the station `station_id`. Returns `false`
+    /// if the packet was delivered before.
+    fn deliver(&mut self, station_id: String, seq_num: u64) -> bool {
+        let delivered = self.delivered.entry(station_id).or_default();
+        if delivered.contains(&seq_num) {
+            return false;
+        }
+
+        if delivered.len() == self.window {
+            delivered.pop_front();
+        }
+        delivered.push_back(seq_num);
+        true
+    }
+}
+
+/// Extension trait for packet streams (see [`Connection::packets`](crate::Connection::packets)).
+pub trait DeduplicationExt: Stream<Item = StreamItem> + Sized {
+    /// Wraps the packet stream dropping data packets whose station and sequence number were
+    /// delivered before (according to `dedup`), such that downstream writers do not archive
+    /// duplicates. Other items are passed through.
+    fn with_deduplication(self, dedup: Deduplication) -> Deduplicated<Self> {
+        Deduplicated {
+            packets: self,
+            dedup,
+        }
+    }
+}
+
+impl<S: Stream<Item = StreamItem>> DeduplicationExt for S {}
+
+pin_project! {
+    /// Stream adapter for [`DeduplicationExt::with_deduplication`].
+    #[must_use = "streams do nothing unless polled"]
+    pub struct Deduplicated<S> {
+        #[pin]
+        packets: S,
+        dedup: Deduplication,
+    }
+}
+
+impl<S> Stream for Deduplicated<S>
+where
+    S: Stream<Item = StreamItem>,
+{
+    type Item = StreamItem;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            let item = match this.packets.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => item,
+                other => return other,
+            };
+
+            if let StreamItem::Packet(ref packet) = item {
+                // XXX(damb): failures are reported when consuming the records
+                if let Some(Ok((sid, seq_num, _))) = packet.to_record() {
+                    let station_id = format!("{}_{}", sid.nslc.net, sid.nslc.sta);
+                    if !this.dedup.deliver(station_id, seq_num) {
+                        debug!(target: trace::STREAM, "dropping duplicate packet ({}, {})", sid, seq_num);
+                        continue;
+                    }
+                }
+            }
+
+            return Poll::Ready(Some(item));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn deliver_window() {
+        let mut dedup = Deduplication::new(2).with_delivered("GE", "WLF", 41);
+
+        assert!(!dedup.deliver("GE_WLF".to_string(), 41));
+        assert!(dedup.deliver("GE_WLF".to_string(), 42));
+        assert!(dedup.deliver("GE_APE".to_string(), 41));
+        assert!(!dedup.deliver("GE_WLF".to_string(), 42));
+        assert!(dedup.deliver("GE_WLF".to_string(), 43));
+        // evicted from the window
+        assert!(dedup.deliver("GE_WLF".to_string(), 41));
+    }
+}
@@ -0,0 +1,125 @@
+use std::io;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{SeedLinkPacket, SeedLinkPacketV3, SeedLinkResult};
+
+/// Version of the [`PacketEnvelope`] schema. Incremented on incompatible changes, only.
+pub const ENVELOPE_SCHEMA_VERSION: u8 = 1;
+
+/// Stable, protocol version independent envelope of a data packet, e.g. shared by exporters
+/// publishing packets to message buses.
+///
+/// Envelopes are serialized by means of [`serde`], e.g. to JSON (see
+/// [`PacketEnvelope::to_json`]) or, if the `cbor` feature is enabled, to CBOR (see
+/// `PacketEnvelope::to_cbor`). The payload (i.e. the record) is serialized as byte string (i.e.
+/// as array of bytes in JSON).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PacketEnvelope {
+    /// Version of the envelope schema (see [`ENVELOPE_SCHEMA_VERSION`]).
+    pub schema_version: u8,
+    /// SeedLink protocol version the packet was received with (i.e. `3` or `4`).
+    pub protocol_version: u8,
+    /// Packet sequence number.
+    pub seq_num: u64,
+    /// Station identifier (i.e. `NET_STA`).
+    pub station_id: String,
+    /// Data format code (e.g. `2` for miniSEED 2.x).
+    pub format: char,
+    /// Subformat code (e.g. `D` for data records).
+    pub subformat: char,
+    /// Time the packet was received.
+    #[serde(with = "time::serde::rfc3339")]
+    pub received: OffsetDateTime,
+    /// Packet payload, i.e. the raw record.
+    pub payload: Bytes,
+}
+
+impl PacketEnvelope {
+    /// Creates the envelope of the data packet `packet` received at `received`. Returns `None`
+    /// if the packet is not a data packet.
+    ///
+    /// Note that the payload is not copied.
+    pub fn new(packet: &SeedLinkPacket, received: OffsetDateTime) -> Option<SeedLinkResult<Self>> {
+        let (sid, seq_num, _) = match packet.to_record()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let (protocol_version, format, subformat, raw, payload_len) = match packet {
+            SeedLinkPacket::V3(SeedLinkPacketV3::GenericData(packet)) => {
+                (3, '2', 'D', packet.raw_bytes(), packet.raw_payload().len())
+            }
+            SeedLinkPacket::V3(SeedLinkPacketV3::Info(_)) => return None,
+            SeedLinkPacket::V4(packet) => (
+                4,
+                packet.format_code(),
+                packet.subformat_code(),
+                packet.raw_bytes(),
+                packet.payload_raw().len(),
+            ),
+        };
+
+        Some(Ok(Self {
+            schema_version: ENVELOPE_SCHEMA_VERSION,
+            protocol_version,
+            seq_num,
+            station_id: format!("{}_{}", sid.nslc.net, sid.nslc.sta),
+            format,
+            subformat,
+            received,
+            // XXX(damb): the payload trails the packet header
+            payload: raw.slice(raw.len() - payload_len..),
+        }))
+    }
+
+    /// Serializes the envelope to JSON.
+    pub fn to_json(&self) -> SeedLinkResult<String> {
+        serde_json::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()).into())
+    }
+
+    /// Serializes the envelope to CBOR.
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self) -> SeedLinkResult<Vec<u8>> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(self, &mut buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use time::macros::datetime;
+
+    #[test]
+    fn serialize_json() {
+        let envelope = PacketEnvelope {
+            schema_version: ENVELOPE_SCHEMA_VERSION,
+            protocol_version: 4,
+            seq_num: 42,
+            station_id: "GE_WLF".to_string(),
+            format: '2',
+            subformat: 'D',
+            received: datetime!(2023-01-01 12:00 UTC),
+            payload: Bytes::from_static(&[1, 2, 3]),
+        };
+
+        let json = envelope.to_json().unwrap();
+        assert_eq!(
+            json,
+            r#"{"schema_version":1,"protocol_version":4,"seq_num":42,"station_id":"GE_WLF","format":"2","subformat":"D","received":"2023-01-01T12:00:00Z","payload":[1,2,3]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<PacketEnvelope>(&json).unwrap(),
+            envelope
+        );
+    }
+}
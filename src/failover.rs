@@ -0,0 +1,249 @@
+use std::time::Duration;
+
+use futures::stream::{self, LocalBoxStream, Stream, StreamExt};
+use tokio::time as tokio_time;
+use tracing::{info, warn};
+
+use crate::metrics;
+use crate::trace;
+use crate::{
+    Client, Connection, DataTransferMode, IntoConnectionInfo, SeedLinkError, SeedLinkResult,
+    StateDB, StateTrackingExt, StreamConfig, StreamEnd, StreamItem,
+};
+
+/// Default delay before failing over after the active connection terminated.
+const DEFAULT_FAILOVER_DELAY: Duration = Duration::from_secs(1);
+
+/// Client streaming from a prioritized list of SeedLink servers (e.g. a primary and a backup
+/// data center).
+///
+/// Connections are established to the first server (in order of priority) accepting the
+/// connection and the stream configuration. If the active connection fails, the client
+/// transparently fails over, i.e. connects again starting with the server of highest priority.
+/// Streams are resumed from the sequence numbers kept track of in the shared `StateDB`.
+///
+/// Note that all servers share the namespace of the `StateDB`, i.e. the servers are expected to
+/// provide the same sequence numbers (e.g. servers chained from a common source). Otherwise,
+/// resume streams by time (see [`FailoverClient::resume_by_time`]).
+///
+/// Example usage::
+///
+/// ```rust,no_run
+/// let db = slink::StateDB::open("state.db").await.unwrap();
+/// let mut client =
+///     slink::FailoverClient::open(["slink://primary/", "slink://backup/"], db).unwrap();
+/// client.add_stream_config(slink::StreamConfig::try_new("GE", "WLF").unwrap());
+/// let packets = client.packets();
+/// ```
+#[derive(Debug)]
+pub struct FailoverClient {
+    clients: Vec<Client>,
+    state_db: StateDB,
+    stream_configs: Vec<StreamConfig>,
+    data_transfer_mode: DataTransferMode,
+    keep_alive_interval: Option<Duration>,
+    failover_delay: Duration,
+    resume_by_time: bool,
+}
+
+impl FailoverClient {
+    /// Creates a client from the connection parameters `params` ordered by priority. Fails if no
+    /// connection parameters are provided.
+    pub fn open<T, I>(params: I, state_db: StateDB) -> SeedLinkResult<Self>
+    where
+        T: IntoConnectionInfo,
+        I: IntoIterator<Item = T>,
+    {
+        let clients = params
+            .into_iter()
+            .map(Client::open)
+            .collect::<SeedLinkResult<Vec<Client>>>()?;
+        if clients.is_empty() {
+            return Err(SeedLinkError::InvalidClientConfig(
+                "no servers configured".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            clients,
+            state_db,
+            stream_configs: Vec::new(),
+            data_transfer_mode: DataTransferMode::RealTime,
+            keep_alive_interval: None,
+            failover_delay: DEFAULT_FAILOVER_DELAY,
+            resume_by_time: false,
+        })
+    }
+
+    /// Adds the stream configuration `stream_config` requested from the servers.
+    pub fn add_stream_config(&mut self, stream_config: StreamConfig) {
+        self.stream_configs.push(stream_config);
+    }
+
+    /// Sets the data transfer mode connections are configured with. Defaults to
+    /// [`DataTransferMode::RealTime`].
+    pub fn data_transfer_mode(&mut self, data_transfer_mode: DataTransferMode) {
+        self.data_transfer_mode = data_transfer_mode;
+    }
+
+    /// Sets the keepalive interval of connections (see [`Connection::packets`]).
+    pub fn keep_alive_interval(&mut self, interval: Option<Duration>) {
+        self.keep_alive_interval = interval;
+    }
+
+    /// Sets the delay before failing over after the active connection terminated.
+    pub fn failover_delay(&mut self, delay: Duration) {
+        self.failover_delay = delay;
+    }
+
+    /// Enables or disables resuming streams by the end time of the most recent record received
+    /// instead of the sequence number (see [`Connection::recover_state_by_time`]), e.g. if the
+    /// servers provide different sequence numbers. Disabled by default.
+    pub fn resume_by_time(&mut self, enabled: bool) {
+        self.resume_by_time = enabled;
+    }
+
+    /// Returns a stream producing the packets received from the active connection.
+    ///
+    /// The stream fails over if the active connection fails or is closed by the server (see
+    /// [`StreamEnd::Error`] and [`StreamEnd::ServerClosed`]). The stream terminates with a
+    /// [`StreamItem::End`] item if the data transfer terminated otherwise or if none of the
+    /// servers accepted the connection.
+    pub fn packets(self) -> impl Stream<Item = StreamItem> {
+        let state: Option<(Self, Option<LocalBoxStream<'static, StreamItem>>)> = Some((self, None));
+        stream::unfold(state, |state| async move {
+            let (client, mut active) = state?;
+            loop {
+                let packets = match active.as_mut() {
+                    Some(packets) => packets,
+                    None => match client.connect().await {
+                        Ok((_, con)) => active.insert(
+                            con.packets(client.keep_alive_interval)
+                                .with_state_tracking(client.state_db.clone())
+                                .boxed_local(),
+                        ),
+                        Err(e) => return Some((StreamItem::End(StreamEnd::Error(e)), None)),
+                    },
+                };
+
+                match packets.next().await {
+                    Some(StreamItem::End(StreamEnd::Error(e))) => {
+                        warn!(target: trace::CONNECTION, "connection failed ({}): failing over", e);
+                    }
+                    Some(StreamItem::End(StreamEnd::ServerClosed(msg))) => {
+                        warn!(target: trace::CONNECTION,
+                            "connection closed by server ({}): failing over",
+                            msg.unwrap_or_default()
+                        );
+                    }
+                    Some(item @ StreamItem::End(_)) => return Some((item, None)),
+                    Some(item) => return Some((item, Some((client, active)))),
+                    None => return None,
+                }
+
+                active = None;
+                metrics::reconnect();
+                tokio_time::sleep(client.failover_delay).await;
+            }
+        })
+        .boxed_local()
+    }
+
+    /// Connects to the servers in order of priority and returns the index of the server
+    /// connected to together with the configured connection.
+    async fn connect(&self) -> SeedLinkResult<(usize, Connection)> {
+        let mut last_err = None;
+        for (i, client) in self.clients.iter().enumerate() {
+            match self.configure(client).await {
+                Ok(con) => {
+                    info!(target: trace::CONNECTION, "connected to {}", con.addr());
+                    return Ok((i, con));
+                }
+                Err(e) => {
+                    warn!(target: trace::CONNECTION,
+                        "failed to connect to {}: {}",
+                        client.get_connection_info().addr,
+                        e
+                    );
+                    last_err = Some(e);
+                }
+            }
+        }
+
+        Err(last_err.unwrap())
+    }
+
+    async fn configure(&self, client: &Client) -> SeedLinkResult<Connection> {
+        let mut con = client.get_connection().await?;
+        for stream_config in &self.stream_configs {
+            con.add_stream_config(stream_config.clone())?;
+        }
+        let mut state_db = self.state_db.clone();
+        if self.resume_by_time {
+            con.recover_state_by_time(&mut state_db, false).await?;
+        } else {
+            con.recover_state(&mut state_db, false).await?;
+        }
+        con.configure(self.data_transfer_mode, None, false).await?;
+
+        Ok(con)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn connect_backup() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        // XXX(damb): bind and drop to obtain an address refusing connections
+        let refusing = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let backup = listener.local_addr().unwrap();
+
+        let server = async {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+            write
+                .write_all(b"SeedLink v3.1 (2020.075)\r\nGEOFON\r\n")
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "station WLF GE");
+            write.write_all(b"OK\r\n").await.unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "data");
+            write.write_all(b"OK\r\n").await.unwrap();
+            (lines, write)
+        };
+
+        let state_db = StateDB::open(":memory:").await.unwrap();
+        let mut client = FailoverClient::open(
+            [
+                format!("slink://{}/", refusing),
+                format!("slink://{}/", backup),
+            ],
+            state_db.clone(),
+        )
+        .unwrap();
+        client.add_stream_config(StreamConfig::try_new("GE", "WLF").unwrap());
+
+        let (res, _) = tokio::join!(client.connect(), server);
+        let (i, con) = res.unwrap();
+        assert_eq!(i, 1);
+        assert_eq!(con.addr().to_string(), backup.to_string());
+
+        assert!(matches!(
+            FailoverClient::open(Vec::<String>::new(), state_db),
+            Err(SeedLinkError::InvalidClientConfig(_))
+        ));
+    }
+}
@@ -1,10 +1,16 @@
+use bytes::Bytes;
+
 /// A frame in the SeedLink protocol.
+///
+/// Packet frames share the buffer the packets were decoded from, i.e. they are not copied.
 #[derive(Clone, Debug)]
 pub enum Frame {
     Line(Vec<u8>),
-    InfoPacket(Vec<u8>),
-    GenericDataPacket(Vec<u8>),
-    Error,
+    InfoPacket(Bytes),
+    GenericDataPacket(Bytes),
+    /// `ERROR` response, together with the reason of an extended reply (see
+    /// [`CAPABILITY_EXTREPLY_V3`](crate::CAPABILITY_EXTREPLY_V3)), if any.
+    Error(Option<String>),
     End,
     Ok,
 }
@@ -0,0 +1,258 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::stream::{self, LocalBoxStream, Stream, StreamExt};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info};
+
+use crate::trace;
+use crate::{
+    Client, Connection, DataTransferMode, SeedLinkError, SeedLinkPacket, SeedLinkResult,
+    StreamConfig, StreamItem,
+};
+
+/// Maximum `v3` sequence number (i.e. `FFFFFF`), wrapping to `0`.
+const MAX_SEQ_NUM_V3: u64 = 0xFFFFFF;
+
+/// Handle handing the packet stream of a connection over to another connection, e.g. during
+/// planned server maintenance (see [`Handover::packets`]).
+///
+/// The connection taking over is established and configured while the active connection keeps
+/// streaming. Streams are resumed from the packets delivered most recently, i.e. the connections
+/// overlap. Once configured, the packet stream switches to the connection taking over and drops
+/// the packets delivered before (i.e. the overlap), such that no packets are lost or duplicated.
+///
+/// Example usage::
+///
+/// ```rust,no_run
+/// use slink::{Client, Connection, DataTransferMode, Handover, StreamRequest};
+///
+/// let con = Connection::builder("slink://primary/")
+///     .unwrap()
+///     .stream(StreamRequest::new("GE", "WLF"))
+///     .connect()
+///     .await
+///     .unwrap();
+/// let (handover, packets) = Handover::packets(con, DataTransferMode::RealTime, None);
+///
+/// // before the primary server is restarted
+/// let backup = Client::open("slink://backup/").unwrap();
+/// handover.hand_over(&backup).await.unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Handover {
+    send: mpsc::Sender<HandoverRequest>,
+    subscription: Arc<Mutex<Subscription>>,
+}
+
+impl Handover {
+    /// Returns the stream producing the packets received from the configured connection `con`
+    /// together with the handle handing the stream over to other connections.
+    ///
+    /// `data_transfer_mode` is the data transfer mode connections taking over are configured
+    /// with. `keep_alive_interval` applies to all connections (see [`Connection::packets`]).
+    pub fn packets(
+        con: Connection,
+        data_transfer_mode: DataTransferMode,
+        keep_alive_interval: Option<Duration>,
+    ) -> (Self, impl Stream<Item = StreamItem>) {
+        let subscription = Arc::new(Mutex::new(Subscription {
+            stream_configs: con.stream_configs(),
+            data_transfer_mode,
+            tracker: SeqNumTracker::new(con.protocol_version() == 3),
+        }));
+        let (send, recv) = mpsc::channel(1);
+
+        let state = HandoverState {
+            active: con.packets(keep_alive_interval).boxed_local(),
+            recv,
+            subscription: subscription.clone(),
+            keep_alive_interval,
+        };
+        let packets = stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            loop {
+                tokio::select! {
+                    biased;
+                    Some(req) = state.recv.recv() => state.switch(req),
+                    item = state.active.next() => match item {
+                        Some(StreamItem::Packet(packet)) if state.is_duplicate(&packet) => {}
+                        Some(item @ StreamItem::End(_)) => return Some((item, None)),
+                        Some(item) => return Some((item, Some(state))),
+                        None => return None,
+                    }
+                }
+            }
+        })
+        .boxed_local();
+
+        (Self { send, subscription }, packets)
+    }
+
+    /// Establishes a connection by means of `client`, replicates the subscription of the active
+    /// connection and switches the packet stream to the connection established. Returns once
+    /// the packet stream switched.
+    ///
+    /// Note that the packet stream must be polled in order to switch. If establishing or
+    /// configuring the connection fails, the active connection keeps streaming.
+    pub async fn hand_over(&self, client: &Client) -> SeedLinkResult<()> {
+        let (stream_configs, data_transfer_mode) = {
+            let subscription = self.subscription.lock().unwrap();
+            (subscription.replicate(), subscription.data_transfer_mode)
+        };
+
+        let mut con = client.get_connection().await?;
+        for stream_config in stream_configs {
+            con.add_stream_config(stream_config)?;
+        }
+        con.configure(data_transfer_mode, None, false).await?;
+        info!(target: trace::CONNECTION, "handing over to {}", con.addr());
+
+        let terminated = || SeedLinkError::ClientError("packet stream terminated".to_string());
+        let (done, switched) = oneshot::channel();
+        self.send
+            .send(HandoverRequest { con, done })
+            .await
+            .map_err(|_| terminated())?;
+        switched.await.map_err(|_| terminated())
+    }
+}
+
+#[derive(Debug)]
+struct HandoverRequest {
+    con: Connection,
+    done: oneshot::Sender<()>,
+}
+
+/// Subscription shared by the packet stream and the [`Handover`] handles.
+#[derive(Debug)]
+struct Subscription {
+    stream_configs: Vec<StreamConfig>,
+    data_transfer_mode: DataTransferMode,
+    tracker: SeqNumTracker,
+}
+
+impl Subscription {
+    /// Returns the stream configurations resuming from the packets delivered most recently.
+    fn replicate(&self) -> Vec<StreamConfig> {
+        self.stream_configs
+            .iter()
+            .map(|stream_config| {
+                let station_id = format!("{}_{}", stream_config.network, stream_config.station);
+                match self.tracker.seq_nums.get(&station_id) {
+                    Some(seq_num) => stream_config.clone().with_seq_num(*seq_num),
+                    None => stream_config.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+struct HandoverState {
+    active: LocalBoxStream<'static, StreamItem>,
+    recv: mpsc::Receiver<HandoverRequest>,
+    subscription: Arc<Mutex<Subscription>>,
+    keep_alive_interval: Option<Duration>,
+}
+
+impl HandoverState {
+    /// Switches to the connection of the request `req`. The active connection is dropped.
+    fn switch(&mut self, req: HandoverRequest) {
+        let mut subscription = self.subscription.lock().unwrap();
+        subscription.stream_configs = req.con.stream_configs();
+        subscription.tracker.begin_overlap();
+        drop(subscription);
+
+        info!(target: trace::CONNECTION, "handed over to {}", req.con.addr());
+        self.active = req.con.packets(self.keep_alive_interval).boxed_local();
+        let _ = req.done.send(());
+    }
+
+    /// Returns whether the data packet `packet` was delivered before. Otherwise, the packet is
+    /// recorded as delivered.
+    fn is_duplicate(&self, packet: &SeedLinkPacket) -> bool {
+        let (sid, seq_num) = match packet.to_record() {
+            Some(Ok((sid, seq_num, _))) => (sid, seq_num),
+            // XXX(damb): failures are reported when consuming the records
+            Some(Err(_)) | None => return false,
+        };
+
+        let station_id = format!("{}_{}", sid.nslc.net, sid.nslc.sta);
+        let duplicate = !self
+            .subscription
+            .lock()
+            .unwrap()
+            .tracker
+            .deliver(station_id, seq_num);
+        if duplicate {
+            debug!(target: trace::STREAM, "dropping duplicate packet ({}, {})", sid, seq_num);
+        }
+
+        duplicate
+    }
+}
+
+/// Keeps track of the sequence numbers of the packets delivered per station.
+#[derive(Debug)]
+struct SeqNumTracker {
+    /// Whether sequence numbers wrap at [`MAX_SEQ_NUM_V3`].
+    wraps: bool,
+    /// Sequence numbers of the packets delivered most recently per station.
+    seq_nums: HashMap<String, u64>,
+    /// Stations of which the packets delivered before are expected to be received again.
+    overlapping: HashSet<String>,
+}
+
+impl SeqNumTracker {
+    fn new(wraps: bool) -> Self {
+        Self {
+            wraps,
+            seq_nums: HashMap::new(),
+            overlapping: HashSet::new(),
+        }
+    }
+
+    /// Marks the beginning of an overlap, i.e. the packets delivered before are received again.
+    fn begin_overlap(&mut self) {
+        self.overlapping = self.seq_nums.keys().cloned().collect();
+    }
+
+    /// Records the delivery of the packet `seq_num` of the station `station_id`. Returns `false`
+    /// if the packet was delivered before.
+    fn deliver(&mut self, station_id: String, seq_num: u64) -> bool {
+        if self.overlapping.contains(&station_id) {
+            let prev_seq_num = self.seq_nums[&station_id];
+            if seq_num <= prev_seq_num
+                && !(self.wraps && prev_seq_num - seq_num > MAX_SEQ_NUM_V3 / 2)
+            {
+                return false;
+            }
+            self.overlapping.remove(&station_id);
+        }
+
+        self.seq_nums.insert(station_id, seq_num);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn deliver_overlap() {
+        let mut tracker = SeqNumTracker::new(true);
+        assert!(tracker.deliver("GE_WLF".to_string(), 41));
+        assert!(tracker.deliver("GE_WLF".to_string(), 42));
+        assert!(tracker.deliver("GE_APE".to_string(), MAX_SEQ_NUM_V3));
+
+        tracker.begin_overlap();
+        assert!(!tracker.deliver("GE_WLF".to_string(), 41));
+        assert!(!tracker.deliver("GE_WLF".to_string(), 42));
+        assert!(tracker.deliver("GE_WLF".to_string(), 43));
+        assert!(tracker.deliver("GE_APE".to_string(), 0));
+        assert!(tracker.deliver("GE_KMBO".to_string(), 7));
+    }
+}
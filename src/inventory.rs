@@ -7,8 +7,8 @@ use std::ops::Deref;
 use time::OffsetDateTime;
 
 use crate::{
-    StationIdV4, StationV3, StationV4, InventoryV3, StreamFormatV4, StreamIdV4, StreamSubFormatV4,
-    StreamTypeV3, StreamV3, StreamV4,
+    SeedLinkResult, StationIdV4, StationV3, StationV4, InventoryV3, StreamFormatV4, StreamIdV4,
+    StreamSubFormatV4, StreamTypeV3, StreamV3, StreamV4,
 };
 
 const SID_DELIMITER: char = '_';
@@ -103,6 +103,36 @@ impl Station {
             None => None,
         }
     }
+
+    /// Returns the station if it matches `station_pattern` (i.e. `NET_STA`), retaining the
+    /// streams matching `stream_pattern` (i.e. `LOC_B_S_SS`).
+    ///
+    /// Returns `None` if the station does not match or none of its streams matches.
+    pub(crate) fn filter(
+        mut self,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+    ) -> Option<Self> {
+        if let Some(pat) = station_pattern {
+            if !matches(pat, &self.id.to_string()) {
+                return None;
+            }
+        }
+
+        if let Some(pat) = stream_pattern {
+            self.streams.retain(|s| matches(pat, &s.id.to_string()));
+            if self.streams.is_empty() {
+                return None;
+            }
+        }
+
+        Some(self)
+    }
+
+    /// Removes the streams of the station.
+    pub(crate) fn strip_streams(&mut self) {
+        self.streams.clear();
+    }
 }
 
 impl From<StationV3> for Station {
@@ -421,6 +451,15 @@ impl Hash for Stream {
     }
 }
 
+/// Enumeration of inventory detail levels.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InventoryLevel {
+    /// Station level, i.e. stations without streams
+    Station,
+    /// Stream level, i.e. stations including their streams
+    Stream,
+}
+
 /// Struct representing the SeedLink server's stream information available.
 #[derive(Debug, Clone, Default)]
 pub struct Inventory {
@@ -442,6 +481,20 @@ impl Inventory {
         }
     }
 
+    /// Returns whether any station of the inventory matches `station_pattern` (i.e. `NET_STA`).
+    ///
+    /// The pattern may contain the wildcards `*` and `?`.
+    pub(crate) fn contains_match(&self, station_pattern: &str) -> bool {
+        self.stations
+            .iter()
+            .any(|s| matches(station_pattern, &s.id.to_string()))
+    }
+
+    /// Returns an iterator over the stations of the inventory.
+    pub fn iter(&self) -> impl Iterator<Item = &Station> {
+        self.stations.iter()
+    }
+
     /// Adds a new station to the inventory.
     pub fn insert(&mut self, station: Station) -> Option<Station> {
         if let Some(idx) = self.stations_idx.get(&station.id) {
@@ -456,6 +509,66 @@ impl Inventory {
             None
         }
     }
+
+    /// Retains the stations matching `station_pattern` (i.e. `NET_STA`) and the streams
+    /// matching `stream_pattern` (i.e. `LOC_B_S_SS`).
+    ///
+    /// Patterns may contain the wildcards `*` and `?`. Stations without any stream matching
+    /// `stream_pattern` are removed.
+    pub fn filter(self, station_pattern: Option<&str>, stream_pattern: Option<&str>) -> Self {
+        self.stations
+            .into_iter()
+            .filter_map(|s| s.filter(station_pattern, stream_pattern))
+            .collect()
+    }
+}
+
+impl FromIterator<Station> for Inventory {
+    fn from_iter<I: IntoIterator<Item = Station>>(iter: I) -> Self {
+        let stas: Vec<Station> = iter.into_iter().collect();
+        let idx: HashMap<StationId, usize> = stas
+            .iter()
+            .enumerate()
+            .map(|(idx, s)| (s.id.clone(), idx))
+            .collect();
+        Self {
+            stations: stas,
+            stations_idx: idx,
+        }
+    }
+}
+
+/// Iterator over the stations of the SeedLink server's inventory.
+///
+/// Stations are parsed lazily from the server's response, i.e. callers may stop early without
+/// materializing the entire inventory. Note that the raw response is still buffered.
+pub struct Stations {
+    inner: Box<dyn Iterator<Item = SeedLinkResult<Station>> + Send>,
+}
+
+impl Stations {
+    pub(crate) fn new<I>(iter: I) -> Self
+    where
+        I: Iterator<Item = SeedLinkResult<Station>> + Send + 'static,
+    {
+        Self {
+            inner: Box::new(iter),
+        }
+    }
+}
+
+impl Iterator for Stations {
+    type Item = SeedLinkResult<Station>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl fmt::Debug for Stations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Stations").finish_non_exhaustive()
+    }
 }
 
 impl Deref for Inventory {
@@ -511,4 +624,53 @@ impl From<InventoryV3> for Inventory {
     }
 }
 
+/// Returns whether `s` matches `pattern`.
+///
+/// The pattern may contain the wildcards `*` (matching any sequence of characters) and `?`
+/// (matching any single character).
+fn matches(pattern: &str, s: &str) -> bool {
+    let (pattern, s) = (pattern.as_bytes(), s.as_bytes());
+    let (mut p_idx, mut s_idx) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while s_idx < s.len() {
+        match pattern.get(p_idx) {
+            Some(b'*') => {
+                backtrack = Some((p_idx, s_idx));
+                p_idx += 1;
+            }
+            Some(c) if *c == b'?' || *c == s[s_idx] => {
+                p_idx += 1;
+                s_idx += 1;
+            }
+            _ => match backtrack {
+                Some((p, s)) => {
+                    p_idx = p + 1;
+                    s_idx = s + 1;
+                    backtrack = Some((p, s + 1));
+                }
+                None => return false,
+            },
+        }
+    }
 
+    pattern[p_idx..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn wildcard_matches() {
+        assert!(matches("GE_WLF", "GE_WLF"));
+        assert!(matches("GE_*", "GE_WLF"));
+        assert!(matches("*_W?F", "GE_WLF"));
+        assert!(matches("*", ""));
+        assert!(matches("_B_H_?", "_B_H_Z"));
+        assert!(!matches("GE_*", "CH_WLF"));
+        assert!(!matches("GE_W?", "GE_WLF"));
+        assert!(!matches("", "GE_WLF"));
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use time::{Duration, OffsetDateTime};
+use tracing::warn;
+
+use crate::trace;
+use crate::{SeedLinkPacket, SeedLinkResult, StreamItem};
+
+/// Number of latencies summarized per stream.
+const WINDOW_SIZE: usize = 100;
+
+/// Summary of the latencies (i.e. the delay between the record end time and the time the record
+/// was received) of the records received most recently for a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    /// Latency of the record received last.
+    pub last: Duration,
+    /// Minimum latency.
+    pub min: Duration,
+    /// Maximum latency.
+    pub max: Duration,
+    /// Mean latency.
+    pub mean: Duration,
+    /// Number of latencies summarized.
+    pub samples: usize,
+}
+
+/// Handle providing the latencies of the streams received (see [`Connection::latency_monitor`]).
+///
+/// [`Connection::latency_monitor`]: crate::Connection::latency_monitor
+#[derive(Debug, Clone, Default)]
+pub struct LatencyMonitor {
+    streams: Arc<Mutex<HashMap<String, VecDeque<Duration>>>>,
+}
+
+impl LatencyMonitor {
+    /// Returns the latency summary of the stream identified by the FDSN source identifier `sid`,
+    /// if any record was received.
+    pub fn summary(&self, sid: &str) -> Option<LatencySummary> {
+        self.streams.lock().unwrap().get(sid).map(summarize)
+    }
+
+    /// Returns the latency summaries of all streams received ordered by source identifier.
+    pub fn summaries(&self) -> Vec<(String, LatencySummary)> {
+        let mut rv: Vec<(String, LatencySummary)> = self
+            .streams
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(sid, latencies)| (sid.clone(), summarize(latencies)))
+            .collect();
+        rv.sort_by(|a, b| a.0.cmp(&b.0));
+
+        rv
+    }
+
+    /// Records the latency of the data packet passing through. Items other than data packets are
+    /// ignored.
+    pub(crate) fn observe(&self, item: &StreamItem) {
+        let packet = match item {
+            StreamItem::Packet(packet) => packet,
+            StreamItem::End(_) => return,
+        };
+        match latency(packet) {
+            Some(Ok((sid, latency))) => self.record(sid, latency),
+            Some(Err(e)) => {
+                warn!(target: trace::STREAM, "failed to determine packet latency: {}", e)
+            }
+            None => {}
+        }
+    }
+
+    fn record(&self, sid: String, latency: Duration) {
+        let mut streams = self.streams.lock().unwrap();
+        let latencies = streams.entry(sid).or_default();
+        if latencies.len() == WINDOW_SIZE {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+}
+
+fn latency(packet: &SeedLinkPacket) -> Option<SeedLinkResult<(String, Duration)>> {
+    let received = OffsetDateTime::now_utc();
+    Some(
+        packet.to_record()?.and_then(|(sid, _, ms_record)| {
+            Ok((sid.to_string(), received - ms_record.end_time()?))
+        }),
+    )
+}
+
+fn summarize(latencies: &VecDeque<Duration>) -> LatencySummary {
+    let sum: Duration = latencies.iter().sum();
+    LatencySummary {
+        last: *latencies.back().unwrap(),
+        min: *latencies.iter().min().unwrap(),
+        max: *latencies.iter().max().unwrap(),
+        mean: sum / latencies.len() as u32,
+        samples: latencies.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn summarize_latencies() {
+        let monitor = LatencyMonitor::default();
+        assert_eq!(monitor.summary("FDSN:GE_WLF_00_B_H_Z"), None);
+
+        for secs in [3, 1, 2] {
+            monitor.record("FDSN:GE_WLF_00_B_H_Z".to_string(), Duration::seconds(secs));
+        }
+        for secs in 0..(WINDOW_SIZE as i64 + 1) {
+            monitor.record("FDSN:GE_APE_00_B_H_Z".to_string(), Duration::seconds(secs));
+        }
+
+        assert_eq!(
+            monitor.summary("FDSN:GE_WLF_00_B_H_Z"),
+            Some(LatencySummary {
+                last: Duration::seconds(2),
+                min: Duration::seconds(1),
+                max: Duration::seconds(3),
+                mean: Duration::seconds(2),
+                samples: 3,
+            })
+        );
+
+        let summaries = monitor.summaries();
+        assert_eq!(summaries[0].0, "FDSN:GE_APE_00_B_H_Z");
+        assert_eq!(summaries[0].1.min, Duration::seconds(1));
+        assert_eq!(summaries[0].1.samples, WINDOW_SIZE);
+    }
+}
@@ -1,56 +1,155 @@
+extern crate alloc;
+
 use std::io;
+use std::time::Duration;
 
+#[cfg(feature = "v3-client")]
+pub use crate::builder::{ConnectionBuilder, StreamRequest};
+#[cfg(feature = "v3-client")]
+pub use crate::capabilities::Capabilities;
+#[cfg(feature = "v3-client")]
+pub use crate::catch_up::{CatchUpTracking, CatchUpTrackingExt, CaughtUp, Watermarks};
+#[cfg(feature = "v3-client")]
 pub use crate::client::Client;
+#[cfg(feature = "v3-client")]
 pub use crate::connection::{
-    parse_slink_url, Connection, ConnectionInfo, DataTransferMode, IntoConnectionInfo,
-    SeedLinkConnectionInfo,
+    parse_slink_url, Connection, ConnectionAddr, ConnectionControl, ConnectionInfo,
+    ConnectionStats, DataTransferMode, HelloFallback, IntoConnectionInfo, InventoryValidation,
+    NegotiationProgress, NegotiationReport, PacketStream, SeedLinkConnectionInfo, StreamEnd,
+    StreamItem,
+};
+#[cfg(feature = "v3-client")]
+pub use crate::continuity::{
+    ContinuityTracking, ContinuityTrackingExt, GapDetected, StreamPosition,
 };
+#[cfg(feature = "v4-client")]
+pub use crate::credentials::{Credentials, CredentialsProvider};
+#[cfg(feature = "v3-client")]
+pub use crate::dedup::{Deduplicated, Deduplication, DeduplicationExt};
+pub use crate::envelope::{PacketEnvelope, ENVELOPE_SCHEMA_VERSION};
+#[cfg(all(feature = "state-sqlite", feature = "v3-client"))]
+pub use crate::failover::FailoverClient;
 pub use crate::frame::Frame;
-pub use crate::inventory::{Format, Inventory, Station, StationId, Stream, StreamId, SubFormat};
+#[cfg(feature = "v3-client")]
+pub use crate::handover::Handover;
+pub use crate::inventory::{
+    Format, Inventory, InventoryLevel, Station, StationId, Stations, Stream, StreamId, SubFormat,
+};
+#[cfg(feature = "v3-client")]
+pub use crate::latency::{LatencyMonitor, LatencySummary};
+#[cfg(feature = "v3-client")]
+pub use crate::pacing::{StationPaced, StationPacing, StationPacingExt};
 pub use crate::packet::SeedLinkPacket;
-pub use crate::state::StateDB;
+#[cfg(feature = "v3-client")]
+pub use crate::socket::{TcpKeepaliveOptions, TcpSocketOptions};
+#[cfg(feature = "state-sqlite")]
+pub use crate::state::{StateDB, StreamState};
+#[cfg(all(feature = "state-sqlite", feature = "v3-client"))]
+pub use crate::state_tracking::{StateTracking, StateTrackingExt};
+#[cfg(feature = "v3-client")]
+pub use crate::stream_config::{StreamConfig, StreamSelector, SubscribedStation};
 pub use crate::util::{FDSNSourceId, NSLC};
+#[cfg(feature = "gzip")]
+pub use crate::v3::{compress_info_payload_v3, CAPABILITY_INFO_GZIP_V3};
 pub use crate::v3::{
-    BatchCmdV3, ByeCmdV3, CommandV3, DataCmdV3, EndCmdV3, FetchCmdV3, HelloCmdV3, InfoCmdItemV3,
-    InfoCmdV3, InventoryV3, ProtocolErrorV3, SeedLinkGenericDataPacketV3, SeedLinkInfoPacketV3,
-    SeedLinkPacketV3, SelectCmdV3, StationCmdV3, StationV3, StreamTypeV3, StreamV3, TimeCmdV3,
-    UnknownCmdV3, SEEDLINK_PACKET_HEADER_SIZE_V3, SEEDLINK_PACKET_RECORD_SIZE_V3,
+    BatchCmdV3, ByeCmdV3, CapabilitiesCmdV3, CommandV3, ConnectionV3, ConnectionsStationV3,
+    ConnectionsV3, DataCmdV3, EndCmdV3, FetchCmdV3, GapV3, GapsStationV3, GapsStreamV3, GapsV3,
+    HelloCmdV3, InfoCmdItemV3, InfoCmdV3, InventoryV3, ProtocolErrorV3,
+    SeedLinkGenericDataPacketV3, SeedLinkInfoPacketV3, SeedLinkPacketV3, SelectCmdV3, SelectorV3,
+    StationCmdV3, StationV3, StreamTypeV3, StreamV3, TimeCmdV3, UnknownCmdV3, CAPABILITY_CAP_V3,
+    CAPABILITY_EXTREPLY_V3, SEEDLINK_PACKET_HEADER_SIZE_V3, SEEDLINK_PACKET_RECORD_SIZE_V3,
     SEEDLINK_PACKET_SIZE_V3,
 };
 pub use crate::v4::{
     pack_info_err_v4, pack_info_ok_v4, pack_ms_record_v4, pack_packet_v4,
-    pack_packet_with_seq_num_v4, to_first_hello_resp_line_v4, to_id_info_v4, AuthCmdMethodV4,
-    AuthCmdV4, AuthV4, ByeCmdV4, CapabilitiesInfoV4, CommandV4, ConnectionsInfoV4, DataCmdV4,
-    DataFormatV4, EndCmdV4, EndFetchCmdV4, ErrorCodeV4, ErrorInfoV4, FormatsInfoV4, FrameV4,
-    HelloCmdV4, IdInfoV4, InfoCmdItemV4, InfoCmdV4, InfoV4, ProtocolErrorV4, SeedLinkPacketV4,
+    pack_packet_with_seq_num_v4, AuthCmdMethodV4, AuthCmdV4, AuthV4, ByeCmdV4, CapabilitiesInfoV4,
+    ClientTrafficInfoV4, CommandV4, ConnectionsInfoV4, DataCmdV4, DataFormatV4, EndCmdV4,
+    EndFetchCmdV4, ErrorCodeV4, ErrorInfoV4, FormatsInfoV4, FrameV4, HelloCmdV4, IdInfoV4,
+    InfoBuilderV4, InfoCmdItemV4, InfoCmdV4, InfoV4, ProtocolErrorV4, SeedLinkPacketV4,
     SelectCmdPatternV4, SelectCmdV4, SequenceNumberV4, SlProtoCmdV4, StationCmdV4, StationIdV4,
-    StationV4, StationsInfoV4, StreamFormatV4, StreamIdV4, StreamOriginV4, StreamSubFormatV4,
-    StreamV4, StreamsInfoV4, UnknownCmdV4, UserAgentCmdInfoV4, UserAgentCmdV4,
+    StationV4, StationsInfoBuilderV4, StationsInfoV4, StreamFormatV4, StreamIdV4, StreamOriginV4,
+    StreamSubFormatV4, StreamV4, StreamsInfoV4, UnknownCmdV4, UserAgentCmdInfoV4, UserAgentCmdV4,
+    CAPABILITY_AUTH_REFRESH_V4,
+};
+#[cfg(any(feature = "server", feature = "v4-client"))]
+pub use crate::v4::{
+    sign_packet_v4, verify_packet_v4, PACKET_SIGNATURE_CAPABILITY_V4, PACKET_SIGNATURE_SIZE_V4,
 };
+#[cfg(feature = "server")]
+pub use crate::v4::{to_first_hello_resp_line_v4, to_id_info_v4};
+#[cfg(feature = "v3-client")]
+pub use crate::warning::SeedLinkWarning;
 
-use crate::connection::{connect, ActualConnection, TcpConnection};
-use crate::stream_config::StreamConfig;
+#[cfg(feature = "tls")]
+use crate::connection::TlsConnection;
+#[cfg(feature = "v3-client")]
+use crate::connection::{connect, ActualConnection, MemConnection, TcpConnection};
+#[cfg(feature = "v3-client")]
 use crate::v3::{SeedLinkConnectionV3, SeedLinkDataTransferModeV3};
+#[cfg(feature = "v4-client")]
+use crate::v4::{SeedLinkConnectionV4, SeedLinkDataTransferModeV4};
 
+#[cfg(feature = "v3-client")]
+mod builder;
+#[cfg(feature = "v3-client")]
+mod capabilities;
+#[cfg(feature = "v3-client")]
+mod catch_up;
+#[cfg(feature = "v3-client")]
 mod client;
+#[cfg(feature = "v3-client")]
 mod connection;
+#[cfg(feature = "v3-client")]
+mod continuity;
+#[cfg(feature = "v4-client")]
+mod credentials;
+#[cfg(feature = "v3-client")]
+mod dedup;
+mod envelope;
+#[cfg(all(feature = "state-sqlite", feature = "v3-client"))]
+mod failover;
 mod frame;
+#[cfg(feature = "v3-client")]
+mod handover;
 mod inventory;
+#[cfg(feature = "v3-client")]
+mod latency;
+#[cfg(feature = "v3-client")]
+pub mod metrics;
+#[cfg(feature = "v3-client")]
+mod pacing;
 mod packet;
+#[cfg(feature = "v3-client")]
+mod socket;
+#[cfg(feature = "state-sqlite")]
 mod state;
+#[cfg(all(feature = "state-sqlite", feature = "v3-client"))]
+mod state_tracking;
+#[cfg(feature = "v3-client")]
 mod stream_config;
+#[cfg(feature = "tls")]
+mod tls;
+pub mod trace;
 mod util;
 mod v3;
 mod v4;
+#[cfg(feature = "v3-client")]
+mod warning;
+pub mod wire;
 
 /// Default port that a SeedLink server listens on.
 pub const DEFAULT_PORT: u16 = 18000;
 
 /// Available client protocol versions (sorted, non-decreasing) implemented by the library.
+#[cfg(all(feature = "v3-client", not(feature = "v4-client")))]
 pub const AVAILABLE_CLIENT_PROTO_VERSIONS: [u8; 1] = [3];
+/// Available client protocol versions (sorted, non-decreasing) implemented by the library.
+#[cfg(feature = "v4-client")]
+pub const AVAILABLE_CLIENT_PROTO_VERSIONS: [u8; 2] = [3, 4];
 
 /// Generic library error type.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum SeedLinkError {
     #[error("{0}")]
     UnsupportedCommand(String),
@@ -58,6 +157,9 @@ pub enum SeedLinkError {
     UnexpectedCommand(String),
     #[error("{0}")]
     UnauthorizedCommand(String),
+    /// A SeedLink `v4` server rejected the `AUTH` command (i.e. `ERROR AUTH [<message>]`).
+    #[error("{0}")]
+    AuthenticationFailed(ProtocolErrorV4),
     #[error("{0}")]
     InvalidProtocolVersion(String),
     #[error("{0}")]
@@ -67,9 +169,40 @@ pub enum SeedLinkError {
     #[error("{0}")]
     InvalidClientConfig(String),
     #[error("{0}")]
-    StateDBError(String),
-    #[error("{0}")]
     InvalidStreamId(String),
+    /// Establishing the connection to `addr` did not complete within `timeout`.
+    #[error("connection timeout: {addr} (after {timeout:?})")]
+    ConnectTimeout { addr: String, timeout: Duration },
+    /// The server rejected the action command (e.g. `DATA`) of the station `station` (i.e.
+    /// `NET_STA`) while negotiating. For SeedLink `v4`, `reason` holds the server's error
+    /// response.
+    #[error("handshake rejected: station {station}{}", fmt_reason(.reason))]
+    HandshakeRejected {
+        station: String,
+        reason: Option<String>,
+    },
+    /// None of the protocol versions `offered` by the server is implemented by the client or
+    /// matches the protocol version `requested`.
+    #[error(
+        "incompatible seedlink protocol versions (offered: {}, requested: {})",
+        .offered.join(", "),
+        .requested.map_or_else(|| "any".to_string(), |v| format!("v{}", v))
+    )]
+    UnsupportedProtocolVersion {
+        offered: Vec<String>,
+        requested: Option<u8>,
+    },
+    /// State database failure, e.g. a failing SQLite query.
+    #[error("{message}")]
+    StateDB {
+        message: String,
+        #[source]
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
+    /// Error response of a SeedLink `v4` server (i.e. `ERROR <code> [<message>]`). Note that
+    /// authentication failures are reported as [`SeedLinkError::AuthenticationFailed`].
+    #[error("{0}")]
+    Protocol(ProtocolErrorV4),
     #[error(transparent)]
     MSError(#[from] mseed::MSError),
     #[error(transparent)]
@@ -80,3 +213,59 @@ pub enum SeedLinkError {
 ///
 /// [`Result`]: enum@std::result::Result
 pub type SeedLinkResult<T> = std::result::Result<T, SeedLinkError>;
+
+impl SeedLinkError {
+    /// Returns the error code of the `v4` server error response, if any (see
+    /// [`SeedLinkError::Protocol`] and [`SeedLinkError::AuthenticationFailed`]).
+    pub fn protocol_error_code(&self) -> Option<&ErrorCodeV4> {
+        match self {
+            Self::Protocol(err) | Self::AuthenticationFailed(err) => Some(&err.code),
+            _ => None,
+        }
+    }
+
+    /// Creates a [`SeedLinkError::StateDB`] error without source.
+    #[cfg(feature = "state-sqlite")]
+    pub(crate) fn state_db(message: impl Into<String>) -> Self {
+        Self::StateDB {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Creates a [`SeedLinkError::StateDB`] error caused by `source`.
+    #[cfg(feature = "state-sqlite")]
+    pub(crate) fn state_db_with_source(
+        message: &str,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        Self::StateDB {
+            message: message.to_string(),
+            source: Some(Box::new(source)),
+        }
+    }
+}
+
+fn fmt_reason(reason: &Option<String>) -> String {
+    reason
+        .as_ref()
+        .map(|reason| format!(" ({})", reason))
+        .unwrap_or_default()
+}
+
+impl From<wire::Error> for SeedLinkError {
+    fn from(err: wire::Error) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err.to_string()).into()
+    }
+}
+
+impl std::error::Error for wire::Error {}
+
+impl From<ProtocolErrorV4> for SeedLinkError {
+    fn from(err: ProtocolErrorV4) -> Self {
+        match err.code {
+            ErrorCodeV4::AuthenticationFailed => Self::AuthenticationFailed(err),
+            _ => Self::Protocol(err),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Metrics of the client.
+//!
+//! If the `metrics` feature is enabled, the metrics below are recorded by means of the
+//! [`metrics`](https://docs.rs/metrics) facade, i.e. applications installing a recorder (e.g. a
+//! Prometheus exporter) collect them without further configuration. Otherwise, recording is a
+//! no-op.
+
+use std::time::Duration;
+
+/// Counter of the connection attempts, with the label `result` (i.e. `ok` or `error`).
+pub const CONNECT_ATTEMPTS: &str = "slink_connect_attempts_total";
+/// Counter of the reconnects (e.g. a [`FailoverClient`](crate::FailoverClient) failing over).
+pub const RECONNECTS: &str = "slink_reconnects_total";
+/// Counter of the packets received, with the label `kind` (i.e. `data` or `info`).
+pub const PACKETS_RECEIVED: &str = "slink_packets_received_total";
+/// Counter of the frames which failed to decode.
+pub const DECODE_ERRORS: &str = "slink_decode_errors_total";
+/// Counter of the failed negotiations (i.e. configuring a connection failed).
+pub const NEGOTIATION_FAILURES: &str = "slink_negotiation_failures_total";
+/// Histogram of the keepalive round trip times (in seconds).
+pub const KEEP_ALIVE_RTT: &str = "slink_keep_alive_rtt_seconds";
+
+/// All metrics of the client.
+pub const METRICS: &[&str] = &[
+    CONNECT_ATTEMPTS,
+    RECONNECTS,
+    PACKETS_RECEIVED,
+    DECODE_ERRORS,
+    NEGOTIATION_FAILURES,
+    KEEP_ALIVE_RTT,
+];
+
+/// Records a connection attempt.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn connect_attempt(ok: bool) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(CONNECT_ATTEMPTS, "result" => if ok { "ok" } else { "error" }).increment(1);
+}
+
+/// Records a reconnect.
+pub(crate) fn reconnect() {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(RECONNECTS).increment(1);
+}
+
+/// Records a packet received.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn packet_received(info: bool) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(PACKETS_RECEIVED, "kind" => if info { "info" } else { "data" })
+        .increment(1);
+}
+
+/// Records a frame which failed to decode.
+pub(crate) fn decode_error() {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(DECODE_ERRORS).increment(1);
+}
+
+/// Records a failed negotiation.
+pub(crate) fn negotiation_failed() {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(NEGOTIATION_FAILURES).increment(1);
+}
+
+/// Records the round trip time `rtt` of a keepalive.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn keep_alive_rtt(rtt: Duration) {
+    #[cfg(feature = "metrics")]
+    ::metrics::histogram!(KEEP_ALIVE_RTT).record(rtt.as_secs_f64());
+}
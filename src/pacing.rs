@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::stream::Stream;
+use pin_project_lite::pin_project;
+use tokio::time::{self as tokio_time, Instant, Sleep};
+use tracing::debug;
+
+use crate::trace;
+use crate::StreamItem;
+
+/// Rate of the packets delivered per station.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rate {
+    /// Interval between packets delivered at the sustained rate.
+    interval: Duration,
+    /// Number of packets delivered in a burst.
+    burst: u32,
+}
+
+impl Rate {
+    fn new(max_packets: u32, per: Duration) -> Self {
+        assert!(max_packets > 0, "max_packets must be greater than zero");
+        assert!(!per.is_zero(), "per must be greater than zero");
+
+        Self {
+            interval: per / max_packets,
+            burst: max_packets,
+        }
+    }
+
+    /// Returns the time packets may be delivered ahead of the sustained rate.
+    fn tolerance(&self) -> Duration {
+        self.interval * (self.burst - 1)
+    }
+}
+
+/// Pacing of the packets delivered per station, e.g. such that downstream processing (e.g.
+/// database inserts per stream) is not overwhelmed when fetching a large backlog.
+///
+/// Each station may deliver up to `max_packets` packets per `per` (including bursts of up to
+/// `max_packets` packets). Packets exceeding the rate are delayed, i.e. the packet stream is
+/// paused and the server is throttled by means of TCP flow control.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StationPacing {
+    rate: Rate,
+    /// Rates overriding the default rate per station (i.e. `NET_STA`).
+    station_rates: HashMap<String, Rate>,
+}
+
+impl StationPacing {
+    /// Creates the pacing limiting each station to `max_packets` packets per `per`.
+    ///
+    /// Panics if `max_packets` or `per` is zero.
+    pub fn new(max_packets: u32, per: Duration) -> Self {
+        Self {
+            rate: Rate::new(max_packets, per),
+            station_rates: HashMap::new(),
+        }
+    }
+
+    /// Limits the station `sta` of network `net` to `max_packets` packets per `per`, overriding
+    /// the default rate.
+    ///
+    /// Panics if `max_packets` or `per` is zero.
+    pub fn with_station(mut self, net: &str, sta: &str, max_packets: u32, per: Duration) -> Self {
+        self.station_rates
+            .insert(format!("{}_{}", net, sta), Rate::new(max_packets, per));
+        self
+    }
+
+    fn rate(&self, station_id: &str) -> Rate {
+        self.station_rates
+            .get(station_id)
+            .copied()
+            .unwrap_or(self.rate)
+    }
+}
+
+/// Extension trait for packet streams (see [`Connection::packets`](crate::Connection::packets)).
+pub trait StationPacingExt: Stream<Item = StreamItem> + Sized {
+    /// Wraps the packet stream delaying data packets according to `pacing`.
+    ///
+    /// Other items (e.g. `INFO` packets) are passed through. Items are passed through in order,
+    /// i.e. a delayed packet delays the items received subsequently.
+    fn with_station_pacing(self, pacing: StationPacing) -> StationPaced<Self> {
+        StationPaced {
+            packets: self,
+            pacer: Pacer::new(pacing),
+            delayed: None,
+        }
+    }
+}
+
+impl<S: Stream<Item = StreamItem>> StationPacingExt for S {}
+
+pin_project! {
+    /// Stream adapter for [`StationPacingExt::with_station_pacing`].
+    #[must_use = "streams do nothing unless polled"]
+    pub struct StationPaced<S> {
+        #[pin]
+        packets: S,
+        pacer: Pacer,
+        delayed: Option<(StreamItem, Pin<Box<Sleep>>)>,
+    }
+}
+
+impl<S> Stream for StationPaced<S>
+where
+    S: Stream<Item = StreamItem>,
+{
+    type Item = StreamItem;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        if let Some((_, sleep)) = this.delayed {
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            let (item, _) = this.delayed.take().unwrap();
+            return Poll::Ready(Some(item));
+        }
+
+        let item = match this.packets.poll_next(cx) {
+            Poll::Ready(Some(item)) => item,
+            other => return other,
+        };
+
+        let station_id = match item {
+            StreamItem::Packet(ref packet) => match packet.to_record() {
+                Some(Ok((sid, _, _))) => format!("{}_{}", sid.nslc.net, sid.nslc.sta),
+                // XXX(damb): failures are reported when consuming the records
+                Some(Err(_)) | None => return Poll::Ready(Some(item)),
+            },
+            StreamItem::End(_) => return Poll::Ready(Some(item)),
+        };
+
+        match this.pacer.reserve(&station_id, Instant::now()) {
+            Some(due) => {
+                debug!(target: trace::STREAM, "pacing station {}", station_id);
+                let mut sleep = Box::pin(tokio_time::sleep_until(due));
+                if sleep.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(Some(item));
+                }
+                *this.delayed = Some((item, sleep));
+                Poll::Pending
+            }
+            None => Poll::Ready(Some(item)),
+        }
+    }
+}
+
+/// Keeps track of the packets delivered per station (by means of the generic cell rate
+/// algorithm).
+#[derive(Debug)]
+struct Pacer {
+    pacing: StationPacing,
+    /// Theoretical arrival time of the next packet per station.
+    tats: HashMap<String, Instant>,
+}
+
+impl Pacer {
+    fn new(pacing: StationPacing) -> Self {
+        Self {
+            pacing,
+            tats: HashMap::new(),
+        }
+    }
+
+    /// Reserves the delivery of a packet of the station `station_id` at `now`. Returns the time
+    /// the packet is due if delayed.
+    fn reserve(&mut self, station_id: &str, now: Instant) -> Option<Instant> {
+        let rate = self.pacing.rate(station_id);
+        let tat = self.tats.entry(station_id.to_string()).or_insert(now);
+        let t = (*tat).max(now);
+        *tat = t + rate.interval;
+
+        t.checked_sub(rate.tolerance()).filter(|due| *due > now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn reserve_bursts() {
+        let mut pacer = Pacer::new(StationPacing::new(2, Duration::from_secs(2)).with_station(
+            "GE",
+            "APE",
+            1,
+            Duration::from_secs(10),
+        ));
+        let now = Instant::now();
+
+        assert_eq!(pacer.reserve("GE_WLF", now), None);
+        assert_eq!(pacer.reserve("GE_WLF", now), None);
+        assert_eq!(
+            pacer.reserve("GE_WLF", now),
+            Some(now + Duration::from_secs(1))
+        );
+        assert_eq!(pacer.reserve("GE_APE", now), None);
+        assert_eq!(
+            pacer.reserve("GE_APE", now),
+            Some(now + Duration::from_secs(10))
+        );
+
+        let later = now + Duration::from_secs(60);
+        assert_eq!(pacer.reserve("GE_WLF", later), None);
+        assert_eq!(pacer.reserve("GE_WLF", later), None);
+    }
+}
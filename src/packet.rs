@@ -1,10 +1,12 @@
-use crate::SeedLinkPacketV3;
+use mseed::{MSControlFlags, MSRecord};
+
+use crate::{DataFormatV4, FDSNSourceId, SeedLinkPacketV3, SeedLinkPacketV4, SeedLinkResult};
 
 /// Enumeration of SeedLink packets
 #[derive(Debug)]
 pub enum SeedLinkPacket {
     V3(SeedLinkPacketV3),
-    //V4()
+    V4(SeedLinkPacketV4),
 }
 
 impl SeedLinkPacket {
@@ -12,6 +14,10 @@ impl SeedLinkPacket {
     pub fn is_info(&self) -> bool {
         match self {
             Self::V3(packet) => packet.is_info(),
+            Self::V4(packet) => matches!(
+                packet.format(),
+                DataFormatV4::JsonSeedLinkInfo | DataFormatV4::JsonSeedLinkError
+            ),
         }
     }
 
@@ -19,7 +25,45 @@ impl SeedLinkPacket {
     pub fn is_data(&self) -> bool {
         match self {
             Self::V3(packet) => packet.is_data(),
+            Self::V4(_) => !self.is_info(),
         }
     }
-}
 
+    /// Returns the latency of the data packet, i.e. the delay between the end time of the record and
+    /// the current time. Returns `None` if the packet is not a data packet.
+    pub fn latency(&self) -> Option<SeedLinkResult<time::Duration>> {
+        let received = time::OffsetDateTime::now_utc();
+        Some(
+            self.to_record()?
+                .and_then(|(_, _, ms_record)| Ok(received - ms_record.end_time()?)),
+        )
+    }
+
+    /// Decodes the payload of the data packet as miniSEED record. Returns the record together with
+    /// its FDSN source identifier and the packet sequence number, or `None` if the packet is not a
+    /// data packet.
+    pub fn to_record(&self) -> Option<SeedLinkResult<(FDSNSourceId, u64, MSRecord)>> {
+        let res = match self {
+            Self::V3(SeedLinkPacketV3::GenericData(packet)) => {
+                packet.sequence_number().and_then(|seq_num| {
+                    let ms_record = packet.payload(MSControlFlags::empty())?;
+                    Ok((ms_record, seq_num as u64))
+                })
+            }
+            Self::V3(SeedLinkPacketV3::Info(_)) => return None,
+            Self::V4(packet) => {
+                if !self.is_data() {
+                    return None;
+                }
+                packet
+                    .payload_to_ms_record()
+                    .map(|ms_record| (ms_record, packet.sequence_number()))
+            }
+        };
+
+        Some(res.and_then(|(ms_record, seq_num)| {
+            let sid = ms_record.sid()?.parse()?;
+            Ok((sid, seq_num, ms_record))
+        }))
+    }
+}
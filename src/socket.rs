@@ -0,0 +1,197 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::{self, TcpStream};
+use tokio::select;
+use tokio::time as tokio_time;
+
+/// Delay between staggered connection attempts (see RFC 8305, section 5).
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// TCP keepalive (i.e. `SO_KEEPALIVE`) configuration.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TcpKeepaliveOptions {
+    /// Time the connection must be idle before keepalive probes are sent.
+    pub time: Duration,
+    /// Time between individual keepalive probes. If `None`, the system default applies.
+    pub interval: Option<Duration>,
+}
+
+/// TCP socket options applied to connections established by address. By default, the system
+/// defaults apply.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TcpSocketOptions {
+    /// TCP keepalive configuration. If `None`, keepalive probes are not enabled explicitly.
+    pub keepalive: Option<TcpKeepaliveOptions>,
+    /// Whether to disable Nagle's algorithm (i.e. `TCP_NODELAY`).
+    pub nodelay: bool,
+    /// Size of the send buffer (i.e. `SO_SNDBUF`).
+    pub send_buffer_size: Option<usize>,
+    /// Size of the receive buffer (i.e. `SO_RCVBUF`).
+    pub recv_buffer_size: Option<usize>,
+}
+
+impl TcpSocketOptions {
+    /// Applies the socket options to the TCP stream `stream`.
+    pub(crate) fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        let sock_ref = SockRef::from(stream);
+
+        if let Some(keepalive) = self.keepalive {
+            let mut tcp_keepalive = TcpKeepalive::new().with_time(keepalive.time);
+            if let Some(interval) = keepalive.interval {
+                tcp_keepalive = tcp_keepalive.with_interval(interval);
+            }
+            sock_ref.set_tcp_keepalive(&tcp_keepalive)?;
+        }
+        if self.nodelay {
+            sock_ref.set_nodelay(true)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            sock_ref.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            sock_ref.set_recv_buffer_size(size)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Establishes a TCP connection to `host` and `port`.
+///
+/// All addresses `host` resolves to are dialed (i.e. "Happy Eyeballs", see RFC 8305): connection
+/// attempts are started staggered in the order of [`interleave`] and the first connection
+/// established wins. A failing attempt starts the next attempt immediately.
+pub(crate) async fn connect(host: &str, port: u16) -> io::Result<TcpStream> {
+    let addrs = net::lookup_host((host, port)).await?.collect();
+    connect_any(interleave(addrs)).await
+}
+
+async fn connect_any(addrs: Vec<SocketAddr>) -> io::Result<TcpStream> {
+    let mut pending = addrs.into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_err = None;
+
+    loop {
+        if let Some(addr) = pending.next() {
+            attempts.push(TcpStream::connect(addr));
+        }
+        if attempts.is_empty() {
+            break;
+        }
+
+        select! {
+            res = attempts.next() => match res {
+                Some(Ok(stream)) => return Ok(stream),
+                Some(Err(e)) => last_err = Some(e),
+                None => {}
+            },
+            _ = tokio_time::sleep(CONNECTION_ATTEMPT_DELAY), if pending.len() != 0 => {}
+        }
+    }
+
+    Err(last_err
+        .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no addresses resolved")))
+}
+
+/// Interleaves the addresses `addrs` by address family starting with the family of the first
+/// address (see RFC 8305, section 4).
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+
+    let mut rv = Vec::with_capacity(preferred.len() + other.len());
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (a, b) => rv.extend(a.into_iter().chain(b)),
+        }
+    }
+
+    rv
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn interleave_address_families() {
+        let addrs: Vec<SocketAddr> = [
+            "[::1]:18000",
+            "[::2]:18000",
+            "[::3]:18000",
+            "127.0.0.1:18000",
+        ]
+        .iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect();
+        let interleaved: Vec<String> = interleave(addrs)
+            .iter()
+            .map(SocketAddr::to_string)
+            .collect();
+        assert_eq!(
+            interleaved,
+            vec![
+                "[::1]:18000",
+                "127.0.0.1:18000",
+                "[::2]:18000",
+                "[::3]:18000"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn connect_fallback() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        // XXX(damb): bind and drop to obtain an address refusing connections
+        let refusing = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let stream = connect_any(vec![refusing, listener.local_addr().unwrap()])
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+
+        assert!(connect_any(vec![refusing]).await.is_err());
+        assert!(connect_any(vec![]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn apply_socket_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let options = TcpSocketOptions {
+            keepalive: Some(TcpKeepaliveOptions {
+                time: Duration::from_secs(60),
+                interval: Some(Duration::from_secs(20)),
+            }),
+            nodelay: true,
+            send_buffer_size: Some(64 * 1024),
+            recv_buffer_size: Some(64 * 1024),
+        };
+        options.apply(&stream).unwrap();
+
+        let sock_ref = SockRef::from(&stream);
+        assert!(sock_ref.keepalive().unwrap());
+        assert!(sock_ref.nodelay().unwrap());
+        assert!(sock_ref.send_buffer_size().unwrap() >= 64 * 1024);
+        assert!(sock_ref.recv_buffer_size().unwrap() >= 64 * 1024);
+    }
+}
@@ -1,182 +1,910 @@
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use rusqlite::{Connection, OptionalExtension};
+use rusqlite::{Connection, DatabaseName, OpenFlags, OptionalExtension};
+use time::OffsetDateTime;
 use tokio::task;
+use tokio::time as tokio_time;
+use tracing::{info, warn};
 
+use crate::trace;
 use crate::{FDSNSourceId, SeedLinkError, SeedLinkResult};
 
+/// Maximum sequence number of SeedLink `v3` packets (24-bit).
+const MAX_SEQ_NUM_V3: i64 = 0xFFFFFF;
+
+/// Stream specific state information.
+#[derive(Debug, Clone)]
+pub struct StreamState {
+    /// The FDSN source identifier of the stream.
+    pub sid: FDSNSourceId,
+    /// The sequence number of the most recent packet received.
+    pub seq_num: i64,
+    /// The SeedLink protocol version the sequence number refers to.
+    ///
+    /// Note that sequence numbers of different protocol versions are not interchangeable.
+    pub protocol_version: u8,
+    /// The end time of the most recent record received, if known.
+    ///
+    /// Contrary to sequence numbers, end times are server independent (see
+    /// [`Connection::recover_state_by_time`](crate::Connection::recover_state_by_time)).
+    pub end_time: Option<OffsetDateTime>,
+}
+
 /// Represents a state database for clients.
 #[derive(Debug, Clone)]
 pub struct StateDB {
     con: Arc<Mutex<rusqlite::Connection>>,
+    namespace: String,
 }
 
 impl StateDB {
     /// Creates a new `StateDB`.
+    ///
+    /// Note that the returned `StateDB` operates on the default (empty) namespace. Use
+    /// [`StateDB::with_namespace`] in order to keep track of multiple servers.
     pub async fn open<P: AsRef<Path>>(p: P) -> SeedLinkResult<Self> {
         let p = p.as_ref().to_path_buf();
-        let join = task::spawn_blocking(move || {
-            let con = Connection::open(p)
-                .map_err(|e| SeedLinkError::StateDBError(e.to_string()))
-                .map_err(|e| {
-                    SeedLinkError::StateDBError(format!(
-                        "failed to open state db ({})",
-                        e.to_string()
-                    ))
-                })?;
+        let join = task::spawn_blocking(move || Self::open_connection(&p));
 
-            con.execute(
-                "CREATE TABLE IF NOT EXISTS stream (\
-                    id INTEGER PRIMARY KEY, \
-                    sid TEXT NOT NULL, \
-                    seq BIGINT NOT NULL \
-                )",
-                (),
-            )
-            .map_err(|e| {
-                SeedLinkError::StateDBError(format!(
-                    "failed to initialize state db ({})",
-                    e.to_string()
-                ))
-            })?;
+        let con = join
+            .await
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))??;
 
-            con.execute(
-                "CREATE UNIQUE INDEX IF NOT EXISTS idx_stream_sid ON stream(sid)",
-                (),
-            )
-            .map_err(|e| {
-                SeedLinkError::StateDBError(format!(
-                    "failed to initialize state db ({})",
-                    e.to_string()
-                ))
-            })?;
+        Ok(Self {
+            con: Arc::new(Mutex::new(con)),
+            namespace: String::new(),
+        })
+    }
 
-            let rv: SeedLinkResult<Connection> = Ok(con);
-            rv
+    /// Creates a new `StateDB` and checks its integrity.
+    ///
+    /// If the database at `p` is corrupted, it is moved aside and recovered from the most recent
+    /// valid snapshot created by [`StateDB::snapshot`] or [`StateDB::snapshot_rotate`] at
+    /// `snapshot_path`.
+    pub async fn open_or_recover<P: AsRef<Path>, Q: AsRef<Path>>(
+        p: P,
+        snapshot_path: Q,
+    ) -> SeedLinkResult<Self> {
+        let p = p.as_ref().to_path_buf();
+        let snapshot_path = snapshot_path.as_ref().to_path_buf();
+        let join = task::spawn_blocking(move || {
+            match Self::open_connection(&p).and_then(|con| {
+                Self::check_integrity(&con)?;
+                Ok(con)
+            }) {
+                Ok(con) => Ok(con),
+                Err(e) => {
+                    warn!(target: trace::STATE, "state db corrupted: {}", e);
+                    Self::recover(&p, &snapshot_path)
+                }
+            }
         });
 
         let con = join
             .await
-            .map_err(|e| SeedLinkError::StateDBError(e.to_string()))??;
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))??;
 
         Ok(Self {
             con: Arc::new(Mutex::new(con)),
+            namespace: String::new(),
         })
     }
 
+    /// Returns a `StateDB` sharing the underlying database but operating on the namespace
+    /// `namespace`.
+    ///
+    /// Namespaces allow to keep track of the sequence numbers of multiple servers (e.g. keyed by
+    /// [`Connection::state_namespace`](crate::Connection::state_namespace)) within a single
+    /// database.
+    pub fn with_namespace(&self, namespace: &str) -> Self {
+        Self {
+            con: self.con.clone(),
+            namespace: namespace.to_string(),
+        }
+    }
+
+    /// Returns the namespace the `StateDB` operates on.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Moves the state information of the namespace `namespace` into the namespace the `StateDB`
+    /// operates on, e.g. the state information of databases created before namespaces were
+    /// introduced (i.e. within the default (empty) namespace). Streams already present within the
+    /// namespace are not overwritten.
+    ///
+    /// Returns the number of entries moved.
+    pub async fn adopt_namespace(&mut self, namespace: &str) -> SeedLinkResult<usize> {
+        let cloned_con = self.con.clone();
+        let from = namespace.to_string();
+        let to = self.namespace.clone();
+        if from == to {
+            return Ok(0);
+        }
+
+        let join = task::spawn_blocking(move || {
+            let con = cloned_con.lock().map_err(|e| {
+                SeedLinkError::state_db(format!("failed to lock connection ({})", e))
+            })?;
+            con.execute(
+                "UPDATE OR IGNORE stream SET namespace=?1 WHERE namespace=?2",
+                (to, from),
+            )
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to execute task", e))
+        });
+
+        join.await
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))?
+    }
+
     /// Stores the sequence number `seq_num` associated with the stream identified by the
-    /// `FDSNSourceId`.
-    pub async fn store(&mut self, sid: &str, seq_num: i64) -> SeedLinkResult<usize> {
+    /// `FDSNSourceId`. The sequence number must refer to the SeedLink protocol version
+    /// `protocol_version`.
+    ///
+    /// Note that a previously stored end time is reset (see [`StateDB::store_batch`]).
+    pub async fn store(
+        &mut self,
+        sid: &str,
+        seq_num: i64,
+        protocol_version: u8,
+    ) -> SeedLinkResult<usize> {
         let cloned_con = self.con.clone();
 
+        Self::validate_seq_num(seq_num, protocol_version)?;
+
+        let namespace = self.namespace.clone();
         let sid = sid.parse::<FDSNSourceId>()?;
+        let updated = OffsetDateTime::now_utc().unix_timestamp();
 
         let join = task::spawn_blocking(move || {
             let con = cloned_con.lock().map_err(|e| {
-                SeedLinkError::StateDBError(format!(
-                    "failed to lock connection ({})",
-                    e.to_string()
-                ))
+                SeedLinkError::state_db(format!("failed to lock connection ({})", e))
             })?;
             con.execute(
-                "REPLACE INTO stream(sid, seq) VALUES(?1, ?2)",
-                (sid.to_string(), seq_num),
+                "REPLACE INTO stream(namespace, sid, seq, proto, updated) \
+                    VALUES(?1, ?2, ?3, ?4, ?5)",
+                (
+                    namespace,
+                    sid.to_string(),
+                    seq_num,
+                    protocol_version,
+                    updated,
+                ),
             )
-            .map_err(|e| {
-                SeedLinkError::StateDBError(format!("failed to execute task ({})", e.to_string()))
-            })
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to execute task", e))
+        });
+
+        join.await
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))?
+    }
+
+    /// Stores the stream states `states` within a single transaction.
+    ///
+    /// Returns the number of rows updated.
+    pub async fn store_batch(&mut self, states: Vec<StreamState>) -> SeedLinkResult<usize> {
+        let cloned_con = self.con.clone();
+
+        for state in &states {
+            Self::validate_seq_num(state.seq_num, state.protocol_version)?;
+        }
+
+        let namespace = self.namespace.clone();
+        let updated = OffsetDateTime::now_utc().unix_timestamp();
+
+        let join = task::spawn_blocking(move || {
+            let mut con = cloned_con.lock().map_err(|e| {
+                SeedLinkError::state_db(format!("failed to lock connection ({})", e))
+            })?;
+
+            Self::store_states(&mut con, &namespace, &states, updated)
+                .map_err(|e| SeedLinkError::state_db_with_source("failed to execute task", e))
         });
 
         join.await
-            .map_err(|e| SeedLinkError::StateDBError(e.to_string()))?
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))?
     }
 
     /// Returns the sequence number associated with station identified by the network code `net`
     /// and the station code `sta`.
-    pub async fn seq_num(&mut self, sid: &str) -> SeedLinkResult<Option<i64>> {
+    ///
+    /// Only sequence numbers referring to the SeedLink protocol version `protocol_version` are
+    /// taken into account.
+    pub async fn seq_num(
+        &mut self,
+        sid: &str,
+        protocol_version: u8,
+    ) -> SeedLinkResult<Option<i64>> {
         let cloned_con = self.con.clone();
 
+        let namespace = self.namespace.clone();
         let sid = sid.parse::<FDSNSourceId>()?;
 
         let join = task::spawn_blocking(move || {
             let con = cloned_con.lock().map_err(|e| {
-                SeedLinkError::StateDBError(format!(
-                    "failed to lock connection ({})",
-                    e.to_string()
-                ))
+                SeedLinkError::state_db(format!("failed to lock connection ({})", e))
             })?;
             let mut stmt = con
-                .prepare("SELECT seq FROM stream WHERE sid=?1")
+                .prepare("SELECT seq FROM stream WHERE namespace=?1 AND sid=?2 AND proto=?3")
                 .map_err(|e| {
-                    SeedLinkError::StateDBError(format!(
-                        "failed to prepare statement ({})",
-                        e.to_string()
-                    ))
+                    SeedLinkError::state_db_with_source("failed to prepare statement", e)
                 })?;
             let res: SeedLinkResult<Option<i64>> = stmt
-                .query_row([sid.to_string()], |row| Ok(row.get(0).optional()?))
-                .map_err(|e| {
-                    SeedLinkError::StateDBError(format!(
-                        "failed to execute query ({})",
-                        e.to_string()
-                    ))
-                });
+                .query_row((namespace, sid.to_string(), protocol_version), |row| {
+                    row.get(0)
+                })
+                .optional()
+                .map_err(|e| SeedLinkError::state_db_with_source("failed to execute query", e));
             res
         });
 
         join.await
-            .map_err(|e| SeedLinkError::StateDBError(e.to_string()))?
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))?
     }
 
-    /// Returns the complete state information available.
-    pub async fn state(&mut self) -> SeedLinkResult<Vec<(FDSNSourceId, i64)>> {
+    /// Returns the end time of the most recent record received of the stream identified by the
+    /// `FDSNSourceId`, if stored.
+    ///
+    /// End times are independent of the SeedLink protocol version.
+    pub async fn end_time(&mut self, sid: &str) -> SeedLinkResult<Option<OffsetDateTime>> {
         let cloned_con = self.con.clone();
 
+        let namespace = self.namespace.clone();
+        let sid = sid.parse::<FDSNSourceId>()?;
+
         let join = task::spawn_blocking(move || {
             let con = cloned_con.lock().map_err(|e| {
-                SeedLinkError::StateDBError(format!(
-                    "failed to lock connection ({})",
-                    e.to_string()
-                ))
+                SeedLinkError::state_db(format!("failed to lock connection ({})", e))
             })?;
-
             let mut stmt = con
-                .prepare("SELECT sid, seq FROM stream ORDER BY sid")
+                .prepare("SELECT end_time FROM stream WHERE namespace=?1 AND sid=?2")
                 .map_err(|e| {
-                    SeedLinkError::StateDBError(format!(
-                        "failed to prepare statement ({})",
-                        e.to_string()
-                    ))
+                    SeedLinkError::state_db_with_source("failed to prepare statement", e)
                 })?;
-            let rows = stmt
-                .query_map([], |row| Self::convert_row(row.get(0)?, row.get(1)?))
+            let end_time: Option<Option<i64>> = stmt
+                .query_row((namespace, sid.to_string()), |row| row.get(0))
+                .optional()
+                .map_err(|e| SeedLinkError::state_db_with_source("failed to execute query", e))?;
+
+            end_time.flatten().map(Self::convert_end_time).transpose()
+        });
+
+        join.await
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))?
+    }
+
+    /// Returns the complete state information available within the namespace.
+    pub async fn state(&mut self) -> SeedLinkResult<Vec<StreamState>> {
+        let cloned_con = self.con.clone();
+        let namespace = self.namespace.clone();
+
+        let join = task::spawn_blocking(move || {
+            let con = cloned_con.lock().map_err(|e| {
+                SeedLinkError::state_db(format!("failed to lock connection ({})", e))
+            })?;
+
+            let mut stmt = con
+                .prepare(
+                    "SELECT sid, seq, proto, end_time FROM stream WHERE namespace=?1 ORDER BY sid",
+                )
                 .map_err(|e| {
-                    SeedLinkError::StateDBError(format!(
-                        "failed to execute query ({})",
-                        e.to_string()
-                    ))
+                    SeedLinkError::state_db_with_source("failed to prepare statement", e)
                 })?;
+            let rows = stmt
+                .query_map([namespace], |row| {
+                    Self::convert_row(row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)
+                })
+                .map_err(|e| SeedLinkError::state_db_with_source("failed to execute query", e))?;
 
             let mut rv = Vec::new();
             for res in rows {
-                let (sid, seq) = res.map_err(|e| {
-                    SeedLinkError::StateDBError(format!(
-                        "error while executing query ({})",
-                        e.to_string()
-                    ))
+                let (sid, seq_num, protocol_version, end_time) = res.map_err(|e| {
+                    SeedLinkError::state_db_with_source("error while executing query", e)
                 })?;
-                rv.push((sid.parse::<FDSNSourceId>()?, seq));
+                rv.push(StreamState {
+                    sid: sid.parse::<FDSNSourceId>()?,
+                    seq_num,
+                    protocol_version,
+                    end_time: end_time.map(Self::convert_end_time).transpose()?,
+                });
             }
 
             Ok(rv)
         });
 
         join.await
-            .map_err(|e| SeedLinkError::StateDBError(e.to_string()))?
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))?
     }
 
-    fn convert_row(sid: String, seq: i64) -> rusqlite::Result<(String, i64)> {
-        Ok((sid, seq))
+    /// Removes state information within the namespace which was not updated for at least `age`.
+    ///
+    /// Returns the number of entries removed.
+    pub async fn prune_older_than(&mut self, age: Duration) -> SeedLinkResult<usize> {
+        let cloned_con = self.con.clone();
+        let namespace = self.namespace.clone();
+
+        let age: i64 = age
+            .as_secs()
+            .try_into()
+            .map_err(|_| SeedLinkError::state_db("invalid age: value out of range"))?;
+        let threshold = OffsetDateTime::now_utc().unix_timestamp() - age;
+
+        let join = task::spawn_blocking(move || {
+            let con = cloned_con.lock().map_err(|e| {
+                SeedLinkError::state_db(format!("failed to lock connection ({})", e))
+            })?;
+            con.execute(
+                "DELETE FROM stream WHERE namespace=?1 AND updated<?2",
+                (namespace, threshold),
+            )
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to execute task", e))
+        });
+
+        join.await
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))?
+    }
+
+    /// Creates a consistent snapshot of the complete database (i.e. including all namespaces) at
+    /// `path`.
+    ///
+    /// The snapshot is written to a temporary file first and then atomically moved to `path`.
+    pub async fn snapshot<P: AsRef<Path>>(&self, path: P) -> SeedLinkResult<()> {
+        let cloned_con = self.con.clone();
+        let path = path.as_ref().to_path_buf();
+
+        let join = task::spawn_blocking(move || {
+            let con = cloned_con.lock().map_err(|e| {
+                SeedLinkError::state_db(format!("failed to lock connection ({})", e))
+            })?;
+
+            Self::snapshot_connection(&con, &path)
+        });
+
+        join.await
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))?
+    }
+
+    /// Creates a consistent snapshot of the complete database at `path` keeping at most `keep`
+    /// snapshots.
+    ///
+    /// Previous snapshots are rotated, i.e. `path` is moved to `path.1`, `path.1` to `path.2` and
+    /// so on.
+    pub async fn snapshot_rotate<P: AsRef<Path>>(
+        &self,
+        path: P,
+        keep: usize,
+    ) -> SeedLinkResult<()> {
+        if keep == 0 {
+            return Err(SeedLinkError::state_db(
+                "invalid number of snapshots to keep: value must be non-zero",
+            ));
+        }
+
+        let cloned_con = self.con.clone();
+        let path = path.as_ref().to_path_buf();
+
+        let join = task::spawn_blocking(move || {
+            let con = cloned_con.lock().map_err(|e| {
+                SeedLinkError::state_db(format!("failed to lock connection ({})", e))
+            })?;
+
+            for i in (1..keep).rev() {
+                let from = Self::snapshot_path(&path, i - 1);
+                if from.exists() {
+                    fs::rename(&from, Self::snapshot_path(&path, i)).map_err(|e| {
+                        SeedLinkError::state_db_with_source("failed to rotate snapshot", e)
+                    })?;
+                }
+            }
+
+            Self::snapshot_connection(&con, &path)
+        });
+
+        join.await
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to join task", e))?
+    }
+
+    /// Spawns a task creating rotated snapshots (see [`StateDB::snapshot_rotate`]) every
+    /// `interval`.
+    pub fn spawn_snapshots<P: AsRef<Path>>(
+        &self,
+        path: P,
+        interval: Duration,
+        keep: usize,
+    ) -> task::JoinHandle<()> {
+        let db = self.clone();
+        let path = path.as_ref().to_path_buf();
+
+        task::spawn(async move {
+            let mut interval = tokio_time::interval(interval);
+            interval.set_missed_tick_behavior(tokio_time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if let Err(e) = db.snapshot_rotate(&path, keep).await {
+                    warn!(target: trace::STATE, "failed to create state db snapshot: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Opens the database at `p` and initializes the database schema.
+    fn open_connection(p: &Path) -> SeedLinkResult<Connection> {
+        let con = Connection::open(p)
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to open state db", e))?;
+
+        Self::initialize(&con)
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to initialize state db", e))?;
+
+        Ok(con)
+    }
+
+    /// Checks the integrity of the database.
+    fn check_integrity(con: &Connection) -> SeedLinkResult<()> {
+        let res: String = con
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to check integrity", e))?;
+
+        if res != "ok" {
+            return Err(SeedLinkError::state_db(format!(
+                "integrity check failed ({})",
+                res
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Recovers the database at `p` from the most recent valid snapshot at `snapshot_path`.
+    fn recover(p: &Path, snapshot_path: &Path) -> SeedLinkResult<Connection> {
+        // XXX(damb): rotated snapshots are taken into account regardless of gaps, e.g. if the
+        // process crashed while rotating
+        for path in Self::rotated_snapshots(snapshot_path) {
+            let valid = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+                .map_err(|e| SeedLinkError::state_db_with_source("failed to open snapshot", e))
+                .and_then(|con| Self::check_integrity(&con));
+            if let Err(e) = valid {
+                warn!(target: trace::STATE, "skipping invalid snapshot {}: {}", path.display(), e);
+                continue;
+            }
+
+            if p.exists() {
+                let mut corrupted = p.as_os_str().to_owned();
+                corrupted.push(".corrupted");
+                fs::rename(p, &corrupted).map_err(|e| {
+                    SeedLinkError::state_db_with_source("failed to move corrupted state db", e)
+                })?;
+            }
+            fs::copy(&path, p).map_err(|e| {
+                SeedLinkError::state_db_with_source("failed to restore snapshot", e)
+            })?;
+
+            info!(target: trace::STATE, "recovered state db from snapshot {}", path.display());
+            return Self::open_connection(p);
+        }
+
+        Err(SeedLinkError::state_db(
+            "failed to recover state db: no valid snapshot available",
+        ))
+    }
+
+    /// Returns the existing snapshots at `path` (including rotated snapshots), most recent first.
+    fn rotated_snapshots(path: &Path) -> Vec<PathBuf> {
+        let file_name = match path.file_name().and_then(|file_name| file_name.to_str()) {
+            Some(file_name) => file_name,
+            None => return Vec::new(),
+        };
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut rv: Vec<(usize, PathBuf)> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let name = entry.file_name();
+                let name = name.to_str()?;
+                let n = if name == file_name {
+                    0
+                } else {
+                    name.strip_prefix(file_name)?
+                        .strip_prefix('.')?
+                        .parse::<usize>()
+                        .ok()?
+                };
+                Some((n, Self::snapshot_path(path, n)))
+            })
+            .collect();
+        rv.sort_by_key(|(n, _)| *n);
+
+        rv.into_iter().map(|(_, path)| path).collect()
+    }
+
+    /// Creates a snapshot of the database `con` at `path`.
+    fn snapshot_connection(con: &Connection, path: &Path) -> SeedLinkResult<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        con.backup(DatabaseName::Main, &tmp, None)
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to create snapshot", e))?;
+        fs::rename(&tmp, path)
+            .map_err(|e| SeedLinkError::state_db_with_source("failed to create snapshot", e))
+    }
+
+    /// Returns the path of the `n`-th rotated snapshot.
+    fn snapshot_path(path: &Path, n: usize) -> PathBuf {
+        if n == 0 {
+            return path.to_path_buf();
+        }
+
+        let mut rv = path.as_os_str().to_owned();
+        rv.push(format!(".{}", n));
+        rv.into()
+    }
+
+    /// Initializes the database schema, migrating databases created by previous versions.
+    fn initialize(con: &Connection) -> rusqlite::Result<()> {
+        con.execute(
+            "CREATE TABLE IF NOT EXISTS stream (\
+                id INTEGER PRIMARY KEY, \
+                namespace TEXT NOT NULL DEFAULT '', \
+                sid TEXT NOT NULL, \
+                seq BIGINT NOT NULL, \
+                proto INTEGER NOT NULL DEFAULT 3, \
+                updated BIGINT NOT NULL DEFAULT 0, \
+                end_time BIGINT \
+            )",
+            (),
+        )?;
+
+        if !Self::has_column(con, "stream", "namespace")? {
+            con.execute(
+                "ALTER TABLE stream ADD COLUMN namespace TEXT NOT NULL DEFAULT ''",
+                (),
+            )?;
+        }
+        // databases created without protocol information contain v3 sequence numbers, only
+        if !Self::has_column(con, "stream", "proto")? {
+            con.execute(
+                "ALTER TABLE stream ADD COLUMN proto INTEGER NOT NULL DEFAULT 3",
+                (),
+            )?;
+        }
+        if !Self::has_column(con, "stream", "updated")? {
+            con.execute(
+                "ALTER TABLE stream ADD COLUMN updated BIGINT NOT NULL DEFAULT 0",
+                (),
+            )?;
+            // XXX(damb): migrated entries count as updated at migration time, such that they
+            // are not pruned immediately
+            con.execute(
+                "UPDATE stream SET updated=?1",
+                [OffsetDateTime::now_utc().unix_timestamp()],
+            )?;
+        }
+        if !Self::has_column(con, "stream", "end_time")? {
+            con.execute("ALTER TABLE stream ADD COLUMN end_time BIGINT", ())?;
+        }
+
+        con.execute("DROP INDEX IF EXISTS idx_stream_sid", ())?;
+        con.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_stream_namespace_sid ON stream(namespace, sid)",
+            (),
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns whether the table `table` has a column named `column`.
+    fn has_column(con: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+        let mut stmt = con.prepare(&format!("PRAGMA table_info({})", table))?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(1)?;
+            if name == column {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Validates the sequence number `seq_num` with regard to the SeedLink protocol version
+    /// `protocol_version`.
+    fn validate_seq_num(seq_num: i64, protocol_version: u8) -> SeedLinkResult<()> {
+        match protocol_version {
+            3 if (0..=MAX_SEQ_NUM_V3).contains(&seq_num) => Ok(()),
+            3 => Err(SeedLinkError::state_db(format!(
+                "invalid sequence number: {} (v3 sequence numbers are 24-bit)",
+                seq_num
+            ))),
+            // v4 sequence numbers are unsigned 64-bit; they are stored bit-preserving
+            4 => Ok(()),
+            _ => Err(SeedLinkError::state_db(format!(
+                "invalid protocol version: {}",
+                protocol_version
+            ))),
+        }
+    }
+
+    /// Stores the stream states `states` within a single transaction.
+    fn store_states(
+        con: &mut Connection,
+        namespace: &str,
+        states: &[StreamState],
+        updated: i64,
+    ) -> rusqlite::Result<usize> {
+        let tx = con.transaction()?;
+        let mut rv = 0;
+        {
+            let mut stmt = tx.prepare_cached(
+                "REPLACE INTO stream(namespace, sid, seq, proto, updated, end_time) \
+                    VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for state in states {
+                // XXX(damb): nanosecond timestamps fit into 64-bit integers until 2262
+                let end_time = state
+                    .end_time
+                    .and_then(|end_time| i64::try_from(end_time.unix_timestamp_nanos()).ok());
+                rv += stmt.execute((
+                    namespace,
+                    state.sid.to_string(),
+                    state.seq_num,
+                    state.protocol_version,
+                    updated,
+                    end_time,
+                ))?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(rv)
+    }
+
+    fn convert_row(
+        sid: String,
+        seq: i64,
+        proto: u8,
+        end_time: Option<i64>,
+    ) -> rusqlite::Result<(String, i64, u8, Option<i64>)> {
+        Ok((sid, seq, proto, end_time))
+    }
+
+    /// Converts the stored end time `end_time` (i.e. nanoseconds since the Unix epoch).
+    fn convert_end_time(end_time: i64) -> SeedLinkResult<OffsetDateTime> {
+        OffsetDateTime::from_unix_timestamp_nanos(end_time.into())
+            .map_err(|e| SeedLinkError::state_db_with_source("invalid end time", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use time::macros::datetime;
+
+    /// Returns a path within the temporary directory unique to the test `name`.
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("slink-state-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("state.db")
+    }
+
+    #[tokio::test]
+    async fn namespaces() {
+        let db = StateDB::open(":memory:").await.unwrap();
+        let mut primary = db.with_namespace("slink://primary:18000");
+        let mut backup = db.with_namespace("slink://backup:18000");
+
+        primary.store("FDSN:GE_WLF_00_B_H_Z", 42, 3).await.unwrap();
+        backup.store("FDSN:GE_WLF_00_B_H_Z", 7, 3).await.unwrap();
+
+        assert_eq!(
+            primary.seq_num("FDSN:GE_WLF_00_B_H_Z", 3).await.unwrap(),
+            Some(42)
+        );
+        assert_eq!(
+            backup.seq_num("FDSN:GE_WLF_00_B_H_Z", 3).await.unwrap(),
+            Some(7)
+        );
+        assert!(db.clone().state().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn seq_num_protocol_versions() {
+        let mut db = StateDB::open(":memory:").await.unwrap();
+
+        assert!(StateDB::validate_seq_num(0xFFFFFF, 3).is_ok());
+        assert!(StateDB::validate_seq_num(0x1000000, 3).is_err());
+        assert!(StateDB::validate_seq_num(-1, 3).is_err());
+        assert!(StateDB::validate_seq_num(-1, 4).is_ok());
+        assert!(StateDB::validate_seq_num(42, 5).is_err());
+        assert!(db
+            .store("FDSN:GE_WLF_00_B_H_Z", 0x1000000, 3)
+            .await
+            .is_err());
+
+        db.store("FDSN:GE_WLF_00_B_H_Z", 0x1000000, 4)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.seq_num("FDSN:GE_WLF_00_B_H_Z", 4).await.unwrap(),
+            Some(0x1000000)
+        );
+        assert_eq!(db.seq_num("FDSN:GE_WLF_00_B_H_Z", 3).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn prune() {
+        let mut db = StateDB::open(":memory:").await.unwrap();
+        db.store("FDSN:GE_WLF_00_B_H_Z", 42, 3).await.unwrap();
+        db.store("FDSN:GE_APE_00_B_H_Z", 7, 3).await.unwrap();
+        db.con
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE stream SET updated=0 WHERE sid='FDSN:GE_APE_00_B_H_Z'",
+                (),
+            )
+            .unwrap();
+
+        assert_eq!(
+            db.prune_older_than(Duration::from_secs(3600))
+                .await
+                .unwrap(),
+            1
+        );
+        let state = db.state().await.unwrap();
+        assert_eq!(state.len(), 1);
+        assert_eq!(state[0].sid.to_string(), "FDSN:GE_WLF_00_B_H_Z");
+    }
+
+    #[tokio::test]
+    async fn snapshot_rotate_recover() {
+        let p = temp_path("snapshot_rotate_recover");
+        let snapshot = p.with_file_name("snapshot.db");
+
+        let mut db = StateDB::open(&p).await.unwrap();
+        for seq_num in 1..=3 {
+            db.store("FDSN:GE_WLF_00_B_H_Z", seq_num, 3).await.unwrap();
+            db.snapshot_rotate(&snapshot, 3).await.unwrap();
+        }
+        assert!(StateDB::snapshot_path(&snapshot, 2).exists());
+        assert!(!StateDB::snapshot_path(&snapshot, 3).exists());
+        drop(db);
+
+        // crash while rotating, i.e. the most recent snapshot is missing
+        fs::remove_file(&snapshot).unwrap();
+        fs::write(&p, b"corrupted").unwrap();
+
+        let mut db = StateDB::open_or_recover(&p, &snapshot).await.unwrap();
+        assert_eq!(
+            db.seq_num("FDSN:GE_WLF_00_B_H_Z", 3).await.unwrap(),
+            Some(2)
+        );
+        assert!(p.with_file_name("state.db.corrupted").exists());
+
+        fs::remove_dir_all(p.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn end_time() {
+        let mut db = StateDB::open(":memory:").await.unwrap();
+        let end_time = datetime!(2023-01-01 12:00:00.123456789 UTC);
+        db.store_batch(vec![
+            StreamState {
+                sid: "FDSN:GE_WLF_00_B_H_Z".parse().unwrap(),
+                seq_num: 42,
+                protocol_version: 3,
+                end_time: Some(end_time),
+            },
+            StreamState {
+                sid: "FDSN:GE_APE_00_B_H_Z".parse().unwrap(),
+                seq_num: 7,
+                protocol_version: 4,
+                end_time: None,
+            },
+        ])
+        .await
+        .unwrap();
+
+        assert_eq!(
+            db.end_time("FDSN:GE_WLF_00_B_H_Z").await.unwrap(),
+            Some(end_time)
+        );
+        assert_eq!(db.end_time("FDSN:GE_APE_00_B_H_Z").await.unwrap(), None);
+        assert_eq!(db.end_time("FDSN:GE_KMBO_00_B_H_Z").await.unwrap(), None);
+
+        let state = db.state().await.unwrap();
+        assert_eq!(state[0].end_time, None);
+        assert_eq!(state[1].end_time, Some(end_time));
+    }
+
+    #[tokio::test]
+    async fn migrate_without_end_time() {
+        let p = temp_path("migrate_without_end_time");
+        {
+            let con = Connection::open(&p).unwrap();
+            con.execute(
+                "CREATE TABLE stream (\
+                    id INTEGER PRIMARY KEY, \
+                    namespace TEXT NOT NULL DEFAULT '', \
+                    sid TEXT NOT NULL, \
+                    seq BIGINT NOT NULL, \
+                    proto INTEGER NOT NULL DEFAULT 3, \
+                    updated BIGINT NOT NULL DEFAULT 0 \
+                )",
+                (),
+            )
+            .unwrap();
+            con.execute(
+                "INSERT INTO stream(sid, seq, proto, updated) \
+                    VALUES('FDSN:GE_WLF_00_B_H_Z', 42, 4, 1)",
+                (),
+            )
+            .unwrap();
+        }
+
+        let mut db = StateDB::open(&p).await.unwrap();
+        let state = db.state().await.unwrap();
+        assert_eq!(state.len(), 1);
+        assert_eq!(state[0].seq_num, 42);
+        assert_eq!(state[0].protocol_version, 4);
+        assert_eq!(state[0].end_time, None);
+
+        fs::remove_dir_all(p.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn migrate_without_namespaces() {
+        let p = temp_path("migrate_without_namespaces");
+        {
+            let con = Connection::open(&p).unwrap();
+            con.execute(
+                "CREATE TABLE stream (id INTEGER PRIMARY KEY, sid TEXT NOT NULL, seq BIGINT NOT NULL)",
+                (),
+            )
+            .unwrap();
+            con.execute("CREATE UNIQUE INDEX idx_stream_sid ON stream(sid)", ())
+                .unwrap();
+            con.execute(
+                "INSERT INTO stream(sid, seq) VALUES('FDSN:GE_WLF_00_B_H_Z', 42)",
+                (),
+            )
+            .unwrap();
+        }
+
+        let db = StateDB::open(&p).await.unwrap();
+        let mut default = db.clone();
+        // migrated entries are not pruned immediately
+        assert_eq!(
+            default
+                .prune_older_than(Duration::from_secs(3600))
+                .await
+                .unwrap(),
+            0
+        );
+
+        let mut namespaced = db.with_namespace("slink://geofon:18000");
+        namespaced
+            .store("FDSN:GE_APE_00_B_H_Z", 7, 3)
+            .await
+            .unwrap();
+        assert_eq!(namespaced.adopt_namespace("").await.unwrap(), 1);
+        assert!(default.state().await.unwrap().is_empty());
+        assert_eq!(
+            namespaced.seq_num("FDSN:GE_WLF_00_B_H_Z", 3).await.unwrap(),
+            Some(42)
+        );
+        assert_eq!(namespaced.state().await.unwrap().len(), 2);
+
+        fs::remove_dir_all(p.parent().unwrap()).unwrap();
     }
 }
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::stream::Stream;
+use mseed::MSControlFlags;
+use pin_project_lite::pin_project;
+use tokio::time as tokio_time;
+use tracing::{debug, warn};
+
+use crate::trace;
+use crate::{
+    SeedLinkPacket, SeedLinkPacketV3, SeedLinkResult, StateDB, StreamEnd, StreamItem, StreamState,
+};
+
+/// Default interval stream states are flushed to the `StateDB`.
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// Default number of pending stream states triggering a flush.
+const DEFAULT_FLUSH_THRESHOLD: usize = 1000;
+
+type FlushFuture = Pin<Box<dyn Future<Output = SeedLinkResult<usize>> + Send>>;
+
+/// Extension trait for packet streams (see [`Connection::packets`](crate::Connection::packets)).
+pub trait StateTrackingExt: Stream<Item = StreamItem> + Sized {
+    /// Wraps the packet stream keeping track of the stream state of the data packets passing
+    /// through.
+    ///
+    /// Sequence numbers (and the end times of the records) are updated in memory and flushed to
+    /// `state_db` every [`StateTracking::flush_interval`] or once
+    /// [`StateTracking::flush_threshold`] streams are pending. Pending stream states are flushed
+    /// before the terminal [`StreamItem::End`] item is yielded. Note that no items are yielded
+    /// while flushing.
+    fn with_state_tracking(self, state_db: StateDB) -> StateTracking<Self> {
+        StateTracking::new(self, state_db)
+    }
+}
+
+impl<S: Stream<Item = StreamItem>> StateTrackingExt for S {}
+
+pin_project! {
+    /// Stream adapter for [`StateTrackingExt::with_state_tracking`].
+    #[must_use = "streams do nothing unless polled"]
+    pub struct StateTracking<S> {
+        #[pin]
+        packets: S,
+        state_db: StateDB,
+        flush_interval: Duration,
+        flush_threshold: usize,
+        pending: HashMap<String, StreamState>,
+        last_flush: tokio_time::Instant,
+        flushing: Option<FlushFuture>,
+        // item yielded once flushing completed
+        deferred: Option<StreamItem>,
+        done: bool,
+    }
+}
+
+impl<S: Stream<Item = StreamItem>> StateTracking<S> {
+    fn new(packets: S, state_db: StateDB) -> Self {
+        Self {
+            packets,
+            state_db,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+            pending: HashMap::new(),
+            last_flush: tokio_time::Instant::now(),
+            flushing: None,
+            deferred: None,
+            done: false,
+        }
+    }
+
+    /// Sets the interval pending stream states are flushed to the `StateDB`.
+    ///
+    /// Note that the interval is checked whenever an item is received (including keepalive
+    /// packets).
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Sets the number of pending stream states triggering a flush to the `StateDB`.
+    pub fn flush_threshold(mut self, threshold: usize) -> Self {
+        self.flush_threshold = threshold;
+        self
+    }
+}
+
+impl<S: Stream<Item = StreamItem>> Stream for StateTracking<S> {
+    type Item = StreamItem;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            if let Some(flushing) = this.flushing.as_mut() {
+                let res = match flushing.as_mut().poll(cx) {
+                    Poll::Ready(res) => res,
+                    Poll::Pending => return Poll::Pending,
+                };
+                *this.flushing = None;
+                *this.last_flush = tokio_time::Instant::now();
+
+                match res {
+                    Ok(updated) => {
+                        debug!(target: trace::STATE, "flushed {} stream states", updated)
+                    }
+                    Err(e) => {
+                        *this.done = true;
+                        return Poll::Ready(Some(StreamItem::End(StreamEnd::Error(e))));
+                    }
+                }
+
+                match this.deferred.take() {
+                    Some(item) => return Poll::Ready(Some(item)),
+                    None => continue,
+                }
+            }
+
+            if *this.done {
+                return Poll::Ready(None);
+            }
+
+            let item = match this.packets.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => item,
+                Poll::Ready(None) => {
+                    *this.done = true;
+                    if this.pending.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    *this.flushing = Some(flush(this.state_db, this.pending));
+                    continue;
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let is_end = match item {
+                StreamItem::Packet(ref packet) => {
+                    track(this.pending, packet);
+                    false
+                }
+                StreamItem::End(_) => true,
+            };
+
+            if !this.pending.is_empty()
+                && (is_end
+                    || this.pending.len() >= *this.flush_threshold
+                    || this.last_flush.elapsed() >= *this.flush_interval)
+            {
+                *this.flushing = Some(flush(this.state_db, this.pending));
+                *this.deferred = Some(item);
+                continue;
+            }
+
+            return Poll::Ready(Some(item));
+        }
+    }
+}
+
+/// Updates the stream state of `packet` in `pending`. Packets other than data packets are
+/// ignored.
+fn track(pending: &mut HashMap<String, StreamState>, packet: &SeedLinkPacket) {
+    match stream_state(packet) {
+        Some(Ok(state)) => {
+            pending.insert(state.sid.to_string(), state);
+        }
+        Some(Err(e)) => warn!(target: trace::STATE, "failed to track stream state: {}", e),
+        None => {}
+    }
+}
+
+fn stream_state(packet: &SeedLinkPacket) -> Option<SeedLinkResult<StreamState>> {
+    let res = match packet {
+        SeedLinkPacket::V3(SeedLinkPacketV3::GenericData(packet)) => {
+            packet.sequence_number().and_then(|seq_num| {
+                let ms_record = packet.payload(MSControlFlags::empty())?;
+                Ok((
+                    ms_record.sid()?,
+                    seq_num as i64,
+                    3,
+                    ms_record.end_time().ok(),
+                ))
+            })
+        }
+        SeedLinkPacket::V3(SeedLinkPacketV3::Info(_)) => return None,
+        SeedLinkPacket::V4(packet_v4) => {
+            if !packet.is_data() {
+                return None;
+            }
+            packet_v4.payload_to_ms_record().and_then(|ms_record| {
+                Ok((
+                    ms_record.sid()?,
+                    packet_v4.sequence_number() as i64,
+                    4,
+                    ms_record.end_time().ok(),
+                ))
+            })
+        }
+    };
+
+    Some(res.and_then(|(sid, seq_num, protocol_version, end_time)| {
+        Ok(StreamState {
+            sid: sid.parse()?,
+            seq_num,
+            protocol_version,
+            end_time,
+        })
+    }))
+}
+
+/// Returns a future flushing the `pending` stream states to `state_db`.
+fn flush(state_db: &StateDB, pending: &mut HashMap<String, StreamState>) -> FlushFuture {
+    let mut state_db = state_db.clone();
+    let states: Vec<StreamState> = pending.drain().map(|(_, state)| state).collect();
+
+    Box::pin(async move { state_db.store_batch(states).await })
+}
@@ -1,19 +1,193 @@
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
+use std::str::FromStr;
 
 use time::PrimitiveDateTime;
 
-#[derive(Debug, Clone)]
-pub(crate) struct StreamConfig {
+use crate::{DataTransferMode, SeedLinkError, SeedLinkResult};
+
+/// Maximum length of network and station codes.
+const MAX_CODE_LEN: usize = 8;
+
+/// Stream selector (i.e. the argument of the `SELECT` command), e.g. `BH?` or `00BHZ.D`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct StreamSelector(String);
+
+impl StreamSelector {
+    /// Creates a new stream selector. Fails if `selector` contains invalid characters.
+    pub fn new(selector: &str) -> SeedLinkResult<Self> {
+        if selector.is_empty()
+            || !selector
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "?*.-!_:".contains(c))
+        {
+            return Err(SeedLinkError::InvalidCommandArgument(format!(
+                "invalid stream selector: {:?}",
+                selector
+            )));
+        }
+
+        Ok(Self(selector.to_string()))
+    }
+
+    /// Returns the selector as string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for StreamSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for StreamSelector {
+    type Err = SeedLinkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+/// Station subscribed to, i.e. negotiated successfully (see
+/// [`Connection::subscription`](crate::Connection::subscription)).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SubscribedStation {
+    /// The network code.
     pub network: String,
+    /// The station code.
     pub station: String,
+    /// The stream selectors requested. If empty, all streams of the station are requested.
+    pub selectors: Vec<String>,
+    /// The sequence number data is resumed from, if any.
+    pub seq_num: Option<u64>,
+    /// The start time data is requested from, if any.
+    pub start_time: Option<PrimitiveDateTime>,
+    /// The end of the time window data is requested for, if any.
+    pub end_time: Option<PrimitiveDateTime>,
+    /// The effective data transfer mode. Note that time windows are transferred in dial-up mode.
+    pub data_transfer_mode: DataTransferMode,
+}
+
+/// Stream configuration of a station, i.e. the data requested for the station.
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    pub(crate) network: String,
+    pub(crate) station: String,
     select_args: Vec<String>,
-    pub seq_num: Option<String>,
-    pub time: Option<PrimitiveDateTime>,
+    pub(crate) seq_num: Option<String>,
+    pub(crate) time: Option<PrimitiveDateTime>,
+    /// Station specific end of the time window data is requested for.
+    pub(crate) end_time: Option<PrimitiveDateTime>,
+    /// Station specific data transfer mode overriding the connection's data transfer mode.
+    pub(crate) data_transfer_mode: Option<DataTransferMode>,
 }
 
 impl StreamConfig {
-    pub fn new(
+    /// Creates a new stream configuration for the station `sta` of network `net`. Fails if the
+    /// codes are invalid.
+    ///
+    /// The codes may contain the wildcards `?` and `*`.
+    pub fn try_new(net: &str, sta: &str) -> SeedLinkResult<Self> {
+        validate_code("network", net)?;
+        validate_code("station", sta)?;
+
+        Ok(Self::new(net, sta, None, None, None))
+    }
+
+    /// Adds the stream selector `selector`. By default, all streams of the station are
+    /// requested.
+    pub fn with_selector(mut self, selector: StreamSelector) -> Self {
+        self.select_args.push(selector.0);
+        self
+    }
+
+    /// Requests data starting from the packet with sequence number `seq_num`.
+    pub fn with_seq_num(mut self, seq_num: u64) -> Self {
+        self.seq_num = Some(format!("{:x}", seq_num));
+        self
+    }
+
+    /// Requests data starting from `start_time`.
+    pub fn with_start_time(mut self, start_time: PrimitiveDateTime) -> Self {
+        self.time = Some(start_time);
+        self
+    }
+
+    /// Requests data for the time window from `start_time` to `end_time`.
+    pub fn with_time_window(
+        mut self,
+        start_time: PrimitiveDateTime,
+        end_time: PrimitiveDateTime,
+    ) -> Self {
+        self.time = Some(start_time);
+        self.end_time = Some(end_time);
+        self
+    }
+
+    /// Sets the station specific data transfer mode.
+    pub fn with_data_transfer_mode(mut self, data_transfer_mode: DataTransferMode) -> Self {
+        self.data_transfer_mode = Some(data_transfer_mode);
+        self
+    }
+
+    /// Returns the network code.
+    pub fn network(&self) -> &str {
+        &self.network
+    }
+
+    /// Returns the station code.
+    pub fn station(&self) -> &str {
+        &self.station
+    }
+
+    /// Returns the stream selectors.
+    pub fn selectors(&self) -> &[String] {
+        &self.select_args
+    }
+
+    /// Returns the sequence number data is requested from, if any.
+    pub fn seq_num(&self) -> Option<u64> {
+        self.seq_num
+            .as_deref()
+            .and_then(|seq_num| u64::from_str_radix(seq_num, 16).ok())
+    }
+
+    /// Returns the time window (i.e. start and end time) data is requested for.
+    pub fn time_window(&self) -> (Option<PrimitiveDateTime>, Option<PrimitiveDateTime>) {
+        (self.time, self.end_time)
+    }
+
+    /// Returns the station subscribed to if the connection was configured with
+    /// `data_transfer_mode` and (optionally) `end_time`.
+    pub(crate) fn subscribed(
+        &self,
+        data_transfer_mode: DataTransferMode,
+        end_time: Option<PrimitiveDateTime>,
+    ) -> SubscribedStation {
+        // station specific time windows take precedence, station specific data transfer modes do
+        // not apply in time window mode
+        let (data_transfer_mode, end_time) = match (self.end_time, end_time) {
+            (Some(end_time), _) | (None, Some(end_time)) => {
+                (DataTransferMode::DialUp, Some(end_time))
+            }
+            (None, None) => (self.data_transfer_mode.unwrap_or(data_transfer_mode), None),
+        };
+
+        SubscribedStation {
+            network: self.network.clone(),
+            station: self.station.clone(),
+            selectors: self.select_args.clone(),
+            seq_num: self.seq_num(),
+            start_time: self.time,
+            end_time,
+            data_transfer_mode,
+        }
+    }
+
+    pub(crate) fn new(
         network: &str,
         station: &str,
         selector_arg: Option<String>,
@@ -30,16 +204,18 @@ impl StreamConfig {
             select_args,
             seq_num,
             time,
+            end_time: None,
+            data_transfer_mode: None,
         }
     }
 
     /// Adds a `SELECT` command argument to the stream configuration.
-    pub fn add_select_arg(&mut self, select_arg: &str) {
+    pub(crate) fn add_select_arg(&mut self, select_arg: &str) {
         self.select_args.push(select_arg.to_string());
     }
 
     /// Clears selectors for a given station.
-    pub fn clear_select_args(&mut self) {
+    pub(crate) fn clear_select_args(&mut self) {
         self.select_args.clear();
     }
 }
@@ -59,3 +235,56 @@ impl Hash for StreamConfig {
     }
 }
 
+fn validate_code(name: &str, code: &str) -> SeedLinkResult<()> {
+    if code.is_empty()
+        || code.len() > MAX_CODE_LEN
+        || !code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '?' || c == '*')
+    {
+        return Err(SeedLinkError::InvalidCommandArgument(format!(
+            "invalid {} code: {:?}",
+            name, code
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use time::macros::datetime;
+
+    #[test]
+    fn stream_config() {
+        let stream_config = StreamConfig::try_new("GE", "WLF")
+            .unwrap()
+            .with_selector("BH?".parse().unwrap())
+            .with_seq_num(42)
+            .with_time_window(datetime!(2023-01-01 0:00), datetime!(2023-01-02 0:00));
+        assert_eq!(stream_config.network(), "GE");
+        assert_eq!(stream_config.selectors(), &["BH?".to_string()]);
+        assert_eq!(stream_config.seq_num, Some("2a".to_string()));
+        assert_eq!(stream_config.seq_num(), Some(42));
+        assert_eq!(
+            stream_config.time_window(),
+            (
+                Some(datetime!(2023-01-01 0:00)),
+                Some(datetime!(2023-01-02 0:00))
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_codes() {
+        assert!(StreamConfig::try_new("GE", "W*").is_ok());
+        assert!(StreamConfig::try_new("", "WLF").is_err());
+        assert!(StreamConfig::try_new("GE", "WLF GE").is_err());
+        assert!(StreamConfig::try_new("GE", "WALFERDANGE").is_err());
+        assert!(StreamSelector::new("00BHZ.D").is_ok());
+        assert!(StreamSelector::new("BHZ\r\nEND").is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+use crate::{SeedLinkConnectionInfo, SeedLinkError, SeedLinkResult};
+
+/// Performs the TLS handshake with `host` on top of the established TCP connection `stream`.
+pub(crate) async fn connect(
+    host: &str,
+    stream: TcpStream,
+    slink_connection_info: &SeedLinkConnectionInfo,
+) -> SeedLinkResult<TlsStream<TcpStream>> {
+    let config = client_config(slink_connection_info)?;
+    let server_name = ServerName::try_from(host).map_err(|_| {
+        SeedLinkError::InvalidClientConfig(format!("invalid TLS server name: {}", host))
+    })?;
+
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream)
+        .await?;
+
+    Ok(stream)
+}
+
+fn client_config(slink_connection_info: &SeedLinkConnectionInfo) -> SeedLinkResult<ClientConfig> {
+    let mut root_store = RootCertStore::empty();
+    match slink_connection_info.tls_ca_file {
+        Some(ref path) => add_ca_file(&mut root_store, path)?,
+        None => root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        })),
+    }
+
+    let mut config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+
+    if slink_connection_info.tls_insecure {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(NoCertificateVerification));
+    }
+
+    Ok(config)
+}
+
+/// Adds the PEM encoded CA certificates from `path` to `root_store`.
+fn add_ca_file(root_store: &mut RootCertStore, path: &Path) -> SeedLinkResult<()> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    if certs.is_empty() {
+        return Err(SeedLinkError::InvalidClientConfig(format!(
+            "no CA certificates found: {}",
+            path.display()
+        )));
+    }
+
+    for cert in certs {
+        root_store.add(&Certificate(cert)).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid CA certificate ({}): {}", path.display(), e),
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Certificate verifier accepting any server certificate.
+///
+/// Note that disabling certificate verification makes the connection vulnerable to
+/// man-in-the-middle attacks.
+struct NoCertificateVerification;
+
+impl ServerCertVerifier for NoCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
@@ -0,0 +1,81 @@
+//! Tracing targets of the library.
+//!
+//! Events and spans are emitted with the targets below, i.e. subsystems may be debugged
+//! individually, e.g. by means of the `RUST_LOG=warn,slink::negotiate=debug` directives.
+//!
+//! Spans:
+//!
+//! - `configure` (target [`NEGOTIATE`]): handshaking, with the fields `data_transfer_mode`,
+//!   `end_time` and `pipelining`.
+//! - `recover_state` (target [`STATE`]): recovery of the stream states, with the field
+//!   `namespace`.
+
+use std::fmt;
+
+use tracing::Level;
+
+/// Target of the connection life cycle (e.g. establishing connections and streaming packets).
+pub const CONNECTION: &str = "slink::connection";
+/// Target of the encoding and decoding of frames.
+pub const CODEC: &str = "slink::codec";
+/// Target of the handshaking (i.e. the commands sent and the responses received).
+pub const NEGOTIATE: &str = "slink::negotiate";
+/// Target of the stream state tracking (see [`StateDB`](crate::StateDB)).
+pub const STATE: &str = "slink::state";
+/// Target of the packet stream adapters (e.g. continuity and catch up tracking).
+pub const STREAM: &str = "slink::stream";
+
+/// All targets of the library.
+pub const TARGETS: &[&str] = &[CONNECTION, CODEC, NEGOTIATE, STATE, STREAM];
+
+/// Preset of tracing filter directives, i.e. a default level and levels per target.
+///
+/// Example usage::
+///
+/// ```rust
+/// use slink::trace::{self, FilterPreset};
+/// use tracing::Level;
+///
+/// let preset = FilterPreset::new(Level::WARN).target(trace::NEGOTIATE, Level::DEBUG);
+/// assert_eq!(preset.to_string(), "warn,slink::negotiate=debug");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterPreset {
+    default: Level,
+    targets: Vec<(String, Level)>,
+}
+
+impl FilterPreset {
+    /// Creates a preset enabling events up to the level `default` for all targets.
+    pub fn new(default: Level) -> Self {
+        Self {
+            default,
+            targets: Vec::new(),
+        }
+    }
+
+    /// Enables events up to the level `level` for the target `target` (e.g. [`NEGOTIATE`]).
+    pub fn target(mut self, target: &str, level: Level) -> Self {
+        self.targets.retain(|(t, _)| t != target);
+        self.targets.push((target.to_string(), level));
+        self
+    }
+
+    /// Returns the `EnvFilter` corresponding to the preset.
+    #[cfg(feature = "env-filter")]
+    pub fn to_env_filter(&self) -> tracing_subscriber::EnvFilter {
+        tracing_subscriber::EnvFilter::new(self.to_string())
+    }
+}
+
+impl fmt::Display for FilterPreset {
+    /// Formats the preset as filter directives (e.g. `warn,slink::negotiate=debug`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_lowercase())?;
+        for (target, level) in &self.targets {
+            write!(f, ",{}={}", target, level.as_str().to_lowercase())?;
+        }
+
+        Ok(())
+    }
+}
@@ -2,39 +2,79 @@ use std::fmt;
 use std::io;
 use std::str::FromStr;
 
+use tracing::warn;
+
 use crate::{SeedLinkError, SeedLinkResult};
 
 pub struct ParsedHelloResponse {
     pub protocol_versions: Vec<String>,
+    /// The capabilities advertised (including `SLPROTO` capabilities).
+    pub capabilities: Vec<String>,
+    /// The server software identifier, e.g. `SeedLink v4.0 (2023.123 RingServer)`.
+    pub software: String,
     pub station_or_datacenter_desc: String,
 }
 
+/// Parses the response to `HELLO`.
+///
+/// If `fallback_protocol_version` is provided, nonstandard responses are accepted, i.e. the
+/// fallback protocol version is assumed if the server does not advertise a valid protocol version
+/// (e.g. `SeedLink Server`).
 pub fn parse_hello_response(
     first_resp_line: &str,
     second_resp_line: String,
+    fallback_protocol_version: Option<&str>,
 ) -> SeedLinkResult<ParsedHelloResponse> {
-    let split: Vec<&str> = first_resp_line.splitn(2, " v").collect();
-    if split.len() != 2 || split[1].len() < 3 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "failed to parse SeedLink protocol version",
-        )
-        .into());
-    }
-
-    if let Err(_) = split[1][..3].parse::<f32>() {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "failed to parse SeedLink protocol version",
-        )
-        .into());
+    let (seedlink_id, version) = first_resp_line
+        .split_once(" v")
+        .unwrap_or((first_resp_line, ""));
+    let highest_supported_protocol_version = match version.get(..3) {
+        Some(v) if v.parse::<f32>().is_ok() => v.to_string(),
+        _ => match fallback_protocol_version {
+            Some(v) => {
+                warn!(
+                    "nonstandard response to 'HELLO' ('{}'): assuming protocol version {}",
+                    first_resp_line, v
+                );
+                v.to_string()
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "failed to parse SeedLink protocol version",
+                )
+                .into());
+            }
+        },
+    };
+
+    // SeedLink v4 servers additionally advertise the supported protocol versions as capabilities,
+    // e.g. `SeedLink v4.0 (2021.123) :: SLPROTO:4.0 SLPROTO:3.1`; malformed versions are ignored
+    let mut protocol_versions = vec![highest_supported_protocol_version];
+    let capabilities: Vec<String> = first_resp_line
+        .split_once("::")
+        .map(|(_, capabilities)| capabilities.split_whitespace().map(String::from).collect())
+        .unwrap_or_default();
+    for proto_version in capabilities
+        .iter()
+        .filter_map(|cap| cap.strip_prefix("SLPROTO:"))
+        .filter(|v| parse_protocol_version(v).is_some())
+    {
+        if !protocol_versions.iter().any(|v| v == proto_version) {
+            protocol_versions.push(proto_version.to_string());
+        }
     }
-
-    let highest_supported_protocol_version = split[1][..3].to_string();
-
-    // TODO(damb): prepare for SeedLink v4.0 and parse additionally supported protocol versions
-
-    let seedlink_id = split[0].to_lowercase();
+    // highest protocol version first
+    protocol_versions.sort_by_key(|v| std::cmp::Reverse(parse_protocol_version(v)));
+
+    let seedlink_id = match fallback_protocol_version {
+        Some(_) => seedlink_id
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase(),
+        None => seedlink_id.to_lowercase(),
+    };
     if seedlink_id != "seedlink" {
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
@@ -43,12 +83,26 @@ pub fn parse_hello_response(
         .into());
     }
 
+    let software = first_resp_line
+        .split_once("::")
+        .map_or(first_resp_line, |(software, _)| software)
+        .trim()
+        .to_string();
+
     Ok(ParsedHelloResponse {
-        protocol_versions: vec![highest_supported_protocol_version],
+        protocol_versions,
+        capabilities,
+        software,
         station_or_datacenter_desc: second_resp_line,
     })
 }
 
+/// Parses the protocol version `version` (e.g. `4.0`) into its major and minor version.
+pub fn parse_protocol_version(version: &str) -> Option<(u8, u8)> {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
 /// Utility structure for network, station, location, and channel code identifiers.
 #[derive(Debug, Clone)]
 pub struct NSLC {
@@ -147,3 +201,38 @@ pub fn get_select_arg_v4(sid: &FDSNSourceId) -> String {
     format!("{}{}{}", sid.nslc.loc, NSLC::SEP, sid.nslc.cha)
 }
 
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_hello_slproto() {
+        let resp = parse_hello_response(
+            "SeedLink v4.0 (2023.123 RingServer) :: SLPROTO:3.1 SLPROTO:4.0 SLPROTO:x CAP",
+            "GEOFON".to_string(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(resp.protocol_versions, vec!["4.0", "3.1"]);
+        assert_eq!(
+            resp.capabilities,
+            vec!["SLPROTO:3.1", "SLPROTO:4.0", "SLPROTO:x", "CAP"]
+        );
+        assert_eq!(resp.software, "SeedLink v4.0 (2023.123 RingServer)");
+
+        let resp =
+            parse_hello_response("SeedLink v3.1 (2020.075)", "GEOFON".to_string(), None).unwrap();
+        assert_eq!(resp.protocol_versions, vec!["3.1"]);
+        assert!(resp.capabilities.is_empty());
+    }
+
+    #[test]
+    fn parse_hello_lenient() {
+        assert!(parse_hello_response("SeedLink Server", String::new(), None).is_err());
+
+        let resp = parse_hello_response("SeedLink Server", String::new(), Some("3.0")).unwrap();
+        assert_eq!(resp.protocol_versions, vec!["3.0"]);
+        assert!(parse_hello_response("Foo Server", String::new(), Some("3.0")).is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use std::fmt;
+use std::str;
+
+use crate::ProtocolErrorV3;
+
+/// Capability indicating extended replies, i.e. `ERROR` responses followed by the reason of the
+/// failure (e.g. `ERROR invalid station`).
+///
+/// Servers advertise the capability in response to `HELLO` (together with the `CAP` capability),
+/// clients enable it by means of the `CAPABILITIES` command.
+pub const CAPABILITY_EXTREPLY: &str = "EXTREPLY";
+
+/// Capability indicating that the server accepts the `CAPABILITIES` command.
+pub const CAPABILITY_CAP: &str = "CAP";
+
+/// Command to enable the client capabilities `capabilities` (e.g. `EXTREPLY`).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Capabilities {
+    capabilities: Vec<String>,
+}
+
+impl Capabilities {
+    pub const NAME: &'static str = "capabilities";
+
+    pub fn new(capabilities: Vec<String>) -> Self {
+        Self { capabilities }
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", Capabilities::NAME, self.capabilities.join(" "))
+    }
+}
+
+impl str::FromStr for Capabilities {
+    type Err = ProtocolErrorV3;
+
+    fn from_str(s: &str) -> Result<Capabilities, Self::Err> {
+        let capabilities: Vec<String> = s.split_whitespace().map(String::from).collect();
+        if capabilities.is_empty() {
+            return Err(ProtocolErrorV3);
+        }
+
+        Ok(Capabilities::new(capabilities))
+    }
+}
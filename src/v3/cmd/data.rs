@@ -1,9 +1,11 @@
 use std::fmt;
+use std::str;
 
 // TODO(damb): use OffsetDataTime
 use time::PrimitiveDateTime;
 
 use super::super::util;
+use crate::ProtocolErrorV3;
 
 /// Action command to enable *real-time* mode for a given station.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -34,3 +36,24 @@ impl fmt::Display for Data {
     }
 }
 
+impl str::FromStr for Data {
+    type Err = ProtocolErrorV3;
+
+    fn from_str(s: &str) -> Result<Data, Self::Err> {
+        let split: Vec<&str> = s.split_whitespace().collect();
+        if split.len() > 2 {
+            return Err(ProtocolErrorV3);
+        }
+
+        let seq_num = split
+            .first()
+            .map(|seq_num| i32::from_str_radix(seq_num, 16).map_err(|_| ProtocolErrorV3))
+            .transpose()?;
+        let begin = split
+            .get(1)
+            .map(|begin| util::parse_seedlink_time(begin).ok_or(ProtocolErrorV3))
+            .transpose()?;
+
+        Ok(Data { seq_num, begin })
+    }
+}
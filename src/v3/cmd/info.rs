@@ -1,4 +1,7 @@
 use std::fmt;
+use std::str;
+
+use crate::ProtocolErrorV3;
 
 /// Command to request information about the SeedLink server.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -45,3 +48,20 @@ impl fmt::Display for InfoItem {
         write!(f, "{}", item)
     }
 }
+
+impl str::FromStr for InfoItem {
+    type Err = ProtocolErrorV3;
+
+    fn from_str(s: &str) -> Result<InfoItem, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "id" => InfoItem::Id,
+            "capabilities" => InfoItem::Capabilities,
+            "stations" => InfoItem::Stations,
+            "streams" => InfoItem::Streams,
+            "gaps" => InfoItem::Gaps,
+            "connections" => InfoItem::Connections,
+            "all" => InfoItem::All,
+            _ => return Err(ProtocolErrorV3),
+        })
+    }
+}
@@ -1,7 +1,9 @@
 use std::fmt;
+use std::str::{self, FromStr};
 
 pub use batch::Batch;
 pub use bye::Bye;
+pub use capabilities::{Capabilities, CAPABILITY_CAP, CAPABILITY_EXTREPLY};
 pub use data::Data;
 pub use end::End;
 pub use fetch::Fetch;
@@ -12,10 +14,11 @@ pub use self::time::Time;
 pub use station::Station;
 pub use unknown::Unknown;
 
-use crate::Frame;
+use crate::{Frame, ProtocolErrorV3};
 
 mod batch;
 mod bye;
+mod capabilities;
 mod data;
 mod end;
 mod fetch;
@@ -26,12 +29,14 @@ mod station;
 mod time;
 mod unknown;
 
+/// Enumeration of SeedLink `v3` commands.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Command {
     Bye(Bye),
     Hello(Hello),
     Info(Info),
     Batch(Batch),
+    Capabilities(Capabilities),
     Station(Station),
     Select(Select),
     Data(Data),
@@ -45,6 +50,54 @@ impl Command {
     pub fn into_frame(&self) -> Frame {
         Frame::Line(self.to_string().as_bytes().to_vec())
     }
+
+    /// Returns the wire representation of the command, i.e. the command line including the
+    /// `<CR><LF>` terminator.
+    pub fn to_wire(&self) -> Vec<u8> {
+        let mut buf = self.to_string().into_bytes();
+        buf.extend_from_slice(b"\r\n");
+        buf
+    }
+
+    /// Parses the command from a buffer (e.g. as returned by [`Command::to_wire`]). A trailing
+    /// line terminator is ignored.
+    pub fn parse(buf: &[u8]) -> Result<Self, ProtocolErrorV3> {
+        let s = str::from_utf8(buf).map_err(|_| ProtocolErrorV3)?;
+
+        Self::from_str(s.trim_end_matches(['\r', '\n']))
+    }
+}
+
+impl str::FromStr for Command {
+    type Err = ProtocolErrorV3;
+
+    fn from_str(s: &str) -> Result<Command, Self::Err> {
+        let (cmd_id, args) = s
+            .trim()
+            .split_once([' ', '\t'])
+            .map_or((s.trim(), ""), |(cmd_id, args)| (cmd_id, args.trim()));
+        if cmd_id.is_empty() {
+            return Err(ProtocolErrorV3);
+        }
+
+        let cmd = match cmd_id.to_lowercase().as_str() {
+            Bye::NAME if args.is_empty() => Self::Bye(Bye),
+            Hello::NAME if args.is_empty() => Self::Hello(Hello),
+            Info::NAME => Self::Info(Info::new(InfoItem::from_str(args)?)),
+            Batch::NAME if args.is_empty() => Self::Batch(Batch),
+            Capabilities::NAME => Self::Capabilities(Capabilities::from_str(args)?),
+            Station::NAME => Self::Station(Station::from_str(args)?),
+            Select::NAME => Self::Select(Select::from_str(args)?),
+            Data::NAME => Self::Data(Data::from_str(args)?),
+            Fetch::NAME => Self::Fetch(Fetch::from_str(args)?),
+            Time::NAME => Self::Time(Time::from_str(args)?),
+            End::NAME if args.is_empty() => Self::End(End),
+            Bye::NAME | Hello::NAME | Batch::NAME | End::NAME => return Err(ProtocolErrorV3),
+            other => Self::Unknown(Unknown::new(other, (!args.is_empty()).then_some(args))),
+        };
+
+        Ok(cmd)
+    }
 }
 
 impl fmt::Display for Command {
@@ -54,6 +107,7 @@ impl fmt::Display for Command {
             Self::Hello(cmd) => cmd.to_string(),
             Self::Info(cmd) => cmd.to_string(),
             Self::Batch(cmd) => cmd.to_string(),
+            Self::Capabilities(cmd) => cmd.to_string(),
             Self::Station(cmd) => cmd.to_string(),
             Self::Select(cmd) => cmd.to_string(),
             Self::Data(cmd) => cmd.to_string(),
@@ -66,3 +120,43 @@ impl fmt::Display for Command {
     }
 }
 
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use ::time::macros::datetime;
+
+    #[test]
+    fn wire_round_trip() {
+        let cmds = [
+            Command::Hello(Hello),
+            Command::Capabilities(Capabilities::new(vec![
+                "EXTREPLY".to_string(),
+                "NSWILDCARD".to_string(),
+            ])),
+            Command::Station(Station::new("WLF", Some("GE".to_string()))),
+            Command::Select(Select::new(Some("BH?.D".to_string()))),
+            Command::Data(Data::new(Some(0x2A), Some(datetime!(2023-05-01 12:00:00)))),
+            Command::Fetch(Fetch::default()),
+            Command::Time(Time::new(Some(datetime!(2023-05-01 12:00:00)), None)),
+            Command::Info(Info::new(InfoItem::Streams)),
+            Command::End(End),
+        ];
+        for cmd in cmds {
+            assert_eq!(Command::parse(&cmd.to_wire()).unwrap(), cmd);
+        }
+
+        assert_eq!(
+            Command::Station(Station::new("WLF", Some("GE".to_string()))).to_wire(),
+            b"station WLF GE\r\n"
+        );
+        assert_eq!(
+            Command::parse(b"DATA 2A 2023,05,01,12,00,00").unwrap(),
+            Command::Data(Data::new(Some(0x2A), Some(datetime!(2023-05-01 12:00:00))))
+        );
+        assert!(Command::parse(b"hello world").is_err());
+        assert!(Command::parse(b"data xyz").is_err());
+        assert!(Command::parse(b"").is_err());
+    }
+}
@@ -1,4 +1,7 @@
 use std::fmt;
+use std::str;
+
+use crate::ProtocolErrorV3;
 
 /// Command to select streams for a given station.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -23,3 +26,16 @@ impl fmt::Display for Select {
         write!(f, "{}{}", Select::NAME, pattern_str)
     }
 }
+
+impl str::FromStr for Select {
+    type Err = ProtocolErrorV3;
+
+    fn from_str(s: &str) -> Result<Select, Self::Err> {
+        let split: Vec<&str> = s.split_whitespace().collect();
+        match split[..] {
+            [] => Ok(Select::default()),
+            [pattern] => Ok(Select::new(Some(pattern.to_string()))),
+            _ => Err(ProtocolErrorV3),
+        }
+    }
+}
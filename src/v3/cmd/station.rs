@@ -1,4 +1,7 @@
 use std::fmt;
+use std::str;
+
+use crate::ProtocolErrorV3;
 
 /// Command to request station data during handshaking.
 ///
@@ -29,3 +32,16 @@ impl fmt::Display for Station {
         write!(f, "{} {}{}", Station::NAME, self.station, net_str)
     }
 }
+
+impl str::FromStr for Station {
+    type Err = ProtocolErrorV3;
+
+    fn from_str(s: &str) -> Result<Station, Self::Err> {
+        let split: Vec<&str> = s.split_whitespace().collect();
+        match split[..] {
+            [station] => Ok(Station::new(station, None)),
+            [station, network] => Ok(Station::new(station, Some(network.to_string()))),
+            _ => Err(ProtocolErrorV3),
+        }
+    }
+}
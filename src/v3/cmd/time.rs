@@ -1,9 +1,11 @@
 use std::fmt;
+use std::str;
 
 // TODO(damb): use `time::OffsetDataTime`
 use time::PrimitiveDateTime;
 
 use super::super::util;
+use crate::ProtocolErrorV3;
 
 /// Action command to request a time window for a given station.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -33,3 +35,22 @@ impl fmt::Display for Time {
         write!(f, "{}{}", Time::NAME, time_str)
     }
 }
+
+impl str::FromStr for Time {
+    type Err = ProtocolErrorV3;
+
+    fn from_str(s: &str) -> Result<Time, Self::Err> {
+        let split: Vec<&str> = s.split_whitespace().collect();
+        if split.is_empty() || split.len() > 2 {
+            return Err(ProtocolErrorV3);
+        }
+
+        let mut times = split
+            .iter()
+            .map(|t| util::parse_seedlink_time(t).ok_or(ProtocolErrorV3));
+        let begin = times.next().transpose()?;
+        let end = times.next().transpose()?;
+
+        Ok(Time { begin, end })
+    }
+}
@@ -3,20 +3,27 @@ use std::fmt;
 /// Represents an *unknown* command. This is not a real SeedLink `v3` command.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Unknown {
-    command_name: String,
+    pub command_name: String,
+    /// The raw command arguments, if any.
+    pub args: Option<String>,
 }
 
 impl Unknown {
     /// Creates a new `Unknown` command.
-    pub(crate) fn new(key: impl ToString) -> Unknown {
+    pub(crate) fn new(key: impl ToString, args: Option<&str>) -> Unknown {
         Unknown {
             command_name: key.to_string(),
+            args: args.map(|args| args.to_string()),
         }
     }
 }
 
 impl fmt::Display for Unknown {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref args) = self.args {
+            return write!(f, "{} {}", self.command_name, args);
+        }
+
         write!(f, "{}", self.command_name)
     }
 }
@@ -1,24 +1,57 @@
+use std::collections::VecDeque;
 use std::io;
+use std::sync::Arc;
 
 use futures::stream::StreamExt;
 use quick_xml::de;
 use time::PrimitiveDateTime;
-use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::io::{self as tokio_io, AsyncWriteExt, BufWriter, DuplexStream, ReadHalf, WriteHalf};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+#[cfg(feature = "tls")]
+use tokio::net::TcpStream;
+use tokio::time as tokio_time;
+#[cfg(feature = "tls")]
+use tokio_rustls::client::TlsStream;
 use tokio_util::codec::FramedRead;
 use tracing::{debug, instrument, warn};
 
+use crate::connection::{
+    disconnected, BufferCapacities, NegotiationProgressCallback, NegotiationProgressTracker,
+    StatsRecorder,
+};
+use crate::metrics;
+use crate::trace;
+use crate::wire::conformance;
+#[cfg(feature = "tls")]
+use crate::TlsConnection;
+#[cfg(feature = "gzip")]
+use crate::CAPABILITY_INFO_GZIP_V3;
 use crate::{
-    ActualConnection, BatchCmdV3, ByeCmdV3, CommandV3, EndCmdV3, Frame, HelloCmdV3, InfoCmdItemV3,
-    InfoCmdV3, InventoryV3, SeedLinkError, SeedLinkInfoPacketV3, SeedLinkResult, StreamConfig,
-    TcpConnection,
+    ActualConnection, BatchCmdV3, ByeCmdV3, CapabilitiesCmdV3, CommandV3, ConnectionsV3,
+    DataTransferMode, EndCmdV3, Frame, GapsV3, HelloCmdV3, InfoCmdItemV3, InfoCmdV3, Inventory,
+    InventoryLevel, InventoryV3, MemConnection, SeedLinkError, SeedLinkInfoPacketV3,
+    SeedLinkResult, Station, Stations, StreamConfig, SubscribedStation, TcpConnection,
+    CAPABILITY_EXTREPLY_V3,
 };
 
+#[cfg(feature = "gzip")]
+use super::gzip::decompress_info_payload;
+use super::packet::HEADER_SIZE;
 use negotiate::Negotiator;
 use seedlink::SeedLinkCodec;
+use stations::StationsXml;
 
 mod negotiate;
 mod seedlink;
+mod stations;
+
+/// Formats the reason of the extended `ERROR` reply `frame` (e.g. ` (invalid station)`), if any.
+fn fmt_reason(frame: &Frame) -> String {
+    match frame {
+        Frame::Error(Some(reason)) => format!(" ({})", reason),
+        _ => String::new(),
+    }
+}
 
 #[derive(Debug)]
 struct FramedTcpConnection {
@@ -28,15 +61,38 @@ struct FramedTcpConnection {
     open: bool,
 }
 
+#[cfg(feature = "tls")]
+#[derive(Debug)]
+struct FramedTlsConnection {
+    read: FramedRead<ReadHalf<TlsStream<TcpStream>>, SeedLinkCodec>,
+    write: BufWriter<WriteHalf<TlsStream<TcpStream>>>,
+
+    open: bool,
+}
+
+#[derive(Debug)]
+struct FramedMemConnection {
+    read: FramedRead<ReadHalf<DuplexStream>, SeedLinkCodec>,
+    write: BufWriter<WriteHalf<DuplexStream>>,
+
+    open: bool,
+}
+
 #[derive(Debug)]
 enum ActualFramedConnection {
     Tcp(FramedTcpConnection),
+    #[cfg(feature = "tls")]
+    Tls(FramedTlsConnection),
+    Mem(FramedMemConnection),
 }
 
 impl ActualFramedConnection {
     pub async fn flush(&mut self) -> SeedLinkResult<()> {
         match self {
             Self::Tcp(FramedTcpConnection { ref mut write, .. }) => write.flush().await?,
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection { ref mut write, .. }) => write.flush().await?,
+            Self::Mem(FramedMemConnection { ref mut write, .. }) => write.flush().await?,
         }
 
         Ok(())
@@ -45,6 +101,9 @@ impl ActualFramedConnection {
     pub async fn write_all(&mut self, buf: &[u8]) -> SeedLinkResult<()> {
         match self {
             Self::Tcp(FramedTcpConnection { ref mut write, .. }) => write.write_all(buf).await?,
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection { ref mut write, .. }) => write.write_all(buf).await?,
+            Self::Mem(FramedMemConnection { ref mut write, .. }) => write.write_all(buf).await?,
         }
 
         Ok(())
@@ -60,6 +119,23 @@ impl ActualFramedConnection {
                 _ = write.shutdown().await;
                 *open = false;
             }
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection {
+                ref mut write,
+                ref mut open,
+                ..
+            }) => {
+                _ = write.shutdown().await;
+                *open = false;
+            }
+            Self::Mem(FramedMemConnection {
+                ref mut write,
+                ref mut open,
+                ..
+            }) => {
+                _ = write.shutdown().await;
+                *open = false;
+            }
         }
 
         Ok(())
@@ -68,20 +144,49 @@ impl ActualFramedConnection {
     pub fn is_open(&self) -> bool {
         match self {
             Self::Tcp(FramedTcpConnection { ref open, .. }) => *open,
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection { ref open, .. }) => *open,
+            Self::Mem(FramedMemConnection { ref open, .. }) => *open,
+        }
+    }
+
+    /// Returns the total number of bytes received.
+    pub fn bytes_received(&self) -> u64 {
+        match self {
+            Self::Tcp(FramedTcpConnection { ref read, .. }) => read.decoder().bytes_decoded(),
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection { ref read, .. }) => read.decoder().bytes_decoded(),
+            Self::Mem(FramedMemConnection { ref read, .. }) => read.decoder().bytes_decoded(),
         }
     }
 }
 
 impl ActualFramedConnection {
     /// Creates a new `ActualFramedConnection` from the actual connection `con`.
-    fn new(con: ActualConnection) -> Self {
-        // TODO(damb): allow to configure read buffer size
+    fn new(con: ActualConnection, capacities: BufferCapacities) -> Self {
         match con {
             ActualConnection::Tcp(TcpConnection { rw, open }) => {
                 let (read, write) = rw.into_split();
                 Self::Tcp(FramedTcpConnection {
-                    read: FramedRead::with_capacity(read, SeedLinkCodec::new(), 8 * 1024),
-                    write: BufWriter::with_capacity(255, write),
+                    read: FramedRead::with_capacity(read, SeedLinkCodec::new(), capacities.read),
+                    write: BufWriter::with_capacity(capacities.write, write),
+                    open,
+                })
+            }
+            #[cfg(feature = "tls")]
+            ActualConnection::Tls(TlsConnection { rw, open }) => {
+                let (read, write) = tokio_io::split(rw);
+                Self::Tls(FramedTlsConnection {
+                    read: FramedRead::with_capacity(read, SeedLinkCodec::new(), capacities.read),
+                    write: BufWriter::with_capacity(capacities.write, write),
+                    open,
+                })
+            }
+            ActualConnection::Mem(MemConnection { rw, open }) => {
+                let (read, write) = tokio_io::split(rw);
+                Self::Mem(FramedMemConnection {
+                    read: FramedRead::with_capacity(read, SeedLinkCodec::new(), capacities.read),
+                    write: BufWriter::with_capacity(capacities.write, write),
                     open,
                 })
             }
@@ -106,19 +211,56 @@ pub(crate) struct FramedConnectionV3 {
     con: ActualFramedConnection,
     state: FramedConnectionState,
     batch_cmd_mode: bool,
+    /// Whether to fall back to non-batch negotiation if the server rejects batch command mode.
+    batch_fallback: bool,
+    /// Whether batch command mode was requested but rejected by the server.
+    batch_cmd_mode_unavailable: bool,
+    /// Maximum number of stations negotiated ahead (i.e. without awaiting the responses) if batch
+    /// command mode is disabled.
+    negotiation_concurrency: usize,
+    /// Callback notified about the negotiation progress.
+    negotiation_progress: Option<NegotiationProgressCallback>,
+    /// The stations negotiated successfully.
+    subscription: Vec<SubscribedStation>,
+    strict: bool,
+    /// The last response line (e.g. an error) received.
+    last_message: Option<String>,
 
     expect_info_resp: bool,
+    /// Time the first unacknowledged keepalive was sent.
+    keep_alive_sent: Option<tokio_time::Instant>,
+    /// Number of keepalives sent since the last acknowledgement.
+    unanswered_keep_alives: u32,
+    /// Time the last frame was received.
+    last_frame: Option<tokio_time::Instant>,
+    stats: Arc<StatsRecorder>,
+    /// Whether `INFO` responses are gzip compressed.
+    #[cfg(feature = "gzip")]
+    gzip_info: bool,
 }
 
 impl FramedConnectionV3 {
     /// Creates a new `FramedConnection`, backed by the actual connection `con`.
-    pub fn new(con: ActualConnection) -> Self {
+    pub fn new(con: ActualConnection, capacities: BufferCapacities) -> Self {
         Self {
-            con: ActualFramedConnection::new(con),
+            con: ActualFramedConnection::new(con, capacities),
             state: FramedConnectionState::Initialized,
             batch_cmd_mode: false,
+            batch_fallback: false,
+            batch_cmd_mode_unavailable: false,
+            negotiation_concurrency: 1,
+            negotiation_progress: None,
+            subscription: Vec::new(),
+            strict: false,
+            last_message: None,
 
             expect_info_resp: false,
+            keep_alive_sent: None,
+            unanswered_keep_alives: 0,
+            last_frame: None,
+            stats: Arc::new(StatsRecorder::default()),
+            #[cfg(feature = "gzip")]
+            gzip_info: false,
         }
     }
 
@@ -127,13 +269,56 @@ impl FramedConnectionV3 {
         self.con.is_open()
     }
 
+    /// Enables or disables strict mode, i.e. whether unsolicited server messages received while
+    /// awaiting a command response fail the handshake.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Enables or disables falling back to non-batch negotiation if the server rejects batch
+    /// command mode.
+    pub fn set_batch_fallback(&mut self, batch_fallback: bool) {
+        self.batch_fallback = batch_fallback;
+    }
+
+    /// Sets the maximum number of stations negotiated ahead, i.e. without awaiting the
+    /// responses. Values smaller than or equal to `1` disable pipelining.
+    pub fn set_negotiation_concurrency(&mut self, negotiation_concurrency: usize) {
+        self.negotiation_concurrency = negotiation_concurrency.max(1);
+    }
+
+    /// Returns whether batch command mode was requested but rejected by the server.
+    pub fn batch_cmd_mode_unavailable(&self) -> bool {
+        self.batch_cmd_mode_unavailable
+    }
+
+    /// Sets the callback notified about the negotiation progress.
+    pub fn set_negotiation_progress(&mut self, callback: NegotiationProgressCallback) {
+        self.negotiation_progress = Some(callback);
+    }
+
+    /// Returns the stations negotiated successfully.
+    pub fn subscription(&self) -> &[SubscribedStation] {
+        &self.subscription
+    }
+
+    /// Returns the statistics recorded.
+    pub fn stats(&self) -> &Arc<StatsRecorder> {
+        &self.stats
+    }
+
+    /// Returns the last response line (e.g. an error) received, if any.
+    pub fn last_message(&self) -> Option<&str> {
+        self.last_message.as_deref()
+    }
+
     /// Returns whether batch command mode is enabled.
     pub fn batch_cmd_mode(&self) -> bool {
         self.batch_cmd_mode
     }
 
     /// Sends the `HELLO` command and returns the corresponding response.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn say_hello(&mut self) -> SeedLinkResult<(String, String)> {
         if self.state >= FramedConnectionState::HandShaking {
             return Err(SeedLinkError::ClientError(
@@ -144,7 +329,7 @@ impl FramedConnectionV3 {
         let cmd = CommandV3::Hello(HelloCmdV3);
         let frame = cmd.into_frame();
 
-        debug!("sending command: '{}'", cmd);
+        debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
         self.write_frame(&frame).await?;
 
         let first_response_line = self.read_line_frame().await?;
@@ -154,7 +339,7 @@ impl FramedConnectionV3 {
     }
 
     /// Performs a connection shutdown.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn shutdown(&mut self) -> SeedLinkResult<()> {
         self.say_bye().await?;
         self.con.shutdown().await?;
@@ -164,12 +349,12 @@ impl FramedConnectionV3 {
     }
 
     /// Requests the SeedLink server's information at level `item` and returns XML.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_info(&mut self, item: InfoCmdItemV3) -> SeedLinkResult<String> {
         self.try_send_info(item).await?;
         self.expect_info_resp = true;
 
-        let mut info_packet_buf = String::new();
+        let mut info_packet_buf = Vec::new();
         loop {
             match self.read_frame().await? {
                 Frame::InfoPacket(buf) => {
@@ -179,9 +364,7 @@ impl FramedConnectionV3 {
                             "INFO level request is not supported.".to_string(),
                         ));
                     }
-                    let payload = packet.payload()?;
-                    // debug!("{}", payload);
-                    info_packet_buf.push_str(&payload);
+                    info_packet_buf.extend(packet.payload_bytes()?);
 
                     if packet.is_last() {
                         break;
@@ -195,11 +378,92 @@ impl FramedConnectionV3 {
 
         self.expect_info_resp = false;
 
-        Ok(info_packet_buf)
+        self.decode_info_payload(info_packet_buf)
+    }
+
+    /// Enables extended replies by means of the `CAPABILITIES` command, i.e. `ERROR` responses
+    /// carry the reason of the failure.
+    ///
+    /// The server must have advertised the [`CAPABILITY_EXTREPLY_V3`] capability.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn enable_extended_replies(&mut self) -> SeedLinkResult<()> {
+        let cmd = CommandV3::Capabilities(CapabilitiesCmdV3::new(vec![
+            CAPABILITY_EXTREPLY_V3.to_string()
+        ]));
+        let frame = cmd.into_frame();
+
+        debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
+        self.write_frame(&frame).await?;
+
+        match self.read_response_frame().await? {
+            Frame::Ok => {
+                debug!(target: trace::NEGOTIATE, "response: capabilities is OK (extended replies enabled)");
+                Ok(())
+            }
+            frame @ Frame::Error(_) => Err(SeedLinkError::UnsupportedCommand(format!(
+                "failed to enable extended replies{}",
+                fmt_reason(&frame)
+            ))),
+            frame => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "response: invalid response to command ({}): {:?}",
+                    cmd, frame
+                ),
+            )
+            .into()),
+        }
+    }
+
+    /// Enables gzip compressed `INFO` responses by means of the `CAPABILITIES` command.
+    ///
+    /// The server must have advertised the [`CAPABILITY_INFO_GZIP_V3`] capability.
+    #[cfg(feature = "gzip")]
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn enable_info_gzip(&mut self) -> SeedLinkResult<()> {
+        let cmd = CommandV3::Capabilities(CapabilitiesCmdV3::new(vec![
+            CAPABILITY_INFO_GZIP_V3.to_string()
+        ]));
+        let frame = cmd.into_frame();
+
+        debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
+        self.write_frame(&frame).await?;
+
+        match self.read_response_frame().await? {
+            Frame::Ok => {
+                debug!(target: trace::NEGOTIATE, "response: capabilities is OK (gzip compressed INFO responses enabled)");
+                self.gzip_info = true;
+                Ok(())
+            }
+            frame @ Frame::Error(_) => Err(SeedLinkError::UnsupportedCommand(format!(
+                "failed to enable gzip compressed INFO responses{}",
+                fmt_reason(&frame)
+            ))),
+            frame => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "response: invalid response to command ({}): {:?}",
+                    cmd, frame
+                ),
+            )
+            .into()),
+        }
+    }
+
+    /// Decodes the concatenated payload `buf` of an `INFO` response, decompressing it if
+    /// required.
+    pub(crate) fn decode_info_payload(&self, buf: Vec<u8>) -> SeedLinkResult<String> {
+        #[cfg(feature = "gzip")]
+        if self.gzip_info {
+            return decompress_info_payload(&buf);
+        }
+
+        String::from_utf8(buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()).into())
     }
 
     /// Configures the connection and completes the handshaking.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn configure(
         &mut self,
         stream_configs: &[StreamConfig],
@@ -210,23 +474,30 @@ impl FramedConnectionV3 {
             return Ok(());
         }
 
+        let started = tokio_time::Instant::now();
         if batch_cmd_mode {
             let cmd = CommandV3::Batch(BatchCmdV3);
             let frame = cmd.into_frame();
 
-            debug!("sending command: '{}'", cmd);
+            debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
             self.write_frame(&frame).await?;
 
-            match self.read_frame().await? {
+            match self.read_response_frame().await? {
                 Frame::Ok => {
-                    debug!("response: batch is OK (batch command mode enabled)");
+                    debug!(target: trace::NEGOTIATE, "response: batch is OK (batch command mode enabled)");
                     self.batch_cmd_mode = true;
                 }
-                Frame::Error => {
-                    warn!("response: batch is ERROR (failed to switch to batch command mode)");
-                    return Err(SeedLinkError::UnsupportedCommand(
-                        "failed to switch to batch mode".to_string(),
-                    ));
+                ref frame @ Frame::Error(_) if self.batch_fallback => {
+                    warn!(target: trace::NEGOTIATE, "response: batch is ERROR (falling back to non-batch command mode){}", fmt_reason(frame));
+                    self.batch_cmd_mode_unavailable = true;
+                }
+                ref frame @ Frame::Error(_) => {
+                    warn!(target: trace::NEGOTIATE, "response: batch is ERROR (failed to switch to batch command mode){}", fmt_reason(frame));
+                    self.batch_cmd_mode_unavailable = true;
+                    return Err(SeedLinkError::UnsupportedCommand(format!(
+                        "failed to switch to batch mode{}",
+                        fmt_reason(frame)
+                    )));
                 }
                 frame => {
                     return Err(io::Error::new(
@@ -243,41 +514,106 @@ impl FramedConnectionV3 {
 
         self.state = FramedConnectionState::HandShaking;
 
+        let (mode, end_time) = match data_transfer_mode {
+            SeedLinkDataTransferModeV3::RealTime => (DataTransferMode::RealTime, None),
+            SeedLinkDataTransferModeV3::DialUp => (DataTransferMode::DialUp, None),
+            SeedLinkDataTransferModeV3::TimeWindow(end_time) => {
+                (DataTransferMode::DialUp, Some(*end_time))
+            }
+        };
+        self.subscription.clear();
+
+        let callback = self.negotiation_progress.clone();
+        let mut progress = NegotiationProgressTracker::new(stream_configs.len(), callback.as_ref());
         let mut accepted_sta_cnt = 0;
-        for stream_config in stream_configs {
-            let negotiator = Negotiator { stream_config };
-            if negotiator.negotiate(self, &data_transfer_mode).await? {
-                accepted_sta_cnt += 1;
+        if self.batch_cmd_mode || self.negotiation_concurrency == 1 {
+            for stream_config in stream_configs {
+                let negotiator = Negotiator { stream_config };
+                let accepted = negotiator.negotiate(self, data_transfer_mode).await?;
+                if accepted {
+                    self.subscription
+                        .push(stream_config.subscribed(mode, end_time));
+                }
+                accepted_sta_cnt = progress.advance(accepted);
+            }
+            // XXX(damb): in batch command mode the commands of all stations are flushed at once
+            self.flush().await?;
+        } else {
+            // send the commands of up to `negotiation_concurrency` stations ahead and reconcile
+            // the responses in order
+            let mut in_flight = VecDeque::with_capacity(self.negotiation_concurrency);
+            for stream_config in stream_configs {
+                let negotiator = Negotiator { stream_config };
+                let cmds = negotiator.send(self, data_transfer_mode).await?;
+                in_flight.push_back((negotiator, cmds));
+
+                if in_flight.len() >= self.negotiation_concurrency {
+                    self.flush().await?;
+                    let (negotiator, cmds) = in_flight.pop_front().unwrap();
+                    let accepted = negotiator.reconcile(self, &cmds).await?;
+                    if accepted {
+                        self.subscription
+                            .push(negotiator.stream_config.subscribed(mode, end_time));
+                    }
+                    accepted_sta_cnt = progress.advance(accepted);
+                }
+            }
+            self.flush().await?;
+            while let Some((negotiator, cmds)) = in_flight.pop_front() {
+                let accepted = negotiator.reconcile(self, &cmds).await?;
+                if accepted {
+                    self.subscription
+                        .push(negotiator.stream_config.subscribed(mode, end_time));
+                }
+                accepted_sta_cnt = progress.advance(accepted);
             }
         }
 
         if accepted_sta_cnt == 0 {
             self.state = FramedConnectionState::Initialized;
-            warn!("no station selected");
+            warn!(target: trace::NEGOTIATE, "no station selected");
         } else {
             // switch to data transfer mode
             self.state = FramedConnectionState::DataTransfer;
+            self.last_message = None;
             match &mut self.con {
                 ActualFramedConnection::Tcp(FramedTcpConnection { ref mut read, .. }) => {
                     read.decoder_mut().enable_data_transfer_phase();
                 }
+                #[cfg(feature = "tls")]
+                ActualFramedConnection::Tls(FramedTlsConnection { ref mut read, .. }) => {
+                    read.decoder_mut().enable_data_transfer_phase();
+                }
+                ActualFramedConnection::Mem(FramedMemConnection { ref mut read, .. }) => {
+                    read.decoder_mut().enable_data_transfer_phase();
+                }
             }
 
             // end handshaking in multi-station mode
             let cmd = CommandV3::End(EndCmdV3);
             let frame = cmd.into_frame();
 
-            debug!("sending command: '{}'", cmd);
+            debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
             self.write_frame(&frame).await?;
         }
+        self.stats.set_negotiation_duration(started.elapsed());
 
         Ok(())
     }
 
     /// Tries to send a keep alive packet to the SeedLink server.
+    ///
+    /// Note that no keepalive is sent while an `INFO` response is outstanding. Though, the
+    /// keepalive counts as unanswered (see [`Self::unanswered_keep_alives`]).
     pub(crate) async fn try_send_keep_alive(&mut self) -> SeedLinkResult<()> {
+        self.unanswered_keep_alives += 1;
         let resp = match self.try_send_info(InfoCmdItemV3::Id).await {
-            Ok(()) => Ok(()),
+            Ok(()) => {
+                self.keep_alive_sent
+                    .get_or_insert_with(tokio_time::Instant::now);
+                self.stats.add_keep_alive();
+                Ok(())
+            }
             Err(e) => match e {
                 SeedLinkError::ClientError(_) => {
                     // ignore client errors
@@ -291,36 +627,135 @@ impl FramedConnectionV3 {
     }
 
     pub(crate) fn ack_keep_alive(&mut self) {
+        if let Some(keep_alive_sent) = self.keep_alive_sent {
+            metrics::keep_alive_rtt(keep_alive_sent.elapsed());
+        }
         self.expect_info_resp = false;
+        self.keep_alive_sent = None;
+        self.unanswered_keep_alives = 0;
+    }
+
+    /// Returns the time the first unacknowledged keepalive was sent, if any.
+    pub(crate) fn keep_alive_sent(&self) -> Option<tokio_time::Instant> {
+        self.keep_alive_sent
+    }
+
+    /// Returns the number of keepalives sent since the last acknowledgement.
+    pub(crate) fn unanswered_keep_alives(&self) -> u32 {
+        self.unanswered_keep_alives
+    }
+
+    /// Returns the time the last frame was received, if any.
+    pub(crate) fn last_frame(&self) -> Option<tokio_time::Instant> {
+        self.last_frame
+    }
+
+    /// Returns whether an `INFO` response is outstanding.
+    pub(crate) fn expects_info_resp(&self) -> bool {
+        self.expect_info_resp
+    }
+
+    /// Sends an `INFO` request without awaiting the response.
+    pub(crate) async fn send_info_request(&mut self, item: InfoCmdItemV3) -> SeedLinkResult<()> {
+        self.try_send_info(item).await?;
+        self.expect_info_resp = true;
+
+        Ok(())
     }
 
     /// Low level function which writes a `Frame` literal to the underlying actual framed connection.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn write_frame(&mut self, frame: &Frame) -> SeedLinkResult<()> {
+        self.feed_frame(frame).await?;
+        self.con.flush().await
+    }
+
+    /// Writes a `Frame` literal to the underlying actual framed connection without flushing it,
+    /// e.g. in order to pipeline commands (see [`FramedConnectionV3::flush`]).
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn feed_frame(&mut self, frame: &Frame) -> SeedLinkResult<()> {
         match frame {
             Frame::Line(buf) => {
+                debug_assert_eq!(conformance::check_command_line(buf), Ok(()));
                 self.con.write_all(buf).await?;
                 self.con.write_all(b"\r\n").await?;
-                self.con.flush().await?;
+                self.stats.add_bytes_sent(buf.len() + 2);
+            }
+            frame => {
+                return Err(SeedLinkError::ClientError(format!(
+                    "unable to write frame (command lines only): {:?}",
+                    frame
+                )));
             }
-            _ => unimplemented!(),
         }
 
         Ok(())
     }
 
+    /// Flushes the frames written to the underlying actual framed connection.
+    pub async fn flush(&mut self) -> SeedLinkResult<()> {
+        self.con.flush().await
+    }
+
     /// Low level function which reads a `Frame` literal from the underlying actual framed connection.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn read_frame(&mut self) -> SeedLinkResult<Frame> {
-        match &mut self.con {
+        let frame = match &mut self.con {
             ActualFramedConnection::Tcp(FramedTcpConnection { ref mut read, .. }) => {
-                if let Some(frame) = read.next().await {
-                    return frame;
-                }
+                read.next().await
+            }
+            #[cfg(feature = "tls")]
+            ActualFramedConnection::Tls(FramedTlsConnection { ref mut read, .. }) => {
+                read.next().await
+            }
+            ActualFramedConnection::Mem(FramedMemConnection { ref mut read, .. }) => {
+                read.next().await
             }
         }
+        .ok_or_else(|| disconnected(self.last_message()))??;
+        self.last_frame = Some(tokio_time::Instant::now());
+        self.stats.set_bytes_received(self.con.bytes_received());
 
-        Err(io::Error::new(io::ErrorKind::BrokenPipe, "disconnected").into())
+        match frame {
+            Frame::Line(ref buf) => {
+                self.last_message = Some(String::from_utf8_lossy(buf).into_owned());
+            }
+            Frame::Error(ref reason) => {
+                self.last_message = Some(match reason {
+                    Some(reason) => format!("ERROR {}", reason),
+                    None => "ERROR".to_string(),
+                });
+            }
+            Frame::InfoPacket(ref buf) => {
+                self.stats
+                    .add_packet(true, HEADER_SIZE, buf.len() - HEADER_SIZE)
+            }
+            Frame::GenericDataPacket(ref buf) => {
+                self.stats
+                    .add_packet(false, HEADER_SIZE, buf.len() - HEADER_SIZE)
+            }
+            _ => {}
+        }
+
+        Ok(frame)
+    }
+
+    /// Reads the response frame to a command from the underlying actual framed connection.
+    ///
+    /// Unless strict mode is enabled, unsolicited server messages (i.e. line frames) are skipped
+    /// with a warning.
+    pub(crate) async fn read_response_frame(&mut self) -> SeedLinkResult<Frame> {
+        loop {
+            match self.read_frame().await? {
+                Frame::Line(buf) if !self.strict => {
+                    warn!(target: trace::NEGOTIATE,
+                        "unsolicited server message: '{}'",
+                        String::from_utf8_lossy(&buf)
+                    );
+                }
+                frame => return Ok(frame),
+            }
+        }
     }
 
     /// Reads a response line frame from the underlying actual framed connection.
@@ -337,16 +772,16 @@ impl FramedConnectionV3 {
     }
 
     /// Sends the `BYE` command to the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     async fn say_bye(&mut self) -> SeedLinkResult<()> {
         let cmd = CommandV3::Bye(ByeCmdV3);
         let frame = cmd.into_frame();
 
-        debug!("sending command: '{}'", cmd);
+        debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
         self.write_frame(&frame).await
     }
 
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     async fn try_send_info(&mut self, item: InfoCmdItemV3) -> SeedLinkResult<()> {
         if self.expect_info_resp {
             return Err(SeedLinkError::ClientError(
@@ -357,7 +792,7 @@ impl FramedConnectionV3 {
         let cmd = CommandV3::Info(InfoCmdV3::new(item));
         let frame = cmd.into_frame();
 
-        debug!("sending command: '{}'", cmd);
+        debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
         self.write_frame(&frame).await
     }
 }
@@ -388,8 +823,8 @@ pub(crate) struct SeedLinkConnectionV3 {
 }
 
 impl SeedLinkConnectionV3 {
-    pub(crate) fn new(con: ActualConnection) -> Self {
-        let con = FramedConnectionV3::new(con);
+    pub(crate) fn new(con: ActualConnection, capacities: BufferCapacities) -> Self {
+        let con = FramedConnectionV3::new(con, capacities);
         Self { con }
     }
 
@@ -409,61 +844,61 @@ impl SeedLinkConnectionV3 {
     }
 
     /// Sends the `HELLO` command to the SeedLink server and returns the raw response.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn say_hello_raw(&mut self) -> SeedLinkResult<(String, String)> {
         self.con.say_hello().await
     }
 
     /// Performs a connection shutdown.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn shutdown(&mut self) -> SeedLinkResult<()> {
         self.con.shutdown().await
     }
 
     /// Requests the raw id information XML from the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_id_info_raw(&mut self) -> SeedLinkResult<String> {
         self.con.request_info(InfoCmdItemV3::Id).await
     }
 
     /// Requests the raw station information XML from the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_station_info_raw(&mut self) -> SeedLinkResult<String> {
         self.con.request_info(InfoCmdItemV3::Stations).await
     }
 
     /// Requests the raw stream information XML from the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_stream_info_raw(&mut self) -> SeedLinkResult<String> {
         self.con.request_info(InfoCmdItemV3::Streams).await
     }
 
     /// Requests the raw connection information XML from the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_connection_info_raw(&mut self) -> SeedLinkResult<String> {
         self.con.request_info(InfoCmdItemV3::Connections).await
     }
 
     /// Requests the raw gap information XML from the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_gap_info_raw(&mut self) -> SeedLinkResult<String> {
         self.con.request_info(InfoCmdItemV3::Gaps).await
     }
 
     /// Requests the raw capability information XML from the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_capability_info_raw(&mut self) -> SeedLinkResult<String> {
         self.con.request_info(InfoCmdItemV3::Capabilities).await
     }
 
     /// Requests the raw information XML from the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_all_info_raw(&mut self) -> SeedLinkResult<String> {
         self.con.request_info(InfoCmdItemV3::All).await
     }
 
     /// Requests station information from the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_station_info(&mut self) -> SeedLinkResult<InventoryV3> {
         let resp_xml = self.request_station_info_raw().await?;
 
@@ -480,7 +915,7 @@ impl SeedLinkConnectionV3 {
     }
 
     /// Requests stream information from the SeedLink server.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn request_stream_info(&mut self) -> SeedLinkResult<InventoryV3> {
         let resp_xml = self.request_stream_info_raw().await?;
 
@@ -496,8 +931,106 @@ impl SeedLinkConnectionV3 {
         Ok(ret)
     }
 
+    /// Requests connection information from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_connection_info(&mut self) -> SeedLinkResult<ConnectionsV3> {
+        let resp_xml = self.request_connection_info_raw().await?;
+
+        let ret = de::from_str::<ConnectionsV3>(&resp_xml).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid response to INFO command: {}", e),
+            )
+        })?;
+
+        Ok(ret)
+    }
+
+    /// Requests gap information from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_gap_info(&mut self) -> SeedLinkResult<GapsV3> {
+        let resp_xml = self.request_gap_info_raw().await?;
+
+        let ret = de::from_str::<GapsV3>(&resp_xml).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid response to INFO command: {}", e),
+            )
+        })?;
+
+        Ok(ret)
+    }
+
+    /// Requests the inventory from the SeedLink server.
+    ///
+    /// Since SeedLink `v3` does not support filtering by the server, the inventory is filtered
+    /// by means of `station_pattern` (i.e. `NET_STA`) and `stream_pattern` (i.e. `LOC_B_S_SS`)
+    /// on the client side.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_inventory(
+        &mut self,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+        level: InventoryLevel,
+    ) -> SeedLinkResult<Inventory> {
+        self.request_stations(station_pattern, stream_pattern, level)
+            .await?
+            .collect()
+    }
+
+    /// Requests the inventory from the SeedLink server, returning an iterator parsing the
+    /// stations lazily.
+    ///
+    /// See also [`SeedLinkConnectionV3::request_inventory`].
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_stations(
+        &mut self,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+        level: InventoryLevel,
+    ) -> SeedLinkResult<Stations> {
+        // XXX(damb): filtering by stream pattern requires stream information
+        let resp_xml = if level == InventoryLevel::Stream || stream_pattern.is_some() {
+            self.request_stream_info_raw().await?
+        } else {
+            self.request_station_info_raw().await?
+        };
+
+        Ok(Self::parse_stations(
+            resp_xml,
+            station_pattern,
+            stream_pattern,
+            level,
+        ))
+    }
+
+    /// Parses the raw station or stream information XML `resp_xml` lazily, filtering the stations
+    /// by means of `station_pattern` and `stream_pattern`.
+    pub(crate) fn parse_stations(
+        resp_xml: String,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+        level: InventoryLevel,
+    ) -> Stations {
+        let station_pattern = station_pattern.map(|pat| pat.to_string());
+        let stream_pattern = stream_pattern.map(|pat| pat.to_string());
+        Stations::new(StationsXml::new(resp_xml).filter_map(move |res| {
+            match res {
+                Ok(station) => Station::from(station)
+                    .filter(station_pattern.as_deref(), stream_pattern.as_deref())
+                    .map(|mut station| {
+                        if level == InventoryLevel::Station {
+                            station.strip_streams();
+                        }
+                        Ok(station)
+                    }),
+                Err(e) => Some(Err(e)),
+            }
+        }))
+    }
+
     /// Configures the connection and completes handshaking.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub async fn configure(
         &mut self,
         stream_configs: &[StreamConfig],
@@ -3,9 +3,13 @@ use std::io;
 use tracing::{debug, instrument};
 
 use super::super::cmd::{Command, Data, Fetch, Select, Station, Time};
-use super::FramedConnectionV3;
+use super::{fmt_reason, FramedConnectionV3};
 
-use crate::{Frame, SeedLinkDataTransferModeV3, SeedLinkError, SeedLinkResult, StreamConfig};
+use crate::trace;
+use crate::{
+    DataTransferMode, Frame, SeedLinkDataTransferModeV3, SeedLinkError, SeedLinkResult, SelectorV3,
+    StreamConfig,
+};
 
 pub(crate) struct Negotiator<'a> {
     pub stream_config: &'a StreamConfig,
@@ -13,7 +17,7 @@ pub(crate) struct Negotiator<'a> {
 
 impl<'a> Negotiator<'a> {
     /// Configures the remote peer SeedLink server with `stream_config`.
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     pub(crate) async fn negotiate(
         &self,
         connection: &mut FramedConnectionV3,
@@ -25,8 +29,8 @@ impl<'a> Negotiator<'a> {
         ));
         let frame = cmd.into_frame();
 
-        debug!("sending command: '{}'", cmd);
-        connection.write_frame(&frame).await?;
+        debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
+        write(connection, &frame).await?;
 
         if connection.batch_cmd_mode() {
             self.negotiate_streams(connection).await?;
@@ -36,9 +40,9 @@ impl<'a> Negotiator<'a> {
             return Ok(true);
         }
 
-        match connection.read_frame().await? {
+        match connection.read_response_frame().await? {
             Frame::Ok => {
-                debug!(
+                debug!(target: trace::NEGOTIATE,
                     "response: station ({}_{}) is OK (station selected)",
                     self.stream_config.network, self.stream_config.station
                 );
@@ -47,10 +51,10 @@ impl<'a> Negotiator<'a> {
                 self.negotiate_data_transfer_mode(connection, data_transfer_mode)
                     .await?
             }
-            Frame::Error => {
-                debug!(
-                    "response: station ({}_{}) is ERROR (station omitted)",
-                    self.stream_config.network, self.stream_config.station
+            ref frame @ Frame::Error(_) => {
+                debug!(target: trace::NEGOTIATE,
+                    "response: station ({}_{}) is ERROR (station omitted){}",
+                    self.stream_config.network, self.stream_config.station, fmt_reason(frame)
                 );
                 return Ok(false);
             }
@@ -69,33 +73,127 @@ impl<'a> Negotiator<'a> {
         Ok(true)
     }
 
-    #[instrument(skip(self))]
+    /// Sends the commands configuring the remote peer with `stream_config` without awaiting the
+    /// responses. Returns the commands sent.
+    ///
+    /// Note that the commands are not flushed (see [`FramedConnectionV3::flush`]).
+    ///
+    /// The responses must be reconciled by means of [`Negotiator::reconcile`].
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub(crate) async fn send(
+        &self,
+        connection: &mut FramedConnectionV3,
+        data_transfer_mode: &SeedLinkDataTransferModeV3,
+    ) -> SeedLinkResult<Vec<Command>> {
+        let mut cmds = vec![Command::Station(Station::new(
+            &self.stream_config.station,
+            Some(self.stream_config.network.clone()),
+        ))];
+        cmds.extend(self.select_cmds()?);
+        cmds.push(self.action_cmd(data_transfer_mode)?);
+
+        for cmd in &cmds {
+            debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
+            connection.feed_frame(&cmd.into_frame()).await?;
+        }
+
+        Ok(cmds)
+    }
+
+    /// Reads the responses to the commands `cmds` previously sent by means of
+    /// [`Negotiator::send`]. Returns whether the station was accepted.
+    #[instrument(target = "slink::negotiate", skip(self, cmds))]
+    pub(crate) async fn reconcile(
+        &self,
+        connection: &mut FramedConnectionV3,
+        cmds: &[Command],
+    ) -> SeedLinkResult<bool> {
+        let mut accepted = true;
+        for (i, cmd) in cmds.iter().enumerate() {
+            let frame = connection.read_response_frame().await?;
+            if !matches!(frame, Frame::Ok | Frame::Error(_)) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "response: invalid response to command ({}): {:?}",
+                        cmd, frame
+                    ),
+                )
+                .into());
+            }
+
+            // XXX(damb): the remaining commands of an omitted station are expected to be
+            // rejected, too
+            if !accepted {
+                continue;
+            }
+
+            match (cmd, &frame) {
+                (Command::Station(_), Frame::Ok) => {
+                    debug!(target: trace::NEGOTIATE,
+                        "response: station ({}_{}) is OK (station selected)",
+                        self.stream_config.network, self.stream_config.station
+                    );
+                }
+                (Command::Station(_), _) => {
+                    debug!(target: trace::NEGOTIATE,
+                        "response: station ({}_{}) is ERROR (station omitted){}",
+                        self.stream_config.network, self.stream_config.station, fmt_reason(&frame)
+                    );
+                    accepted = false;
+                }
+                (Command::Select(_), Frame::Ok) => {
+                    debug!(target: trace::NEGOTIATE, "response: select arg ({}) is OK (selected)", cmd);
+                }
+                (Command::Select(_), _) => {
+                    debug!(target: trace::NEGOTIATE,
+                        "response: select arg ({}) is ERROR (select arg omitted){}",
+                        cmd,
+                        fmt_reason(&frame)
+                    );
+                }
+                (_, Frame::Ok) if i == cmds.len() - 1 => {
+                    debug!(target: trace::NEGOTIATE, "response: action command successful");
+                }
+                _ => {
+                    debug!(target: trace::NEGOTIATE,
+                        "response: action command ({}) not accepted{}", cmd, fmt_reason(&frame)
+                    );
+                    return Err(self.handshake_rejected(&frame));
+                }
+            }
+        }
+
+        Ok(accepted)
+    }
+
+    #[instrument(target = "slink::negotiate", skip(self))]
     async fn negotiate_streams(&self, connection: &mut FramedConnectionV3) -> SeedLinkResult<()> {
         if self.stream_config.len() == 0 {
             return Ok(());
         }
 
         let mut accepted_sel_cnt = 0;
-        for select_arg in self.stream_config.iter() {
-            let cmd = Command::Select(Select::new(Some(select_arg.clone())));
+        for (select_arg, cmd) in self.stream_config.iter().zip(self.select_cmds()?) {
             let frame = cmd.into_frame();
 
-            debug!("sending command: '{}'", cmd);
-            connection.write_frame(&frame).await?;
+            debug!(target: trace::NEGOTIATE, "sending command: '{}'", cmd);
+            write(connection, &frame).await?;
 
             if connection.batch_cmd_mode() {
                 continue;
             }
 
-            match connection.read_frame().await? {
+            match connection.read_response_frame().await? {
                 Frame::Ok => {
                     accepted_sel_cnt += 1;
-                    debug!("response: select arg ({}) is OK (selected)", select_arg);
+                    debug!(target: trace::NEGOTIATE, "response: select arg ({}) is OK (selected)", select_arg);
                 }
-                Frame::Error => {
-                    debug!(
-                        "response: select arg ({}) is ERROR (select arg omitted)",
-                        select_arg
+                ref frame @ Frame::Error(_) => {
+                    debug!(target: trace::NEGOTIATE,
+                        "response: select arg ({}) is ERROR (select arg omitted){}",
+                        select_arg,
+                        fmt_reason(frame)
                     );
                 }
                 frame => {
@@ -112,58 +210,37 @@ impl<'a> Negotiator<'a> {
         }
 
         if !connection.batch_cmd_mode() {
-            debug!("number of accepted selectors: {}", accepted_sel_cnt);
+            debug!(target: trace::NEGOTIATE, "number of accepted selectors: {}", accepted_sel_cnt);
         }
 
         Ok(())
     }
 
-    #[instrument(skip(self))]
+    #[instrument(target = "slink::negotiate", skip(self))]
     async fn negotiate_data_transfer_mode(
         &self,
         connection: &mut FramedConnectionV3,
         data_transfer_mode: &SeedLinkDataTransferModeV3,
     ) -> SeedLinkResult<()> {
-        let cmd: Command;
-        match data_transfer_mode {
-            SeedLinkDataTransferModeV3::RealTime | SeedLinkDataTransferModeV3::DialUp => {
-                let mut seq_num: Option<i32> = None;
-                if let Some(seq_num_str) = &self.stream_config.seq_num {
-                    seq_num = Some(
-                        i32::from_str_radix(&seq_num_str, 16)
-                            .map_err(|e| SeedLinkError::ClientError(e.to_string()))?,
-                    );
-                }
-
-                if *data_transfer_mode == SeedLinkDataTransferModeV3::RealTime {
-                    cmd = Command::Data(Data::new(seq_num, self.stream_config.time.clone()));
-                } else {
-                    cmd = Command::Fetch(Fetch::new(seq_num, self.stream_config.time.clone()));
-                }
-            }
-            SeedLinkDataTransferModeV3::TimeWindow(t) => {
-                cmd = Command::Time(Time::new(self.stream_config.time.clone(), Some(t.clone())));
-            }
-        }
-
+        let cmd = self.action_cmd(data_transfer_mode)?;
         let frame = cmd.into_frame();
 
-        debug!("sending action command: '{}'", cmd);
-        connection.write_frame(&frame).await?;
+        debug!(target: trace::NEGOTIATE, "sending action command: '{}'", cmd);
+        write(connection, &frame).await?;
 
         if connection.batch_cmd_mode() {
             return Ok(());
         }
 
-        match connection.read_frame().await? {
+        match connection.read_response_frame().await? {
             Frame::Ok => {
-                debug!("response: action command successful");
+                debug!(target: trace::NEGOTIATE, "response: action command successful");
             }
-            Frame::Error => {
-                return Err(SeedLinkError::ClientError(format!(
-                    "response: action command not accepted: {}",
-                    cmd
-                )));
+            ref frame @ Frame::Error(_) => {
+                debug!(target: trace::NEGOTIATE,
+                    "response: action command ({}) not accepted{}", cmd, fmt_reason(frame)
+                );
+                return Err(self.handshake_rejected(frame));
             }
             frame => {
                 return Err(io::Error::new(
@@ -179,4 +256,84 @@ impl<'a> Negotiator<'a> {
 
         Ok(())
     }
+
+    /// Returns the `SELECT` commands. Fails if a selector is invalid.
+    fn select_cmds(&self) -> SeedLinkResult<Vec<Command>> {
+        self.stream_config
+            .iter()
+            .map(|select_arg| {
+                let selector: SelectorV3 = select_arg.parse()?;
+                Ok(Command::Select(Select::new(Some(selector.to_string()))))
+            })
+            .collect()
+    }
+
+    /// Returns the action command (i.e. `DATA`, `FETCH` or `TIME`) for `data_transfer_mode`.
+    fn action_cmd(
+        &self,
+        data_transfer_mode: &SeedLinkDataTransferModeV3,
+    ) -> SeedLinkResult<Command> {
+        // station specific time windows take precedence, station specific data transfer modes do
+        // not apply in time window mode
+        let data_transfer_mode = match (
+            data_transfer_mode,
+            self.stream_config.end_time,
+            self.stream_config.data_transfer_mode,
+        ) {
+            (_, Some(end_time), _) => SeedLinkDataTransferModeV3::TimeWindow(end_time),
+            (SeedLinkDataTransferModeV3::TimeWindow(_), None, _) | (_, None, None) => {
+                data_transfer_mode.clone()
+            }
+            (_, None, Some(DataTransferMode::RealTime)) => SeedLinkDataTransferModeV3::RealTime,
+            (_, None, Some(DataTransferMode::DialUp)) => SeedLinkDataTransferModeV3::DialUp,
+        };
+
+        let cmd: Command;
+        match &data_transfer_mode {
+            SeedLinkDataTransferModeV3::RealTime | SeedLinkDataTransferModeV3::DialUp => {
+                let mut seq_num: Option<i32> = None;
+                if let Some(seq_num_str) = &self.stream_config.seq_num {
+                    seq_num = Some(
+                        i32::from_str_radix(&seq_num_str, 16)
+                            .map_err(|e| SeedLinkError::ClientError(e.to_string()))?,
+                    );
+                }
+
+                if data_transfer_mode == SeedLinkDataTransferModeV3::RealTime {
+                    cmd = Command::Data(Data::new(seq_num, self.stream_config.time.clone()));
+                } else {
+                    cmd = Command::Fetch(Fetch::new(seq_num, self.stream_config.time.clone()));
+                }
+            }
+            SeedLinkDataTransferModeV3::TimeWindow(t) => {
+                cmd = Command::Time(Time::new(self.stream_config.time.clone(), Some(t.clone())));
+            }
+        }
+
+        Ok(cmd)
+    }
+
+    /// Returns the error of the station's action command rejected by means of `frame`.
+    fn handshake_rejected(&self, frame: &Frame) -> SeedLinkError {
+        SeedLinkError::HandshakeRejected {
+            station: format!(
+                "{}_{}",
+                self.stream_config.network, self.stream_config.station
+            ),
+            reason: match frame {
+                Frame::Error(reason) => reason.clone(),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Writes `frame`. In batch command mode the frame is not flushed, i.e. the commands are flushed
+/// once per batch.
+async fn write(connection: &mut FramedConnectionV3, frame: &Frame) -> SeedLinkResult<()> {
+    if connection.batch_cmd_mode() {
+        return connection.feed_frame(frame).await;
+    }
+
+    connection.write_frame(frame).await
 }
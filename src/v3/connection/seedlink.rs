@@ -1,6 +1,9 @@
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use tokio_util::codec::Decoder;
+use tracing::{debug, trace};
 
+use crate::metrics;
+use crate::trace;
 use crate::{Frame, SeedLinkError};
 
 use crate::v3::packet::{
@@ -17,7 +20,9 @@ enum SessionPhase {
 #[derive(Debug)]
 pub struct SeedLinkCodec {
     session_phase: SessionPhase,
-    buf: Vec<u8>,
+    buf: BytesMut,
+    /// Total number of bytes consumed.
+    bytes_decoded: u64,
 }
 
 impl SeedLinkCodec {
@@ -25,10 +30,16 @@ impl SeedLinkCodec {
     pub fn new() -> Self {
         Self {
             session_phase: SessionPhase::HandShaking,
-            buf: Vec::with_capacity(8 * 1024),
+            buf: BytesMut::with_capacity(8 * 1024),
+            bytes_decoded: 0,
         }
     }
 
+    /// Returns the total number of bytes consumed.
+    pub fn bytes_decoded(&self) -> u64 {
+        self.bytes_decoded
+    }
+
     /// Switches into data transfer phase.
     pub fn enable_data_transfer_phase(&mut self) {
         self.session_phase = SessionPhase::DataTransfer;
@@ -61,7 +72,7 @@ impl SeedLinkCodec {
         &mut self,
         src: &mut BytesMut,
         bytes_missing: usize,
-    ) -> Option<Vec<u8>> {
+    ) -> Option<Bytes> {
         if src.len() < bytes_missing {
             return None;
         }
@@ -69,10 +80,7 @@ impl SeedLinkCodec {
         self.buf.extend_from_slice(&src[..bytes_missing]);
         src.advance(bytes_missing);
 
-        let copied = self.buf.to_vec();
-        self.buf.clear();
-
-        Some(copied)
+        Some(self.buf.split().freeze())
     }
 
     fn try_finalize_packet_frame(&mut self, src: &mut BytesMut) -> Option<Frame> {
@@ -95,16 +103,11 @@ impl SeedLinkCodec {
 
         return self.try_finalize_waveform_data_packet_frame(src, RECORD_SIZE);
     }
-}
-
-impl Decoder for SeedLinkCodec {
-    type Item = Frame;
-    type Error = SeedLinkError;
 
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, SeedLinkError> {
         match self.session_phase {
             SessionPhase::HandShaking => {
-                if self.buf == INFO_SIGNATURE {
+                if self.buf[..] == INFO_SIGNATURE[..] {
                     return Ok(self.try_finalize_info_packet_frame(
                         src,
                         HEADER_SIZE + RECORD_SIZE - INFO_SIGNATURE.len(),
@@ -122,16 +125,16 @@ impl Decoder for SeedLinkCodec {
                         // <LF>
                         10 => {
                             // remove <CR> (i.e. b"\r")
-                            self.buf.pop();
+                            self.buf.truncate(self.buf.len().saturating_sub(1));
 
-                            if self.buf == OK_SIGNATURE {
+                            if self.buf[..] == OK_SIGNATURE[..] {
                                 self.buf.clear();
                                 return Ok(Some(Frame::Ok));
                             }
 
-                            if self.buf == ERROR_SIGNATURE {
+                            if let Some(frame) = error_frame(&self.buf) {
                                 self.buf.clear();
-                                return Ok(Some(Frame::Error));
+                                return Ok(Some(frame));
                             }
 
                             let copied = self.buf.to_vec();
@@ -139,15 +142,15 @@ impl Decoder for SeedLinkCodec {
 
                             return Ok(Some(Frame::Line(copied)));
                         }
-                        _ => self.buf.push(byte),
+                        _ => self.buf.put_u8(byte),
                     }
 
-                    if self.buf == END_SIGNATURE {
+                    if self.buf[..] == END_SIGNATURE[..] {
                         self.buf.clear();
                         return Ok(Some(Frame::End));
                     }
 
-                    if self.buf == INFO_SIGNATURE {
+                    if self.buf[..] == INFO_SIGNATURE[..] {
                         return Ok(self.try_finalize_info_packet_frame(
                             src,
                             HEADER_SIZE + RECORD_SIZE - INFO_SIGNATURE.len(),
@@ -160,21 +163,38 @@ impl Decoder for SeedLinkCodec {
                     return Ok(self.try_finalize_packet_frame(src));
                 }
 
+                // packets received in one piece are split off without copying
+                if self.buf.is_empty()
+                    && src.len() >= HEADER_SIZE + RECORD_SIZE
+                    && src.starts_with(SIGNATURE)
+                {
+                    let buf = src.split_to(HEADER_SIZE + RECORD_SIZE).freeze();
+                    if buf.starts_with(INFO_SIGNATURE) {
+                        return Ok(Some(Frame::InfoPacket(buf)));
+                    }
+                    return Ok(Some(Frame::GenericDataPacket(buf)));
+                }
+
                 loop {
                     if src.is_empty() {
                         return Ok(None);
                     }
 
-                    // TODO(damb): fix implementation -> before entering the loop try to finalize SL
-                    // packets
-
-                    self.buf.push(src.get_u8());
+                    self.buf.put_u8(src.get_u8());
 
-                    if self.buf == SIGNATURE {
+                    if self.buf[..] == SIGNATURE[..] {
                         return Ok(self.try_finalize_packet_frame(src));
-                    } else if self.buf == END_SIGNATURE {
+                    } else if self.buf[..] == END_SIGNATURE[..] {
                         self.buf.clear();
                         return Ok(Some(Frame::End));
+                    } else if self.buf.ends_with(b"\r\n") {
+                        // e.g. an error response sent before the server disconnects
+                        self.buf.truncate(self.buf.len() - 2);
+                        let frame = error_frame(&self.buf)
+                            .unwrap_or_else(|| Frame::Line(self.buf.to_vec()));
+                        self.buf.clear();
+
+                        return Ok(Some(frame));
                     }
                 }
             }
@@ -182,3 +202,95 @@ impl Decoder for SeedLinkCodec {
     }
 }
 
+/// Returns the `ERROR` frame corresponding to the response line `line`, if any. Extended replies
+/// (i.e. `ERROR <reason>`) carry the reason of the failure.
+fn error_frame(line: &[u8]) -> Option<Frame> {
+    let rest = line.strip_prefix(&ERROR_SIGNATURE[..])?;
+    if rest.is_empty() {
+        return Some(Frame::Error(None));
+    }
+
+    let reason = String::from_utf8_lossy(rest.strip_prefix(b" ")?)
+        .trim()
+        .to_string();
+    if reason.is_empty() {
+        return Some(Frame::Error(None));
+    }
+
+    Some(Frame::Error(Some(reason)))
+}
+
+impl Decoder for SeedLinkCodec {
+    type Item = Frame;
+    type Error = SeedLinkError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let len = src.len();
+        let res = self.decode_frame(src);
+        let decoded = len - src.len();
+        self.bytes_decoded += decoded as u64;
+        match res {
+            Ok(Some(_)) => trace!(target: trace::CODEC, "decoded frame ({} bytes)", decoded),
+            Err(ref e) => {
+                debug!(target: trace::CODEC, "failed to decode frame: {}", e);
+                metrics::decode_error();
+            }
+            Ok(None) => {}
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn decode_extended_reply() {
+        let mut codec = SeedLinkCodec::new();
+        let mut src = BytesMut::from(&b"ERROR\r\nERROR invalid station\r\nERRORS\r\n"[..]);
+
+        assert!(matches!(
+            codec.decode(&mut src),
+            Ok(Some(Frame::Error(None)))
+        ));
+        assert!(matches!(
+            codec.decode(&mut src),
+            Ok(Some(Frame::Error(Some(reason)))) if reason == "invalid station"
+        ));
+        assert!(matches!(
+            codec.decode(&mut src),
+            Ok(Some(Frame::Line(line))) if line == b"ERRORS"
+        ));
+    }
+
+    #[test]
+    fn decode_packets_zero_copy() {
+        let mut codec = SeedLinkCodec::new();
+        codec.enable_data_transfer_phase();
+
+        let mut packet = b"SL00002A".to_vec();
+        packet.resize(HEADER_SIZE + RECORD_SIZE, 0);
+        let mut src = BytesMut::from(&packet[..4]);
+        assert!(matches!(codec.decode(&mut src), Ok(None)));
+        src.extend_from_slice(&packet[4..]);
+        src.extend_from_slice(&packet);
+        assert!(matches!(
+            codec.decode(&mut src),
+            Ok(Some(Frame::GenericDataPacket(buf))) if buf[..] == packet[..]
+        ));
+
+        // the second packet is received in one piece
+        let ptr = src.as_ptr();
+        match codec.decode(&mut src) {
+            Ok(Some(Frame::GenericDataPacket(buf))) => {
+                assert_eq!(buf[..], packet[..]);
+                assert_eq!(buf.as_ptr(), ptr);
+            }
+            frame => panic!("unexpected frame: {:?}", frame),
+        }
+        assert!(src.is_empty());
+    }
+}
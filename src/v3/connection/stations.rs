@@ -0,0 +1,93 @@
+use std::io;
+
+use quick_xml::de;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+
+use crate::{SeedLinkResult, StationV3};
+
+const STATION_TAG: &[u8] = b"station";
+
+/// Iterator lazily parsing the stations of an `INFO STATIONS` or `INFO STREAMS` XML response.
+#[derive(Debug)]
+pub(crate) struct StationsXml {
+    xml: String,
+    pos: usize,
+}
+
+impl StationsXml {
+    pub(crate) fn new(xml: String) -> Self {
+        Self { xml, pos: 0 }
+    }
+
+    fn next_station(&mut self) -> Result<Option<StationV3>, String> {
+        let mut reader = Reader::from_str(&self.xml[self.pos..]);
+        loop {
+            let start = reader.buffer_position();
+            let end = match reader.read_event().map_err(|e| e.to_string())? {
+                Event::Start(e) if e.name().as_ref() == STATION_TAG => {
+                    reader.read_to_end(e.name()).map_err(|e| e.to_string())?;
+                    reader.buffer_position()
+                }
+                Event::Empty(e) if e.name().as_ref() == STATION_TAG => reader.buffer_position(),
+                Event::Eof => return Ok(None),
+                _ => continue,
+            };
+
+            let station = de::from_str::<StationV3>(&self.xml[self.pos + start..self.pos + end])
+                .map_err(|e| e.to_string())?;
+            self.pos += end;
+
+            return Ok(Some(station));
+        }
+    }
+}
+
+impl Iterator for StationsXml {
+    type Item = SeedLinkResult<StationV3>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_station() {
+            Ok(station) => station.map(Ok),
+            Err(e) => {
+                // stop parsing
+                self.pos = self.xml.len();
+                Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid response to INFO command: {}", e),
+                )
+                .into()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_stations_lazily() {
+        let xml = r#"<?xml version="1.0"?>
+            <seedlink software="SeedLink v3.1 (2020.075)" organization="GEOFON" started="2021/03/30 08:50:25.0617">
+                <station name="WLF" network="GE" description="GEOFON Station Walferdange" begin_seq="000000" end_seq="00002A" stream_check="enabled"/>
+                <station name="APE" network="GE" description="GEOFON Station Apirathos" begin_seq="000000" end_seq="00002A" stream_check="enabled">
+                    <stream location="" seedname="BHZ" type="D" begin_time="2021/03/30 08:50:25.0617" end_time="2021/03/30 09:50:25.0617" begin_recno="0" end_recno="42" gap_check="enabled" gap_treshold="0"/>
+                </station>
+            </seedlink>"#;
+
+        let mut stations = StationsXml::new(xml.to_string());
+        let station = stations.next().unwrap().unwrap();
+        assert_eq!(station.code, "WLF");
+        assert!(station.stream.is_none());
+        let station = stations.next().unwrap().unwrap();
+        assert_eq!(station.code, "APE");
+        assert_eq!(station.stream.unwrap().len(), 1);
+        assert!(stations.next().is_none());
+
+        let mut stations = StationsXml::new(r#"<seedlink><station name="WLF"/>"#.to_string());
+        assert!(stations.next().unwrap().is_err());
+        assert!(stations.next().is_none());
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Deserializer};
+
+use time::OffsetDateTime;
+
+use super::inventory::{deserialize_datetime, deserialize_seq_num};
+
+/// Structure representing a client connection to a station.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename(deserialize = "connection"))]
+pub struct Connection {
+    /// IP address of the client
+    #[serde(rename = "@host")]
+    pub host: String,
+    /// Port of the client
+    #[serde(rename = "@port")]
+    pub port: u16,
+    /// Time the connection was established
+    #[serde(rename = "@ctime", deserialize_with = "deserialize_datetime")]
+    pub ctime: OffsetDateTime,
+    /// Packet sequence number the connection started with
+    #[serde(rename = "@begin_seq", deserialize_with = "deserialize_seq_num")]
+    pub begin_seq: i32,
+    /// Packet sequence number of the packet transferred most recently
+    #[serde(rename = "@current_seq", deserialize_with = "deserialize_seq_num")]
+    pub current_seq: i32,
+    /// Number of sequence gaps
+    #[serde(rename = "@sequence_gaps")]
+    pub sequence_gaps: u64,
+    /// Number of packets transferred
+    #[serde(rename = "@txcount")]
+    pub txcount: u64,
+    /// Whether the sequence number requested was valid
+    #[serde(rename = "@begin_seq_valid", deserialize_with = "deserialize_flag")]
+    pub begin_seq_valid: bool,
+    /// Whether the connection is in real-time mode
+    #[serde(rename = "@realtime", deserialize_with = "deserialize_flag")]
+    pub realtime: bool,
+    /// Whether the end of data was reached (i.e. in dial-up mode)
+    #[serde(rename = "@end_of_data", deserialize_with = "deserialize_flag")]
+    pub end_of_data: bool,
+}
+
+/// Structure representing a station with the client connections to it.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Station {
+    /// Network code
+    #[serde(rename = "@network")]
+    pub network: String,
+    /// Station code
+    #[serde(rename = "@name")]
+    pub code: String,
+    /// Description
+    #[serde(rename = "@description")]
+    pub description: String,
+
+    /// Client connections
+    #[serde(default)]
+    pub connection: Vec<Connection>,
+}
+
+/// Struct representing the SeedLink server's client connection information.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename(deserialize = "seedlink"))]
+pub struct Connections {
+    #[serde(default)]
+    pub station: Vec<Station>,
+}
+
+fn deserialize_flag<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    let buf: &str = Deserialize::deserialize(deserializer)?;
+    match buf {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        other => Err(D::Error::custom(format!("invalid flag: {}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use quick_xml::de::from_str;
+    use time::macros::datetime;
+
+    use super::{Connection, Connections};
+
+    #[test]
+    fn deserialize_connections() {
+        let xml = r#"<?xml version="1.0"?>
+            <seedlink software="SeedLink v3.1 (2020.075)" organization="GEOFON" started="2023/05/01 00:00:00.0000">
+            <station name="WLF" network="GE" description="GEOFON Station Walferdange" begin_seq="563200" end_seq="58275F" stream_check="enabled">
+                <connection host="192.0.2.1" port="51234" ctime="2023/05/02 08:15:00.0000" begin_seq="582700" current_seq="58275E" sequence_gaps="0" txcount="94" begin_seq_valid="yes" realtime="yes" end_of_data="no">
+                    <selector pattern="BH?.D"/>
+                </connection>
+            </station>
+            <station name="APE" network="GE" description="GEOFON Station Apirathos" begin_seq="0" end_seq="2A" stream_check="enabled"/>
+            </seedlink>"#;
+
+        let connections: Connections = from_str(xml).unwrap();
+        assert_eq!(connections.station.len(), 2);
+        assert_eq!(
+            connections.station[0].connection,
+            vec![Connection {
+                host: "192.0.2.1".to_string(),
+                port: 51234,
+                ctime: datetime!(2023-05-02 08:15:00 UTC),
+                begin_seq: 0x582700,
+                current_seq: 0x58275E,
+                sequence_gaps: 0,
+                txcount: 94,
+                begin_seq_valid: true,
+                realtime: true,
+                end_of_data: false,
+            }]
+        );
+        assert!(connections.station[1].connection.is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+use serde::Deserialize;
+
+use time::OffsetDateTime;
+
+use super::inventory::{deserialize_datetime, StreamType};
+
+/// Structure representing a gap in the data of a stream.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename(deserialize = "gap"))]
+pub struct Gap {
+    /// Time the gap begins
+    #[serde(rename = "@begin_time", deserialize_with = "deserialize_datetime")]
+    pub begin_time: OffsetDateTime,
+    /// Time the gap ends
+    #[serde(rename = "@end_time", deserialize_with = "deserialize_datetime")]
+    pub end_time: OffsetDateTime,
+}
+
+/// Structure representing a stream with the gaps in its data.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename(deserialize = "stream"))]
+pub struct Stream {
+    /// Location code
+    #[serde(rename = "@location")]
+    pub location: String,
+    /// Channel code
+    #[serde(rename = "@seedname")]
+    pub channel: String,
+    /// Stream type
+    #[serde(rename = "@type")]
+    pub stream_type: StreamType,
+
+    /// Gaps
+    #[serde(default)]
+    pub gap: Vec<Gap>,
+}
+
+/// Structure representing a station with the gaps in the data of its streams.
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Station {
+    /// Network code
+    #[serde(rename = "@network")]
+    pub network: String,
+    /// Station code
+    #[serde(rename = "@name")]
+    pub code: String,
+    /// Description
+    #[serde(rename = "@description")]
+    pub description: String,
+
+    /// Streams
+    #[serde(default)]
+    pub stream: Vec<Stream>,
+}
+
+impl Station {
+    /// Returns the gaps of all streams of the station together with the stream they belong to.
+    pub fn gaps(&self) -> impl Iterator<Item = (&Stream, &Gap)> {
+        self.stream
+            .iter()
+            .flat_map(|stream| stream.gap.iter().map(move |gap| (stream, gap)))
+    }
+}
+
+/// Struct representing the SeedLink server's gap information.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename(deserialize = "seedlink"))]
+pub struct Gaps {
+    #[serde(default)]
+    pub station: Vec<Station>,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use quick_xml::de::from_str;
+    use time::macros::datetime;
+
+    use super::{Gap, Gaps};
+
+    #[test]
+    fn deserialize_gaps() {
+        let xml = r#"<?xml version="1.0"?>
+            <seedlink software="SeedLink v3.1 (2020.075)" organization="GEOFON" started="2023/05/01 00:00:00.0000">
+            <station name="WLF" network="GE" description="GEOFON Station Walferdange" begin_seq="563200" end_seq="58275F" stream_check="enabled">
+                <stream location="" seedname="BHZ" type="D" begin_time="2023/05/01 00:00:00.0000" end_time="2023/05/02 12:30:15.4250" begin_recno="0" end_recno="0" gap_check="enabled" gap_treshold="10">
+                    <gap begin_time="2023/05/01 12:00:00.0000" end_time="2023/05/01 12:01:30.5000"/>
+                    <gap begin_time="2023/05/02 03:15:00.0000" end_time="2023/05/02 03:15:10.0000"/>
+                </stream>
+                <stream location="" seedname="BHN" type="D" begin_time="2023/05/01 00:00:00.0000" end_time="2023/05/02 12:30:12.6750" begin_recno="0" end_recno="0" gap_check="enabled" gap_treshold="10"/>
+            </station>
+            </seedlink>"#;
+
+        let gaps: Gaps = from_str(xml).unwrap();
+        assert_eq!(gaps.station.len(), 1);
+        let sta = &gaps.station[0];
+        assert_eq!(sta.stream.len(), 2);
+        assert!(sta.stream[1].gap.is_empty());
+        assert_eq!(
+            sta.gaps()
+                .map(|(stream, gap)| (stream.channel.as_str(), gap.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "BHZ",
+                    Gap {
+                        begin_time: datetime!(2023-05-01 12:00:00 UTC),
+                        end_time: datetime!(2023-05-01 12:01:30.5 UTC),
+                    }
+                ),
+                (
+                    "BHZ",
+                    Gap {
+                        begin_time: datetime!(2023-05-02 03:15:00 UTC),
+                        end_time: datetime!(2023-05-02 03:15:10 UTC),
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::io::{self, Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::SeedLinkResult;
+
+/// Capability indicating gzip compressed `INFO` responses.
+///
+/// Servers advertise the capability in response to `HELLO`, clients enable it by means of the
+/// `CAPABILITIES` command. The concatenated payload of the `INFO` packets of a response is
+/// compressed as a whole.
+///
+/// Note that this is a non-standard extension.
+pub const CAPABILITY_INFO_GZIP: &str = "INFO:GZIP";
+
+/// Compresses the `INFO` response `payload` (i.e. XML) for clients which enabled the
+/// [`CAPABILITY_INFO_GZIP`] capability.
+pub fn compress_info_payload(payload: &str) -> SeedLinkResult<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(payload.as_bytes())?;
+
+    Ok(encoder.finish()?)
+}
+
+/// Decompresses the concatenated payload `buf` of a compressed `INFO` response.
+pub(crate) fn decompress_info_payload(buf: &[u8]) -> SeedLinkResult<String> {
+    let mut rv = String::new();
+    GzDecoder::new(buf).read_to_string(&mut rv).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid compressed INFO payload: {}", e),
+        )
+    })?;
+
+    Ok(rv)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn compress_roundtrip() {
+        let xml = r#"<?xml version="1.0"?><seedlink software="SeedLink v3.1"/>"#;
+        let compressed = compress_info_payload(xml).unwrap();
+        assert_ne!(compressed, xml.as_bytes());
+        assert_eq!(decompress_info_payload(&compressed).unwrap(), xml);
+
+        assert!(decompress_info_payload(xml.as_bytes()).is_err());
+    }
+}
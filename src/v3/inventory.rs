@@ -76,7 +76,7 @@ pub struct Inventory {
     pub station: Vec<Station>,
 }
 
-fn deserialize_seq_num<'de, D>(deserializer: D) -> Result<i32, D::Error>
+pub(super) fn deserialize_seq_num<'de, D>(deserializer: D) -> Result<i32, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -85,7 +85,7 @@ where
     Ok(i32::from_str_radix(buf, 16).map_err(D::Error::custom)?)
 }
 
-fn deserialize_datetime<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+pub(super) fn deserialize_datetime<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
 where
     D: Deserializer<'de>,
 {
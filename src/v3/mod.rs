@@ -1,9 +1,20 @@
 pub use cmd::{
-    Batch as BatchCmdV3, Bye as ByeCmdV3, Command as CommandV3, Data as DataCmdV3, End as EndCmdV3,
-    Fetch as FetchCmdV3, Hello as HelloCmdV3, Info as InfoCmdV3, InfoItem as InfoCmdItemV3,
-    Select as SelectCmdV3, Station as StationCmdV3, Time as TimeCmdV3, Unknown as UnknownCmdV3,
+    Batch as BatchCmdV3, Bye as ByeCmdV3, Capabilities as CapabilitiesCmdV3, Command as CommandV3,
+    Data as DataCmdV3, End as EndCmdV3, Fetch as FetchCmdV3, Hello as HelloCmdV3,
+    Info as InfoCmdV3, InfoItem as InfoCmdItemV3, Select as SelectCmdV3, Station as StationCmdV3,
+    Time as TimeCmdV3, Unknown as UnknownCmdV3, CAPABILITY_CAP as CAPABILITY_CAP_V3,
+    CAPABILITY_EXTREPLY as CAPABILITY_EXTREPLY_V3,
+};
+pub use connections::{
+    Connection as ConnectionV3, Connections as ConnectionsV3, Station as ConnectionsStationV3,
 };
 pub use error::Error as ProtocolErrorV3;
+pub use gaps::{Gap as GapV3, Gaps as GapsV3, Station as GapsStationV3, Stream as GapsStreamV3};
+#[cfg(feature = "gzip")]
+pub use gzip::{
+    compress_info_payload as compress_info_payload_v3,
+    CAPABILITY_INFO_GZIP as CAPABILITY_INFO_GZIP_V3,
+};
 pub use inventory::{
     Inventory as InventoryV3, Station as StationV3, Stream as StreamV3, StreamType as StreamTypeV3,
 };
@@ -11,16 +22,24 @@ pub use packet::{
     SeedLinkGenericDataPacketV3, SeedLinkInfoPacketV3, SeedLinkPacketV3,
     HEADER_SIZE as SEEDLINK_PACKET_HEADER_SIZE_V3, RECORD_SIZE as SEEDLINK_PACKET_RECORD_SIZE_V3,
 };
+pub use selector::Selector as SelectorV3;
 
+#[cfg(feature = "v3-client")]
 pub(crate) use connection::{
     SeedLinkConnectionV3, SeedLinkDataTransferModeV3, 
 };
 
 mod cmd;
+#[cfg(feature = "v3-client")]
 mod connection;
+mod connections;
 mod error;
+mod gaps;
+#[cfg(feature = "gzip")]
+mod gzip;
 mod inventory;
 mod packet;
+mod selector;
 mod util;
 
 /// SeedLink v3 packet size
@@ -1,35 +1,28 @@
 use std::io;
 use std::str;
 
+use bytes::Bytes;
 use mseed::{MSControlFlags, MSRecord};
 
+use crate::wire::v3::{parse_header, Header};
 use crate::SeedLinkResult;
 
-/// SeedLink packet header size.
-pub const HEADER_SIZE: usize = 8;
-/// SeedLink packet record size.
-pub const RECORD_SIZE: usize = 512;
-/// SeedLink packet signature.
-pub const SIGNATURE: &[u8; 2] = b"SL";
-/// SeedLink info packet signature.
-pub const INFO_SIGNATURE: &[u8; 6] = b"SLINFO";
+pub use crate::wire::v3::{HEADER_SIZE, INFO_SIGNATURE, RECORD_SIZE, SIGNATURE};
+
 /// SeedLink error packet signature.
 pub const ERROR_SIGNATURE: &[u8; 5] = b"ERROR";
 /// SeedLink end packet signature.
 pub const END_SIGNATURE: &[u8; 3] = b"END";
 /// SeedLink ok packet signature
 pub const OK_SIGNATURE: &[u8; 2] = b"OK";
-/// SeedLink info packet flag indicating that the info packet is the last packet for a
-/// given request.
-pub const INFO_TERMINATION_FLAG: &[u8; 1] = b"*";
 
 #[derive(Debug)]
 struct SeedLinkPacketBase {
-    packet: Vec<u8>,
+    packet: Bytes,
 }
 
 impl SeedLinkPacketBase {
-    fn new(buf: Vec<u8>) -> Self {
+    fn new(buf: Bytes) -> Self {
         if buf.len() != HEADER_SIZE + RECORD_SIZE {}
         Self { packet: buf }
     }
@@ -58,9 +51,11 @@ pub struct SeedLinkInfoPacketV3 {
 }
 
 impl SeedLinkInfoPacketV3 {
-    pub fn new(buf: Vec<u8>) -> Self {
+    /// Creates a packet from the raw packet bytes `buf`. Note that `Bytes` buffers are not
+    /// copied.
+    pub fn new(buf: impl Into<Bytes>) -> Self {
         Self {
-            base: SeedLinkPacketBase::new(buf),
+            base: SeedLinkPacketBase::new(buf.into()),
         }
     }
 
@@ -69,6 +64,11 @@ impl SeedLinkInfoPacketV3 {
         self.base.raw()
     }
 
+    /// Returns the raw packet bytes as shared buffer, i.e. without copying.
+    pub fn raw_bytes(&self) -> Bytes {
+        self.base.packet.clone()
+    }
+
     /// Returns whether the packet meets an error condition.
     pub fn is_err(&self) -> bool {
         match self.base.ms_record(MSControlFlags::empty()) {
@@ -82,7 +82,7 @@ impl SeedLinkInfoPacketV3 {
 
     /// Returns `true` if the packet is marked as the last packet for a request, else `false`.
     pub fn is_last(&self) -> bool {
-        self.base.header()[HEADER_SIZE - 1] != INFO_TERMINATION_FLAG[0]
+        matches!(parse_header(self.base.header()), Ok(Header::Info(true)))
     }
 
     /// Returns the raw packet payload.
@@ -92,11 +92,16 @@ impl SeedLinkInfoPacketV3 {
 
     /// Returns the decoded packet payload.
     pub fn payload(&self) -> SeedLinkResult<String> {
+        String::from_utf8(self.payload_bytes()?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()).into())
+    }
+
+    /// Returns the decoded packet payload bytes, e.g. in case the payload is compressed.
+    pub fn payload_bytes(&self) -> SeedLinkResult<Vec<u8>> {
         let msr = self.base.ms_record(MSControlFlags::MSF_UNPACKDATA)?;
 
         if let Some(data_samples) = &msr.data_samples() {
-            String::from_utf8(data_samples.to_vec())
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()).into())
+            Ok(data_samples.to_vec())
         } else {
             Err(io::Error::new(io::ErrorKind::InvalidData, "missing payload").into())
         }
@@ -110,9 +115,11 @@ pub struct SeedLinkGenericDataPacketV3 {
 }
 
 impl SeedLinkGenericDataPacketV3 {
-    pub fn new(buf: Vec<u8>) -> Self {
+    /// Creates a packet from the raw packet bytes `buf`. Note that `Bytes` buffers are not
+    /// copied.
+    pub fn new(buf: impl Into<Bytes>) -> Self {
         Self {
-            base: SeedLinkPacketBase::new(buf),
+            base: SeedLinkPacketBase::new(buf.into()),
         }
     }
 
@@ -121,6 +128,11 @@ impl SeedLinkGenericDataPacketV3 {
         self.base.raw()
     }
 
+    /// Returns the raw packet bytes as shared buffer, i.e. without copying.
+    pub fn raw_bytes(&self) -> Bytes {
+        self.base.packet.clone()
+    }
+
     /// Returns the raw packet payload.
     pub fn raw_payload(&self) -> &[u8] {
         self.base.raw_ms_record()
@@ -139,8 +151,12 @@ impl SeedLinkGenericDataPacketV3 {
 
     /// Returns the decoded packet sequence number
     pub fn sequence_number(&self) -> SeedLinkResult<i32> {
-        i32::from_str_radix(self.sequence_number_str()?, 16)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()).into())
+        match parse_header(self.base.header())? {
+            Header::Data(seq_num) => Ok(seq_num as i32),
+            Header::Info(_) => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "not a data packet").into())
+            }
+        }
     }
 }
 
@@ -0,0 +1,187 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::StreamTypeV3;
+use crate::{SeedLinkError, SelectCmdPatternV4};
+
+/// SeedLink `v3` stream selector, i.e. `[!][LL]CCC[.T]`.
+///
+/// Location and channel codes may contain the `?` wildcard. An empty location code is denoted
+/// by `--`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Selector {
+    /// Whether the streams selected are excluded.
+    pub exclude: bool,
+    /// Location code pattern. If `None`, any location code matches.
+    pub location: Option<String>,
+    /// Channel code pattern.
+    pub channel: String,
+    /// Stream type. If `None`, any stream type matches.
+    pub stream_type: Option<StreamTypeV3>,
+}
+
+impl Selector {
+    /// Returns the corresponding SeedLink `v4` select pattern.
+    pub fn to_select_pattern(&self) -> SelectCmdPatternV4 {
+        let location = match self.location.as_deref() {
+            None => "*",
+            Some("--") => "",
+            Some(location) => location,
+        };
+        let mut channel = self.channel.chars();
+        let stream_pattern = format!(
+            "{}_{}_{}_{}",
+            location,
+            channel.next().unwrap(),
+            channel.next().unwrap(),
+            channel.as_str()
+        );
+
+        SelectCmdPatternV4 {
+            exclude: self.exclude,
+            stream_pattern,
+            // XXX(damb): `v4` subformats are identical to `v3` stream types
+            format_subformat_pattern: self
+                .stream_type
+                .as_ref()
+                .map(|stream_type| format!("?{}", stream_type_code(stream_type))),
+            filter: None,
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = SeedLinkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SeedLinkError::InvalidCommandArgument(format!("invalid selector: {}", s));
+
+        let (exclude, selector) = match s.strip_prefix('!') {
+            Some(selector) => (true, selector),
+            None => (false, s),
+        };
+        let (codes, stream_type) = match selector.split_once('.') {
+            Some((codes, stream_type)) => (
+                codes,
+                Some(parse_stream_type(stream_type).ok_or_else(invalid)?),
+            ),
+            None => (selector, None),
+        };
+
+        if !codes.is_ascii() {
+            return Err(invalid());
+        }
+        let (location, channel) = match codes.len() {
+            3 => (None, codes),
+            5 => (Some(&codes[..2]), &codes[2..]),
+            _ => return Err(invalid()),
+        };
+        if !channel
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '?')
+        {
+            return Err(invalid());
+        }
+        if let Some(location) = location {
+            if !(location == "--"
+                || location
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '?'))
+            {
+                return Err(invalid());
+            }
+        }
+
+        Ok(Self {
+            exclude,
+            location: location.map(|location| location.to_string()),
+            channel: channel.to_string(),
+            stream_type,
+        })
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.exclude {
+            write!(f, "!")?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, "{}", location)?;
+        }
+        write!(f, "{}", self.channel)?;
+        if let Some(stream_type) = &self.stream_type {
+            write!(f, ".{}", stream_type_code(stream_type))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&Selector> for SelectCmdPatternV4 {
+    fn from(selector: &Selector) -> Self {
+        selector.to_select_pattern()
+    }
+}
+
+fn parse_stream_type(s: &str) -> Option<StreamTypeV3> {
+    match s {
+        "D" => Some(StreamTypeV3::Data),
+        "E" => Some(StreamTypeV3::Event),
+        "C" => Some(StreamTypeV3::Calibration),
+        "O" => Some(StreamTypeV3::Blockette),
+        "T" => Some(StreamTypeV3::Timing),
+        "L" => Some(StreamTypeV3::Log),
+        _ => None,
+    }
+}
+
+fn stream_type_code(stream_type: &StreamTypeV3) -> char {
+    match stream_type {
+        StreamTypeV3::Data => 'D',
+        StreamTypeV3::Event => 'E',
+        StreamTypeV3::Calibration => 'C',
+        StreamTypeV3::Blockette => 'O',
+        StreamTypeV3::Timing => 'T',
+        StreamTypeV3::Log => 'L',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_selector() {
+        let selector: Selector = "00HH?.D".parse().unwrap();
+        assert_eq!(
+            selector,
+            Selector {
+                exclude: false,
+                location: Some("00".to_string()),
+                channel: "HH?".to_string(),
+                stream_type: Some(StreamTypeV3::Data),
+            }
+        );
+        assert_eq!(selector.to_string(), "00HH?.D");
+
+        let selector: Selector = "!LOG".parse().unwrap();
+        assert!(selector.exclude);
+        assert_eq!(selector.location, None);
+        assert_eq!(selector.to_string(), "!LOG");
+
+        for invalid in ["", "BH", "0BHZ", "BHZ.X", "BHZ.", "BH*", "00BHZ.D:filter"] {
+            assert!(invalid.parse::<Selector>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn to_select_pattern() {
+        let selector: Selector = "HH?.D".parse().unwrap();
+        assert_eq!(selector.to_select_pattern().to_string(), "*_H_H_?.?D");
+
+        let selector: Selector = "!--LOG".parse().unwrap();
+        assert_eq!(selector.to_select_pattern().to_string(), "!_L_O_G");
+    }
+}
@@ -1,3 +1,4 @@
+use time::macros::format_description;
 use time::PrimitiveDateTime;
 
 pub fn time_as_seedlink_str(t: &PrimitiveDateTime) -> String {
@@ -12,3 +13,8 @@ pub fn time_as_seedlink_str(t: &PrimitiveDateTime) -> String {
     )
 }
 
+/// Parses a time formatted as `YYYY,MM,DD,hh,mm,ss` (see [`time_as_seedlink_str`]).
+pub fn parse_seedlink_time(s: &str) -> Option<PrimitiveDateTime> {
+    let format = format_description!("[year],[month],[day],[hour],[minute],[second]");
+    PrimitiveDateTime::parse(s, &format).ok()
+}
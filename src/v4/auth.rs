@@ -1,3 +1,11 @@
+use crate::AuthCmdMethodV4;
+
+/// Capability indicating that authenticated clients may re-authenticate (e.g. with a refreshed
+/// JSON Web Token) before their credentials expire.
+///
+/// Note that this is a non-standard extension.
+pub const CAPABILITY_REFRESH: &str = "AUTH:REFRESH";
+
 /// Enumeration of possible SeedLink v4 authentication method types.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Auth {
@@ -6,3 +14,21 @@ pub enum Auth {
     /// JSON Web Token (RFC 7519).
     JWT(String),
 }
+
+impl From<&AuthCmdMethodV4> for Auth {
+    fn from(item: &AuthCmdMethodV4) -> Self {
+        match item {
+            AuthCmdMethodV4::UserPass(user, pass) => Self::UserPass(user.clone(), pass.clone()),
+            AuthCmdMethodV4::JWT(token) => Self::JWT(token.clone()),
+        }
+    }
+}
+
+impl From<Auth> for AuthCmdMethodV4 {
+    fn from(item: Auth) -> Self {
+        match item {
+            Auth::UserPass(user, pass) => Self::UserPass(user, pass),
+            Auth::JWT(token) => Self::JWT(token),
+        }
+    }
+}
@@ -56,6 +56,11 @@ impl Auth {
     pub fn new(method: AuthMethod) -> Self {
         Self { method }
     }
+
+    /// Returns the authentication method.
+    pub fn method(&self) -> &AuthMethod {
+        &self.method
+    }
 }
 
 impl str::FromStr for Auth {
@@ -74,7 +79,7 @@ impl fmt::Display for Auth {
                 format!("userpass {} {}", user, pass)
             }
             AuthMethod::JWT(ref token) => {
-                format!("jwt {}", token)
+                format!("token {}", token)
             }
         };
         write!(f, "{} {}", Self::NAME, s)
@@ -4,7 +4,7 @@ use std::str;
 use crate::ProtocolErrorV4;
 
 /// Command to request information about the SeedLink server.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Info {
     pub item: InfoItem,
 
@@ -121,7 +121,7 @@ impl fmt::Display for Info {
 }
 
 /// Enumeration of `INFO` command items.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum InfoItem {
     Id,
     Formats,
@@ -47,12 +47,20 @@ pub enum Command {
 }
 
 impl Command {
-    /// Parses the command from a buffer.
+    /// Parses the command from a buffer (e.g. as returned by [`Command::to_wire`]). A trailing
+    /// line terminator is ignored.
     pub fn parse(buf: &[u8]) -> Result<Self, ProtocolErrorV4> {
-        let s =
-            String::from_utf8(buf.to_vec()).map_err(|_| ProtocolErrorV4::unsupported_command())?;
+        let s = str::from_utf8(buf).map_err(|_| ProtocolErrorV4::unsupported_command())?;
 
-        Self::from_str(s.as_str())
+        Self::from_str(s.trim_end_matches(['\r', '\n']))
+    }
+
+    /// Returns the wire representation of the command, i.e. the command line including the
+    /// `<CR><LF>` terminator.
+    pub fn to_wire(&self) -> Vec<u8> {
+        let mut buf = self.to_string().into_bytes();
+        buf.extend_from_slice(b"\r\n");
+        buf
     }
 }
 
@@ -60,7 +68,9 @@ impl str::FromStr for Command {
     type Err = ProtocolErrorV4;
 
     fn from_str(s: &str) -> Result<Command, Self::Err> {
-        assert!(!s.is_empty());
+        if s.is_empty() {
+            return Err(ProtocolErrorV4::unsupported_command());
+        }
         let split: Vec<&str> = s.splitn(2, [' ', '\t']).collect();
 
         let cmd_id = split[0].to_lowercase();
@@ -75,7 +85,7 @@ impl str::FromStr for Command {
                 Self::Bye(Bye)
             }
             Data::NAME => {
-                if split.len() == 2 {
+                if split.len() == 1 {
                     Self::Data(Data::default())
                 } else {
                     Self::Data(Data::from_str(split[1])?)
@@ -124,7 +134,7 @@ impl str::FromStr for Command {
                 check_cmd_length(&split, 2)?;
                 Self::UserAgent(UserAgent::from_str(split[1])?)
             }
-            other => Self::Unknown(Unknown::new(other)),
+            other => Self::Unknown(Unknown::new(other, split.get(1).copied())),
         };
 
         Ok(cmd)
@@ -159,3 +169,27 @@ fn check_cmd_length(cmd: &[&str], expected_length: usize) -> Result<(), Protocol
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn wire_round_trip() {
+        let cmds = [
+            Command::Hello(Hello),
+            Command::Station(Station::from_str("GE_WLF").unwrap()),
+            Command::Select(Select::from_str("00_H_H_?").unwrap()),
+            Command::Data(Data::default()),
+            Command::Data(Data::new(Some(SequenceNumber::Number(42)), None, None)),
+            Command::End(End),
+        ];
+        for cmd in cmds {
+            assert_eq!(Command::parse(&cmd.to_wire()).unwrap(), cmd);
+        }
+
+        assert_eq!(Command::Hello(Hello).to_wire(), b"hello\r\n");
+        assert!(Command::parse(b"").is_err());
+    }
+}
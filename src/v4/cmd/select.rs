@@ -44,11 +44,10 @@ impl str::FromStr for SelectPattern {
             None
         };
 
-        let exclude = if split[0].chars().next().unwrap() == '!' {
-            true
-        } else {
-            false
-        };
+        if split[0].is_empty() {
+            return Err(ProtocolErrorV4::incorrect_arguments());
+        }
+        let exclude = split[0].starts_with('!');
 
         // XXX: the `:filter` suffix MUST NOT be used together with the `!` prefix.
         if exclude && filter.is_some() {
@@ -41,6 +41,6 @@ impl str::FromStr for SlProto {
 
 impl fmt::Display for SlProto {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", SlProto::NAME)
+        write!(f, "{} {}", SlProto::NAME, self.version())
     }
 }
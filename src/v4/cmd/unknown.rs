@@ -4,19 +4,26 @@ use std::fmt;
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Unknown {
     pub command_name: String,
+    /// The raw command arguments, if any.
+    pub args: Option<String>,
 }
 
 impl Unknown {
     /// Create a new `Unknown` command.
-    pub(crate) fn new(key: impl ToString) -> Self {
+    pub(crate) fn new(key: impl ToString, args: Option<&str>) -> Self {
         Self {
             command_name: key.to_string(),
+            args: args.map(|args| args.to_string()),
         }
     }
 }
 
 impl fmt::Display for Unknown {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref args) = self.args {
+            return write!(f, "{} {}", self.command_name, args);
+        }
+
         write!(f, "{}", self.command_name)
     }
 }
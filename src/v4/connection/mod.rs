@@ -0,0 +1,1048 @@
+use std::io;
+use std::sync::Arc;
+
+use futures::stream::StreamExt;
+use serde::de::DeserializeOwned;
+use time::PrimitiveDateTime;
+use tokio::io::{self as tokio_io, AsyncWriteExt, BufWriter, DuplexStream, ReadHalf, WriteHalf};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+#[cfg(feature = "tls")]
+use tokio::net::TcpStream;
+use tokio::time as tokio_time;
+#[cfg(feature = "tls")]
+use tokio_rustls::client::TlsStream;
+use tokio_util::codec::FramedRead;
+use tracing::{debug, instrument, warn};
+
+use crate::connection::{
+    disconnected, BufferCapacities, NegotiationProgressCallback, NegotiationProgressTracker,
+    StatsRecorder,
+};
+use crate::metrics;
+use crate::trace;
+use crate::wire::conformance;
+#[cfg(feature = "tls")]
+use crate::TlsConnection;
+use crate::{
+    ActualConnection, AuthCmdMethodV4, AuthCmdV4, ByeCmdV4, CapabilitiesInfoV4, CommandV4,
+    ConnectionsInfoV4, DataFormatV4, DataTransferMode, EndCmdV4, EndFetchCmdV4, Format,
+    FormatsInfoV4, FrameV4, HelloCmdV4, IdInfoV4, InfoCmdItemV4, InfoCmdV4, Inventory,
+    InventoryLevel, MemConnection, ProtocolErrorV4, SeedLinkError, SeedLinkPacketV4,
+    SeedLinkResult, SlProtoCmdV4, Station, Stations, StationsInfoV4, StreamConfig, StreamsInfoV4,
+    SubscribedStation, TcpConnection, UserAgentCmdInfoV4, UserAgentCmdV4,
+};
+
+use negotiate::Negotiator;
+use seedlink::SeedLinkCodec;
+use stations::StationsJson;
+
+mod negotiate;
+mod seedlink;
+mod stations;
+
+#[derive(Debug)]
+struct FramedTcpConnection {
+    read: FramedRead<OwnedReadHalf, SeedLinkCodec>,
+    write: BufWriter<OwnedWriteHalf>,
+
+    open: bool,
+}
+
+#[cfg(feature = "tls")]
+#[derive(Debug)]
+struct FramedTlsConnection {
+    read: FramedRead<ReadHalf<TlsStream<TcpStream>>, SeedLinkCodec>,
+    write: BufWriter<WriteHalf<TlsStream<TcpStream>>>,
+
+    open: bool,
+}
+
+#[derive(Debug)]
+struct FramedMemConnection {
+    read: FramedRead<ReadHalf<DuplexStream>, SeedLinkCodec>,
+    write: BufWriter<WriteHalf<DuplexStream>>,
+
+    open: bool,
+}
+
+#[derive(Debug)]
+enum ActualFramedConnection {
+    Tcp(FramedTcpConnection),
+    #[cfg(feature = "tls")]
+    Tls(FramedTlsConnection),
+    Mem(FramedMemConnection),
+}
+
+impl ActualFramedConnection {
+    pub async fn flush(&mut self) -> SeedLinkResult<()> {
+        match self {
+            Self::Tcp(FramedTcpConnection { ref mut write, .. }) => write.flush().await?,
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection { ref mut write, .. }) => write.flush().await?,
+            Self::Mem(FramedMemConnection { ref mut write, .. }) => write.flush().await?,
+        }
+
+        Ok(())
+    }
+
+    pub async fn write_all(&mut self, buf: &[u8]) -> SeedLinkResult<()> {
+        match self {
+            Self::Tcp(FramedTcpConnection { ref mut write, .. }) => write.write_all(buf).await?,
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection { ref mut write, .. }) => write.write_all(buf).await?,
+            Self::Mem(FramedMemConnection { ref mut write, .. }) => write.write_all(buf).await?,
+        }
+
+        Ok(())
+    }
+
+    pub async fn shutdown(&mut self) -> SeedLinkResult<()> {
+        match self {
+            Self::Tcp(FramedTcpConnection {
+                ref mut write,
+                ref mut open,
+                ..
+            }) => {
+                _ = write.shutdown().await;
+                *open = false;
+            }
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection {
+                ref mut write,
+                ref mut open,
+                ..
+            }) => {
+                _ = write.shutdown().await;
+                *open = false;
+            }
+            Self::Mem(FramedMemConnection {
+                ref mut write,
+                ref mut open,
+                ..
+            }) => {
+                _ = write.shutdown().await;
+                *open = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_open(&self) -> bool {
+        match self {
+            Self::Tcp(FramedTcpConnection { ref open, .. }) => *open,
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection { ref open, .. }) => *open,
+            Self::Mem(FramedMemConnection { ref open, .. }) => *open,
+        }
+    }
+
+    /// Returns the total number of bytes received.
+    pub fn bytes_received(&self) -> u64 {
+        match self {
+            Self::Tcp(FramedTcpConnection { ref read, .. }) => read.decoder().bytes_decoded(),
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection { ref read, .. }) => read.decoder().bytes_decoded(),
+            Self::Mem(FramedMemConnection { ref read, .. }) => read.decoder().bytes_decoded(),
+        }
+    }
+
+    /// Sets the key packet signatures are verified with.
+    pub fn set_signing_key(&mut self, key: Option<Vec<u8>>) {
+        match self {
+            Self::Tcp(FramedTcpConnection { ref mut read, .. }) => {
+                read.decoder_mut().set_signing_key(key)
+            }
+            #[cfg(feature = "tls")]
+            Self::Tls(FramedTlsConnection { ref mut read, .. }) => {
+                read.decoder_mut().set_signing_key(key)
+            }
+            Self::Mem(FramedMemConnection { ref mut read, .. }) => {
+                read.decoder_mut().set_signing_key(key)
+            }
+        }
+    }
+}
+
+impl ActualFramedConnection {
+    /// Creates a new `ActualFramedConnection` from the actual connection `con`.
+    fn new(con: ActualConnection, capacities: BufferCapacities) -> Self {
+        match con {
+            ActualConnection::Tcp(TcpConnection { rw, open }) => {
+                let (read, write) = rw.into_split();
+                Self::Tcp(FramedTcpConnection {
+                    read: FramedRead::with_capacity(read, SeedLinkCodec::new(), capacities.read),
+                    write: BufWriter::with_capacity(capacities.write, write),
+                    open,
+                })
+            }
+            #[cfg(feature = "tls")]
+            ActualConnection::Tls(TlsConnection { rw, open }) => {
+                let (read, write) = tokio_io::split(rw);
+                Self::Tls(FramedTlsConnection {
+                    read: FramedRead::with_capacity(read, SeedLinkCodec::new(), capacities.read),
+                    write: BufWriter::with_capacity(capacities.write, write),
+                    open,
+                })
+            }
+            ActualConnection::Mem(MemConnection { rw, open }) => {
+                let (read, write) = tokio_io::split(rw);
+                Self::Mem(FramedMemConnection {
+                    read: FramedRead::with_capacity(read, SeedLinkCodec::new(), capacities.read),
+                    write: BufWriter::with_capacity(capacities.write, write),
+                    open,
+                })
+            }
+        }
+    }
+}
+
+/// Enumeration representing the various connection states.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum FramedConnectionState {
+    Initialized,
+    HandShaking,
+    DataTransfer,
+    Closed,
+}
+
+/// Stateful SeedLink `v4` framed connection structure encapsulating the actual connection.
+///
+/// Receives and sends frames to a remote peer.
+#[derive(Debug)]
+pub(crate) struct FramedConnectionV4 {
+    con: ActualFramedConnection,
+    state: FramedConnectionState,
+    strict: bool,
+    /// The last response line (e.g. an error) received.
+    last_message: Option<String>,
+    /// Callback notified about the negotiation progress.
+    negotiation_progress: Option<NegotiationProgressCallback>,
+    /// The stations negotiated successfully.
+    subscription: Vec<SubscribedStation>,
+
+    expect_info_resp: bool,
+    /// Time the first unacknowledged keepalive was sent.
+    keep_alive_sent: Option<tokio_time::Instant>,
+    /// Number of keepalives sent since the last acknowledgement.
+    unanswered_keep_alives: u32,
+    /// Time the last frame was received.
+    last_frame: Option<tokio_time::Instant>,
+    stats: Arc<StatsRecorder>,
+}
+
+impl FramedConnectionV4 {
+    /// Creates a new `FramedConnectionV4`, backed by the actual connection `con`.
+    pub fn new(con: ActualConnection, capacities: BufferCapacities) -> Self {
+        Self {
+            con: ActualFramedConnection::new(con, capacities),
+            state: FramedConnectionState::Initialized,
+            strict: false,
+            last_message: None,
+            negotiation_progress: None,
+            subscription: Vec::new(),
+
+            expect_info_resp: false,
+            keep_alive_sent: None,
+            unanswered_keep_alives: 0,
+            last_frame: None,
+            stats: Arc::new(StatsRecorder::default()),
+        }
+    }
+
+    /// Returns whether the connection is open.
+    pub fn is_open(&self) -> bool {
+        self.con.is_open()
+    }
+
+    /// Enables or disables strict mode, i.e. whether unsolicited server messages received while
+    /// awaiting a command response fail the handshake.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Sets the key the signatures of the packets received are verified with. If `None`, packets
+    /// are expected to be unsigned.
+    pub fn set_signing_key(&mut self, key: Option<Vec<u8>>) {
+        self.con.set_signing_key(key);
+    }
+
+    /// Sets the callback notified about the negotiation progress.
+    pub fn set_negotiation_progress(&mut self, callback: NegotiationProgressCallback) {
+        self.negotiation_progress = Some(callback);
+    }
+
+    /// Returns the stations negotiated successfully.
+    pub fn subscription(&self) -> &[SubscribedStation] {
+        &self.subscription
+    }
+
+    /// Returns the statistics recorded.
+    pub fn stats(&self) -> &Arc<StatsRecorder> {
+        &self.stats
+    }
+
+    /// Returns the last response line (e.g. an error) received, if any.
+    pub fn last_message(&self) -> Option<&str> {
+        self.last_message.as_deref()
+    }
+
+    /// Sends the `HELLO` command and returns the corresponding response.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn say_hello(&mut self) -> SeedLinkResult<(String, String)> {
+        if self.state >= FramedConnectionState::HandShaking {
+            return Err(SeedLinkError::ClientError(
+                "invalid connection state".to_string(),
+            ));
+        }
+
+        self.write_cmd(&CommandV4::Hello(HelloCmdV4)).await?;
+
+        let first_response_line = self.read_line_frame().await?;
+        let second_response_line = self.read_line_frame().await?;
+
+        Ok((first_response_line, second_response_line))
+    }
+
+    /// Sends the `SLPROTO` command in order to select the protocol version `version`.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn negotiate_protocol_version(
+        &mut self,
+        version: &SlProtoCmdV4,
+    ) -> SeedLinkResult<()> {
+        let cmd = CommandV4::SlProto(version.clone());
+        self.write_cmd(&cmd).await?;
+
+        match self.read_response(&cmd).await? {
+            Ok(()) => {
+                debug!(target: trace::NEGOTIATE,
+                    "response: slproto is OK (protocol version {})",
+                    version.version()
+                );
+                Ok(())
+            }
+            Err(err) => Err(SeedLinkError::InvalidProtocolVersion(format!(
+                "failed to select protocol version {}: {}",
+                version.version(),
+                err
+            ))),
+        }
+    }
+
+    /// Authenticates by means of the `AUTH` command using the authentication method `method`.
+    ///
+    /// Note that credentials (i.e. passwords and tokens) are neither logged nor included in error
+    /// messages.
+    #[instrument(target = "slink::negotiate", skip_all)]
+    pub async fn authenticate(&mut self, method: AuthCmdMethodV4) -> SeedLinkResult<()> {
+        let redacted_cmd = self.send_auth(method).await?;
+
+        match self.read_response(&redacted_cmd).await? {
+            Ok(()) => {
+                debug!(target: trace::NEGOTIATE, "response: auth is OK");
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Sends the `AUTH` command without awaiting the response (e.g. in order to re-authenticate
+    /// while streaming packets) and returns the command with the credentials redacted.
+    #[instrument(target = "slink::negotiate", skip_all)]
+    pub async fn send_auth(&mut self, method: AuthCmdMethodV4) -> SeedLinkResult<CommandV4> {
+        let redacted_method = match method {
+            AuthCmdMethodV4::UserPass(ref username, _) => {
+                AuthCmdMethodV4::UserPass(username.clone(), "***".to_string())
+            }
+            AuthCmdMethodV4::JWT(_) => AuthCmdMethodV4::JWT("***".to_string()),
+        };
+        let cmd = CommandV4::Auth(AuthCmdV4::new(method));
+        let redacted_cmd = CommandV4::Auth(AuthCmdV4::new(redacted_method));
+        debug!(target: trace::NEGOTIATE, "sending command: '{}'", redacted_cmd);
+        self.write_line(&cmd.to_string()).await?;
+
+        Ok(redacted_cmd)
+    }
+
+    /// Sends the `USERAGENT` command identifying the client by means of `info`. If `info` is
+    /// empty, the library identifies itself (i.e. `slink/<version>`).
+    ///
+    /// Note that failing to identify is not considered to be an error.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn send_user_agent(&mut self, info: &[UserAgentCmdInfoV4]) -> SeedLinkResult<()> {
+        let info = match info {
+            [] => vec![UserAgentCmdInfoV4::new(
+                env!("CARGO_PKG_NAME").to_string(),
+                env!("CARGO_PKG_VERSION").to_string(),
+            )],
+            info => info.to_vec(),
+        };
+        let cmd = CommandV4::UserAgent(UserAgentCmdV4::new(info));
+        self.write_cmd(&cmd).await?;
+
+        if let Err(err) = self.read_response(&cmd).await? {
+            warn!(target: trace::NEGOTIATE, "response: useragent is ERROR ({})", err);
+        }
+
+        Ok(())
+    }
+
+    /// Performs a connection shutdown.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn shutdown(&mut self) -> SeedLinkResult<()> {
+        self.write_cmd(&CommandV4::Bye(ByeCmdV4)).await?;
+        self.con.shutdown().await?;
+        self.state = FramedConnectionState::Closed;
+
+        Ok(())
+    }
+
+    /// Requests the SeedLink server's information `cmd` and returns JSON.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_info(&mut self, cmd: InfoCmdV4) -> SeedLinkResult<String> {
+        self.try_send_info(cmd).await?;
+        self.expect_info_resp = true;
+
+        let rv = loop {
+            match self.read_frame().await? {
+                FrameV4::Packet(packet) => match packet.format() {
+                    DataFormatV4::JsonSeedLinkInfo => break packet.payload_to_string(),
+                    DataFormatV4::JsonSeedLinkError => break Err(parse_info_error(&packet)),
+                    _ => {
+                        // ignore
+                    }
+                },
+                FrameV4::Error(err) => break Err(err.into()),
+                frame => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("response: invalid response to INFO command: {:?}", frame),
+                    )
+                    .into());
+                }
+            }
+        };
+
+        self.expect_info_resp = false;
+
+        rv
+    }
+
+    /// Configures the connection and completes the handshaking.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn configure(
+        &mut self,
+        stream_configs: &[StreamConfig],
+        data_transfer_mode: &SeedLinkDataTransferModeV4,
+        pipelining: bool,
+    ) -> SeedLinkResult<()> {
+        if stream_configs.is_empty() {
+            return Ok(());
+        }
+
+        // XXX(damb): v4 switches the data transfer mode per connection (i.e. `END` vs `ENDFETCH`)
+        let mode = match data_transfer_mode {
+            SeedLinkDataTransferModeV4::RealTime => Some(DataTransferMode::RealTime),
+            SeedLinkDataTransferModeV4::DialUp => Some(DataTransferMode::DialUp),
+            SeedLinkDataTransferModeV4::TimeWindow(_) => None,
+        };
+        if let Some(stream_config) = stream_configs.iter().find(|stream_config| {
+            mode.is_some()
+                && stream_config.data_transfer_mode.is_some()
+                && stream_config.data_transfer_mode != mode
+        }) {
+            return Err(SeedLinkError::InvalidClientConfig(format!(
+                "mixing data transfer modes is not supported by protocol version v4 (station: {}_{})",
+                stream_config.network, stream_config.station
+            )));
+        }
+
+        self.state = FramedConnectionState::HandShaking;
+        let started = tokio_time::Instant::now();
+
+        let (mode, end_time) = match data_transfer_mode {
+            SeedLinkDataTransferModeV4::RealTime => (DataTransferMode::RealTime, None),
+            SeedLinkDataTransferModeV4::DialUp => (DataTransferMode::DialUp, None),
+            SeedLinkDataTransferModeV4::TimeWindow(end_time) => {
+                (DataTransferMode::DialUp, Some(*end_time))
+            }
+        };
+        self.subscription.clear();
+
+        let callback = self.negotiation_progress.clone();
+        let mut progress = NegotiationProgressTracker::new(stream_configs.len(), callback.as_ref());
+        let mut accepted_sta_cnt = 0;
+        for stream_config in stream_configs {
+            let negotiator = Negotiator { stream_config };
+            let accepted = negotiator
+                .negotiate(self, data_transfer_mode, pipelining)
+                .await?;
+            if accepted {
+                self.subscription
+                    .push(stream_config.subscribed(mode, end_time));
+            }
+            accepted_sta_cnt = progress.advance(accepted);
+        }
+
+        if accepted_sta_cnt == 0 {
+            self.state = FramedConnectionState::Initialized;
+            warn!(target: trace::NEGOTIATE, "no station selected");
+        } else {
+            // switch to data transfer mode
+            self.state = FramedConnectionState::DataTransfer;
+            self.last_message = None;
+
+            let cmd = match data_transfer_mode {
+                SeedLinkDataTransferModeV4::DialUp => CommandV4::EndFetch(EndFetchCmdV4),
+                _ => CommandV4::End(EndCmdV4),
+            };
+            self.write_cmd(&cmd).await?;
+        }
+        self.stats.set_negotiation_duration(started.elapsed());
+
+        Ok(())
+    }
+
+    /// Tries to send a keep alive packet to the SeedLink server.
+    ///
+    /// Note that no keepalive is sent while an `INFO` response is outstanding. Though, the
+    /// keepalive counts as unanswered (see [`Self::unanswered_keep_alives`]).
+    pub(crate) async fn try_send_keep_alive(&mut self) -> SeedLinkResult<()> {
+        self.unanswered_keep_alives += 1;
+        let resp = match self.try_send_info(InfoCmdV4::new(InfoCmdItemV4::Id)).await {
+            Ok(()) => {
+                self.keep_alive_sent
+                    .get_or_insert_with(tokio_time::Instant::now);
+                self.stats.add_keep_alive();
+                Ok(())
+            }
+            Err(e) => match e {
+                SeedLinkError::ClientError(_) => {
+                    // ignore client errors
+                    Ok(())
+                }
+                e => Err(e),
+            },
+        };
+        self.expect_info_resp = true;
+        resp
+    }
+
+    /// Returns whether an `INFO` response is outstanding.
+    pub(crate) fn expects_info_resp(&self) -> bool {
+        self.expect_info_resp
+    }
+
+    /// Sends an `INFO` request without awaiting the response, e.g. while streaming packets.
+    pub(crate) async fn send_info_request(&mut self, cmd: InfoCmdV4) -> SeedLinkResult<()> {
+        self.try_send_info(cmd).await?;
+        self.expect_info_resp = true;
+
+        Ok(())
+    }
+
+    pub(crate) fn ack_keep_alive(&mut self) {
+        if let Some(keep_alive_sent) = self.keep_alive_sent {
+            metrics::keep_alive_rtt(keep_alive_sent.elapsed());
+        }
+        self.expect_info_resp = false;
+        self.keep_alive_sent = None;
+        self.unanswered_keep_alives = 0;
+    }
+
+    /// Returns the time the first unacknowledged keepalive was sent, if any.
+    pub(crate) fn keep_alive_sent(&self) -> Option<tokio_time::Instant> {
+        self.keep_alive_sent
+    }
+
+    /// Returns the number of keepalives sent since the last acknowledgement.
+    pub(crate) fn unanswered_keep_alives(&self) -> u32 {
+        self.unanswered_keep_alives
+    }
+
+    /// Returns the time the last frame was received, if any.
+    pub(crate) fn last_frame(&self) -> Option<tokio_time::Instant> {
+        self.last_frame
+    }
+
+    /// Low level function which writes the command `cmd` to the underlying actual framed
+    /// connection.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn write_cmd(&mut self, cmd: &CommandV4) -> SeedLinkResult<()> {
+        let line = cmd.to_string();
+        debug!(target: trace::NEGOTIATE, "sending command: '{}'", line);
+
+        self.write_line(&line).await
+    }
+
+    /// Writes the command line `line` (excluding the `<CR><LF>` terminator) to the underlying
+    /// actual framed connection.
+    async fn write_line(&mut self, line: &str) -> SeedLinkResult<()> {
+        debug_assert_eq!(conformance::check_command_line(line.as_bytes()), Ok(()));
+        self.con.write_all(line.as_bytes()).await?;
+        self.con.write_all(b"\r\n").await?;
+        self.con.flush().await?;
+        self.stats.add_bytes_sent(line.len() + 2);
+
+        Ok(())
+    }
+
+    /// Low level function which reads a `FrameV4` literal from the underlying actual framed
+    /// connection.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn read_frame(&mut self) -> SeedLinkResult<FrameV4> {
+        let frame = match &mut self.con {
+            ActualFramedConnection::Tcp(FramedTcpConnection { ref mut read, .. }) => {
+                read.next().await
+            }
+            #[cfg(feature = "tls")]
+            ActualFramedConnection::Tls(FramedTlsConnection { ref mut read, .. }) => {
+                read.next().await
+            }
+            ActualFramedConnection::Mem(FramedMemConnection { ref mut read, .. }) => {
+                read.next().await
+            }
+        }
+        .ok_or_else(|| disconnected(self.last_message()))??;
+        self.last_frame = Some(tokio_time::Instant::now());
+        self.stats.set_bytes_received(self.con.bytes_received());
+
+        match frame {
+            FrameV4::Lines(ref lines) => self.last_message = lines.last().cloned(),
+            FrameV4::Error(ref err) => self.last_message = Some(err.to_string()),
+            FrameV4::Packet(ref packet) => {
+                let len_payload = packet.payload_raw().len();
+                self.stats.add_packet(
+                    matches!(
+                        packet.format(),
+                        DataFormatV4::JsonSeedLinkInfo | DataFormatV4::JsonSeedLinkError
+                    ),
+                    packet.raw().len() - len_payload,
+                    len_payload,
+                )
+            }
+            _ => {}
+        }
+
+        Ok(frame)
+    }
+
+    /// Reads the response to the command `cmd`, i.e. either `OK` or `ERROR`.
+    ///
+    /// Unless strict mode is enabled, unsolicited server messages (i.e. line frames) are skipped
+    /// with a warning.
+    pub(crate) async fn read_response(
+        &mut self,
+        cmd: &CommandV4,
+    ) -> SeedLinkResult<Result<(), ProtocolErrorV4>> {
+        loop {
+            match self.read_frame().await? {
+                FrameV4::Ok => return Ok(Ok(())),
+                FrameV4::Error(err) => return Ok(Err(err)),
+                FrameV4::Lines(lines) if !self.strict => {
+                    for line in lines {
+                        warn!(target: trace::NEGOTIATE, "unsolicited server message: '{}'", line);
+                    }
+                }
+                frame => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "response: invalid response to command ({}): {:?}",
+                            cmd, frame
+                        ),
+                    )
+                    .into())
+                }
+            }
+        }
+    }
+
+    /// Reads a response line frame from the underlying actual framed connection.
+    async fn read_line_frame(&mut self) -> SeedLinkResult<String> {
+        match self.read_frame().await? {
+            FrameV4::Lines(mut lines) if lines.len() == 1 => Ok(lines.remove(0)),
+            frame => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("response: invalid response: {:?}", frame),
+            )
+            .into()),
+        }
+    }
+
+    #[instrument(target = "slink::negotiate", skip(self))]
+    async fn try_send_info(&mut self, cmd: InfoCmdV4) -> SeedLinkResult<()> {
+        if self.expect_info_resp {
+            return Err(SeedLinkError::ClientError(
+                "multiple concurrent info requests are not allowed".to_string(),
+            ));
+        }
+
+        self.write_cmd(&CommandV4::Info(cmd)).await
+    }
+}
+
+/// Converts the `INFO` error packet `packet` into an error.
+fn parse_info_error(packet: &SeedLinkPacketV4) -> SeedLinkError {
+    let payload = match packet.payload_to_string() {
+        Ok(payload) => payload,
+        Err(e) => return e,
+    };
+
+    let value: serde_json::Value = match serde_json::from_str(&payload) {
+        Ok(value) => value,
+        Err(e) => {
+            return io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid INFO error response: {}", e),
+            )
+            .into()
+        }
+    };
+
+    let mut err = value["error"]["code"]
+        .as_str()
+        .and_then(|code| code.parse().ok())
+        .map(ProtocolErrorV4::new)
+        .unwrap_or_else(ProtocolErrorV4::generic);
+    if let Some(message) = value["error"]["message"].as_str() {
+        err.message = Some(message.to_string().into());
+    }
+    err.info = true;
+
+    err.into()
+}
+
+/// Parses the `INFO <item>` response `info`.
+fn parse_info<T: DeserializeOwned>(info: &str, item: &str) -> SeedLinkResult<T> {
+    serde_json::from_str(info).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid INFO {} response: {}", item, e),
+        )
+        .into()
+    })
+}
+
+/// Parses the formats from the `INFO FORMATS` response `formats_info`.
+fn parse_formats(formats_info: &str) -> SeedLinkResult<Vec<Format>> {
+    let value: serde_json::Value = serde_json::from_str(formats_info).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid INFO FORMATS response: {}", e),
+        )
+    })?;
+
+    // XXX(damb): servers omit the dictionary of formats if empty
+    let mut rv: Vec<Format> = value["format"]
+        .as_object()
+        .map(|formats| {
+            formats
+                .keys()
+                .filter_map(|code| match code.as_str() {
+                    "2" => Some(Format::MiniSeed2),
+                    "3" => Some(Format::MiniSeed3),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    rv.sort_by_key(|format| format.to_string());
+
+    Ok(rv)
+}
+
+/// Enumeration of the possible SeedLink v4 data transfer modes.
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+pub enum SeedLinkDataTransferModeV4 {
+    /// Real-time mode.
+    RealTime,
+    /// The connection will be closed once all buffered data was transferred.
+    DialUp,
+    /// Request data in *time window* mode. I.e. data will be requested until the given *end time*.
+    TimeWindow(PrimitiveDateTime),
+}
+
+/// Represents an established connection to a SeedLink server.
+///
+/// Implements SeedLink protocol version 4.0. Note that at the time being only *multi-station*
+/// mode is implemented.
+#[derive(Debug)]
+pub(crate) struct SeedLinkConnectionV4 {
+    con: FramedConnectionV4,
+}
+
+impl SeedLinkConnectionV4 {
+    pub(crate) fn new(con: ActualConnection, capacities: BufferCapacities) -> Self {
+        let con = FramedConnectionV4::new(con, capacities);
+        Self { con }
+    }
+
+    /// Returns a reference to the underlying framed connection.
+    pub fn get_framed_connection(&self) -> &FramedConnectionV4 {
+        &self.con
+    }
+
+    /// Returns a mutable reference to the underlying framed connection.
+    pub fn get_framed_connection_mut(&mut self) -> &mut FramedConnectionV4 {
+        &mut self.con
+    }
+
+    /// Returns whether the connection is open.
+    pub fn is_open(&self) -> bool {
+        self.con.is_open()
+    }
+
+    /// Sends the `HELLO` command to the SeedLink server and returns the raw response.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn say_hello_raw(&mut self) -> SeedLinkResult<(String, String)> {
+        self.con.say_hello().await
+    }
+
+    /// Selects the protocol version `version` and identifies the client by means of `user_agent`
+    /// (see [`FramedConnectionV4::send_user_agent`]).
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn negotiate(
+        &mut self,
+        version: &SlProtoCmdV4,
+        user_agent: &[UserAgentCmdInfoV4],
+    ) -> SeedLinkResult<()> {
+        self.con.negotiate_protocol_version(version).await?;
+        self.con.send_user_agent(user_agent).await
+    }
+
+    /// Identifies the client by means of `user_agent` (see
+    /// [`FramedConnectionV4::send_user_agent`]).
+    pub async fn send_user_agent(
+        &mut self,
+        user_agent: &[UserAgentCmdInfoV4],
+    ) -> SeedLinkResult<()> {
+        self.con.send_user_agent(user_agent).await
+    }
+
+    /// Authenticates using the authentication method `method` (see
+    /// [`FramedConnectionV4::authenticate`]).
+    pub async fn authenticate(&mut self, method: AuthCmdMethodV4) -> SeedLinkResult<()> {
+        self.con.authenticate(method).await
+    }
+
+    /// Performs a connection shutdown.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn shutdown(&mut self) -> SeedLinkResult<()> {
+        self.con.shutdown().await
+    }
+
+    /// Requests the raw id information JSON from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_id_info_raw(&mut self) -> SeedLinkResult<String> {
+        self.con
+            .request_info(InfoCmdV4::new(InfoCmdItemV4::Id))
+            .await
+    }
+
+    /// Requests the raw station information JSON from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_station_info_raw(&mut self) -> SeedLinkResult<String> {
+        self.con
+            .request_info(InfoCmdV4::new(InfoCmdItemV4::Stations))
+            .await
+    }
+
+    /// Requests the raw stream information JSON from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_stream_info_raw(&mut self) -> SeedLinkResult<String> {
+        self.con
+            .request_info(InfoCmdV4::new(InfoCmdItemV4::Streams))
+            .await
+    }
+
+    /// Requests the raw connection information JSON from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_connection_info_raw(&mut self) -> SeedLinkResult<String> {
+        self.con
+            .request_info(InfoCmdV4::new(InfoCmdItemV4::Connections))
+            .await
+    }
+
+    /// Requests the raw capability information JSON from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_capability_info_raw(&mut self) -> SeedLinkResult<String> {
+        self.con
+            .request_info(InfoCmdV4::new(InfoCmdItemV4::Capabilities))
+            .await
+    }
+
+    /// Requests the raw format information JSON from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_formats_info_raw(&mut self) -> SeedLinkResult<String> {
+        self.con
+            .request_info(InfoCmdV4::new(InfoCmdItemV4::Formats))
+            .await
+    }
+
+    /// Requests the id information from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_id_info(&mut self) -> SeedLinkResult<IdInfoV4> {
+        let info = self.request_id_info_raw().await?;
+        parse_info(&info, "ID")
+    }
+
+    /// Requests the format information from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_formats_info(&mut self) -> SeedLinkResult<FormatsInfoV4> {
+        let info = self.request_formats_info_raw().await?;
+        parse_info(&info, "FORMATS")
+    }
+
+    /// Requests the capability information from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_capabilities_info(&mut self) -> SeedLinkResult<CapabilitiesInfoV4> {
+        let info = self.request_capability_info_raw().await?;
+        parse_info(&info, "CAPABILITIES")
+    }
+
+    /// Decodes the `INFO` response packet `packet` into the JSON payload. `INFO` error packets are
+    /// converted into an error.
+    pub(crate) fn decode_info_packet(packet: &SeedLinkPacketV4) -> SeedLinkResult<String> {
+        match packet.format() {
+            DataFormatV4::JsonSeedLinkError => Err(parse_info_error(packet)),
+            _ => packet.payload_to_string(),
+        }
+    }
+
+    /// Parses the stations from the `INFO STATIONS` or `INFO STREAMS` response `resp_json`.
+    ///
+    /// In contrast to [`SeedLinkConnectionV4::request_stations`] the stations are filtered by the
+    /// client by means of `station_pattern` and `stream_pattern`.
+    pub(crate) fn parse_stations(
+        resp_json: String,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+        level: InventoryLevel,
+    ) -> Stations {
+        let station_pattern = station_pattern.map(|pat| pat.to_string());
+        let stream_pattern = stream_pattern.map(|pat| pat.to_string());
+        Stations::new(StationsJson::new(resp_json).filter_map(move |res| {
+            match res {
+                Ok(station) => Station::from(station)
+                    .filter(station_pattern.as_deref(), stream_pattern.as_deref())
+                    .map(|mut station| {
+                        if level == InventoryLevel::Station {
+                            station.strip_streams();
+                        }
+                        Ok(station)
+                    }),
+                Err(e) => Some(Err(e)),
+            }
+        }))
+    }
+
+    /// Requests the station information from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_stations_info(&mut self) -> SeedLinkResult<StationsInfoV4> {
+        let info = self.request_station_info_raw().await?;
+        parse_info(&info, "STATIONS")
+    }
+
+    /// Requests the stream information from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_streams_info(&mut self) -> SeedLinkResult<StreamsInfoV4> {
+        let info = self.request_stream_info_raw().await?;
+        parse_info(&info, "STREAMS")
+    }
+
+    /// Requests the connection information from the SeedLink server.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_connections_info(&mut self) -> SeedLinkResult<ConnectionsInfoV4> {
+        let info = self.request_connection_info_raw().await?;
+        parse_info(&info, "CONNECTIONS")
+    }
+
+    /// Requests the formats supported by the SeedLink server. Formats unknown to the library are
+    /// ignored.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_formats(&mut self) -> SeedLinkResult<Vec<Format>> {
+        let formats_info = self.request_formats_info_raw().await?;
+        parse_formats(&formats_info)
+    }
+
+    /// Requests the inventory from the SeedLink server.
+    ///
+    /// The inventory is filtered by the server by means of `station_pattern` (i.e. `NET_STA`)
+    /// and `stream_pattern` (i.e. `LOC_B_S_SS`).
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_inventory(
+        &mut self,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+        level: InventoryLevel,
+    ) -> SeedLinkResult<Inventory> {
+        self.request_stations(station_pattern, stream_pattern, level)
+            .await?
+            .collect()
+    }
+
+    /// Requests the inventory from the SeedLink server, returning an iterator parsing the
+    /// stations lazily.
+    ///
+    /// See also [`SeedLinkConnectionV4::request_inventory`].
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn request_stations(
+        &mut self,
+        station_pattern: Option<&str>,
+        stream_pattern: Option<&str>,
+        level: InventoryLevel,
+    ) -> SeedLinkResult<Stations> {
+        let item = match level {
+            InventoryLevel::Station => InfoCmdItemV4::Stations,
+            InventoryLevel::Stream => InfoCmdItemV4::Streams,
+        };
+        let mut cmd = InfoCmdV4::new(item);
+        // XXX(damb): a stream pattern requires a station pattern to be specified
+        cmd.station_pattern = match (station_pattern, stream_pattern) {
+            (None, Some(_)) => Some("*".to_string()),
+            (station_pattern, _) => station_pattern.map(|pat| pat.to_string()),
+        };
+        cmd.stream_pattern = stream_pattern.map(|pat| pat.to_string());
+
+        let resp_json = self.con.request_info(cmd).await?;
+        Ok(Stations::new(
+            StationsJson::new(resp_json).map(|res| res.map(Station::from)),
+        ))
+    }
+
+    /// Configures the connection and completes handshaking.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub async fn configure(
+        &mut self,
+        stream_configs: &[StreamConfig],
+        data_transfer_mode: &SeedLinkDataTransferModeV4,
+        pipelining: bool,
+    ) -> SeedLinkResult<()> {
+        self.con
+            .configure(stream_configs, data_transfer_mode, pipelining)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_formats_info() {
+        let formats_info = r#"{"software":"SeedLink v4.0","organization":"GEOFON","format":{"2":{"mimetype":"application/vnd.fdsn.mseed","subformat":{"D":"data/generic"}},"3":{"mimetype":"application/vnd.fdsn.mseed3","subformat":{"D":"data/generic"}}}}"#;
+
+        let info: FormatsInfoV4 = parse_info(formats_info, "FORMATS").unwrap();
+        assert_eq!(info.id.organization, "GEOFON");
+        assert!(info.filter.is_empty());
+        assert_eq!(info.format["3"].mimetype, "application/vnd.fdsn.mseed3");
+        assert_eq!(
+            parse_formats(formats_info).unwrap(),
+            vec![Format::MiniSeed2, Format::MiniSeed3]
+        );
+
+        assert!(parse_info::<FormatsInfoV4>("{}", "FORMATS").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use tracing::{debug, instrument};
+
+use super::{FramedConnectionV4, SeedLinkDataTransferModeV4};
+
+use crate::trace;
+use crate::{
+    CommandV4, DataCmdV4, ProtocolErrorV4, SeedLinkError, SeedLinkResult, SelectCmdV4, SelectorV3,
+    SequenceNumberV4, StationCmdV4, StreamConfig,
+};
+
+pub(crate) struct Negotiator<'a> {
+    pub stream_config: &'a StreamConfig,
+}
+
+impl<'a> Negotiator<'a> {
+    /// Configures the remote peer SeedLink server with `stream_config`.
+    ///
+    /// If `pipelining` is enabled, the commands are sent without awaiting the individual
+    /// responses. Responses to commands following a rejected `STATION` command are ignored.
+    #[instrument(target = "slink::negotiate", skip(self))]
+    pub(crate) async fn negotiate(
+        &self,
+        connection: &mut FramedConnectionV4,
+        data_transfer_mode: &SeedLinkDataTransferModeV4,
+        pipelining: bool,
+    ) -> SeedLinkResult<bool> {
+        let station_cmd = CommandV4::Station(StationCmdV4 {
+            station_pattern: format!(
+                "{}_{}",
+                self.stream_config.network, self.stream_config.station
+            ),
+        });
+        let select_cmds = self.select_cmds()?;
+        let data_cmd = self.data_cmd(data_transfer_mode)?;
+
+        if pipelining {
+            connection.write_cmd(&station_cmd).await?;
+            for cmd in &select_cmds {
+                connection.write_cmd(cmd).await?;
+            }
+            connection.write_cmd(&data_cmd).await?;
+
+            let accepted =
+                self.handle_station_response(connection.read_response(&station_cmd).await?);
+            for cmd in &select_cmds {
+                let resp = connection.read_response(cmd).await?;
+                if accepted {
+                    self.handle_select_response(cmd, resp);
+                }
+            }
+            let resp = connection.read_response(&data_cmd).await?;
+            if accepted {
+                self.handle_data_response(&data_cmd, resp)?;
+            }
+
+            return Ok(accepted);
+        }
+
+        connection.write_cmd(&station_cmd).await?;
+        if !self.handle_station_response(connection.read_response(&station_cmd).await?) {
+            return Ok(false);
+        }
+
+        for cmd in &select_cmds {
+            connection.write_cmd(cmd).await?;
+            let resp = connection.read_response(cmd).await?;
+            self.handle_select_response(cmd, resp);
+        }
+
+        connection.write_cmd(&data_cmd).await?;
+        let resp = connection.read_response(&data_cmd).await?;
+        self.handle_data_response(&data_cmd, resp)?;
+
+        Ok(true)
+    }
+
+    fn select_cmds(&self) -> SeedLinkResult<Vec<CommandV4>> {
+        self.stream_config
+            .iter()
+            .map(|select_arg| {
+                // translate v3 style selectors (e.g. `00BHZ.D`), `v4` stream identifiers are
+                // separated by `_`
+                if !select_arg.contains('_') {
+                    if let Ok(selector) = select_arg.parse::<SelectorV3>() {
+                        return Ok(CommandV4::Select(SelectCmdV4 {
+                            patterns: vec![selector.to_select_pattern()],
+                        }));
+                    }
+                }
+
+                select_arg
+                    .parse::<SelectCmdV4>()
+                    .map(CommandV4::Select)
+                    .map_err(|_| {
+                        SeedLinkError::InvalidCommandArgument(format!(
+                            "invalid select argument: {}",
+                            select_arg
+                        ))
+                    })
+            })
+            .collect()
+    }
+
+    fn data_cmd(
+        &self,
+        data_transfer_mode: &SeedLinkDataTransferModeV4,
+    ) -> SeedLinkResult<CommandV4> {
+        let seq_num = match &self.stream_config.seq_num {
+            Some(seq_num_str) => Some(SequenceNumberV4::Number(
+                u64::from_str_radix(seq_num_str, 16)
+                    .map_err(|e| SeedLinkError::ClientError(e.to_string()))?,
+            )),
+            None => None,
+        };
+        let start_time = self.stream_config.time.map(|t| t.assume_utc());
+
+        // station specific time windows take precedence
+        let end_time = match (data_transfer_mode, self.stream_config.end_time) {
+            (_, Some(end_time)) => Some(end_time),
+            (SeedLinkDataTransferModeV4::TimeWindow(end_time), None) => Some(*end_time),
+            _ => None,
+        };
+
+        let cmd = match end_time {
+            None => {
+                // XXX(damb): the start time requires a sequence number to be specified
+                let seq_num = match (seq_num, start_time) {
+                    (None, Some(_)) => Some(SequenceNumberV4::All),
+                    (seq_num, _) => seq_num,
+                };
+                DataCmdV4::new(seq_num, start_time, None)
+            }
+            Some(end_time) => {
+                if start_time.is_none() {
+                    return Err(SeedLinkError::InvalidClientConfig(format!(
+                        "missing start time of time window (station: {}_{})",
+                        self.stream_config.network, self.stream_config.station
+                    )));
+                }
+
+                DataCmdV4::new(
+                    Some(seq_num.unwrap_or(SequenceNumberV4::All)),
+                    start_time,
+                    Some(end_time.assume_utc()),
+                )
+            }
+        };
+
+        Ok(CommandV4::Data(cmd))
+    }
+
+    fn handle_station_response(&self, resp: Result<(), ProtocolErrorV4>) -> bool {
+        match resp {
+            Ok(()) => {
+                debug!(target: trace::NEGOTIATE,
+                    "response: station ({}_{}) is OK (station selected)",
+                    self.stream_config.network, self.stream_config.station
+                );
+                true
+            }
+            Err(err) => {
+                debug!(target: trace::NEGOTIATE,
+                    "response: station ({}_{}) is ERROR (station omitted): {}",
+                    self.stream_config.network, self.stream_config.station, err
+                );
+                false
+            }
+        }
+    }
+
+    fn handle_select_response(&self, cmd: &CommandV4, resp: Result<(), ProtocolErrorV4>) {
+        match resp {
+            Ok(()) => {
+                debug!(target: trace::NEGOTIATE, "response: select arg ({}) is OK (selected)", cmd)
+            }
+            Err(err) => debug!(target: trace::NEGOTIATE,
+                "response: select arg ({}) is ERROR (select arg omitted): {}",
+                cmd, err
+            ),
+        }
+    }
+
+    fn handle_data_response(
+        &self,
+        cmd: &CommandV4,
+        resp: Result<(), ProtocolErrorV4>,
+    ) -> SeedLinkResult<()> {
+        match resp {
+            Ok(()) => {
+                debug!(target: trace::NEGOTIATE, "response: action command successful");
+                Ok(())
+            }
+            Err(err) => {
+                debug!(target: trace::NEGOTIATE,
+                    "response: action command ({}) not accepted: {}", cmd, err
+                );
+                Err(SeedLinkError::HandshakeRejected {
+                    station: format!(
+                        "{}_{}",
+                        self.stream_config.network, self.stream_config.station
+                    ),
+                    reason: Some(err.to_string()),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::io;
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::Decoder;
+use tracing::{debug, trace};
+
+use crate::metrics;
+use crate::trace;
+use crate::wire::{self, v4::SIGNATURE};
+use crate::{
+    verify_packet_v4, FrameV4, ProtocolErrorV4, SeedLinkError, SeedLinkPacketV4,
+    PACKET_SIGNATURE_SIZE_V4,
+};
+
+/// Maximum length of a response line (excluding the `<CR><LF>` terminator).
+const MAX_RESPONSE_LINE_LENGTH: usize = 8 * 1024;
+
+const OK_SIGNATURE: &[u8] = b"OK";
+const END_SIGNATURE: &[u8] = b"END";
+const ERROR_SIGNATURE: &[u8] = b"ERROR";
+
+/// Decodes SeedLink `v4` response lines and packets.
+///
+/// Note that response lines are terminated with `<CR><LF>` while packets are identified by means
+/// of the `SE` packet signature. If a signing key is set, packets are expected to be followed by
+/// their signature (see [`PACKET_SIGNATURE_CAPABILITY_V4`](crate::PACKET_SIGNATURE_CAPABILITY_V4)).
+#[derive(Debug, Default)]
+pub struct SeedLinkCodec {
+    /// Total number of bytes consumed.
+    bytes_decoded: u64,
+    /// Key packet signatures are verified with.
+    signing_key: Option<Vec<u8>>,
+}
+
+impl SeedLinkCodec {
+    /// Creates a new `SeedLinkCodec` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the total number of bytes consumed.
+    pub fn bytes_decoded(&self) -> u64 {
+        self.bytes_decoded
+    }
+
+    /// Sets the key packet signatures are verified with. If `None`, packets are expected to be
+    /// unsigned.
+    pub fn set_signing_key(&mut self, key: Option<Vec<u8>>) {
+        self.signing_key = key;
+    }
+
+    fn decode_packet(&mut self, src: &mut BytesMut) -> Result<Option<FrameV4>, SeedLinkError> {
+        let len_packet = match wire::v4::parse_header(src) {
+            Ok(header) => header.len_packet(),
+            Err(wire::Error::Incomplete(needed)) => {
+                src.reserve(needed - src.len());
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let len_frame = match self.signing_key {
+            Some(_) => len_packet + PACKET_SIGNATURE_SIZE_V4,
+            None => len_packet,
+        };
+        if src.len() < len_frame {
+            src.reserve(len_frame - src.len());
+            return Ok(None);
+        }
+
+        let buf = src.split_to(len_frame).freeze();
+        let buf = match self.signing_key {
+            Some(ref key) => {
+                verify_packet_v4(&buf, key)?;
+                buf.slice(..len_packet)
+            }
+            None => buf,
+        };
+        Ok(Some(FrameV4::Packet(SeedLinkPacketV4::parse_bytes(buf)?)))
+    }
+
+    fn decode_line(&mut self, src: &mut BytesMut) -> Result<Option<FrameV4>, SeedLinkError> {
+        let newline_offset = match src.iter().position(|b| *b == b'\n') {
+            Some(offset) => offset,
+            None if src.len() > MAX_RESPONSE_LINE_LENGTH => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "maximum response line length exceeded",
+                )
+                .into());
+            }
+            None => return Ok(None),
+        };
+
+        let line = src.split_to(newline_offset + 1);
+        let line = &line[..newline_offset];
+        // remove <CR> (i.e. b"\r")
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        if line == OK_SIGNATURE {
+            return Ok(Some(FrameV4::Ok));
+        }
+
+        if line == END_SIGNATURE {
+            return Ok(Some(FrameV4::End));
+        }
+
+        let line = String::from_utf8(line.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        if line.as_bytes().starts_with(ERROR_SIGNATURE) {
+            let err = line.parse::<ProtocolErrorV4>().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid error response: {}", line),
+                )
+            })?;
+            return Ok(Some(FrameV4::Error(err)));
+        }
+
+        Ok(Some(FrameV4::Lines(vec![line])))
+    }
+
+    fn decode_frame(&mut self, src: &mut BytesMut) -> Result<Option<FrameV4>, SeedLinkError> {
+        if src.remaining() < SIGNATURE.len() && !src.contains(&b'\n') {
+            return Ok(None);
+        }
+
+        // XXX(damb): response lines never start with the (upper case) packet signature
+        if src.starts_with(SIGNATURE) {
+            return self.decode_packet(src);
+        }
+
+        self.decode_line(src)
+    }
+}
+
+impl Decoder for SeedLinkCodec {
+    type Item = FrameV4;
+    type Error = SeedLinkError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let len = src.len();
+        let res = self.decode_frame(src);
+        let decoded = len - src.len();
+        self.bytes_decoded += decoded as u64;
+        match res {
+            Ok(Some(_)) => trace!(target: trace::CODEC, "decoded frame ({} bytes)", decoded),
+            Err(ref e) => {
+                debug!(target: trace::CODEC, "failed to decode frame: {}", e);
+                metrics::decode_error();
+            }
+            Ok(None) => {}
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::{pack_info_ok_v4, sign_packet_v4};
+
+    #[test]
+    fn decode_lines_and_packets() {
+        let packet = pack_info_ok_v4(r#"{"software":"foo"}"#).unwrap();
+
+        let mut buf = BytesMut::from("SeedLink v4.0 :: SLPROTO:4.0\r\nOK\r\n");
+        buf.extend_from_slice(&packet[..packet.len() - 1]);
+
+        let mut codec = SeedLinkCodec::new();
+        assert!(matches!(
+            codec.decode(&mut buf).unwrap(),
+            Some(FrameV4::Lines(lines)) if lines == vec!["SeedLink v4.0 :: SLPROTO:4.0".to_string()]
+        ));
+        assert!(matches!(codec.decode(&mut buf).unwrap(), Some(FrameV4::Ok)));
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+
+        buf.extend_from_slice(&packet[packet.len() - 1..]);
+        buf.extend_from_slice(b"ERROR UNEXPECTED\r\nEND\r\n");
+        assert!(matches!(
+            codec.decode(&mut buf).unwrap(),
+            Some(FrameV4::Packet(packet)) if packet.payload_to_string().unwrap() == r#"{"software":"foo"}"#
+        ));
+        assert!(matches!(
+            codec.decode(&mut buf).unwrap(),
+            Some(FrameV4::Error(err)) if err == ProtocolErrorV4 { message: None, ..ProtocolErrorV4::unexpected_command() }
+        ));
+        assert!(matches!(
+            codec.decode(&mut buf).unwrap(),
+            Some(FrameV4::End)
+        ));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn decode_signed_packets() {
+        const KEY: &[u8] = b"secret";
+
+        let packet = pack_info_ok_v4(r#"{"software":"foo"}"#).unwrap();
+        let signed = sign_packet_v4(&packet, KEY).unwrap();
+
+        let mut codec = SeedLinkCodec::new();
+        codec.set_signing_key(Some(KEY.to_vec()));
+
+        let mut buf = BytesMut::from(&signed[..signed.len() - 1]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(&signed[signed.len() - 1..]);
+        buf.extend_from_slice(b"OK\r\n");
+        assert!(matches!(
+            codec.decode(&mut buf).unwrap(),
+            Some(FrameV4::Packet(packet)) if packet.payload_to_string().unwrap() == r#"{"software":"foo"}"#
+        ));
+        assert!(matches!(codec.decode(&mut buf).unwrap(), Some(FrameV4::Ok)));
+        assert!(buf.is_empty());
+
+        let mut buf = BytesMut::from(&signed[..]);
+        let idx = packet.len() - 1;
+        buf[idx] ^= 0xff;
+        assert!(codec.decode(&mut buf).is_err());
+
+        let mut buf = BytesMut::from(&packet[..]);
+        buf.extend_from_slice(&[0; PACKET_SIGNATURE_SIZE_V4]);
+        assert!(codec.decode(&mut buf).is_err());
+    }
+}
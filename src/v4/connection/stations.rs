@@ -0,0 +1,165 @@
+use std::io;
+
+use serde::de::{Deserialize, IgnoredAny};
+
+use crate::{SeedLinkResult, StationV4};
+
+const STATION_KEY: &str = "station";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum State {
+    Start,
+    Array,
+    Done,
+}
+
+/// Iterator lazily parsing the stations of an `INFO STATIONS` or `INFO STREAMS` JSON response.
+#[derive(Debug)]
+pub(crate) struct StationsJson {
+    json: String,
+    pos: usize,
+    state: State,
+}
+
+impl StationsJson {
+    pub(crate) fn new(json: String) -> Self {
+        Self {
+            json,
+            pos: 0,
+            state: State::Start,
+        }
+    }
+
+    fn next_station(&mut self) -> Result<Option<StationV4>, String> {
+        match self.state {
+            State::Start => {
+                self.seek_station_array()?;
+                self.state = State::Array;
+                if self.peek() == Some(b']') {
+                    self.state = State::Done;
+                    return Ok(None);
+                }
+            }
+            State::Array => match self.next_byte() {
+                Some(b',') => {}
+                Some(b']') => {
+                    self.state = State::Done;
+                    return Ok(None);
+                }
+                _ => return Err("expected `,` or `]`".to_string()),
+            },
+            State::Done => return Ok(None),
+        }
+
+        Self::next_value::<StationV4>(&self.json, &mut self.pos).map(Some)
+    }
+
+    /// Advances to the first element of the station array.
+    fn seek_station_array(&mut self) -> Result<(), String> {
+        self.expect(b'{')?;
+        if self.peek() != Some(b'}') {
+            loop {
+                let key: String = Self::next_value(&self.json, &mut self.pos)?;
+                self.expect(b':')?;
+                if key == STATION_KEY {
+                    return self.expect(b'[');
+                }
+
+                Self::next_value::<IgnoredAny>(&self.json, &mut self.pos)?;
+                match self.next_byte() {
+                    Some(b',') => {}
+                    Some(b'}') => break,
+                    _ => return Err("expected `,` or `}`".to_string()),
+                }
+            }
+        }
+
+        Err(format!("missing field `{}`", STATION_KEY))
+    }
+
+    fn next_value<'a, T: Deserialize<'a>>(json: &'a str, pos: &mut usize) -> Result<T, String> {
+        let mut it = serde_json::Deserializer::from_str(&json[*pos..]).into_iter::<T>();
+        match it.next() {
+            Some(Ok(value)) => {
+                *pos += it.byte_offset();
+                Ok(value)
+            }
+            Some(Err(e)) => Err(e.to_string()),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        match self.next_byte() {
+            Some(b) if b == c => Ok(()),
+            _ => Err(format!("expected `{}`", c as char)),
+        }
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let b = self.peek();
+        if b.is_some() {
+            self.pos += 1;
+        }
+        b
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        while matches!(
+            self.json.as_bytes().get(self.pos),
+            Some(b' ' | b'\n' | b'\r' | b'\t')
+        ) {
+            self.pos += 1;
+        }
+
+        self.json.as_bytes().get(self.pos).copied()
+    }
+}
+
+impl Iterator for StationsJson {
+    type Item = SeedLinkResult<StationV4>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_station() {
+            Ok(station) => station.map(Ok),
+            Err(e) => {
+                // stop parsing
+                self.state = State::Done;
+                Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid response to INFO command: {}", e),
+                )
+                .into()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_stations_lazily() {
+        let json = r#"{
+            "software": "SeedLink v4.0 (2023.1) :: station",
+            "station": [
+                {"id": "GE_WLF", "description": "GEOFON Station Walferdange", "start_seq": 0, "end_seq": 42},
+                {"id": "GE_APE", "description": "GEOFON Station Apirathos", "start_seq": 0, "end_seq": 42}
+            ]
+        }"#;
+
+        let mut stations = StationsJson::new(json.to_string());
+        assert_eq!(stations.next().unwrap().unwrap().id().to_string(), "GE_WLF");
+        assert_eq!(stations.next().unwrap().unwrap().id().to_string(), "GE_APE");
+        assert!(stations.next().is_none());
+
+        let mut stations = StationsJson::new(r#"{"station": []}"#.to_string());
+        assert!(stations.next().is_none());
+
+        let mut stations = StationsJson::new(r#"{"software": "foo"}"#.to_string());
+        assert!(stations.next().unwrap().is_err());
+        assert!(stations.next().is_none());
+    }
+}
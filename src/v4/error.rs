@@ -1,7 +1,8 @@
 use std::borrow;
 use std::fmt;
+use std::str;
 
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// SeedLink `v4` protocol error codes.
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -54,6 +55,26 @@ impl ErrorCode {
     }
 }
 
+impl str::FromStr for ErrorCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "GENERIC" => Self::Generic,
+            "UNSUPPORTED" => Self::UnsupportedCommand,
+            "UNEXPECTED" => Self::UnexpectedCommand,
+            "UNAUTHORIZED" => Self::UnauthorizedCommand,
+            "LIMIT" => Self::LimitExceeded,
+            "ARGUMENTS" => Self::IncorrectArguments,
+            "AUTH" => Self::AuthenticationFailed,
+            "INTERNAL" => Self::Internal,
+            _ => {
+                return Err(Error::generic());
+            }
+        })
+    }
+}
+
 impl Serialize for ErrorCode {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -63,6 +84,19 @@ impl Serialize for ErrorCode {
     }
 }
 
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+        let s: String = Deserialize::deserialize(deserializer)?;
+
+        s.parse()
+            .map_err(|_| D::Error::custom(format!("invalid error code: {}", s)))
+    }
+}
+
 impl fmt::Display for ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.code())
@@ -70,14 +104,14 @@ impl fmt::Display for ErrorCode {
 }
 
 /// SeedLink `v4` protocol error.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct Error {
     pub code: ErrorCode,
     /// A short description of the error.
     #[serde(default = "default_message")]
     pub message: Option<borrow::Cow<'static, str>>,
     ///Flag indicating whether the error is related to a info request
-    #[serde(skip_serializing)]
+    #[serde(skip)]
     pub info: bool,
 }
 
@@ -143,8 +177,53 @@ impl fmt::Display for Error {
     }
 }
 
+impl str::FromStr for Error {
+    type Err = Error;
+
+    /// Parses an error response line, e.g. `ERROR ARGUMENTS: Incorrect command arguments`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s
+            .strip_prefix("ERROR")
+            .ok_or_else(Error::generic)?
+            .trim_start();
+        let (code, message) = match s.find([' ', ':']) {
+            Some(idx) => (&s[..idx], s[idx..].trim_start_matches([' ', ':'])),
+            None => (s, ""),
+        };
+
+        Ok(Self {
+            code: code.parse()?,
+            message: if message.is_empty() {
+                None
+            } else {
+                Some(borrow::Cow::Owned(message.to_string()))
+            },
+            info: false,
+        })
+    }
+}
+
 impl std::error::Error for Error {}
 
 fn default_message() -> Option<borrow::Cow<'static, str>> {
     Some(borrow::Cow::Borrowed("unknown"))
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let err = Error::incorrect_arguments();
+        assert_eq!(err.to_string().parse::<Error>().unwrap(), err);
+
+        let err: Error = "ERROR UNSUPPORTED".parse().unwrap();
+        assert_eq!(err.code, ErrorCode::UnsupportedCommand);
+        assert_eq!(err.message, None);
+
+        assert!("ERROR FOO bar".parse::<Error>().is_err());
+        assert!("OK".parse::<Error>().is_err());
+    }
+}
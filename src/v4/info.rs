@@ -1,12 +1,10 @@
 use std::collections::HashMap;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::ProtocolErrorV4;
 use crate::StationV4;
 
-// TODO(damb): implement `Deserialize` for client deserialization
-
 /// SeedLink v4 `INFO` response information.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Info {
@@ -22,7 +20,7 @@ pub enum Info {
 /// Dictionary of filters supported by the server
 type Filters = HashMap<String, String>;
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct Format {
     /// MIME type of format
     pub mimetype: String,
@@ -34,7 +32,7 @@ pub struct Format {
 type Formats = HashMap<String, Format>;
 
 /// SeedLink `v4` `INFO ID` response information.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct IdInfo {
     /// Software ID as in HELLO response
     pub software: String,
@@ -43,16 +41,16 @@ pub struct IdInfo {
 }
 
 /// SeedLink `v4` `INFO STATIONS` response information.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct StationsInfo {
     #[serde(flatten)]
     pub id: IdInfo,
 
     /// Dictionary of filters supported by the server
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub filter: Filters,
     /// Dictionary of formats supported by the server
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub format: Formats,
 
     pub station: Vec<StationV4>,
@@ -62,21 +60,21 @@ pub struct StationsInfo {
 pub type StreamsInfo = StationsInfo;
 
 /// SeedLink `v4` `INFO FORMATS` response information.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct FormatsInfo {
     #[serde(flatten)]
     pub id: IdInfo,
 
     /// Dictionary of filters supported by the server
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub filter: Filters,
     /// Dictionary of formats supported by the server
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub format: Formats,
 }
 
 /// SeedLink `v4` `INFO CAPABILITIES` response information.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct CapabilitiesInfo {
     #[serde(flatten)]
     pub id: IdInfo,
@@ -85,18 +83,232 @@ pub struct CapabilitiesInfo {
 }
 
 /// SeedLink `v4` `INFO CONNECTIONS` response information.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct ConnectionsInfo {
     #[serde(flatten)]
     pub id: IdInfo,
-    // TODO(damb):
+    // TODO(damb): stations
+    /// Byte accounting of the clients connected.
+    ///
+    /// Note that this is a non-standard extension.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub client: Vec<ClientTrafficInfo>,
+}
+
+/// Byte accounting of a client connection (see [`ConnectionsInfo`]).
+///
+/// Note that this is a non-standard extension.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ClientTrafficInfo {
+    /// Server-side client identifier
+    pub id: u64,
+    /// Number of bytes received from the client
+    pub bytes_received: u64,
+    /// Number of bytes sent to the client
+    pub bytes_sent: u64,
+    /// Number of packet header bytes sent to the client
+    pub header_bytes_sent: u64,
+    /// Number of packet payload bytes sent to the client
+    pub payload_bytes_sent: u64,
 }
 
 /// SeedLink `v4` `INFO` error response information.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct ErrorInfo {
     #[serde(flatten)]
     pub id: IdInfo,
 
     pub error: ProtocolErrorV4,
+
+    /// Correlation identifier of the failed request.
+    ///
+    /// Note that this is a non-standard extension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Builder of SeedLink `v4` `INFO` response information.
+#[derive(Debug, Clone, Copy)]
+pub struct InfoBuilder;
+
+impl InfoBuilder {
+    /// Returns a builder of `INFO STATIONS` response information.
+    pub fn stations(id: IdInfo) -> StationsInfoBuilder {
+        StationsInfoBuilder::new(id, false)
+    }
+
+    /// Returns a builder of `INFO STREAMS` response information.
+    pub fn streams(id: IdInfo) -> StationsInfoBuilder {
+        StationsInfoBuilder::new(id, true)
+    }
+}
+
+/// Builder of SeedLink `v4` `INFO STATIONS` and `INFO STREAMS` response information.
+///
+/// Stations pushed are normalized according to the `INFO` item requested, i.e. streams are
+/// omitted for `INFO STATIONS` responses while `INFO STREAMS` responses always come with a
+/// (possibly empty) list of streams.
+#[derive(Debug, Clone)]
+pub struct StationsInfoBuilder {
+    info: StationsInfo,
+    with_streams: bool,
+}
+
+impl StationsInfoBuilder {
+    fn new(id: IdInfo, with_streams: bool) -> Self {
+        Self {
+            info: StationsInfo {
+                id,
+                filter: HashMap::new(),
+                format: HashMap::new(),
+                station: Vec::new(),
+            },
+            with_streams,
+        }
+    }
+
+    /// Adds the filters `filters` supported by the server.
+    pub fn with_filters<I, K, V>(mut self, filters: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.info.filter.extend(
+            filters
+                .into_iter()
+                .map(|(name, description)| (name.into(), description.into())),
+        );
+        self
+    }
+
+    /// Adds the formats `formats` supported by the server.
+    pub fn with_formats<I, K>(mut self, formats: I) -> Self
+    where
+        I: IntoIterator<Item = (K, Format)>,
+        K: Into<String>,
+    {
+        self.info.format.extend(
+            formats
+                .into_iter()
+                .map(|(name, format)| (name.into(), format)),
+        );
+        self
+    }
+
+    /// Pushes the station `station`.
+    pub fn push_station(mut self, station: StationV4) -> Self {
+        self.push(station);
+        self
+    }
+
+    /// Pushes the stations `stations`.
+    pub fn extend_stations<I>(mut self, stations: I) -> Self
+    where
+        I: IntoIterator<Item = StationV4>,
+    {
+        for station in stations {
+            self.push(station);
+        }
+        self
+    }
+
+    fn push(&mut self, mut station: StationV4) {
+        let streams = station.streams_mut();
+        if !self.with_streams {
+            streams.take();
+        } else if streams.is_none() {
+            *streams = Some(Vec::new());
+        }
+
+        self.info.station.push(station);
+    }
+
+    /// Returns the response information built.
+    pub fn build(self) -> StationsInfo {
+        self.info
+    }
+
+    /// Returns the response information built wrapped into the corresponding [`Info`] variant.
+    pub fn build_info(self) -> Info {
+        if self.with_streams {
+            Info::Streams(self.info)
+        } else {
+            Info::Stations(self.info)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn id() -> IdInfo {
+        IdInfo {
+            software: "SeedLink v4.0 (slink v0.1.0) :: SLPROTO:4.0".to_string(),
+            organization: "foo".to_string(),
+        }
+    }
+
+    fn station() -> StationV4 {
+        serde_json::from_str(
+            r#"{"id":"GE_WLF","description":"Walferdange","start_seq":0,"end_seq":42,"stream":[]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn stations_omit_empty() {
+        let info = InfoBuilder::stations(id()).push_station(station()).build();
+
+        assert_eq!(
+            serde_json::to_string(&info).unwrap(),
+            r#"{"software":"SeedLink v4.0 (slink v0.1.0) :: SLPROTO:4.0","organization":"foo","station":[{"id":"GE_WLF","description":"Walferdange","start_seq":0,"end_seq":42}]}"#
+        );
+    }
+
+    #[test]
+    fn streams_with_filters() {
+        let mut station = station();
+        station.streams_mut().take();
+
+        let info = InfoBuilder::streams(id())
+            .with_filters([("native", "native format")])
+            .push_station(station)
+            .build_info();
+
+        let info = match info {
+            Info::Streams(info) => info,
+            _ => panic!("invalid info variant"),
+        };
+        assert_eq!(
+            serde_json::to_string(&info).unwrap(),
+            r#"{"software":"SeedLink v4.0 (slink v0.1.0) :: SLPROTO:4.0","organization":"foo","filter":{"native":"native format"},"station":[{"id":"GE_WLF","description":"Walferdange","start_seq":0,"end_seq":42,"stream":[]}]}"#
+        );
+    }
+
+    #[test]
+    fn deserialize() {
+        let info = InfoBuilder::streams(id())
+            .with_filters([("native", "native format")])
+            .push_station(station())
+            .build();
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(serde_json::from_str::<StreamsInfo>(&json).unwrap(), info);
+
+        let info: ErrorInfo = serde_json::from_str(
+            r#"{"software":"SeedLink v4.0 (slink v0.1.0) :: SLPROTO:4.0","organization":"foo","error":{"code":"ARGUMENTS","message":"invalid pattern"}}"#,
+        )
+        .unwrap();
+        assert_eq!(info.id, id());
+        assert_eq!(info.error.code, crate::ErrorCodeV4::IncorrectArguments);
+        assert_eq!(info.error.message.as_deref(), Some("invalid pattern"));
+        assert_eq!(info.request_id, None);
+
+        assert!(serde_json::from_str::<ErrorInfo>(
+            r#"{"software":"","organization":"","error":{"code":"FOO"}}"#
+        )
+        .is_err());
+    }
 }
@@ -3,6 +3,8 @@ use std::fmt;
 
 use time::OffsetDateTime;
 
+use crate::{Format, SubFormat};
+
 const SID_DELIMITER: char = '_';
 
 /// SeedLink v4 station identifier.
@@ -73,6 +75,15 @@ impl fmt::Display for StationId {
     }
 }
 
+impl From<&crate::StationId> for StationId {
+    fn from(item: &crate::StationId) -> Self {
+        Self {
+            net_code: item.net_code().to_string(),
+            sta_code: item.sta_code().to_string(),
+        }
+    }
+}
+
 /// Structure representing a SeedLink v4 station in the inventory.
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct Station {
@@ -113,6 +124,11 @@ impl Station {
         self.end_seq
     }
 
+    /// Sets the first packet sequence number available.
+    pub fn set_start_seq(&mut self, start_seq: u64) {
+        self.start_seq = start_seq;
+    }
+
     /// Returns how many seconds to wait for gaps to fill: -1 = undefined
     pub fn backfill(&self) -> &Option<i32> {
         &self.backfill
@@ -121,6 +137,24 @@ impl Station {
     pub fn streams(&self) -> &Option<Vec<Stream>> {
         &self.stream
     }
+
+    /// Returns a mutable reference to the streams.
+    pub fn streams_mut(&mut self) -> &mut Option<Vec<Stream>> {
+        &mut self.stream
+    }
+}
+
+impl From<&crate::Station> for Station {
+    fn from(item: &crate::Station) -> Self {
+        Self {
+            id: item.id().into(),
+            description: item.description().to_string(),
+            start_seq: item.start_seq(),
+            end_seq: item.end_seq(),
+            backfill: None,
+            stream: Some(item.iter().map(Stream::from).collect()),
+        }
+    }
 }
 
 /// SeedLink v4 stream identifier.
@@ -230,6 +264,17 @@ impl fmt::Display for StreamId {
     }
 }
 
+impl From<&crate::StreamId> for StreamId {
+    fn from(item: &crate::StreamId) -> Self {
+        Self {
+            loc_code: item.loc_code().to_string(),
+            band_code: item.band_code().to_string(),
+            source_code: item.source_code().to_string(),
+            subsource_code: item.subsource_code().to_string(),
+        }
+    }
+}
+
 /// Enumeration
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub enum StreamOrigin {
@@ -260,6 +305,15 @@ pub enum StreamFormat {
     MiniSeed3,
 }
 
+impl From<&Format> for StreamFormat {
+    fn from(item: &Format) -> Self {
+        match item {
+            Format::MiniSeed2 => Self::MiniSeed2,
+            Format::MiniSeed3 => Self::MiniSeed3,
+        }
+    }
+}
+
 /// Enumeration of SeedLink v4 subformat codes.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Deserialize, Serialize)]
 pub enum StreamSubFormat {
@@ -283,6 +337,19 @@ pub enum StreamSubFormat {
     Log,
 }
 
+impl From<&SubFormat> for StreamSubFormat {
+    fn from(item: &SubFormat) -> Self {
+        match item {
+            SubFormat::Data => Self::Data,
+            SubFormat::Event => Self::Event,
+            SubFormat::Calibration => Self::Calibration,
+            SubFormat::Opaque => Self::Opaque,
+            SubFormat::Timing => Self::Timing,
+            SubFormat::Log => Self::Log,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct Stream {
     /// Stream identifier
@@ -332,6 +399,29 @@ impl Stream {
     pub fn end_time(&self) -> &OffsetDateTime {
         &self.end_time
     }
+
+    /// Sets the start time of the first packet buffered.
+    pub fn set_start_time(&mut self, start_time: OffsetDateTime) {
+        self.start_time = start_time;
+    }
+
+    /// Sets the end time of the last packet buffered.
+    pub fn set_end_time(&mut self, end_time: OffsetDateTime) {
+        self.end_time = end_time;
+    }
+}
+
+impl From<&crate::Stream> for Stream {
+    fn from(item: &crate::Stream) -> Self {
+        Self {
+            id: item.id().into(),
+            format: item.format().into(),
+            subformat: item.subformat().into(),
+            origin: None,
+            start_time: *item.start_time(),
+            end_time: *item.end_time(),
+        }
+    }
 }
 
 mod seedlink_datetime {
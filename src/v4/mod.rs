@@ -1,4 +1,4 @@
-pub use auth::Auth as AuthV4;
+pub use auth::{Auth as AuthV4, CAPABILITY_REFRESH as CAPABILITY_AUTH_REFRESH_V4};
 pub use cmd::{
     Auth as AuthCmdV4, AuthMethod as AuthCmdMethodV4, Bye as ByeCmdV4, Command as CommandV4,
     Data as DataCmdV4, End as EndCmdV4, EndFetch as EndFetchCmdV4, Hello as HelloCmdV4,
@@ -7,11 +7,15 @@ pub use cmd::{
     SlProto as SlProtoCmdV4, Station as StationCmdV4, Unknown as UnknownCmdV4,
     UserAgent as UserAgentCmdV4, UserAgentInfo as UserAgentCmdInfoV4,
 };
+#[cfg(feature = "v4-client")]
+pub(crate) use connection::{SeedLinkConnectionV4, SeedLinkDataTransferModeV4};
 pub use error::{Error as ProtocolErrorV4, ErrorCode as ErrorCodeV4};
 pub use info::{
-    CapabilitiesInfo as CapabilitiesInfoV4, ConnectionsInfo as ConnectionsInfoV4,
-    ErrorInfo as ErrorInfoV4, FormatsInfo as FormatsInfoV4, IdInfo as IdInfoV4, Info as InfoV4,
-    StationsInfo as StationsInfoV4, StreamsInfo as StreamsInfoV4,
+    CapabilitiesInfo as CapabilitiesInfoV4, ClientTrafficInfo as ClientTrafficInfoV4,
+    ConnectionsInfo as ConnectionsInfoV4, ErrorInfo as ErrorInfoV4, FormatsInfo as FormatsInfoV4,
+    IdInfo as IdInfoV4, Info as InfoV4, InfoBuilder as InfoBuilderV4,
+    StationsInfo as StationsInfoV4, StationsInfoBuilder as StationsInfoBuilderV4,
+    StreamsInfo as StreamsInfoV4,
 };
 pub use inventory::{
     Station as StationV4, StationId as StationIdV4, Stream as StreamV4,
@@ -24,16 +28,27 @@ pub use packet::{
     pack_packet_with_seq_num as pack_packet_with_seq_num_v4, DataFormat as DataFormatV4,
     SeedLinkPacket as SeedLinkPacketV4,
 };
+#[cfg(any(feature = "server", feature = "v4-client"))]
+pub use sign::{
+    sign_packet as sign_packet_v4, verify_packet as verify_packet_v4,
+    CAPABILITY as PACKET_SIGNATURE_CAPABILITY_V4, SIGNATURE_SIZE as PACKET_SIGNATURE_SIZE_V4,
+};
+#[cfg(feature = "server")]
 pub use util::{
     to_first_hello_resp_line as to_first_hello_resp_line_v4, to_id_info as to_id_info_v4,
 };
 
 mod auth;
 mod cmd;
+#[cfg(feature = "v4-client")]
+mod connection;
 mod error;
 mod info;
 mod inventory;
 mod packet;
+#[cfg(any(feature = "server", feature = "v4-client"))]
+mod sign;
+#[cfg(feature = "server")]
 mod util;
 
 /// SeedLink `v4` frame enumeration.
@@ -3,8 +3,10 @@ use std::fmt;
 use std::io;
 use std::str::{self, FromStr};
 
+use bytes::Bytes;
 use mseed::{MSControlFlags, MSRecord};
 
+use crate::wire::{self, v4::FIXED_HEADER_SIZE};
 use crate::{SeedLinkError, SeedLinkResult};
 
 /// SeedLink `v4` packet data formats.
@@ -103,7 +105,7 @@ impl fmt::Display for DataFormat {
 /// SeedLink `v4` packet.
 #[derive(Debug, Clone)]
 pub struct SeedLinkPacket {
-    packet: Vec<u8>,
+    packet: Bytes,
 
     format: DataFormat,
     len_payload: u32,
@@ -115,39 +117,28 @@ pub struct SeedLinkPacket {
 impl SeedLinkPacket {
     /// Creates a new SeedLink packet.
     pub fn parse(buf: &[u8]) -> SeedLinkResult<Self> {
-        // XXX(damb): packet headers are big endian encoded where required
-        let signature = buf[..2].to_vec();
-        let signature = String::from_utf8(signature).map_err(|e| {
-            SeedLinkError::from(io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
-        })?;
-        if signature != "SE" {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("invalid packet signature: {}", signature),
-            )
-            .into());
-        }
-        let format: [u8; 2] = buf[2..4].try_into().unwrap();
-        let format = DataFormat::try_from(format)?;
-        let len_payload = u32::from_le_bytes(buf[4..8].try_into().unwrap());
-        if len_payload == 0 {
-            return Err(
-                io::Error::new(io::ErrorKind::InvalidData, "missing packet payload").into(),
-            );
-        }
-        let seq_num = u64::from_le_bytes(buf[8..16].try_into().unwrap());
-        let len_sta_id = buf[16];
-        let sta_id = if len_sta_id == 0 {
+        Self::parse_bytes(Bytes::copy_from_slice(buf))
+    }
+
+    /// Creates a new SeedLink packet from the shared buffer `buf`, i.e. without copying.
+    pub fn parse_bytes(buf: Bytes) -> SeedLinkResult<Self> {
+        let header = wire::v4::parse_header(&buf)?;
+        let format = DataFormat::try_from(header.format)?;
+        let sta_id = if header.sta_id.is_empty() {
             None
         } else {
-            let sta_id: Vec<u8> = buf[17..17 + len_sta_id as usize].to_vec();
-            Some(String::from_utf8(sta_id).map_err(|e| {
+            Some(String::from_utf8(header.sta_id.to_vec()).map_err(|e| {
                 SeedLinkError::from(io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
             })?)
         };
+        let (len_payload, seq_num, len_sta_id) = (
+            header.len_payload,
+            header.seq_num,
+            header.sta_id.len() as u8,
+        );
 
         Ok(Self {
-            packet: buf.to_vec(),
+            packet: buf,
             format,
             len_payload,
             seq_num,
@@ -188,7 +179,7 @@ impl SeedLinkPacket {
 
     /// Returns the raw packet station identifier.
     pub fn sta_id_raw(&self) -> &[u8] {
-        &self.packet[17..17 + self.len_sta_id() as usize]
+        &self.packet[FIXED_HEADER_SIZE..FIXED_HEADER_SIZE + self.len_sta_id() as usize]
     }
 
     /// Returns the packet station identifier.
@@ -201,9 +192,14 @@ impl SeedLinkPacket {
         &self.packet
     }
 
+    /// Returns the raw packet bytes as shared buffer, i.e. without copying.
+    pub fn raw_bytes(&self) -> Bytes {
+        self.packet.clone()
+    }
+
     /// Returns the raw packet payload.
     pub fn payload_raw(&self) -> &[u8] {
-        &self.packet[17 + self.len_sta_id() as usize..]
+        &self.packet[FIXED_HEADER_SIZE + self.len_sta_id() as usize..]
     }
 
     /// Returns the packet payload decoded as miniSEED record.
@@ -255,24 +251,6 @@ pub fn pack_ms_record(rec: &MSRecord, seq_num: u64) -> SeedLinkResult<Vec<u8>> {
     })?;
 
     let net_sta = format!("{}_{}", net, sta);
-    let len_sta_id: u8 = net_sta.len().try_into().map_err(|_| {
-        SeedLinkError::from(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "station identifier too large",
-        ))
-    })?;
-
-    let mut net_sta_bytes = Vec::new();
-    for ch in net_sta.chars() {
-        if !ch.is_ascii() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "station identifier contains non-ASCII characters",
-            )
-            .into());
-        }
-        net_sta_bytes.push(ch as _);
-    }
 
     let payload = rec.raw().ok_or_else(|| {
         SeedLinkError::from(io::Error::new(
@@ -280,25 +258,11 @@ pub fn pack_ms_record(rec: &MSRecord, seq_num: u64) -> SeedLinkResult<Vec<u8>> {
             "missing payload",
         ))
     })?;
-    let len_payload: u32 = payload.len().try_into().map_err(|_| {
-        SeedLinkError::from(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "payload too large",
-        ))
-    })?;
 
-    let mut packet = Vec::with_capacity(128);
-    packet.extend(b"SE");
     // TODO(damb): how to correctly determine subformat code?
-    match rec.format_version() {
-        2 => {
-            let format = DataFormat::MiniSeed2xDataGeneric;
-            packet.extend(format.code_to_u8());
-        }
-        3 => {
-            let format = DataFormat::MiniSeed3xDataGeneric;
-            packet.extend(format.code_to_u8());
-        }
+    let format = match rec.format_version() {
+        2 => DataFormat::MiniSeed2xDataGeneric,
+        3 => DataFormat::MiniSeed3xDataGeneric,
         other => {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
@@ -308,16 +272,18 @@ pub fn pack_ms_record(rec: &MSRecord, seq_num: u64) -> SeedLinkResult<Vec<u8>> {
         }
     };
 
-    packet.extend(len_payload.to_le_bytes());
-    packet.extend(seq_num.to_le_bytes());
-    packet.push(len_sta_id);
-    packet.append(&mut net_sta_bytes);
-    packet.extend(payload);
+    let mut packet = Vec::with_capacity(128);
+    wire::v4::write_packet(
+        format.code_to_u8(),
+        seq_num,
+        net_sta.as_bytes(),
+        payload,
+        &mut packet,
+    )?;
 
     Ok(packet)
 }
 
-
 /// Packs a JSON string into a SeedLink `v4` info packet.
 pub fn pack_info_ok(s: &str) -> SeedLinkResult<Vec<u8>> {
     pack_info(s, DataFormat::JsonSeedLinkInfo)
@@ -334,28 +300,7 @@ fn pack_info(s: &str, format: DataFormat) -> SeedLinkResult<Vec<u8>> {
     }
 
     let mut packet = Vec::new();
-    packet.extend(b"SE");
-
-    packet.extend(format.code_to_u8());
-
-    let payload = s.as_bytes();
-    let len_payload: u32 = payload.len().try_into().map_err(|_| {
-        SeedLinkError::from(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "payload too large",
-        ))
-    })?;
-    packet.extend(len_payload.to_le_bytes());
-
-    let seq_num: u64 = 0;
-    packet.extend(seq_num.to_le_bytes());
-
-    // station identifier length
-    packet.push(0);
-
-    packet.extend(payload);
+    wire::v4::write_packet(format.code_to_u8(), 0, b"", s.as_bytes(), &mut packet)?;
 
     Ok(packet)
 }
-
-
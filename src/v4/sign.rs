@@ -0,0 +1,121 @@
+use std::io;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{wire, SeedLinkError, SeedLinkResult};
+
+// TODO(damb): support ed25519 signatures (asymmetric keys)
+
+/// Capability advertised by servers which append a signature to every SeedLink `v4` packet.
+///
+/// Note that this is a non-standard extension.
+pub const CAPABILITY: &str = "SIGN:HMAC-SHA256";
+
+/// Size of the packet signature in bytes.
+pub const SIGNATURE_SIZE: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signs the SeedLink `v4` packet `packet` with `key` and returns the packet with the signature
+/// appended.
+pub fn sign_packet(packet: &[u8], key: &[u8]) -> SeedLinkResult<Vec<u8>> {
+    let len_packet = packet_len(packet)?;
+    if len_packet != packet.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "packet length does not match packet header",
+        )
+        .into());
+    }
+
+    let mut mac = new_mac(key)?;
+    mac.update(packet);
+
+    let mut rv = Vec::with_capacity(packet.len() + SIGNATURE_SIZE);
+    rv.extend_from_slice(packet);
+    rv.extend_from_slice(&mac.finalize().into_bytes());
+
+    Ok(rv)
+}
+
+/// Verifies the signature of the signed SeedLink `v4` packet `buf` with `key` and returns the
+/// packet without the signature.
+pub fn verify_packet<'a>(buf: &'a [u8], key: &[u8]) -> SeedLinkResult<&'a [u8]> {
+    let len_packet = packet_len(buf)?;
+    if buf.len() != len_packet + SIGNATURE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "missing packet signature").into());
+    }
+
+    let (packet, signature) = buf.split_at(len_packet);
+
+    let mut mac = new_mac(key)?;
+    mac.update(packet);
+    mac.verify_slice(signature).map_err(|_| {
+        SeedLinkError::from(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid packet signature",
+        ))
+    })?;
+
+    Ok(packet)
+}
+
+/// Returns the total packet length (header and payload) as encoded in the packet header.
+fn packet_len(buf: &[u8]) -> SeedLinkResult<usize> {
+    Ok(wire::v4::parse_header(buf)?.len_packet())
+}
+
+fn new_mac(key: &[u8]) -> SeedLinkResult<HmacSha256> {
+    HmacSha256::new_from_slice(key).map_err(|e| {
+        SeedLinkError::from(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid signing key: {}", e),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::pack_info_ok_v4;
+
+    const KEY: &[u8] = b"secret";
+
+    #[test]
+    fn sign_and_verify() {
+        let packet = pack_info_ok_v4(r#"{"software":"foo"}"#).unwrap();
+        let signed = sign_packet(&packet, KEY).unwrap();
+        assert_eq!(signed.len(), packet.len() + SIGNATURE_SIZE);
+
+        let verified = verify_packet(&signed, KEY).unwrap();
+        assert_eq!(verified, &packet[..]);
+    }
+
+    #[test]
+    fn verify_tampered() {
+        let packet = pack_info_ok_v4(r#"{"software":"foo"}"#).unwrap();
+        let mut signed = sign_packet(&packet, KEY).unwrap();
+        let idx = packet.len() - 1;
+        signed[idx] ^= 0xff;
+
+        assert!(verify_packet(&signed, KEY).is_err());
+    }
+
+    #[test]
+    fn verify_wrong_key() {
+        let packet = pack_info_ok_v4(r#"{"software":"foo"}"#).unwrap();
+        let signed = sign_packet(&packet, KEY).unwrap();
+
+        assert!(verify_packet(&signed, b"other").is_err());
+    }
+
+    #[test]
+    fn verify_unsigned() {
+        let packet = pack_info_ok_v4(r#"{"software":"foo"}"#).unwrap();
+
+        assert!(verify_packet(&packet, KEY).is_err());
+    }
+}
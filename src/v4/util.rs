@@ -6,7 +6,7 @@ use crate::IdInfoV4;
 pub fn to_first_hello_resp_line(
     implementation: &str,
     implementation_version: &str,
-    protocol_versions: &Vec<(u8, u8)>,
+    protocol_versions: &[(u8, u8)],
     capabilities: &Option<Vec<String>>,
 ) -> String {
     assert!(!protocol_versions.is_empty());
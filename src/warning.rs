@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use time::{Duration, OffsetDateTime};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::trace;
+use crate::{SeedLinkPacket, StreamItem};
+
+/// Maximum `v3` sequence number (i.e. `FFFFFF`), wrapping to `0`.
+const MAX_SEQ_NUM_V3: u64 = 0xFFFFFF;
+
+/// Tolerance of record end times ahead of the local clock before reporting clock skew.
+const CLOCK_SKEW_TOLERANCE: Duration = Duration::seconds(1);
+
+/// Maximum number of warnings kept until a receiver is requested (see
+/// [`Connection::warnings`](crate::Connection::warnings)).
+const MAX_PENDING_WARNINGS: usize = 64;
+
+/// Capabilities known to the client (in addition to `SLPROTO` capabilities).
+const KNOWN_CAPABILITIES: &[&str] = &[
+    "ASYNC",
+    "BATCH",
+    "CAP",
+    "EXTREPLY",
+    "INFO:GZIP",
+    "MULTISTATION",
+    "NSWILDCARD",
+    "SIGN:HMAC-SHA256",
+    "TIME",
+];
+
+/// Prefixes of parameterized capabilities known to the client.
+const KNOWN_CAPABILITY_PREFIXES: &[&str] = &["SLPROTO:", "AUTH:", "WS:"];
+
+/// Non-fatal condition observed on a connection (see
+/// [`Connection::warnings`](crate::Connection::warnings)).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SeedLinkWarning {
+    /// The server did not provide a station or data center description.
+    MissingDescription,
+    /// The server advertised a capability unknown to the client.
+    UnknownCapability(String),
+    /// A packet was received with a sequence number lower than the sequence number of the
+    /// packet received previously for the same station.
+    ReorderedPacket {
+        /// Station identifier (i.e. `NET_STA`).
+        station_id: String,
+        /// Sequence number of the packet received.
+        seq_num: u64,
+        /// Sequence number of the packet received previously.
+        prev_seq_num: u64,
+    },
+    /// A record ends ahead of the local clock, i.e. the clocks of the data source and the client
+    /// are skewed by (at least) `skew`.
+    ClockSkew {
+        /// Source identifier of the record received.
+        sid: String,
+        /// Time the record ends ahead of the local clock.
+        skew: Duration,
+    },
+}
+
+impl fmt::Display for SeedLinkWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingDescription => write!(f, "missing station or datacenter description"),
+            Self::UnknownCapability(cap) => write!(f, "unknown capability: {}", cap),
+            Self::ReorderedPacket {
+                station_id,
+                seq_num,
+                prev_seq_num,
+            } => write!(
+                f,
+                "packet of {} received out of order (sequence number {}, previously {})",
+                station_id, seq_num, prev_seq_num
+            ),
+            Self::ClockSkew { sid, skew } => {
+                write!(
+                    f,
+                    "record of {} ends {} ahead of the local clock",
+                    sid, skew
+                )
+            }
+        }
+    }
+}
+
+/// Returns whether the capability `cap` is known to the client.
+pub(crate) fn is_known_capability(cap: &str) -> bool {
+    KNOWN_CAPABILITIES.contains(&cap)
+        || KNOWN_CAPABILITY_PREFIXES
+            .iter()
+            .any(|prefix| cap.starts_with(prefix))
+}
+
+/// Destination of the warnings emitted by a connection.
+///
+/// Warnings emitted before a receiver is requested are kept (up to [`MAX_PENDING_WARNINGS`])
+/// and passed to the receiver requested first.
+#[derive(Debug, Default)]
+pub(crate) struct WarningSink {
+    send: Option<mpsc::UnboundedSender<SeedLinkWarning>>,
+    pending: Vec<SeedLinkWarning>,
+}
+
+impl WarningSink {
+    /// Emits the warning `warning`.
+    pub fn emit(&mut self, warning: SeedLinkWarning) {
+        debug!(target: trace::CONNECTION, "warning: {}", warning);
+        match self.send {
+            Some(ref send) => {
+                let _ = send.send(warning);
+            }
+            None if self.pending.len() < MAX_PENDING_WARNINGS => self.pending.push(warning),
+            None => {}
+        }
+    }
+
+    /// Returns a new receiver of the warnings emitted. Previous receivers do not receive warnings
+    /// anymore.
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<SeedLinkWarning> {
+        let (send, recv) = mpsc::unbounded_channel();
+        for warning in self.pending.drain(..) {
+            let _ = send.send(warning);
+        }
+        self.send = Some(send);
+
+        recv
+    }
+
+    /// Returns the observer of the packets streamed, if a receiver was requested.
+    pub fn into_observer(self) -> Option<WarningObserver> {
+        self.send.map(|send| WarningObserver {
+            send,
+            seq_nums: HashMap::new(),
+        })
+    }
+}
+
+/// Observer of the packets streamed emitting [`SeedLinkWarning::ReorderedPacket`] and
+/// [`SeedLinkWarning::ClockSkew`] warnings.
+#[derive(Debug)]
+pub(crate) struct WarningObserver {
+    send: mpsc::UnboundedSender<SeedLinkWarning>,
+    /// Sequence numbers of the packets received most recently per station.
+    seq_nums: HashMap<String, u64>,
+}
+
+impl WarningObserver {
+    /// Checks the data packet passing through. Items other than data packets are ignored.
+    pub fn observe(&mut self, item: &StreamItem) {
+        let packet = match item {
+            StreamItem::Packet(packet) => packet,
+            StreamItem::End(_) => return,
+        };
+        let received = OffsetDateTime::now_utc();
+        let (sid, seq_num, ms_record) = match packet.to_record() {
+            Some(Ok(record)) => record,
+            // XXX(damb): failures are reported when consuming the records
+            Some(Err(_)) | None => return,
+        };
+
+        let station_id = format!("{}_{}", sid.nslc.net, sid.nslc.sta);
+        let wraps = matches!(packet, SeedLinkPacket::V3(_));
+        if let Some(warning) = self.check_seq_num(station_id, seq_num, wraps) {
+            let _ = self.send.send(warning);
+        }
+        if let Ok(end_time) = ms_record.end_time() {
+            let skew = end_time - received;
+            if skew > CLOCK_SKEW_TOLERANCE {
+                let _ = self.send.send(SeedLinkWarning::ClockSkew {
+                    sid: sid.to_string(),
+                    skew,
+                });
+            }
+        }
+    }
+
+    /// Checks the sequence number `seq_num` of the packet received for the station
+    /// `station_id`. If `wraps`, sequence numbers wrap at [`MAX_SEQ_NUM_V3`].
+    fn check_seq_num(
+        &mut self,
+        station_id: String,
+        seq_num: u64,
+        wraps: bool,
+    ) -> Option<SeedLinkWarning> {
+        let prev_seq_num = self.seq_nums.insert(station_id.clone(), seq_num)?;
+        if seq_num >= prev_seq_num || (wraps && prev_seq_num - seq_num > MAX_SEQ_NUM_V3 / 2) {
+            return None;
+        }
+
+        Some(SeedLinkWarning::ReorderedPacket {
+            station_id,
+            seq_num,
+            prev_seq_num,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn emit_warnings() {
+        assert!(is_known_capability("SLPROTO:4.0"));
+        assert!(is_known_capability("INFO:GZIP"));
+        assert!(!is_known_capability("FOO"));
+
+        let mut sink = WarningSink::default();
+        sink.emit(SeedLinkWarning::MissingDescription);
+        let mut recv = sink.subscribe();
+        sink.emit(SeedLinkWarning::UnknownCapability("FOO".to_string()));
+        assert_eq!(recv.try_recv(), Ok(SeedLinkWarning::MissingDescription));
+        assert_eq!(
+            recv.try_recv(),
+            Ok(SeedLinkWarning::UnknownCapability("FOO".to_string()))
+        );
+
+        let mut observer = sink.into_observer().unwrap();
+        assert_eq!(
+            observer.check_seq_num("GE_WLF".to_string(), 42, false),
+            None
+        );
+        assert_eq!(
+            observer.check_seq_num("GE_WLF".to_string(), 43, false),
+            None
+        );
+        assert_eq!(
+            observer.check_seq_num("GE_WLF".to_string(), 7, false),
+            Some(SeedLinkWarning::ReorderedPacket {
+                station_id: "GE_WLF".to_string(),
+                seq_num: 7,
+                prev_seq_num: 43,
+            })
+        );
+        assert_eq!(
+            observer.check_seq_num("GE_APE".to_string(), MAX_SEQ_NUM_V3, true),
+            None
+        );
+        assert_eq!(observer.check_seq_num("GE_APE".to_string(), 0, true), None);
+    }
+}
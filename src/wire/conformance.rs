@@ -0,0 +1,248 @@
+//! Protocol conformance checks of outgoing frames.
+//!
+//! The checks are meant to be run as debug assertions (e.g. `debug_assert_eq!(check_line(buf),
+//! Ok(()))`), i.e. they catch protocol violations in test runs without any overhead in release
+//! builds.
+
+use core::fmt;
+
+use super::{v3, v4, Error};
+
+/// Maximum length of a command line, including the `<CR><LF>` terminator.
+pub const MAX_COMMAND_LINE_LENGTH: usize = 255;
+/// Maximum length of an `AUTH` command line, including the `<CR><LF>` terminator. Allows for JSON
+/// Web Tokens (JWT) which easily exceed [`MAX_COMMAND_LINE_LENGTH`].
+pub const MAX_AUTH_COMMAND_LINE_LENGTH: usize = 4096;
+
+/// Protocol conformance violation.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Violation {
+    /// The command line exceeds the maximum length of the command.
+    LineTooLong {
+        /// Line length, including the terminator.
+        len: usize,
+        /// Maximum line length of the command, including the terminator.
+        max: usize,
+    },
+    /// The line contains a character not allowed (i.e. non-printable or non-ASCII).
+    InvalidCharacter(u8),
+    /// The line is not terminated by `<CR><LF>`.
+    MissingLineTerminator,
+    /// The packet header is malformed.
+    InvalidHeader(Error),
+    /// The packet length does not match the length expected.
+    InvalidPacketLength {
+        /// Number of bytes expected.
+        expected: usize,
+        /// Number of bytes found.
+        found: usize,
+    },
+    /// Invalid data format or subformat code.
+    InvalidFormat(u8),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LineTooLong { len, max } => {
+                write!(f, "line too long: {} > {} characters", len, max)
+            }
+            Self::InvalidCharacter(c) => write!(f, "invalid character: {:#04x}", c),
+            Self::MissingLineTerminator => write!(f, "missing line terminator"),
+            Self::InvalidHeader(e) => write!(f, "invalid packet header: {}", e),
+            Self::InvalidPacketLength { expected, found } => write!(
+                f,
+                "invalid packet length: expected {} bytes, found {} bytes",
+                expected, found
+            ),
+            Self::InvalidFormat(c) => write!(f, "invalid format code: {:#04x}", c),
+        }
+    }
+}
+
+impl From<Error> for Violation {
+    fn from(e: Error) -> Self {
+        Self::InvalidHeader(e)
+    }
+}
+
+/// Returns the maximum length of the command line `line` (including the `<CR><LF>` terminator)
+/// according to its command, i.e. `AUTH` command lines may exceed [`MAX_COMMAND_LINE_LENGTH`].
+pub fn max_command_line_length(line: &[u8]) -> usize {
+    let name = line.split(|c| *c == b' ').next().unwrap_or_default();
+    if name.eq_ignore_ascii_case(b"AUTH") {
+        MAX_AUTH_COMMAND_LINE_LENGTH
+    } else {
+        MAX_COMMAND_LINE_LENGTH
+    }
+}
+
+/// Checks the command line `line` (excluding the `<CR><LF>` terminator).
+pub fn check_command_line(line: &[u8]) -> Result<(), Violation> {
+    let len = line.len() + 2;
+    let max = max_command_line_length(line);
+    if len > max {
+        return Err(Violation::LineTooLong { len, max });
+    }
+
+    check_line(line)
+}
+
+/// Checks the response line `line` (excluding the `<CR><LF>` terminator).
+///
+/// Note that response lines are not length limited.
+pub fn check_line(line: &[u8]) -> Result<(), Violation> {
+    match line.iter().find(|c| !(0x20..=0x7e).contains(*c)) {
+        Some(c) => Err(Violation::InvalidCharacter(*c)),
+        None => Ok(()),
+    }
+}
+
+/// Checks the `<CR><LF>` terminated response lines `buf`.
+pub fn check_lines(buf: &[u8]) -> Result<(), Violation> {
+    let buf = buf
+        .strip_suffix(b"\r\n")
+        .ok_or(Violation::MissingLineTerminator)?;
+
+    let mut lines = buf.split(|c| *c == b'\n');
+    if let Some(last) = lines.next_back() {
+        check_line(last)?;
+    }
+
+    lines.try_for_each(|line| {
+        check_line(
+            line.strip_suffix(b"\r")
+                .ok_or(Violation::MissingLineTerminator)?,
+        )
+    })
+}
+
+/// Checks the SeedLink `v3` packet `buf`.
+pub fn check_packet_v3(buf: &[u8]) -> Result<(), Violation> {
+    let expected = v3::HEADER_SIZE + v3::RECORD_SIZE;
+    if buf.len() != expected {
+        return Err(Violation::InvalidPacketLength {
+            expected,
+            found: buf.len(),
+        });
+    }
+
+    if let v3::Header::Data(seq_num) = v3::parse_header(buf)? {
+        if seq_num > v3::MAX_SEQ_NUM {
+            return Err(Error::InvalidSequenceNumber.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the SeedLink `v4` packet `buf`.
+pub fn check_packet_v4(buf: &[u8]) -> Result<(), Violation> {
+    let header = v4::parse_header(buf)?;
+
+    if let Some(c) = header.format.iter().find(|c| !c.is_ascii_alphanumeric()) {
+        return Err(Violation::InvalidFormat(*c));
+    }
+
+    if header
+        .sta_id
+        .iter()
+        .any(|c| !(c.is_ascii_alphanumeric() || *c == b'_'))
+    {
+        return Err(Error::InvalidStationId.into());
+    }
+
+    if buf.len() != header.len_packet() {
+        return Err(Violation::InvalidPacketLength {
+            expected: header.len_packet(),
+            found: buf.len(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn command_line() {
+        assert_eq!(check_command_line(b"STATION WLF GE"), Ok(()));
+        assert_eq!(
+            check_command_line(&[b'A'; MAX_COMMAND_LINE_LENGTH - 1]),
+            Err(Violation::LineTooLong {
+                len: MAX_COMMAND_LINE_LENGTH + 1,
+                max: MAX_COMMAND_LINE_LENGTH
+            })
+        );
+
+        let mut auth = b"auth token ".to_vec();
+        auth.resize(MAX_AUTH_COMMAND_LINE_LENGTH - 2, b'A');
+        assert_eq!(check_command_line(&auth), Ok(()));
+        auth.push(b'A');
+        assert_eq!(
+            check_command_line(&auth),
+            Err(Violation::LineTooLong {
+                len: MAX_AUTH_COMMAND_LINE_LENGTH + 1,
+                max: MAX_AUTH_COMMAND_LINE_LENGTH
+            })
+        );
+        assert_eq!(
+            check_command_line(b"STATION\tWLF GE"),
+            Err(Violation::InvalidCharacter(b'\t'))
+        );
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(
+            check_lines(b"SeedLink v4.0 :: SLPROTO:4.0\r\nGEOFON\r\n"),
+            Ok(())
+        );
+        assert_eq!(
+            check_lines(b"SeedLink v4.0 :: SLPROTO:4.0\r\nGEOFON"),
+            Err(Violation::MissingLineTerminator)
+        );
+        assert_eq!(
+            check_lines(b"SeedLink v4.0 :: SLPROTO:4.0\nGEOFON\r\n"),
+            Err(Violation::MissingLineTerminator)
+        );
+    }
+
+    #[test]
+    fn packet_v3() {
+        let mut buf = vec![0; v3::HEADER_SIZE + v3::RECORD_SIZE];
+        v3::write_header(&v3::Header::Data(42), &mut buf).unwrap();
+        assert_eq!(check_packet_v3(&buf), Ok(()));
+        assert_eq!(
+            check_packet_v3(&buf[..v3::RECORD_SIZE]),
+            Err(Violation::InvalidPacketLength {
+                expected: v3::HEADER_SIZE + v3::RECORD_SIZE,
+                found: v3::RECORD_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn packet_v4() {
+        let mut buf = Vec::new();
+        v4::write_packet(*b"JI", 42, b"GE_WLF", b"{}", &mut buf).unwrap();
+        assert_eq!(check_packet_v4(&buf), Ok(()));
+        assert_eq!(
+            check_packet_v4(&buf[..buf.len() - 1]),
+            Err(Violation::InvalidPacketLength {
+                expected: buf.len(),
+                found: buf.len() - 1
+            })
+        );
+
+        buf.clear();
+        v4::write_packet(*b"J\n", 42, b"GE_WLF", b"{}", &mut buf).unwrap();
+        assert_eq!(check_packet_v4(&buf), Err(Violation::InvalidFormat(b'\n')));
+    }
+}
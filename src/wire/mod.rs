@@ -0,0 +1,50 @@
+//! SeedLink packet wire format.
+//!
+//! The module is transport independent and depends on `core` and `alloc`, only (i.e. neither on
+//! `std` nor on `tokio`). Thus, it may be reused in `no_std` environments, e.g. by dataloggers
+//! generating SeedLink packets.
+
+use core::fmt;
+
+pub mod conformance;
+pub mod transcript;
+pub mod v3;
+pub mod v4;
+
+/// Wire format error.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The buffer is too small. Holds the number of bytes required.
+    Incomplete(usize),
+    /// Invalid packet signature.
+    InvalidSignature,
+    /// Invalid packet sequence number.
+    InvalidSequenceNumber,
+    /// Missing packet payload.
+    MissingPayload,
+    /// Invalid or too large station identifier.
+    InvalidStationId,
+    /// Payload too large.
+    PayloadTooLarge,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incomplete(needed) => write!(f, "incomplete packet: {} bytes required", needed),
+            Self::InvalidSignature => write!(f, "invalid packet signature"),
+            Self::InvalidSequenceNumber => write!(f, "invalid packet sequence number"),
+            Self::MissingPayload => write!(f, "missing packet payload"),
+            Self::InvalidStationId => write!(f, "invalid station identifier"),
+            Self::PayloadTooLarge => write!(f, "payload too large"),
+        }
+    }
+}
+
+fn ensure_len(buf: &[u8], len: usize) -> Result<(), Error> {
+    if buf.len() < len {
+        return Err(Error::Incomplete(len));
+    }
+
+    Ok(())
+}
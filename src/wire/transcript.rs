@@ -0,0 +1,267 @@
+//! Annotated transcripts of captured wire dumps.
+//!
+//! A wire dump is the raw byte stream captured for a single direction of a SeedLink connection.
+//! Decoding the dump results in a human readable transcript of the command and response lines
+//! and the packet headers exchanged, e.g. when analyzing interoperability issues.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use super::{v3, v4, Error};
+
+/// Direction of a captured byte stream.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Direction {
+    /// Byte stream sent by the client (i.e. command lines).
+    ClientToServer,
+    /// Byte stream sent by the server (i.e. response lines and packets).
+    ServerToClient,
+}
+
+/// Item decoded from a wire dump.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Item<'a> {
+    /// Command or response line (excluding the line terminator).
+    Line(&'a [u8]),
+    /// SeedLink `v3` packet.
+    PacketV3 {
+        /// The packet header.
+        header: v3::Header,
+        /// The packet payload.
+        payload: &'a [u8],
+    },
+    /// SeedLink `v4` packet.
+    PacketV4 {
+        /// The packet header.
+        header: v4::Header<'a>,
+        /// The packet payload.
+        payload: &'a [u8],
+    },
+    /// Trailing bytes not forming a complete line or packet.
+    Truncated(&'a [u8]),
+}
+
+impl fmt::Display for Item<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Line(line) => write!(f, "{}", Escaped(line)),
+            Self::PacketV3 {
+                header: v3::Header::Data(seq_num),
+                payload,
+            } => {
+                write!(f, "[v3 data packet] seq={:06X}", seq_num)?;
+                if let Some(id) = ms2_source_id(payload) {
+                    write!(f, " id={}", id)?;
+                }
+                write!(f, " len={}", payload.len())
+            }
+            Self::PacketV3 {
+                header: v3::Header::Info(last),
+                payload,
+            } => write!(f, "[v3 info packet] last={} len={}", last, payload.len()),
+            Self::PacketV4 { header, payload } => write!(
+                f,
+                "[v4 packet] format={} seq={} station={} len={}",
+                Escaped(&header.format),
+                header.seq_num,
+                Escaped(header.sta_id),
+                payload.len()
+            ),
+            Self::Truncated(buf) => write!(f, "[truncated] {} bytes", buf.len()),
+        }
+    }
+}
+
+/// Decodes the wire dump `buf` captured for `direction`.
+///
+/// Returns the items decoded together with their byte offset within `buf`.
+pub fn decode(buf: &[u8], direction: Direction) -> Vec<(usize, Item<'_>)> {
+    let mut items = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let remaining = &buf[pos..];
+        let (item, len) = match direction {
+            Direction::ServerToClient => match decode_packet(remaining) {
+                Some(Ok(packet)) => packet,
+                // XXX(damb): a line might start with a packet signature, too
+                Some(Err(_)) if remaining.contains(&b'\n') => decode_line(remaining),
+                Some(Err(_)) => (Item::Truncated(remaining), remaining.len()),
+                None => decode_line(remaining),
+            },
+            Direction::ClientToServer => decode_line(remaining),
+        };
+
+        items.push((pos, item));
+        pos += len;
+    }
+
+    items
+}
+
+/// Returns an annotated transcript of the wire dump `buf` captured for `direction`.
+///
+/// Each item is written to a separate line prefixed by its byte offset and the direction
+/// (i.e. `->` for items sent by the client and `<-` for items sent by the server).
+pub fn annotate(buf: &[u8], direction: Direction) -> String {
+    let arrow = match direction {
+        Direction::ClientToServer => "->",
+        Direction::ServerToClient => "<-",
+    };
+
+    let mut rv = String::new();
+    for (pos, item) in decode(buf, direction) {
+        // XXX(damb): writing to a string does not fail
+        let _ = writeln!(rv, "{:08x} {} {}", pos, arrow, item);
+    }
+
+    rv
+}
+
+/// Decodes a packet from `buf`.
+///
+/// Returns `None` if `buf` does not start with a packet signature.
+fn decode_packet(buf: &[u8]) -> Option<Result<(Item<'_>, usize), Error>> {
+    if buf.starts_with(v4::SIGNATURE) {
+        return match v4::parse_header(buf) {
+            Ok(header) if is_valid_v4_header(&header) => {
+                let len = header.len_packet();
+                Some(match buf.get(header.len_header()..len) {
+                    Some(payload) => Ok((Item::PacketV4 { header, payload }, len)),
+                    None => Err(Error::Incomplete(len)),
+                })
+            }
+            Err(Error::Incomplete(len)) => Some(Err(Error::Incomplete(len))),
+            // XXX(damb): e.g. a line starting with the packet signature
+            _ => None,
+        };
+    }
+
+    if buf.starts_with(v3::SIGNATURE) {
+        return match v3::parse_header(buf) {
+            Ok(header) => {
+                let len = v3::HEADER_SIZE + v3::RECORD_SIZE;
+                Some(match buf.get(v3::HEADER_SIZE..len) {
+                    Some(payload) => Ok((Item::PacketV3 { header, payload }, len)),
+                    None => Err(Error::Incomplete(len)),
+                })
+            }
+            Err(Error::Incomplete(len)) => Some(Err(Error::Incomplete(len))),
+            _ => None,
+        };
+    }
+
+    None
+}
+
+/// Decodes a `<CR><LF>` (or `<LF>`) terminated line from `buf`.
+fn decode_line(buf: &[u8]) -> (Item<'_>, usize) {
+    match buf.iter().position(|c| *c == b'\n') {
+        Some(idx) => {
+            let line = &buf[..idx];
+            (
+                Item::Line(line.strip_suffix(b"\r").unwrap_or(line)),
+                idx + 1,
+            )
+        }
+        None => (Item::Truncated(buf), buf.len()),
+    }
+}
+
+fn is_valid_v4_header(header: &v4::Header<'_>) -> bool {
+    header.format.iter().all(|c| c.is_ascii_alphanumeric())
+        && header
+            .sta_id
+            .iter()
+            .all(|c| c.is_ascii_alphanumeric() || *c == b'_')
+}
+
+/// Returns the source identifier (i.e. `NET_STA_LOC_CHA`) of the miniSEED 2 record `buf`.
+fn ms2_source_id(buf: &[u8]) -> Option<String> {
+    let code = |range: core::ops::Range<usize>| {
+        let code = core::str::from_utf8(buf.get(range)?).ok()?;
+        code.is_ascii().then(|| code.trim())
+    };
+
+    if !matches!(buf.get(6), Some(b'D' | b'R' | b'Q' | b'M')) {
+        return None;
+    }
+
+    Some(format!(
+        "{}_{}_{}_{}",
+        code(18..20)?,
+        code(8..13)?,
+        code(13..15)?,
+        code(15..18)?
+    ))
+}
+
+/// Displays bytes with non-printable characters escaped.
+struct Escaped<'a>(&'a [u8]);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0
+            .iter()
+            .flat_map(|c| core::ascii::escape_default(*c))
+            .try_for_each(|c| f.write_char(c as char))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use alloc::vec;
+
+    #[test]
+    fn client_to_server() {
+        let buf = b"HELLO\r\nSTATION WLF GE\r\nSELECT BHZ\r\nEND";
+        assert_eq!(
+            decode(buf, Direction::ClientToServer),
+            vec![
+                (0, Item::Line(b"HELLO")),
+                (7, Item::Line(b"STATION WLF GE")),
+                (23, Item::Line(b"SELECT BHZ")),
+                (35, Item::Truncated(b"END")),
+            ]
+        );
+        assert_eq!(
+            annotate(b"HELLO\r\n", Direction::ClientToServer),
+            "00000000 -> HELLO\n"
+        );
+    }
+
+    #[test]
+    fn server_to_client() {
+        let mut buf = b"OK\r\n".to_vec();
+        let mut packet_v3 = vec![0; v3::HEADER_SIZE + v3::RECORD_SIZE];
+        v3::write_header(&v3::Header::Data(42), &mut packet_v3).unwrap();
+        packet_v3[v3::HEADER_SIZE..v3::HEADER_SIZE + 20].copy_from_slice(b"000001D WLF  00BHZGE");
+        buf.extend_from_slice(&packet_v3);
+        v4::write_packet(*b"JI", 42, b"GE_WLF", b"{}", &mut buf).unwrap();
+        buf.extend_from_slice(b"SELECT\r\n");
+
+        assert_eq!(
+            annotate(&buf, Direction::ServerToClient),
+            "00000000 <- OK\n\
+             00000004 <- [v3 data packet] seq=00002A id=GE_WLF_00_BHZ len=512\n\
+             0000020c <- [v4 packet] format=JI seq=42 station=GE_WLF len=2\n\
+             00000225 <- SELECT\n"
+        );
+    }
+
+    #[test]
+    fn truncated_packet() {
+        let mut buf = Vec::new();
+        v4::write_packet(*b"JI", 42, b"GE_WLF", b"{}", &mut buf).unwrap();
+        buf.pop();
+
+        assert_eq!(
+            decode(&buf, Direction::ServerToClient),
+            vec![(0, Item::Truncated(&buf))]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! SeedLink `v3` packet header wire format.
+
+use super::{ensure_len, Error};
+
+/// Packet header size.
+pub const HEADER_SIZE: usize = 8;
+/// Packet record size.
+pub const RECORD_SIZE: usize = 512;
+/// Packet signature.
+pub const SIGNATURE: &[u8; 2] = b"SL";
+/// Info packet signature.
+pub const INFO_SIGNATURE: &[u8; 6] = b"SLINFO";
+/// Info packet flag indicating that further info packets follow for a given request.
+pub const INFO_TERMINATION_FLAG: &[u8; 1] = b"*";
+/// Largest sequence number encodable.
+pub const MAX_SEQ_NUM: u32 = 0xFFFFFF;
+
+const SEQ_NUM_DIGITS: usize = HEADER_SIZE - SIGNATURE.len();
+
+/// SeedLink `v3` packet header.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Header {
+    /// Data packet header including the packet sequence number.
+    Data(u32),
+    /// Info packet header. Indicates whether the packet is the last packet for a given request.
+    Info(bool),
+}
+
+/// Parses the packet header from `buf`.
+pub fn parse_header(buf: &[u8]) -> Result<Header, Error> {
+    ensure_len(buf, HEADER_SIZE)?;
+
+    if &buf[..INFO_SIGNATURE.len()] == INFO_SIGNATURE {
+        return Ok(Header::Info(
+            buf[HEADER_SIZE - 1] != INFO_TERMINATION_FLAG[0],
+        ));
+    }
+
+    if &buf[..SIGNATURE.len()] != SIGNATURE {
+        return Err(Error::InvalidSignature);
+    }
+
+    let mut seq_num: u32 = 0;
+    for b in &buf[SIGNATURE.len()..HEADER_SIZE] {
+        let digit = (*b as char)
+            .to_digit(16)
+            .ok_or(Error::InvalidSequenceNumber)?;
+        seq_num = (seq_num << 4) | digit;
+    }
+
+    Ok(Header::Data(seq_num))
+}
+
+/// Writes the packet header `header` to `buf`.
+pub fn write_header(header: &Header, buf: &mut [u8]) -> Result<(), Error> {
+    ensure_len(buf, HEADER_SIZE)?;
+
+    match *header {
+        Header::Data(seq_num) => {
+            if seq_num > MAX_SEQ_NUM {
+                return Err(Error::InvalidSequenceNumber);
+            }
+
+            buf[..SIGNATURE.len()].copy_from_slice(SIGNATURE);
+            for i in 0..SEQ_NUM_DIGITS {
+                let digit = (seq_num >> (4 * (SEQ_NUM_DIGITS - 1 - i))) & 0xF;
+                buf[SIGNATURE.len() + i] =
+                    char::from_digit(digit, 16).unwrap().to_ascii_uppercase() as u8;
+            }
+        }
+        Header::Info(last) => {
+            buf[..INFO_SIGNATURE.len()].copy_from_slice(INFO_SIGNATURE);
+            buf[INFO_SIGNATURE.len()] = b' ';
+            buf[HEADER_SIZE - 1] = if last { b' ' } else { INFO_TERMINATION_FLAG[0] };
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn data_header() {
+        let mut buf = [0; HEADER_SIZE];
+        write_header(&Header::Data(0x1A2B), &mut buf).unwrap();
+        assert_eq!(&buf, b"SL001A2B");
+        assert_eq!(parse_header(&buf).unwrap(), Header::Data(0x1A2B));
+    }
+
+    #[test]
+    fn info_header() {
+        let mut buf = [0; HEADER_SIZE];
+        write_header(&Header::Info(false), &mut buf).unwrap();
+        assert_eq!(&buf, b"SLINFO *");
+        assert_eq!(parse_header(&buf).unwrap(), Header::Info(false));
+        assert_eq!(parse_header(b"SLINFO  ").unwrap(), Header::Info(true));
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert_eq!(parse_header(b"SL00"), Err(Error::Incomplete(HEADER_SIZE)));
+        assert_eq!(parse_header(b"XX000000"), Err(Error::InvalidSignature));
+        assert_eq!(parse_header(b"SL00000Z"), Err(Error::InvalidSequenceNumber));
+        assert_eq!(
+            write_header(&Header::Data(MAX_SEQ_NUM + 1), &mut [0; HEADER_SIZE]),
+            Err(Error::InvalidSequenceNumber)
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! SeedLink `v4` packet header wire format.
+
+use alloc::vec::Vec;
+
+use super::{ensure_len, Error};
+
+/// Size of the fixed packet header part in bytes (i.e. excluding the station identifier).
+pub const FIXED_HEADER_SIZE: usize = 17;
+/// Packet signature.
+pub const SIGNATURE: &[u8; 2] = b"SE";
+
+/// SeedLink `v4` packet header.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Header<'a> {
+    /// Data format and subformat code.
+    pub format: [u8; 2],
+    /// Payload length in bytes.
+    pub len_payload: u32,
+    /// Packet sequence number.
+    pub seq_num: u64,
+    /// Raw station identifier (may be empty).
+    pub sta_id: &'a [u8],
+}
+
+impl<'a> Header<'a> {
+    /// Returns the header length in bytes.
+    pub fn len_header(&self) -> usize {
+        FIXED_HEADER_SIZE + self.sta_id.len()
+    }
+
+    /// Returns the total packet length (header and payload) in bytes.
+    pub fn len_packet(&self) -> usize {
+        self.len_header() + self.len_payload as usize
+    }
+}
+
+/// Parses the packet header from `buf`.
+///
+/// Note that `buf` is not required to contain the packet payload.
+pub fn parse_header(buf: &[u8]) -> Result<Header<'_>, Error> {
+    ensure_len(buf, FIXED_HEADER_SIZE)?;
+
+    if &buf[..SIGNATURE.len()] != SIGNATURE {
+        return Err(Error::InvalidSignature);
+    }
+
+    // XXX(damb): header fields are little endian encoded
+    let format: [u8; 2] = buf[2..4].try_into().unwrap();
+    let len_payload = u32::from_le_bytes(buf[4..8].try_into().unwrap());
+    if len_payload == 0 {
+        return Err(Error::MissingPayload);
+    }
+    let seq_num = u64::from_le_bytes(buf[8..16].try_into().unwrap());
+    let len_sta_id = buf[16] as usize;
+    ensure_len(buf, FIXED_HEADER_SIZE + len_sta_id)?;
+
+    Ok(Header {
+        format,
+        len_payload,
+        seq_num,
+        sta_id: &buf[FIXED_HEADER_SIZE..FIXED_HEADER_SIZE + len_sta_id],
+    })
+}
+
+/// Appends the packet header `header` to `buf`.
+pub fn write_header(header: &Header<'_>, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let len_sta_id: u8 = header
+        .sta_id
+        .len()
+        .try_into()
+        .map_err(|_| Error::InvalidStationId)?;
+    if !header.sta_id.is_ascii() {
+        return Err(Error::InvalidStationId);
+    }
+
+    buf.reserve(header.len_header());
+    buf.extend_from_slice(SIGNATURE);
+    buf.extend_from_slice(&header.format);
+    buf.extend_from_slice(&header.len_payload.to_le_bytes());
+    buf.extend_from_slice(&header.seq_num.to_le_bytes());
+    buf.push(len_sta_id);
+    buf.extend_from_slice(header.sta_id);
+
+    Ok(())
+}
+
+/// Appends a packet shipping `payload` to `buf`.
+pub fn write_packet(
+    format: [u8; 2],
+    seq_num: u64,
+    sta_id: &[u8],
+    payload: &[u8],
+    buf: &mut Vec<u8>,
+) -> Result<(), Error> {
+    if payload.is_empty() {
+        return Err(Error::MissingPayload);
+    }
+    let len_payload: u32 = payload
+        .len()
+        .try_into()
+        .map_err(|_| Error::PayloadTooLarge)?;
+
+    write_header(
+        &Header {
+            format,
+            len_payload,
+            seq_num,
+            sta_id,
+        },
+        buf,
+    )?;
+    buf.extend_from_slice(payload);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn write_and_parse() {
+        let mut buf = Vec::new();
+        write_packet(*b"JI", 42, b"GE_WLF", b"{}", &mut buf).unwrap();
+        assert_eq!(buf.len(), FIXED_HEADER_SIZE + 6 + 2);
+
+        let header = parse_header(&buf).unwrap();
+        assert_eq!(
+            header,
+            Header {
+                format: *b"JI",
+                len_payload: 2,
+                seq_num: 42,
+                sta_id: b"GE_WLF",
+            }
+        );
+        assert_eq!(header.len_packet(), buf.len());
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut buf = Vec::new();
+        write_packet(*b"JI", 0, b"GE_WLF", b"{}", &mut buf).unwrap();
+
+        assert_eq!(
+            parse_header(&buf[..FIXED_HEADER_SIZE - 1]),
+            Err(Error::Incomplete(FIXED_HEADER_SIZE))
+        );
+        assert_eq!(
+            parse_header(&buf[..FIXED_HEADER_SIZE + 1]),
+            Err(Error::Incomplete(FIXED_HEADER_SIZE + 6))
+        );
+        buf[0] = b'X';
+        assert_eq!(parse_header(&buf), Err(Error::InvalidSignature));
+        assert_eq!(
+            write_packet(*b"JI", 0, b"", b"", &mut Vec::new()),
+            Err(Error::MissingPayload)
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Wire compatibility tests verifying the decoding of golden fixtures (see
+//! `tests/fixtures/wire/README.md`).
+
+use pretty_assertions::assert_eq;
+use slink::wire::transcript::{self, Direction};
+use slink::{InventoryV3, StreamFormatV4, StreamSubFormatV4, StreamTypeV3, StreamsInfoV4};
+
+macro_rules! fixture {
+    ($name:literal) => {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/wire/", $name)
+    };
+}
+
+#[test]
+fn transcript_v3_server() {
+    assert_eq!(
+        transcript::annotate(
+            include_bytes!(fixture!("v3_server.bin")),
+            Direction::ServerToClient
+        ),
+        include_str!(fixture!("v3_server.transcript"))
+    );
+}
+
+#[test]
+fn transcript_v4_server() {
+    assert_eq!(
+        transcript::annotate(
+            include_bytes!(fixture!("v4_server.bin")),
+            Direction::ServerToClient
+        ),
+        include_str!(fixture!("v4_server.transcript"))
+    );
+}
+
+#[test]
+fn transcript_v4_client() {
+    assert_eq!(
+        transcript::annotate(
+            include_bytes!(fixture!("v4_client.bin")),
+            Direction::ClientToServer
+        ),
+        include_str!(fixture!("v4_client.transcript"))
+    );
+}
+
+#[test]
+fn info_streams_v3() {
+    let inv: InventoryV3 =
+        quick_xml::de::from_str(include_str!(fixture!("info_streams_v3.xml"))).unwrap();
+
+    assert_eq!(inv.station.len(), 2);
+    let sta = &inv.station[0];
+    assert_eq!((sta.network.as_str(), sta.code.as_str()), ("GE", "WLF"));
+    assert_eq!((sta.begin_seq, sta.end_seq), (0x563200, 0x58275F));
+    let streams = sta.stream.as_ref().unwrap();
+    assert_eq!(
+        streams
+            .iter()
+            .map(|s| (
+                s.location.as_str(),
+                s.channel.as_str(),
+                s.stream_type.clone()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            ("", "BHZ", StreamTypeV3::Data),
+            ("", "BHN", StreamTypeV3::Data),
+            ("", "LOG", StreamTypeV3::Log),
+        ]
+    );
+    assert_eq!(
+        streams[0].end_time,
+        time::macros::datetime!(2023-05-02 12:30:15.425 UTC)
+    );
+    assert_eq!(inv.station[1].stream, None);
+}
+
+#[test]
+fn info_streams_v4() {
+    let info: StreamsInfoV4 =
+        serde_json::from_str(include_str!(fixture!("info_streams_v4.json"))).unwrap();
+
+    assert_eq!(info.id.software, "SeedLink v4.0 (2021.123)");
+    assert_eq!(info.station.len(), 1);
+    let sta = &info.station[0];
+    assert_eq!((sta.id().net_code(), sta.id().sta_code()), ("GE", "WLF"));
+    assert_eq!((sta.start_seq(), sta.end_seq()), (0x563200, 0x58275F));
+    assert_eq!(sta.backfill(), &Some(-1));
+    let streams = sta.streams().as_ref().unwrap();
+    assert_eq!(streams.len(), 2);
+    assert_eq!(streams[0].id().loc_code(), "00");
+    assert_eq!(streams[0].id().subsource_code(), "Z");
+    assert_eq!(
+        (streams[0].format(), streams[0].subformat()),
+        (&StreamFormatV4::MiniSeed2, &StreamSubFormatV4::Data)
+    );
+    assert_eq!(
+        streams[0].end_time(),
+        &time::macros::datetime!(2023-05-02 12:30:15.425 UTC)
+    );
+
+    let json = serde_json::to_string(&info).unwrap();
+    assert_eq!(serde_json::from_str::<StreamsInfoV4>(&json).unwrap(), info);
+}